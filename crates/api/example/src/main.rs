@@ -21,45 +21,50 @@ fn main() {
 
     // Example building of a colored graph from three FASTA files
     // building also bcalm2-style links across maximal unitigs
-    let graph_file = instance.build_graph(
-        vec![
-            GeneralSequenceBlockData::FASTA((
-                PathBuf::from("../../../example-inputs/sal1.fa"),
-                None,
-            )),
-            GeneralSequenceBlockData::FASTA((
-                PathBuf::from("../../../example-inputs/sal2.fa"),
-                None,
-            )),
-            GeneralSequenceBlockData::FASTA((
-                PathBuf::from("../../../example-inputs/sal3.fa"),
-                None,
-            )),
-        ],
-        graph_file.clone(),
-        Some(&["sal1".to_string(), "sal2".to_string(), "sal3".to_string()]),
-        k,
-        threads_count,
-        false,
-        None,
-        true,
-        1,
-        ExtraElaboration::UnitigLinks,
-    );
+    let graph_file = instance
+        .build_graph(
+            vec![
+                GeneralSequenceBlockData::FASTA((
+                    PathBuf::from("../../../example-inputs/sal1.fa"),
+                    None,
+                )),
+                GeneralSequenceBlockData::FASTA((
+                    PathBuf::from("../../../example-inputs/sal2.fa"),
+                    None,
+                )),
+                GeneralSequenceBlockData::FASTA((
+                    PathBuf::from("../../../example-inputs/sal3.fa"),
+                    None,
+                )),
+            ],
+            graph_file.clone(),
+            Some(&["sal1".to_string(), "sal2".to_string(), "sal3".to_string()]),
+            k,
+            threads_count,
+            false,
+            None,
+            true,
+            1,
+            ExtraElaboration::UnitigLinks,
+        )
+        .unwrap()
+        .unwrap();
 
     let input_query = PathBuf::from("../../../example-inputs/query.fa");
 
-    let output_query = instance.query_graph(
-        graph_file.clone(),
-        input_query,
-        PathBuf::from("/tmp/query-results"),
-        k,
-        threads_count,
-        false,
-        None,
-        true,
-        ColoredQueryOutputFormat::JsonLinesWithNames,
-    );
+    let output_query = instance
+        .query_graph(
+            graph_file.clone(),
+            input_query,
+            PathBuf::from("/tmp/query-results"),
+            k,
+            threads_count,
+            false,
+            None,
+            true,
+            ColoredQueryOutputFormat::JsonLinesWithNames,
+        )
+        .unwrap();
 
     println!("Output query file: {:?}", output_query.display());
 
@@ -68,30 +73,32 @@ fn main() {
     let color_names: Vec<_> =
         GGCATInstance::dump_colors(GGCATInstance::get_colormap_file(&graph_file)).collect();
 
-    instance.dump_unitigs(
-        graph_file,
-        k,
-        None,
-        true,
-        threads_count,
-        false,
-        // WARNING: this function is called asynchronously from multiple threads, so it must be thread-safe.
-        // Also the same_colors boolean is referred to the previous call of this function from the current thread
-        |read, colors, same_colors| {
-            let _lock = print_kmer_lock.lock().unwrap();
-            if read.len() < 100 {
-                println!("Dump unitig '{}'", std::str::from_utf8(read).unwrap());
-            } else {
+    instance
+        .dump_unitigs(
+            graph_file,
+            k,
+            None,
+            true,
+            threads_count,
+            false,
+            // WARNING: this function is called asynchronously from multiple threads, so it must be thread-safe.
+            // Also the same_colors boolean is referred to the previous call of this function from the current thread
+            |read, colors, same_colors| {
+                let _lock = print_kmer_lock.lock().unwrap();
+                if read.len() < 100 {
+                    println!("Dump unitig '{}'", std::str::from_utf8(read).unwrap());
+                } else {
+                    println!(
+                        "Dump unitig '{}...'",
+                        std::str::from_utf8(&read[..100]).unwrap()
+                    );
+                }
                 println!(
-                    "Dump unitig '{}...'",
-                    std::str::from_utf8(&read[..100]).unwrap()
+                    "\t colors: {:?} same_colors: {}",
+                    colors.iter().map(|c| &color_names[*c as usize]).format(" "),
+                    same_colors
                 );
-            }
-            println!(
-                "\t colors: {:?} same_colors: {}",
-                colors.iter().map(|c| &color_names[*c as usize]).format(" "),
-                same_colors
-            );
-        },
-    );
+            },
+        )
+        .unwrap();
 }
@@ -1,6 +1,11 @@
 mod utils;
 
+use ::utils::kmer_length::{validate_lengths, KmerError};
+use ::utils::spill_layout::{create_run_dir, SpillLayout, SpillLayoutError};
+use assembler::checkpoint::ResumeCheckpoint;
+use assembler::AssemblerOutcome;
 use colors::bundles::graph_querying::ColorBundleGraphQuerying;
+use colors::colors_limit::{check_current_colors_limit, ColorsLimitError};
 use colors::colors_manager::ColorsManager;
 use colors::{
     bundles::multifile_building::ColorBundleMultifileBuilding, non_colored::NonColoredManager,
@@ -15,6 +20,7 @@ use parallel_processor::memory_fs::MemoryFs;
 use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parking_lot::Mutex;
 use std::cmp::max;
+use std::fmt::{Display, Formatter};
 use std::fs::create_dir_all;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
@@ -26,6 +32,7 @@ pub use config::ColorIndexType;
 pub use io::sequences_reader::{DnaSequence, DnaSequencesFileType};
 pub use io::sequences_stream::{
     general::{DynamicSequencesStream, GeneralSequenceBlockData},
+    iterator::IteratorSequencesStream,
     SequenceInfo,
 };
 pub use querier::ColoredQueryOutputFormat;
@@ -33,9 +40,36 @@ pub use querier::ColoredQueryOutputFormat;
 pub mod debug {
     use crate::utils::HashType;
     use assembler::AssemblerStartingStep;
+    pub use config::AUTO_TUNE_KMERS_TRANSFORM as DEBUG_AUTO_TUNE_KMERS_TRANSFORM;
+    pub use config::CANONICAL_ORIENTATION_MIN_HASH as DEBUG_CANONICAL_ORIENTATION_MIN_HASH;
+    pub use config::COLORS_PRESENCE_ONLY as DEBUG_COLORS_PRESENCE_ONLY;
+    pub use config::COUNT_BASES_COMPOSITION as DEBUG_COUNT_BASES_COMPOSITION;
+    pub use config::DETERMINISTIC_OUTPUT as DEBUG_DETERMINISTIC_OUTPUT;
+    pub use config::DURABLE_OUTPUT as DEBUG_DURABLE_OUTPUT;
+    pub use config::INPUT_VALIDATION_MAX_REPORTED_ERRORS as DEBUG_INPUT_VALIDATION_MAX_REPORTED_ERRORS;
+    pub use config::INPUT_VALIDATION_MODE as DEBUG_INPUT_VALIDATION_MODE;
     pub use config::KEEP_FILES as DEBUG_KEEP_FILES;
+    pub use config::KMERS_MERGE_MAP_FLUSH_SIZE as DEBUG_KMERS_MERGE_MAP_FLUSH_SIZE;
+    pub use config::KMERS_MERGE_TIME_LIMIT_SECS as DEBUG_KMERS_MERGE_TIME_LIMIT_SECS;
+    pub use config::KMERS_TRANSFORM_MAX_PROCESSORS as DEBUG_KMERS_TRANSFORM_MAX_PROCESSORS;
+    pub use config::KMERS_TRANSFORM_MAX_READERS as DEBUG_KMERS_TRANSFORM_MAX_READERS;
+    pub use config::KMERS_TRANSFORM_MAX_RESPLITTERS as DEBUG_KMERS_TRANSFORM_MAX_RESPLITTERS;
+    pub use config::MAX_COLORS as DEBUG_MAX_COLORS;
+    pub use config::MAX_COLORS_POLICY as DEBUG_MAX_COLORS_POLICY;
+    pub use config::MAX_MULTIPLICITY as DEBUG_MAX_MULTIPLICITY;
+    pub use config::OUTPUT_BUFFER_SIZE as DEBUG_OUTPUT_BUFFER_SIZE;
+    pub use config::PER_CPU_BUFFER_SIZE as DEBUG_PER_CPU_BUFFER_SIZE;
+    pub use config::PRESERVE_READ_NAMES as DEBUG_PRESERVE_READ_NAMES;
+    pub use config::PROCESSORS_MEMORY_LIMIT as DEBUG_PROCESSORS_MEMORY_LIMIT;
+    pub use config::SEQUENTIAL_KMERS_TRANSFORM as DEBUG_SEQUENTIAL_KMERS_TRANSFORM;
+    pub use config::USE_SECOND_BUCKET as DEBUG_USE_SECOND_BUCKET;
+    pub use config::VERIFY_BUCKETS_COUNTERS as DEBUG_VERIFY_BUCKETS_COUNTERS;
+    pub use config::WRITE_EDGE_LIST as DEBUG_WRITE_EDGE_LIST;
+    pub use config::WRITE_GRAPH_DUMP as DEBUG_WRITE_GRAPH_DUMP;
+    pub use config::WRITE_UNITIGS_PROVENANCE as DEBUG_WRITE_UNITIGS_PROVENANCE;
     use parking_lot::Mutex;
     use querier::QuerierStartingStep;
+    use std::path::PathBuf;
     use std::sync::atomic::{AtomicBool, AtomicUsize};
 
     pub static DEBUG_ASSEMBLER_FIRST_STEP: Mutex<AssemblerStartingStep> =
@@ -52,8 +86,65 @@ pub mod debug {
     pub static DEBUG_ONLY_BSTATS: AtomicBool = AtomicBool::new(false);
 
     pub static BUCKETS_COUNT_LOG_FORCE: Mutex<Option<usize>> = Mutex::new(None);
+    /// Read chunks queued for each bucketing compute thread, instead of READ_INTERMEDIATE_QUEUE_MULTIPLIER
+    pub static DEBUG_READ_QUEUE_MULTIPLIER: Mutex<Option<usize>> = Mutex::new(None);
+
+    pub static DEBUG_MASKED_KMERS_FILE: Mutex<Option<PathBuf>> = Mutex::new(None);
+    pub static DEBUG_EXPORT_BUCKETS_READS: Mutex<Option<PathBuf>> = Mutex::new(None);
+    /// Temporary directory of a cancelled build to resume, instead of starting a new one
+    pub static DEBUG_RESUME_RUN_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+}
+
+#[derive(Debug)]
+pub enum GGCATError {
+    /// The build was cancelled, it can be resumed by setting debug::DEBUG_RESUME_RUN_DIR to run_dir
+    Cancelled { run_dir: PathBuf },
+    /// The resume checkpoint of the run directory cannot be read
+    InvalidCheckpoint {
+        run_dir: PathBuf,
+        error: std::io::Error,
+    },
+    /// Colored builds rewrite the colormap from the start, so they cannot be resumed
+    ResumeNotSupported,
+    /// The colors are more than debug::DEBUG_MAX_COLORS with the Error policy
+    TooManyColors(ColorsLimitError),
+    /// The k-mers or minimizers length is not supported
+    InvalidKmerLength(KmerError),
+    /// The run directory cannot name the spill files of the run
+    InvalidRunDir(SpillLayoutError),
+    /// The unitigs provenance is only written for the plain unitigs output, as the links
+    /// and the tigs change the unitigs order
+    ProvenanceNotSupported,
 }
 
+impl Display for GGCATError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GGCATError::Cancelled { run_dir } => write!(
+                f,
+                "Build cancelled, it can be resumed from {}",
+                run_dir.display()
+            ),
+            GGCATError::InvalidCheckpoint { run_dir, error } => write!(
+                f,
+                "Cannot resume the build from {}: {}",
+                run_dir.display(),
+                error
+            ),
+            GGCATError::ResumeNotSupported => write!(f, "Colored builds cannot be resumed"),
+            GGCATError::TooManyColors(err) => write!(f, "Cannot build the colored graph: {}", err),
+            GGCATError::InvalidKmerLength(err) => write!(f, "{}", err),
+            GGCATError::InvalidRunDir(err) => write!(f, "{}", err),
+            GGCATError::ProvenanceNotSupported => write!(
+                f,
+                "The unitigs provenance cannot be written with the links or the tigs output"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GGCATError {}
+
 /// Main config of GGCAT. This config is global and should be passed to GGCATInstance::create
 pub struct GGCATConfig {
     /// Directory for temporary files
@@ -95,18 +186,24 @@ static INSTANCE: Mutex<Option<&'static GGCATInstance>> = Mutex::new(None);
 
 pub struct GGCATInstance(GGCATConfig);
 
-fn create_tempdir(base_path: Option<PathBuf>) -> Option<PathBuf> {
-    base_path.map(|t| {
-        let temp_dir = t.join(&format!("build_graph_{}", uuid::Uuid::new_v4()));
-        let _ = create_dir_all(&temp_dir);
-        temp_dir
-    })
+/// Checks the k-mers length against the selected merging hash and returns the minimizers length
+fn check_lengths(kmer_length: usize, minimizer_length: Option<usize>) -> Result<usize, GGCATError> {
+    validate_lengths(
+        kmer_length,
+        minimizer_length,
+        utils::hash_max_kmer_length(debug::DEBUG_HASH_TYPE.lock().clone()),
+    )
+    .map_err(GGCATError::InvalidKmerLength)
 }
 
-fn remove_tempdir(temp_dir: Option<PathBuf>) {
-    if let Some(temp_dir) = temp_dir {
-        let _ = std::fs::remove_dir_all(temp_dir);
-    }
+/// Creates the run directory, in the current directory if no base path is given, so that
+/// a cancelled build can always be resumed from it
+fn create_tempdir(base_path: Option<PathBuf>, prefix: &str) -> PathBuf {
+    create_run_dir(base_path.unwrap_or_default(), prefix)
+}
+
+fn remove_tempdir(temp_dir: PathBuf) {
+    let _ = std::fs::remove_dir_all(temp_dir);
 }
 
 /// Main GGCAT struct. It's a singleton and can be create by passing a GGCATConfig.
@@ -148,10 +245,13 @@ impl GGCATInstance {
             });
         }
 
+        let memory = MemoryDataSize::from_bytes(
+            (config.memory * (MemoryDataSize::OCTET_GIBIOCTET_FACTOR as f64)) as usize,
+        );
+        config::PROCESSORS_MEMORY_LIMIT.store(memory.as_bytes(), Ordering::Relaxed);
+
         MemoryFs::init(
-            MemoryDataSize::from_bytes(
-                (config.memory * (MemoryDataSize::OCTET_GIBIOCTET_FACTOR as f64)) as usize,
-            ),
+            memory,
             config::FLUSH_QUEUE_FACTOR * config.total_threads_count,
             max(1, config.total_threads_count / 4),
             8192,
@@ -160,7 +260,8 @@ impl GGCATInstance {
         return instance.unwrap();
     }
 
-    /// Builds a new graph from the given input streams, with the specified parameters
+    /// Builds a new graph from the given input streams, with the specified parameters.
+    /// Returns None if the debug options stopped the build before writing the graph
     pub fn build_graph(
         &self,
         // The input streams
@@ -188,7 +289,51 @@ impl GGCATInstance {
         min_multiplicity: usize,
 
         extra_elab: ExtraElaboration,
-    ) -> PathBuf {
+    ) -> Result<Option<PathBuf>, GGCATError> {
+        let minimizer_length = check_lengths(kmer_length, minimizer_length)?;
+
+        if extra_elab != ExtraElaboration::None
+            && debug::DEBUG_WRITE_UNITIGS_PROVENANCE.load(Ordering::Relaxed)
+        {
+            return Err(GGCATError::ProvenanceNotSupported);
+        }
+
+        if colors {
+            let approximate_colors =
+                check_current_colors_limit(color_names.map_or(0, |names| names.len()))
+                    .map_err(GGCATError::TooManyColors)?;
+            if let Some(approximate_colors) = approximate_colors {
+                println!(
+                    "Warning: too many colors, storing the colors subsets as bloom filters of {} bits",
+                    approximate_colors.bits_count()
+                );
+            }
+        }
+
+        let resume_run_dir = debug::DEBUG_RESUME_RUN_DIR.lock().clone();
+        let (temp_dir, first_step, buckets_count_log) = match resume_run_dir {
+            Some(_) if colors => return Err(GGCATError::ResumeNotSupported),
+            Some(run_dir) => {
+                SpillLayout::new(&run_dir).map_err(GGCATError::InvalidRunDir)?;
+                let checkpoint = ResumeCheckpoint::read(&run_dir).map_err(|error| {
+                    GGCATError::InvalidCheckpoint {
+                        run_dir: run_dir.clone(),
+                        error,
+                    }
+                })?;
+                (
+                    run_dir,
+                    checkpoint.next_step,
+                    Some(checkpoint.buckets_count_log),
+                )
+            }
+            None => (
+                create_tempdir(self.0.temp_dir.clone(), "build_graph"),
+                debug::DEBUG_ASSEMBLER_FIRST_STEP.lock().clone(),
+                *debug::BUCKETS_COUNT_LOG_FORCE.lock(),
+            ),
+        };
+
         let bucketing_hash_dispatch = if forward_only {
             <ForwardNtHashIteratorFactory as MinimizerHashFunctionFactory>::dynamic_dispatch_id()
         } else {
@@ -207,21 +352,20 @@ impl GGCATInstance {
             NonColoredManager::dynamic_dispatch_id()
         };
 
-        let temp_dir = create_tempdir(self.0.temp_dir.clone());
-
-        let output_file = assembler::dynamic_dispatch::run_assembler(
+        let outcome = assembler::dynamic_dispatch::run_assembler(
             (bucketing_hash_dispatch, merging_hash_dispatch, colors_hash),
             kmer_length,
-            minimizer_length.unwrap_or(::utils::compute_best_m(kmer_length)),
-            debug::DEBUG_ASSEMBLER_FIRST_STEP.lock().clone(),
+            minimizer_length,
+            first_step,
             debug::DEBUG_ASSEMBLER_LAST_STEP.lock().clone(),
             input_streams,
             color_names.unwrap_or(&[]),
             output_file,
-            temp_dir.clone(),
+            Some(temp_dir.clone()),
             threads_count,
             min_multiplicity,
-            *debug::BUCKETS_COUNT_LOG_FORCE.lock(),
+            debug::DEBUG_MASKED_KMERS_FILE.lock().clone(),
+            buckets_count_log,
             Some(debug::DEBUG_LINK_PHASE_ITERATION_START_STEP.load(Ordering::Relaxed)),
             self.0.intermediate_compression_level,
             extra_elab == ExtraElaboration::UnitigLinks,
@@ -232,11 +376,22 @@ impl GGCATInstance {
                 _ => None,
             },
             debug::DEBUG_ONLY_BSTATS.load(Ordering::Relaxed),
+            debug::DEBUG_EXPORT_BUCKETS_READS.lock().clone(),
+            *debug::DEBUG_READ_QUEUE_MULTIPLIER.lock(),
         );
 
-        remove_tempdir(temp_dir);
-
-        output_file
+        match outcome {
+            AssemblerOutcome::Completed(output_file) => {
+                remove_tempdir(temp_dir);
+                Ok(Some(output_file))
+            }
+            AssemblerOutcome::StoppedAfterStep(_) => {
+                remove_tempdir(temp_dir);
+                Ok(None)
+            }
+            // Keep the temporary files of a cancelled run, as they are needed to resume it
+            AssemblerOutcome::Cancelled { .. } => Err(GGCATError::Cancelled { run_dir: temp_dir }),
+        }
     }
 
     /// Queries a (optionally) colored graph with a specific set of sequences as queries
@@ -264,7 +419,9 @@ impl GGCATInstance {
 
         // Query output format
         color_output_format: ColoredQueryOutputFormat,
-    ) -> PathBuf {
+    ) -> Result<PathBuf, GGCATError> {
+        let minimizer_length = check_lengths(kmer_length, minimizer_length)?;
+
         let bucketing_hash_dispatch = if forward_only {
             <ForwardNtHashIteratorFactory as MinimizerHashFunctionFactory>::dynamic_dispatch_id()
         } else {
@@ -283,26 +440,27 @@ impl GGCATInstance {
             NonColoredManager::dynamic_dispatch_id()
         };
 
-        let temp_dir = create_tempdir(self.0.temp_dir.clone());
+        let temp_dir = create_tempdir(self.0.temp_dir.clone(), "query");
 
         let output_file = querier::dynamic_dispatch::run_query(
             (bucketing_hash_dispatch, merging_hash_dispatch, colors_hash),
             kmer_length,
-            minimizer_length.unwrap_or(::utils::compute_best_m(kmer_length)),
+            minimizer_length,
             debug::DEBUG_QUERIER_FIRST_STEP.lock().clone(),
             input_graph,
             input_query,
             output_file_prefix,
-            temp_dir.clone(),
+            Some(temp_dir.clone()),
             *debug::BUCKETS_COUNT_LOG_FORCE.lock(),
             threads_count,
             self.0.intermediate_compression_level,
             color_output_format,
+            *debug::DEBUG_READ_QUEUE_MULTIPLIER.lock(),
         );
 
         remove_tempdir(temp_dir);
 
-        output_file
+        Ok(output_file)
     }
 
     /// Obtains the standard colormap file path from a graph file path
@@ -349,19 +507,24 @@ impl GGCATInstance {
         // avoiding the need for synchronization in the user code
         single_thread_output_function: bool,
         output_function: impl Fn(&[u8], &[ColorIndexType], bool) + Send + Sync,
-    ) {
-        let temp_dir = create_tempdir(self.0.temp_dir.clone());
+    ) -> Result<(), GGCATError> {
+        // The dump does not depend on the merging hash
+        let minimizer_length = validate_lengths(kmer_length, minimizer_length, None)
+            .map_err(GGCATError::InvalidKmerLength)?;
+
+        let temp_dir = create_tempdir(self.0.temp_dir.clone(), "dump_colors");
 
         if colors {
             dumper::dump_unitigs(
                 kmer_length,
-                minimizer_length.unwrap_or(::utils::compute_best_m(kmer_length)),
+                minimizer_length,
                 graph_input,
-                temp_dir.clone(),
+                Some(temp_dir.clone()),
                 *debug::BUCKETS_COUNT_LOG_FORCE.lock(),
                 threads_count,
                 single_thread_output_function,
                 self.0.intermediate_compression_level,
+                *debug::DEBUG_READ_QUEUE_MULTIPLIER.lock(),
                 output_function,
             );
         } else {
@@ -376,5 +539,6 @@ impl GGCATInstance {
         }
 
         remove_tempdir(temp_dir);
+        Ok(())
     }
 }
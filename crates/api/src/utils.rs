@@ -1,3 +1,4 @@
+use ::utils::kmer_length::MAX_SEQHASH_KMER_LENGTH;
 use dynamic_dispatch::DynamicDispatch;
 
 #[derive(Copy, Clone)]
@@ -9,6 +10,15 @@ pub enum HashType {
     RabinKarp128 = 4,
 }
 
+/// Longest k supported by the hash type, None if it does not bound k.
+/// The automatic selection switches to a rolling hash above the sequence hashes limit
+pub(crate) fn hash_max_kmer_length(hash_type: HashType) -> Option<usize> {
+    match hash_type {
+        HashType::SeqHash => Some(MAX_SEQHASH_KMER_LENGTH),
+        _ => None,
+    }
+}
+
 pub(crate) fn get_hash_static_id(
     hash_type: HashType,
     k: usize,
@@ -18,7 +28,7 @@ pub(crate) fn get_hash_static_id(
 
     let hash_type = match hash_type {
         HashType::Auto => {
-            if k <= 64 {
+            if k <= MAX_SEQHASH_KMER_LENGTH {
                 HashType::SeqHash
             } else {
                 HashType::RabinKarp128
@@ -47,7 +57,7 @@ pub(crate) fn get_hash_static_id(
                 } else {
                     cn_seqhash::u64::CanonicalSeqHashFactory::dynamic_dispatch_id()
                 }
-            } else if k <= 64 {
+            } else if k <= MAX_SEQHASH_KMER_LENGTH {
                 if forward_only {
                     fw_seqhash::u128::ForwardSeqHashFactory::dynamic_dispatch_id()
                 } else {
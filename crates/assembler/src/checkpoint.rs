@@ -0,0 +1,147 @@
+use crate::AssemblerStartingStep;
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+const CHECKPOINT_FILE_NAME: &str = "resume-checkpoint.txt";
+
+/// Information needed to resume an interrupted run from the temporary files
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResumeCheckpoint {
+    pub next_step: AssemblerStartingStep,
+    pub buckets_count_log: usize,
+}
+
+impl ResumeCheckpoint {
+    pub fn get_path(temp_dir: &Path) -> PathBuf {
+        temp_dir.join(CHECKPOINT_FILE_NAME)
+    }
+
+    pub fn write(&self, temp_dir: &Path) -> PathBuf {
+        let path = Self::get_path(temp_dir);
+        let mut file = File::create(&path).unwrap();
+        writeln!(file, "next_step={}", self.next_step.clone() as usize).unwrap();
+        writeln!(file, "buckets_count_log={}", self.buckets_count_log).unwrap();
+        file.sync_all().unwrap();
+        path
+    }
+
+    pub fn read(temp_dir: &Path) -> std::io::Result<Self> {
+        let path = Self::get_path(temp_dir);
+        let invalid_data = |line: &str| {
+            std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Invalid line in resume checkpoint {}: {}",
+                    path.display(),
+                    line
+                ),
+            )
+        };
+
+        let mut next_step = None;
+        let mut buckets_count_log = None;
+
+        for line in BufReader::new(File::open(&path)?).lines() {
+            let line = line?;
+            let (key, value) = line.split_once('=').ok_or_else(|| invalid_data(&line))?;
+            match key {
+                "next_step" => {
+                    next_step = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .and_then(AssemblerStartingStep::from_index)
+                            .ok_or_else(|| invalid_data(&line))?,
+                    );
+                }
+                "buckets_count_log" => {
+                    buckets_count_log = Some(value.parse().map_err(|_| invalid_data(&line))?);
+                }
+                _ => {}
+            }
+        }
+
+        match (next_step, buckets_count_log) {
+            (Some(next_step), Some(buckets_count_log)) => Ok(Self {
+                next_step,
+                buckets_count_log,
+            }),
+            _ => Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Incomplete resume checkpoint {}", path.display()),
+            )),
+        }
+    }
+}
+
+/// Writes the checkpoint to resume a cancelled run from the step after the completed one
+pub fn write_cancelled_run_checkpoint(
+    completed_step: AssemblerStartingStep,
+    temp_dir: &Path,
+    buckets_count_log: usize,
+) -> PathBuf {
+    let next_step = AssemblerStartingStep::from_index(completed_step as usize + 1)
+        .unwrap_or(AssemblerStartingStep::MaximalUnitigsLinks);
+
+    let path = ResumeCheckpoint {
+        next_step,
+        buckets_count_log,
+    }
+    .write(temp_dir);
+
+    println!(
+        "Run cancelled, resume checkpoint written to {}",
+        path.display()
+    );
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checkpoint::{write_cancelled_run_checkpoint, ResumeCheckpoint};
+    use crate::AssemblerStartingStep;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn resume_checkpoint_read() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let temp_dir = test_dir.path();
+
+        assert_eq!(
+            ResumeCheckpoint::read(temp_dir).unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+
+        let checkpoint_path =
+            write_cancelled_run_checkpoint(AssemblerStartingStep::KmersMerge, temp_dir, 9);
+        assert_eq!(checkpoint_path, ResumeCheckpoint::get_path(temp_dir));
+        assert_eq!(
+            ResumeCheckpoint::read(temp_dir).unwrap(),
+            ResumeCheckpoint {
+                next_step: AssemblerStartingStep::HashesSorting,
+                buckets_count_log: 9,
+            }
+        );
+
+        // The last step is resumed from itself
+        write_cancelled_run_checkpoint(AssemblerStartingStep::MaximalUnitigsLinks, temp_dir, 4);
+        assert_eq!(
+            ResumeCheckpoint::read(temp_dir).unwrap().next_step,
+            AssemblerStartingStep::MaximalUnitigsLinks
+        );
+
+        for malformed in [
+            "next_step=2\n",
+            "next_step=7\nbuckets_count_log=9\n",
+            "next_step=2\nbuckets_count_log=nine\n",
+            "next_step 2\nbuckets_count_log=9\n",
+        ] {
+            std::fs::write(ResumeCheckpoint::get_path(temp_dir), malformed).unwrap();
+            assert_eq!(
+                ResumeCheckpoint::read(temp_dir).unwrap_err().kind(),
+                ErrorKind::InvalidData
+            );
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use hashes::canonical_policy::CanonicalOrientationPolicy;
+use hashes::cn_nthash::CanonicalNtHashIteratorFactory;
+use io::sequences_stream::fasta::FastaFileSequencesStream;
+use io::sequences_stream::GenericSequencesStream;
+use std::collections::HashSet;
+use std::path::Path;
+use utils::Utils;
+
+/// Overlap statistics between the unitigs and the canonical kmers of two assemblies
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompareReport {
+    pub shared_unitigs: usize,
+    pub unique_unitigs_a: usize,
+    pub unique_unitigs_b: usize,
+    pub shared_kmers: usize,
+    pub unique_kmers_a: usize,
+    pub unique_kmers_b: usize,
+    pub kmers_jaccard: f64,
+}
+
+struct AssemblySets {
+    unitigs: HashSet<Vec<u8>>,
+    kmers: HashSet<Vec<u8>>,
+}
+
+fn load_assembly_sets(assembly: impl AsRef<Path>, k: usize) -> AssemblySets {
+    let policy = CanonicalOrientationPolicy::current();
+
+    let mut sets = AssemblySets {
+        unitigs: HashSet::new(),
+        kmers: HashSet::new(),
+    };
+
+    let mut compressed = vec![];
+    let mut canonical = vec![];
+
+    FastaFileSequencesStream::new().read_block(
+        &(assembly.as_ref().to_path_buf(), None),
+        false,
+        None,
+        |seq, _info| {
+            if seq.seq.len() < k {
+                return;
+            }
+
+            compressed.clear();
+            compressed.extend(seq.seq.iter().map(|b| Utils::compress_base(*b)));
+
+            canonical.clear();
+            policy.write_canonical_bases::<CanonicalNtHashIteratorFactory>(
+                compressed.as_slice(),
+                &mut canonical,
+            );
+            sets.unitigs.insert(canonical.clone());
+
+            for kmer in compressed.windows(k) {
+                canonical.clear();
+                policy
+                    .write_canonical_bases::<CanonicalNtHashIteratorFactory>(kmer, &mut canonical);
+                if !sets.kmers.contains(&canonical) {
+                    sets.kmers.insert(canonical.clone());
+                }
+            }
+        },
+    );
+
+    sets
+}
+
+/// Compares two assemblies built with the same k, reporting the shared and unique unitigs
+/// and the jaccard index of their canonical kmers sets
+pub fn compare(
+    assembly_a: impl AsRef<Path>,
+    assembly_b: impl AsRef<Path>,
+    k: usize,
+) -> CompareReport {
+    let sets_a = load_assembly_sets(assembly_a, k);
+    let sets_b = load_assembly_sets(assembly_b, k);
+
+    let shared_unitigs = sets_a.unitigs.intersection(&sets_b.unitigs).count();
+    let shared_kmers = sets_a.kmers.intersection(&sets_b.kmers).count();
+    let union_kmers = sets_a.kmers.len() + sets_b.kmers.len() - shared_kmers;
+
+    CompareReport {
+        shared_unitigs,
+        unique_unitigs_a: sets_a.unitigs.len() - shared_unitigs,
+        unique_unitigs_b: sets_b.unitigs.len() - shared_unitigs,
+        shared_kmers,
+        unique_kmers_a: sets_a.kmers.len() - shared_kmers,
+        unique_kmers_b: sets_b.kmers.len() - shared_kmers,
+        kmers_jaccard: if union_kmers == 0 {
+            1.0
+        } else {
+            shared_kmers as f64 / union_kmers as f64
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compare::compare;
+    use std::path::{Path, PathBuf};
+
+    fn write_assembly(dir: &Path, name: &str, unitigs: &[&str]) -> PathBuf {
+        let path = dir.join(format!("{}.fa", name));
+        let mut contents = String::new();
+        for (index, unitig) in unitigs.iter().enumerate() {
+            contents.push_str(&format!(">{}\n{}\n", index, unitig));
+        }
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn compare_self_and_disjoint() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let assembly = write_assembly(temp_dir.path(), "a", &["ACGTTGCAAGGC", "CCCCCAAAAAT"]);
+        // Reverse complemented unitigs have the same canonical kmers
+        let assembly_rc = write_assembly(temp_dir.path(), "a-rc", &["GCCTTGCAACGT", "ATTTTTGGGGG"]);
+        let disjoint = write_assembly(temp_dir.path(), "b", &["GAGAGAGAGAGA", "TATATATATATA"]);
+
+        let self_report = compare(&assembly, &assembly_rc, 5);
+        assert_eq!(self_report.kmers_jaccard, 1.0);
+        assert_eq!(self_report.shared_unitigs, 2);
+        assert_eq!(self_report.unique_unitigs_a, 0);
+        assert_eq!(self_report.unique_kmers_b, 0);
+
+        let disjoint_report = compare(&assembly, &disjoint, 5);
+        assert_eq!(disjoint_report.kmers_jaccard, 0.0);
+        assert_eq!(disjoint_report.shared_unitigs, 0);
+        assert_eq!(disjoint_report.shared_kmers, 0);
+        assert_eq!(disjoint_report.unique_unitigs_b, 2);
+    }
+}
@@ -1,24 +1,33 @@
+use crate::checkpoint::write_cancelled_run_checkpoint;
 use crate::pipeline::build_unitigs::build_unitigs;
 use crate::pipeline::compute_matchtigs::{compute_matchtigs_thread, MatchtigsStorageBackend};
 use crate::pipeline::hashes_sorting::hashes_sorting;
 use crate::pipeline::links_compaction::links_compaction;
 use crate::pipeline::maximal_unitig_links::build_maximal_unitigs_links;
+use crate::pipeline::maximal_unitig_links::edge_list::EdgeListWriter;
 use crate::pipeline::reorganize_reads::reorganize_reads;
 use ::dynamic_dispatch::dynamic_dispatch;
+use assembler_kmers_merge::resume::committed_merge_output;
 use assembler_kmers_merge::structs::RetType;
+use assembler_minimizer_bucketing::AssemblerBucketHasher;
+use colors::colors_manager::color_types::MinimizerBucketingSeqColorDataType;
 use colors::colors_manager::ColorsManager;
 use colors::colors_manager::ColorsMergeManager;
 use config::{
     get_compression_level_info, get_memory_mode, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE,
-    INTERMEDIATE_COMPRESSION_LEVEL_FAST, INTERMEDIATE_COMPRESSION_LEVEL_SLOW, KEEP_FILES,
-    MAXIMUM_SECOND_BUCKETS_LOG, MINIMUM_LOG_DELTA_TIME,
+    DETERMINISTIC_OUTPUT, INTERMEDIATE_COMPRESSION_LEVEL_FAST, INTERMEDIATE_COMPRESSION_LEVEL_SLOW,
+    KEEP_FILES, MAXIMUM_SECOND_BUCKETS_LOG, MINIMUM_LOG_DELTA_TIME, WRITE_EDGE_LIST,
+    WRITE_GRAPH_DUMP, WRITE_UNITIGS_PROVENANCE,
 };
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
 use io::concurrent::structured_sequences::binary::StructSeqBinaryWriter;
 use io::concurrent::structured_sequences::fasta::FastaWriter;
-use io::concurrent::structured_sequences::StructuredSequenceWriter;
+use io::concurrent::structured_sequences::graph_dump::GraphDumpWriter;
+use io::concurrent::structured_sequences::provenance::SequencesProvenanceWriter;
+use io::concurrent::structured_sequences::{CircularUnitigAnnotation, StructuredSequenceWriter};
 use io::sequences_stream::general::GeneralSequenceBlockData;
 use io::{compute_stats_from_input_blocks, generate_bucket_names};
+use kmers_transform::KmersTransformStatus;
 use parallel_processor::buckets::concurrent::BucketsThreadBuffer;
 use parallel_processor::buckets::writers::compressed_binary_writer::CompressedCheckpointSize;
 use parallel_processor::buckets::writers::lock_free_binary_writer::LockFreeBinaryWriter;
@@ -32,13 +41,17 @@ use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Instant;
+use utils::cancellation::CancellationToken;
+use utils::spill_layout::{create_run_dir, SpillLayout};
 
+pub mod checkpoint;
+pub mod compare;
 mod pipeline;
 mod structs;
 
 pub use pipeline::compute_matchtigs::MatchtigMode;
 
-#[derive(Clone, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub enum AssemblerStartingStep {
     MinimizerBucketing = 0,
     KmersMerge = 1,
@@ -49,6 +62,33 @@ pub enum AssemblerStartingStep {
     MaximalUnitigsLinks = 6,
 }
 
+/// How a run of the assembler ended
+#[derive(Clone, Debug, PartialEq)]
+pub enum AssemblerOutcome {
+    /// The graph was written to the output file
+    Completed(PathBuf),
+    /// The run stopped as requested after the given step, or after computing the buckets stats
+    /// or exporting the buckets reads, without writing the graph
+    StoppedAfterStep(AssemblerStartingStep),
+    /// The run was cancelled, and can be resumed from the temporary directory with the checkpoint
+    Cancelled { checkpoint: PathBuf },
+}
+
+impl AssemblerStartingStep {
+    pub fn from_index(index: usize) -> Option<Self> {
+        Some(match index {
+            0 => Self::MinimizerBucketing,
+            1 => Self::KmersMerge,
+            2 => Self::HashesSorting,
+            3 => Self::LinksCompaction,
+            4 => Self::ReorganizeReads,
+            5 => Self::BuildUnitigs,
+            6 => Self::MaximalUnitigsLinks,
+            _ => return None,
+        })
+    }
+}
+
 #[dynamic_dispatch(BucketingHash = [
     hashes::cn_nthash::CanonicalNtHashIteratorFactory,
     #[cfg(not(feature = "devel-build"))] hashes::fw_nthash::ForwardNtHashIteratorFactory
@@ -86,14 +126,19 @@ pub fn run_assembler<
     temp_dir: Option<PathBuf>,
     threads_count: usize,
     min_multiplicity: usize,
+    masked_kmers_file: Option<PathBuf>,
     buckets_count_log: Option<usize>,
     loopit_number: Option<usize>,
     default_compression_level: Option<u32>,
     generate_maximal_unitigs_links: bool,
     compute_tigs_mode: Option<MatchtigMode>,
     only_bstats: bool,
-) -> PathBuf {
-    let temp_dir = temp_dir.unwrap_or(PathBuf::new());
+    export_buckets_reads: Option<PathBuf>,
+    read_queue_multiplier: Option<usize>,
+) -> AssemblerOutcome {
+    // Without a temp dir the run files are kept together in a new directory of the current one
+    let temp_dir = temp_dir.unwrap_or_else(|| create_run_dir("", "build_graph"));
+    let cancellation_token = CancellationToken::global();
 
     PHASES_TIMES_MONITOR.write().init();
 
@@ -112,25 +157,42 @@ pub fn run_assembler<
         AssemblerColorsManager::ColorsMergeManagerType::create_colors_table(
             output_file.with_extension("colors.dat"),
             color_names,
-        ),
+        )
+        .unwrap_or_else(|err| panic!("Cannot build the colored graph: {}", err)),
     );
 
     let (buckets, counters) = if step <= AssemblerStartingStep::MinimizerBucketing {
-        assembler_minimizer_bucketing::static_dispatch::minimizer_bucketing::<
-            BucketingHash,
-            AssemblerColorsManager,
-        >(
-            input_blocks,
-            temp_dir.as_path(),
-            buckets_count,
-            threads_count,
-            k,
-            m,
-        )
+        let (buckets, counters, bases_composition) =
+            assembler_minimizer_bucketing::static_dispatch::minimizer_bucketing::<
+                BucketingHash,
+                AssemblerColorsManager,
+            >(
+                input_blocks,
+                temp_dir.as_path(),
+                buckets_count,
+                threads_count,
+                k,
+                m,
+                read_queue_multiplier,
+            );
+
+        if let Some(bases_composition) = bases_composition {
+            let [a, c, g, t] = bases_composition.counts;
+            println!(
+                "Input bases composition: A {} C {} G {} T {}, GC fraction {:.4}",
+                a,
+                c,
+                g,
+                t,
+                bases_composition.gc_fraction().unwrap_or(0.0)
+            );
+        }
+        (buckets, counters)
     } else {
+        let spill_layout = SpillLayout::new(&temp_dir).unwrap_or_else(|err| panic!("{}", err));
         (
-            generate_bucket_names(temp_dir.join("bucket"), buckets_count, None),
-            temp_dir.join("buckets-counters.dat"),
+            generate_bucket_names(spill_layout.buckets_prefix(), buckets_count, None),
+            spill_layout.buckets_counters_path(),
         )
     };
 
@@ -143,18 +205,28 @@ pub fn run_assembler<
         PHASES_TIMES_MONITOR
             .write()
             .print_stats("Completed minimizer bucketing.".to_string());
-        return PathBuf::new();
+        return AssemblerOutcome::StoppedAfterStep(AssemblerStartingStep::MinimizerBucketing);
     } else {
         MemoryFs::flush_all_to_disk();
         MemoryFs::free_memory();
     }
 
+    if cancellation_token.is_cancelled() {
+        let checkpoint = write_cancelled_run_checkpoint(
+            AssemblerStartingStep::MinimizerBucketing,
+            temp_dir.as_path(),
+            buckets_count_log,
+        );
+        return AssemblerOutcome::Cancelled { checkpoint };
+    }
+
     if only_bstats {
         use rayon::prelude::*;
         buckets.par_iter().enumerate().for_each(|(index, bucket)| {
             kmers_transform::debug_bucket_stats::compute_stats_for_bucket::<
                 BucketingHash,
                 MergingHash,
+                AssemblerBucketHasher<BucketingHash, AssemblerColorsManager>,
             >(
                 bucket.clone(),
                 index,
@@ -164,37 +236,81 @@ pub fn run_assembler<
                 m,
             );
         });
-        return PathBuf::new();
+        return AssemblerOutcome::StoppedAfterStep(AssemblerStartingStep::MinimizerBucketing);
+    }
+
+    if let Some(export_dir) = export_buckets_reads {
+        use rayon::prelude::*;
+        std::fs::create_dir_all(&export_dir).unwrap();
+        buckets.par_iter().enumerate().for_each(|(index, bucket)| {
+            kmers_transform::buckets_export::export_bucket_reads::<
+                MinimizerBucketingSeqColorDataType<AssemblerColorsManager>,
+                typenum::U2,
+            >(bucket.clone(), index, &export_dir);
+        });
+        return AssemblerOutcome::StoppedAfterStep(AssemblerStartingStep::MinimizerBucketing);
     }
 
     let RetType { sequences, hashes } = if step <= AssemblerStartingStep::KmersMerge {
-        assembler_kmers_merge::kmers_merge::<BucketingHash, MergingHash, AssemblerColorsManager, _>(
+        let (merge_output, status) = assembler_kmers_merge::kmers_merge::<
+            BucketingHash,
+            MergingHash,
+            AssemblerColorsManager,
+            _,
+        >(
             buckets,
             counters,
             global_colors_table.clone(),
             buckets_count,
             min_multiplicity,
+            masked_kmers_file.as_deref(),
             temp_dir.as_path(),
             k,
             m,
             threads_count,
+            cancellation_token,
         )
-    } else {
+        .unwrap_or_else(|err| panic!("Kmers merge failed: {}", err));
+
+        if status != KmersTransformStatus::Completed {
+            // The merge is resumed from the buckets it did not complete
+            MemoryFs::flush_all_to_disk();
+            let checkpoint = write_cancelled_run_checkpoint(
+                AssemblerStartingStep::MinimizerBucketing,
+                temp_dir.as_path(),
+                buckets_count_log,
+            );
+            return AssemblerOutcome::Cancelled { checkpoint };
+        }
+        merge_output
+    } else if AssemblerColorsManager::COLORS_ENABLED {
         RetType {
             sequences: generate_bucket_names(temp_dir.join("result"), buckets_count, None),
             hashes: generate_bucket_names(temp_dir.join("hashes"), buckets_count, None),
         }
+    } else {
+        committed_merge_output(temp_dir.as_path(), buckets_count)
+            .unwrap_or_else(|err| panic!("Cannot load the kmers merge output: {}", err))
     };
     if last_step <= AssemblerStartingStep::KmersMerge {
         PHASES_TIMES_MONITOR
             .write()
             .print_stats("Completed kmers merge.".to_string());
-        return PathBuf::new();
+        return AssemblerOutcome::StoppedAfterStep(AssemblerStartingStep::KmersMerge);
     } else {
         MemoryFs::flush_all_to_disk();
         MemoryFs::free_memory();
     }
 
+    if cancellation_token.is_cancelled() {
+        let checkpoint = write_cancelled_run_checkpoint(
+            AssemblerStartingStep::KmersMerge,
+            temp_dir.as_path(),
+            buckets_count_log,
+        );
+        return AssemblerOutcome::Cancelled { checkpoint };
+    }
+
     AssemblerColorsManager::ColorsMergeManagerType::print_color_stats(&global_colors_table);
 
     drop(global_colors_table);
@@ -208,12 +324,21 @@ pub fn run_assembler<
         PHASES_TIMES_MONITOR
             .write()
             .print_stats("Hashes sorting.".to_string());
-        return PathBuf::new();
+        return AssemblerOutcome::StoppedAfterStep(AssemblerStartingStep::HashesSorting);
     } else {
         MemoryFs::flush_all_to_disk();
         MemoryFs::free_memory();
     }
 
+    if cancellation_token.is_cancelled() {
+        let checkpoint = write_cancelled_run_checkpoint(
+            AssemblerStartingStep::HashesSorting,
+            temp_dir.as_path(),
+            buckets_count_log,
+        );
+        return AssemblerOutcome::Cancelled { checkpoint };
+    }
+
     let mut loop_iteration = loopit_number.unwrap_or(0);
 
     let unames = generate_bucket_names(temp_dir.join("unitigs_map"), buckets_count, None);
@@ -329,12 +454,21 @@ pub fn run_assembler<
         PHASES_TIMES_MONITOR
             .write()
             .print_stats("Links Compaction.".to_string());
-        return PathBuf::new();
+        return AssemblerOutcome::StoppedAfterStep(AssemblerStartingStep::LinksCompaction);
     } else {
         MemoryFs::flush_all_to_disk();
         MemoryFs::free_memory();
     }
 
+    if cancellation_token.is_cancelled() {
+        let checkpoint = write_cancelled_run_checkpoint(
+            AssemblerStartingStep::LinksCompaction,
+            temp_dir.as_path(),
+            buckets_count_log,
+        );
+        return AssemblerOutcome::Cancelled { checkpoint };
+    }
+
     let final_unitigs_file = StructuredSequenceWriter::new(
         match output_file.extension() {
             Some(ext) => match ext.to_string_lossy().to_string().as_str() {
@@ -365,6 +499,18 @@ pub fn run_assembler<
             None
         };
 
+    // The unitigs of each bucket are spilled and written in ascending bucket order after each phase
+    let deterministic_output = DETERMINISTIC_OUTPUT.load(Ordering::Relaxed);
+    let (final_unitigs_file, compressed_temp_unitigs_file) = if deterministic_output {
+        (
+            final_unitigs_file.with_ordered_segments(temp_dir.as_path(), "final"),
+            compressed_temp_unitigs_file
+                .map(|file| file.with_ordered_segments(temp_dir.as_path(), "temp-unitigs")),
+        )
+    } else {
+        (final_unitigs_file, compressed_temp_unitigs_file)
+    };
+
     let (reorganized_reads, _final_unitigs_bucket) = if step
         <= AssemblerStartingStep::ReorganizeReads
     {
@@ -373,6 +519,7 @@ pub fn run_assembler<
                 BucketingHash,
                 MergingHash,
                 AssemblerColorsManager,
+                (),
                 StructSeqBinaryWriter<_, _>,
             >(
                 sequences,
@@ -382,7 +529,13 @@ pub fn run_assembler<
                 buckets_count,
             )
         } else {
-            reorganize_reads::<BucketingHash, MergingHash, AssemblerColorsManager, FastaWriter<_, _>>(
+            reorganize_reads::<
+                BucketingHash,
+                MergingHash,
+                AssemblerColorsManager,
+                CircularUnitigAnnotation,
+                FastaWriter<_, _>,
+            >(
                 sequences,
                 reads_map,
                 temp_dir.as_path(),
@@ -400,16 +553,39 @@ pub fn run_assembler<
         )
     };
 
+    final_unitigs_file
+        .merge_ordered_segments(None)
+        .unwrap_or_else(|err| panic!("Cannot write the ordered unitigs: {}", err));
+    if let Some(compressed_temp_unitigs_file) = &compressed_temp_unitigs_file {
+        compressed_temp_unitigs_file
+            .merge_ordered_segments(None)
+            .unwrap_or_else(|err| panic!("Cannot write the ordered unitigs: {}", err));
+    }
+
     if last_step <= AssemblerStartingStep::ReorganizeReads {
         PHASES_TIMES_MONITOR
             .write()
             .print_stats("Reorganize reads.".to_string());
-        return PathBuf::new();
+        return AssemblerOutcome::StoppedAfterStep(AssemblerStartingStep::ReorganizeReads);
     } else {
         MemoryFs::flush_all_to_disk();
         MemoryFs::free_memory();
     }
 
+    if cancellation_token.is_cancelled() {
+        // Flush the unitigs written so far
+        final_unitigs_file.finalize();
+        if let Some(compressed_temp_unitigs_file) = compressed_temp_unitigs_file {
+            compressed_temp_unitigs_file.finalize();
+        }
+        let checkpoint = write_cancelled_run_checkpoint(
+            AssemblerStartingStep::ReorganizeReads,
+            temp_dir.as_path(),
+            buckets_count_log,
+        );
+        return AssemblerOutcome::Cancelled { checkpoint };
+    }
+
     // links_manager.compute_id_offsets();
 
     if step <= AssemblerStartingStep::BuildUnitigs {
@@ -418,22 +594,58 @@ pub fn run_assembler<
                 BucketingHash,
                 MergingHash,
                 AssemblerColorsManager,
+                (),
                 StructSeqBinaryWriter<_, _>,
             >(
                 reorganized_reads,
                 unitigs_map,
                 temp_dir.as_path(),
                 compressed_temp_unitigs_file.as_ref().unwrap(),
+                None,
                 k,
             );
+            compressed_temp_unitigs_file
+                .as_ref()
+                .unwrap()
+                .merge_ordered_segments(None)
+                .unwrap_or_else(|err| panic!("Cannot write the ordered unitigs: {}", err));
         } else {
-            build_unitigs::<BucketingHash, MergingHash, AssemblerColorsManager, FastaWriter<_, _>>(
+            // The provenance is only meaningful when the unitigs indexes are not changed by further processing,
+            // so it is not supported with the links or the tigs output
+            let provenance_file = if WRITE_UNITIGS_PROVENANCE.load(Ordering::Relaxed) {
+                Some(
+                    SequencesProvenanceWriter::new(output_file.with_extension("provenance.tsv"))
+                        .unwrap_or_else(|err| {
+                            panic!("Cannot create the unitigs provenance file: {}", err)
+                        }),
+                )
+            } else {
+                None
+            };
+
+            build_unitigs::<
+                BucketingHash,
+                MergingHash,
+                AssemblerColorsManager,
+                CircularUnitigAnnotation,
+                FastaWriter<_, _>,
+            >(
                 reorganized_reads,
                 unitigs_map,
                 temp_dir.as_path(),
                 &final_unitigs_file,
+                provenance_file.as_ref(),
                 k,
             );
+            final_unitigs_file
+                .merge_ordered_segments(provenance_file.as_ref())
+                .unwrap_or_else(|err| panic!("Cannot write the ordered unitigs: {}", err));
+
+            if let Some(provenance_file) = provenance_file {
+                provenance_file.finalize().unwrap_or_else(|err| {
+                    panic!("Cannot write the unitigs provenance file: {}", err)
+                });
+            }
         }
     }
 
@@ -456,6 +668,7 @@ pub fn run_assembler<
                             MergingHash,
                             AssemblerColorsManager,
                             _,
+                            _,
                         >(
                             k,
                             threads_count,
@@ -475,6 +688,8 @@ pub fn run_assembler<
                     temp_path,
                     temp_dir.as_path(),
                     &StructuredSequenceWriter::new(matchtigs_backend, k),
+                    None,
+                    None,
                     k,
                 );
 
@@ -494,13 +709,39 @@ pub fn run_assembler<
                     k,
                 );
 
+                let edge_list = if WRITE_EDGE_LIST.load(Ordering::Relaxed) {
+                    Some(EdgeListWriter::new(output_file.with_extension("edges.tsv")))
+                } else {
+                    None
+                };
+
+                let graph_dump = if WRITE_GRAPH_DUMP.load(Ordering::Relaxed) {
+                    Some(GraphDumpWriter::new(output_file.with_extension("ggraph"), k))
+                } else {
+                    None
+                };
+
                 build_maximal_unitigs_links::<
                     BucketingHash,
                     MergingHash,
                     AssemblerColorsManager,
                     FastaWriter<_, _>,
-                >(temp_path, temp_dir.as_path(), &final_unitigs_file, k);
+                >(
+                    temp_path,
+                    temp_dir.as_path(),
+                    &final_unitigs_file,
+                    edge_list.as_ref(),
+                    graph_dump.as_ref(),
+                    k,
+                );
                 final_unitigs_file.finalize();
+
+                if let Some(edge_list) = edge_list {
+                    edge_list.finalize();
+                }
+                if let Some(graph_dump) = graph_dump {
+                    graph_dump.finalize();
+                }
             }
         } else {
             final_unitigs_file.finalize();
@@ -515,5 +756,5 @@ pub fn run_assembler<
         .write()
         .print_stats("Compacted De Bruijn graph construction completed.".to_string());
 
-    output_file
+    AssemblerOutcome::Completed(output_file)
 }
@@ -2,12 +2,15 @@ use crate::pipeline::reorganize_reads::ReorganizedReadsExtraData;
 use colors::colors_manager::color_types::PartialUnitigsColorStructure;
 use colors::colors_manager::ColorsMergeManager;
 use colors::colors_manager::{color_types, ColorsManager};
-use config::{DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES};
+use config::{get_output_buffer_size, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES};
 use hashbrown::HashMap;
 use hashes::{HashFunctionFactory, HashableSequence, MinimizerHashFunctionFactory};
 use io::compressed_read::CompressedReadIndipendent;
 use io::concurrent::structured_sequences::concurrent::FastaWriterConcurrentBuffer;
-use io::concurrent::structured_sequences::{StructuredSequenceBackend, StructuredSequenceWriter};
+use io::concurrent::structured_sequences::provenance::SequencesProvenanceWriter;
+use io::concurrent::structured_sequences::{
+    StructuredSequenceBackend, StructuredSequenceWriter, UnitigAnnotation,
+};
 use io::concurrent::temp_reads::creads_utils::CompressedReadsBucketDataSerializer;
 use io::concurrent::temp_reads::extra_data::SequenceExtraDataTempBufferManagement;
 use io::get_bucket_index;
@@ -70,12 +73,14 @@ pub fn build_unitigs<
     H: MinimizerHashFunctionFactory,
     MH: HashFunctionFactory,
     CX: ColorsManager,
-    BK: StructuredSequenceBackend<PartialUnitigsColorStructure<H, MH, CX>, ()>,
+    LI: UnitigAnnotation,
+    BK: StructuredSequenceBackend<PartialUnitigsColorStructure<H, MH, CX>, LI>,
 >(
     mut read_buckets_files: Vec<PathBuf>,
     mut unitig_map_files: Vec<PathBuf>,
     _temp_path: &Path,
-    out_file: &StructuredSequenceWriter<PartialUnitigsColorStructure<H, MH, CX>, (), BK>,
+    out_file: &StructuredSequenceWriter<PartialUnitigsColorStructure<H, MH, CX>, LI, BK>,
+    provenance_file: Option<&SequencesProvenanceWriter>,
     k: usize,
 ) {
     PHASES_TIMES_MONITOR
@@ -95,9 +100,6 @@ pub fn build_unitigs<
             .par_iter()
             .enumerate()
             .for_each(|(_index, (read_file, unitigs_map_file))| {
-                let mut tmp_final_unitigs_buffer =
-                    FastaWriterConcurrentBuffer::new(out_file, DEFAULT_OUTPUT_BUFFER_SIZE, true);
-
                 assert_eq!(
                     get_bucket_index(read_file),
                     get_bucket_index(unitigs_map_file)
@@ -105,6 +107,13 @@ pub fn build_unitigs<
 
                 let bucket_index = get_bucket_index(read_file);
 
+                let mut tmp_final_unitigs_buffer =
+                    FastaWriterConcurrentBuffer::new(out_file, get_output_buffer_size(), true)
+                        .with_provenance(provenance_file, bucket_index)
+                        .with_output_bucket(bucket_index);
+
+                let links_buffer = LI::new_temp_buffer();
+
                 let mut unitigs_map_reader = LockFreeBinaryReader::new(
                     &unitigs_map_file,
                     RemoveFileMode::Remove {
@@ -344,8 +353,8 @@ pub fn build_unitigs<
                         None,
                         writable_color,
                         &final_color_extra_buffer,
-                        (),
-                        &(),
+                        LI::new_unitig(is_circular),
+                        &links_buffer,
                         #[cfg(feature = "support_kmer_counters")]
                         abundance,
                     );
@@ -1,7 +1,7 @@
 use crate::pipeline::maximal_unitig_links::maximal_unitig_index::DoubleMaximalUnitigLinks;
 use colors::colors_manager::color_types::PartialUnitigsColorStructure;
 use colors::colors_manager::{color_types, ColorsManager, ColorsMergeManager};
-use config::DEFAULT_OUTPUT_BUFFER_SIZE;
+use config::get_output_buffer_size;
 use crossbeam::channel::{Receiver, Sender};
 use genome_graph::bigraph::implementation::node_bigraph_wrapper::NodeBigraphWrapper;
 use genome_graph::bigraph::interface::BidirectedData;
@@ -13,7 +13,8 @@ use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
 use io::compressed_read::CompressedReadIndipendent;
 use io::concurrent::structured_sequences::concurrent::FastaWriterConcurrentBuffer;
 use io::concurrent::structured_sequences::{
-    IdentSequenceWriter, SequenceAbundanceType, StructuredSequenceBackend, StructuredSequenceWriter,
+    IdentSequenceWriter, SequenceAbundanceType, StructuredSequenceBackend,
+    StructuredSequenceWriter, UnitigAnnotation,
 };
 use io::concurrent::temp_reads::extra_data::SequenceExtraDataTempBufferManagement;
 use libmatchtigs::{
@@ -312,12 +313,13 @@ pub fn compute_matchtigs_thread<
     H: MinimizerHashFunctionFactory,
     MH: HashFunctionFactory,
     CX: ColorsManager,
-    BK: StructuredSequenceBackend<PartialUnitigsColorStructure<H, MH, CX>, ()>,
+    LI: UnitigAnnotation,
+    BK: StructuredSequenceBackend<PartialUnitigsColorStructure<H, MH, CX>, LI>,
 >(
     k: usize,
     threads_count: usize,
     input_data: Receiver<Arc<StructuredUnitigsStorage<PartialUnitigsColorStructure<H, MH, CX>>>>,
-    out_file: &StructuredSequenceWriter<PartialUnitigsColorStructure<H, MH, CX>, (), BK>,
+    out_file: &StructuredSequenceWriter<PartialUnitigsColorStructure<H, MH, CX>, LI, BK>,
     mode: MatchtigMode,
 ) {
     let iterator = input_data
@@ -393,7 +395,8 @@ pub fn compute_matchtigs_thread<
         .start_phase(format!("phase: {} building [step2]", phase_name));
 
     let mut output_buffer =
-        FastaWriterConcurrentBuffer::new(&out_file, DEFAULT_OUTPUT_BUFFER_SIZE, true);
+        FastaWriterConcurrentBuffer::new(&out_file, get_output_buffer_size(), true);
+    let links_buffer = LI::new_temp_buffer();
 
     let mut read_buffer = Vec::new();
 
@@ -515,8 +518,8 @@ pub fn compute_matchtigs_thread<
             None,
             writable_color,
             &final_color_extra_buffer,
-            (),
-            &(),
+            LI::new_unitig(false),
+            &links_buffer,
             #[cfg(feature = "support_kmer_counters")]
             abundance,
         );
@@ -1,7 +1,9 @@
+pub mod edge_list;
 mod mappings_loader;
 mod maximal_hash_entry;
 pub(crate) mod maximal_unitig_index;
 
+use crate::pipeline::maximal_unitig_links::edge_list::EdgeListWriter;
 use crate::pipeline::maximal_unitig_links::mappings_loader::{
     MaximalUnitigLinksMapping, MaximalUnitigLinksMappingsLoader,
 };
@@ -15,14 +17,15 @@ use crate::pipeline::maximal_unitig_links::maximal_unitig_index::{
 use colors::colors_manager::color_types::PartialUnitigsColorStructure;
 use colors::colors_manager::ColorsManager;
 use config::{
-    get_compression_level_info, get_memory_mode, BucketIndexType, SwapPriority,
-    DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
+    get_compression_level_info, get_memory_mode, get_output_buffer_size, BucketIndexType,
+    SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
 };
 use dashmap::DashSet;
 use hashbrown::HashSet;
 use hashes::{ExtendableHashTraitType, HashFunction, HashableSequence};
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
 use io::concurrent::structured_sequences::concurrent::FastaWriterConcurrentBuffer;
+use io::concurrent::structured_sequences::graph_dump::GraphDumpWriter;
 use io::concurrent::structured_sequences::{
     SequenceAbundanceType, StructuredSequenceBackend, StructuredSequenceWriter,
 };
@@ -58,6 +61,8 @@ pub fn build_maximal_unitigs_links<
         DoubleMaximalUnitigLinks,
         BK,
     >,
+    edge_list: Option<&EdgeListWriter>,
+    graph_dump: Option<&GraphDumpWriter>,
     k: usize,
 ) {
     // TODO: Parametrize depending on the reads count!
@@ -335,11 +340,13 @@ pub fn build_maximal_unitigs_links<
                 .for_each(|thread_index| {
                     let mut tmp_final_unitigs_buffer = FastaWriterConcurrentBuffer::new(
                         out_file,
-                        DEFAULT_OUTPUT_BUFFER_SIZE,
+                        get_output_buffer_size(),
                         false,
                     );
 
                     let mut temp_sequence_buffer = Vec::new();
+                    let mut edges_buffer = Vec::new();
+                    let mut graph_dump_buffer = Vec::new();
 
                     let mut current_mapping = Arc::new(MaximalUnitigLinksMapping::empty());
 
@@ -370,6 +377,22 @@ pub fn build_maximal_unitigs_links<
                             let (mut links, links_buffer) = current_mapping.get_mapping(index);
                             links.is_self_complemental = self_complemental_unitigs.contains(&index);
 
+                            if edge_list.is_some() {
+                                links.write_as_edge_list(index, &mut edges_buffer, links_buffer);
+                            }
+
+                            if graph_dump.is_some() {
+                                GraphDumpWriter::encode_entry(
+                                    &mut graph_dump_buffer,
+                                    index,
+                                    &temp_sequence_buffer,
+                                    &color,
+                                    &links,
+                                    &_abundance,
+                                    (&extra_buffer.0, links_buffer),
+                                );
+                            }
+
                             tmp_final_unitigs_buffer.add_read(
                                 &temp_sequence_buffer,
                                 Some(index),
@@ -383,6 +406,14 @@ pub fn build_maximal_unitigs_links<
                         },
                     ) {
                         tmp_final_unitigs_buffer.flush();
+                        if let Some(edge_list) = edge_list {
+                            edge_list.write_edges(&edges_buffer);
+                            edges_buffer.clear();
+                        }
+                        if let Some(graph_dump) = graph_dump {
+                            graph_dump.write_entries(&graph_dump_buffer);
+                            graph_dump_buffer.clear();
+                        }
                     }
 
                     mappings_loader.notify_thread_ending(thread_index);
@@ -0,0 +1,25 @@
+use parking_lot::Mutex;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Plain edge list of the compacted graph, one `<source>\t<+/->\t<dest>\t<+/->` line for each link
+pub struct EdgeListWriter {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl EdgeListWriter {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            writer: Mutex::new(BufWriter::new(File::create(path).unwrap())),
+        }
+    }
+
+    pub fn write_edges(&self, edges: &[u8]) {
+        self.writer.lock().write_all(edges).unwrap();
+    }
+
+    pub fn finalize(self) {
+        self.writer.into_inner().flush().unwrap();
+    }
+}
@@ -189,6 +189,29 @@ impl DoubleMaximalUnitigLinks {
         ],
         is_self_complemental: false,
     };
+
+    /// Writes the links of the unitig as edge list lines, with the same orientations of the `L:` annotations
+    pub fn write_as_edge_list(
+        &self,
+        index: u64,
+        stream: &mut impl Write,
+        extra_buffer: &Vec<MaximalUnitigIndex>,
+    ) {
+        for entries in &self.links {
+            let entries = entries.entries.get_slice(extra_buffer);
+            for entry in entries {
+                writeln!(
+                    stream,
+                    "{}\t{}\t{}\t{}",
+                    index,
+                    if entry.flags.flip_current() { "-" } else { "+" },
+                    entry.index,
+                    if entry.flags.flip_other() { "-" } else { "+" },
+                )
+                .unwrap();
+            }
+        }
+    }
 }
 
 impl SequenceExtraDataTempBufferManagement for DoubleMaximalUnitigLinks {
@@ -239,16 +262,44 @@ impl SequenceExtraDataTempBufferManagement for DoubleMaximalUnitigLinks {
 }
 
 impl SequenceExtraData for DoubleMaximalUnitigLinks {
-    fn decode_extended(_buffer: &mut Self::TempBuffer, _reader: &mut impl Read) -> Option<Self> {
-        unimplemented!()
+    fn decode_extended(buffer: &mut Self::TempBuffer, reader: &mut impl Read) -> Option<Self> {
+        let mut decode_link = || {
+            let index = decode_varint(|| reader.read_u8().ok())?;
+            let len = decode_varint(|| reader.read_u8().ok())? as usize;
+
+            let start = buffer.len();
+            for _ in 0..len {
+                buffer.push(MaximalUnitigIndex::decode_extended(&mut (), reader)?);
+            }
+            Some(MaximalUnitigLink::new(index, VecSlice::new(start, len)))
+        };
+
+        let links = [decode_link()?, decode_link()?];
+        Some(Self {
+            links,
+            is_self_complemental: reader.read_u8().ok()? != 0,
+        })
     }
 
-    fn encode_extended(&self, _buffer: &Self::TempBuffer, _writer: &mut impl Write) {
-        unimplemented!()
+    fn encode_extended(&self, buffer: &Self::TempBuffer, writer: &mut impl Write) {
+        for link in &self.links {
+            encode_varint(|b| writer.write_all(b), link.index()).unwrap();
+
+            let entries = link.entries.get_slice(buffer);
+            encode_varint(|b| writer.write_all(b), entries.len() as u64).unwrap();
+            for entry in entries {
+                entry.encode_extended(&(), writer);
+            }
+        }
+        writer
+            .write_all(&[self.is_self_complemental as u8])
+            .unwrap();
     }
 
     fn max_size(&self) -> usize {
-        unimplemented!()
+        VARINT_MAX_SIZE * 4
+            + (self.links[0].entries.len() + self.links[1].entries.len()) * (VARINT_MAX_SIZE + 1)
+            + 1
     }
 }
 
@@ -282,3 +333,66 @@ impl IdentSequenceWriter for DoubleMaximalUnitigLinks {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::maximal_unitig_links::maximal_unitig_index::{
+        DoubleMaximalUnitigLinks, MaximalUnitigFlags, MaximalUnitigIndex, MaximalUnitigLink,
+    };
+    use std::collections::BTreeSet;
+    use utils::vec_slice::VecSlice;
+
+    #[test]
+    fn branched_graph_edge_list() {
+        // Unitig 0 branches at its end into unitigs 1 and 2, unitig 2 is joined to unitig 3 in reverse orientation
+        let mut links_buffer = vec![];
+        let mut graph = vec![];
+
+        let mut add_unitig =
+            |index: u64, forward: &[(u64, bool, bool)], backward: &[(u64, bool, bool)]| {
+                let mut links = DoubleMaximalUnitigLinks::EMPTY;
+                for (side, entries) in [forward, backward].into_iter().enumerate() {
+                    let start = links_buffer.len();
+                    for (other, flip_current, flip_other) in entries {
+                        links_buffer.push(MaximalUnitigIndex::new(
+                            *other,
+                            MaximalUnitigFlags::new_direction(*flip_current, *flip_other),
+                        ));
+                    }
+                    links.links[side] =
+                        MaximalUnitigLink::new(index, VecSlice::new(start, entries.len()));
+                }
+                graph.push((index, links));
+            };
+
+        add_unitig(0, &[(1, false, false), (2, false, false)], &[]);
+        add_unitig(1, &[], &[(0, true, true)]);
+        add_unitig(2, &[(3, false, true)], &[(0, true, true)]);
+        add_unitig(3, &[(2, false, true)], &[]);
+
+        let mut edge_list = vec![];
+        for (index, links) in &graph {
+            links.write_as_edge_list(*index, &mut edge_list, &links_buffer);
+        }
+
+        let edges: BTreeSet<_> = std::str::from_utf8(&edge_list)
+            .unwrap()
+            .lines()
+            .map(|l| l.to_string())
+            .collect();
+
+        let expected: BTreeSet<_> = [
+            "0\t+\t1\t+",
+            "0\t+\t2\t+",
+            "1\t-\t0\t-",
+            "2\t+\t3\t-",
+            "2\t-\t0\t-",
+            "3\t+\t2\t-",
+        ]
+        .into_iter()
+        .map(|l| l.to_string())
+        .collect();
+
+        assert_eq!(edges, expected);
+    }
+}
@@ -1,4 +1,4 @@
-use assembler_kmers_merge::structs::PartialUnitigExtraData;
+use assembler_kmers_merge::structs::{PartialUnitigExtraData, PARTIAL_UNITIG_FLAG_CIRCULAR};
 use config::{
     get_compression_level_info, get_memory_mode, SwapPriority, DEFAULT_PER_CPU_BUFFER_SIZE,
     DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
@@ -13,9 +13,11 @@ use structs::unitigs_counters::UnitigsCounters;
 use crate::structs::link_mapping::{LinkMapping, LinkMappingSerializer};
 use colors::colors_manager::color_types::PartialUnitigsColorStructure;
 use colors::colors_manager::{color_types, ColorsManager};
-use config::DEFAULT_OUTPUT_BUFFER_SIZE;
+use config::get_output_buffer_size;
 use io::concurrent::structured_sequences::concurrent::FastaWriterConcurrentBuffer;
-use io::concurrent::structured_sequences::{StructuredSequenceBackend, StructuredSequenceWriter};
+use io::concurrent::structured_sequences::{
+    StructuredSequenceBackend, StructuredSequenceWriter, UnitigAnnotation,
+};
 use io::concurrent::temp_reads::extra_data::{
     SequenceExtraData, SequenceExtraDataConsecutiveCompression, SequenceExtraDataOwned,
     SequenceExtraDataTempBufferManagement,
@@ -156,12 +158,13 @@ pub fn reorganize_reads<
     H: MinimizerHashFunctionFactory,
     MH: HashFunctionFactory,
     CX: ColorsManager,
-    BK: StructuredSequenceBackend<PartialUnitigsColorStructure<H, MH, CX>, ()>,
+    LI: UnitigAnnotation,
+    BK: StructuredSequenceBackend<PartialUnitigsColorStructure<H, MH, CX>, LI>,
 >(
     mut reads: Vec<PathBuf>,
     mut mapping_files: Vec<PathBuf>,
     temp_path: &Path,
-    out_file: &StructuredSequenceWriter<PartialUnitigsColorStructure<H, MH, CX>, (), BK>,
+    out_file: &StructuredSequenceWriter<PartialUnitigsColorStructure<H, MH, CX>, LI, BK>,
     buckets_count: usize,
 ) -> (Vec<PathBuf>, PathBuf) {
     PHASES_TIMES_MONITOR
@@ -200,7 +203,9 @@ pub fn reorganize_reads<
         >::new(&buckets, buffers.take());
 
         let mut tmp_lonely_unitigs_buffer =
-            FastaWriterConcurrentBuffer::new(out_file, DEFAULT_OUTPUT_BUFFER_SIZE, true);
+            FastaWriterConcurrentBuffer::new(out_file, get_output_buffer_size(), true)
+                .with_output_bucket(get_bucket_index(read_file));
+        let links_buffer = LI::new_temp_buffer();
 
         let mut mappings = Vec::new();
 
@@ -239,12 +244,12 @@ pub fn reorganize_reads<
         )
         .decode_all_bucket_items::<CompressedReadsBucketDataSerializer<
             PartialUnitigExtraData<color_types::PartialUnitigsColorStructure<H, MH, CX>>,
-            typenum::U0,
+            typenum::U1,
             false,
         >, _>(
             Vec::new(),
             &mut colors_buffer,
-            |(_, _, extra_data, seq), color_buffer| {
+            |(flags, _, extra_data, seq), color_buffer| {
                 if seq.bases_count() > decompress_buffer.len() {
                     decompress_buffer.resize(seq.bases_count(), 0);
                 }
@@ -274,8 +279,8 @@ pub fn reorganize_reads<
                         None,
                         extra_data.colors,
                         color_buffer,
-                        (),
-                        &(),
+                        LI::new_unitig((flags & PARTIAL_UNITIG_FLAG_CIRCULAR) != 0),
+                        &links_buffer,
                         #[cfg(feature = "support_kmer_counters")]
                         SequenceAbundance {
                             first: extra_data.counters.first,
@@ -1,11 +1,12 @@
 use crate::map_processor::ParallelKmersMergeMapPacket;
-use crate::structs::PartialUnitigExtraData;
+use crate::masked_kmers::is_kmer_masked;
+use crate::structs::{PartialUnitigExtraData, PARTIAL_UNITIG_FLAG_CIRCULAR};
 use crate::{GlobalMergeData, ParallelKmersMergeFactory, ResultsBucket};
 use colors::colors_manager::ColorsMergeManager;
 use colors::colors_manager::{color_types, ColorsManager};
-use config::DEFAULT_PER_CPU_BUFFER_SIZE;
 use config::{READ_FLAG_INCL_BEGIN, READ_FLAG_INCL_END};
 use core::slice::from_raw_parts;
+use hashbrown::{HashMap, HashSet};
 use hashes::HashFunction;
 use hashes::{ExtendableHashTraitType, HashFunctionFactory, MinimizerHashFunctionFactory};
 use instrumenter::local_setup_instrumenter;
@@ -13,10 +14,11 @@ use io::compressed_read::CompressedRead;
 use io::concurrent::temp_reads::extra_data::SequenceExtraDataTempBufferManagement;
 use io::structs::hash_entry::{Direction, HashEntrySerializer};
 use io::varint::decode_varint;
-use kmers_transform::{KmersTransformExecutorFactory, KmersTransformFinalExecutor};
+use kmers_transform::{FinalStats, KmersTransformExecutorFactory, KmersTransformFinalExecutor};
 use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
 use parallel_processor::buckets::writers::lock_free_binary_writer::LockFreeBinaryWriter;
 use parallel_processor::execution_manager::packet::Packet;
+use std::hash::Hash;
 use std::marker::PhantomData;
 use std::ops::DerefMut;
 use structs::map_entry::MapEntry;
@@ -26,6 +28,159 @@ use utils::Utils;
 
 local_setup_instrumenter!();
 
+/// Tries each base after the kmer, returning how many of the resulting kmers are kept in the graph
+/// and the hash and base of the last one found. The filtered kmers are not successors
+#[inline(always)]
+pub(crate) fn find_successors<MH: HashFunctionFactory>(
+    hash: MH::HashTypeExtendable,
+    k: usize,
+    out_base: u8,
+    compute_hash: fn(
+        hash: MH::HashTypeExtendable,
+        kmer_length: usize,
+        out_b: u8,
+        in_b: u8,
+    ) -> MH::HashTypeExtendable,
+    mut is_kmer_valid: impl FnMut(&MH::HashTypeUnextendable) -> bool,
+) -> (usize, Option<(MH::HashTypeExtendable, u8)>) {
+    let mut count = 0;
+    let mut successor = None;
+    for idx in 0..4 {
+        let new_hash = compute_hash(hash, k, out_base, idx);
+        if is_kmer_valid(&new_hash.to_unextendable()) {
+            count += 1;
+            successor = Some((new_hash, idx));
+        }
+    }
+    (count, successor)
+}
+
+/// Kmers kept in the graph, and the ones where the unitigs extension stops
+pub(crate) struct KmersFilter<'a, T> {
+    pub min_multiplicity: usize,
+    pub max_multiplicity: Option<usize>,
+    pub masked_kmers: Option<&'a HashSet<T>>,
+}
+
+impl<'a, T: Hash + Eq> KmersFilter<'a, T> {
+    /// The kmers with a multiplicity out of range are removed from the graph
+    #[inline(always)]
+    pub fn is_kmer_valid<CHI>(&self, entry: &MapEntry<CHI>) -> bool {
+        entry.is_multiplicity_in_range(self.min_multiplicity, self.max_multiplicity)
+    }
+
+    /// The masked kmers are kept in the graph, but no unitig extends through them
+    #[inline(always)]
+    pub fn is_kmer_masked(&self, hash: &T) -> bool {
+        is_kmer_masked(self.masked_kmers, hash)
+    }
+}
+
+/// Last kmer reached by the extension of a unitig
+pub(crate) struct UnitigEnd<HE> {
+    pub hash: HE,
+    /// The unitig continues in another bucket
+    pub continues: bool,
+    /// The extension reached a kmer already in the unitig
+    pub is_cycle: bool,
+}
+
+/// Extends the unitig in output, ending with the kmer of the given hash, while its last kmer has a single
+/// successor with a single predecessor. A masked kmer is a unitig on its own: the extension does not start
+/// from it and stops right before it. Calls on_extend with the entry of each kmer added to the unitig
+#[inline(always)]
+pub(crate) fn extend_unitig<MH: HashFunctionFactory, CHI>(
+    rhash_map: &HashMap<MH::HashTypeUnextendable, MapEntry<CHI>>,
+    filter: &KmersFilter<MH::HashTypeUnextendable>,
+    hash: MH::HashTypeExtendable,
+    k: usize,
+    output: &mut Vec<u8>,
+    compute_hash_fw: fn(
+        hash: MH::HashTypeExtendable,
+        kmer_length: usize,
+        out_b: u8,
+        in_b: u8,
+    ) -> MH::HashTypeExtendable,
+    compute_hash_bw: fn(
+        hash: MH::HashTypeExtendable,
+        kmer_length: usize,
+        out_b: u8,
+        in_b: u8,
+    ) -> MH::HashTypeExtendable,
+    mut on_extend: impl FnMut(&MapEntry<CHI>),
+) -> UnitigEnd<MH::HashTypeExtendable> {
+    let stop = |hash| UnitigEnd {
+        hash,
+        continues: false,
+        is_cycle: false,
+    };
+
+    if filter.is_kmer_masked(&hash.to_unextendable()) {
+        return stop(hash);
+    }
+
+    let is_kmer_valid = |hash: &MH::HashTypeUnextendable| {
+        rhash_map
+            .get(hash)
+            .map_or(false, |entry| filter.is_kmer_valid(entry))
+    };
+
+    let mut current_hash = hash;
+    loop {
+        let (new_hash, base) = match find_successors::<MH>(
+            current_hash,
+            k,
+            Utils::compress_base(output[output.len() - k]),
+            compute_hash_fw,
+            is_kmer_valid,
+        ) {
+            (1, Some(successor)) => successor,
+            _ => return stop(current_hash),
+        };
+
+        // Test for backward branches
+        let (ocount, _) = find_successors::<MH>(new_hash, k, base, compute_hash_bw, is_kmer_valid);
+        if ocount > 1 {
+            return stop(current_hash);
+        }
+        assert_eq!(ocount, 1);
+
+        if filter.is_kmer_masked(&new_hash.to_unextendable()) {
+            return stop(current_hash);
+        }
+
+        let entryref = rhash_map.get(&new_hash.to_unextendable()).unwrap();
+
+        // Found a cycle unitig
+        if entryref.is_used() {
+            return UnitigEnd {
+                hash: new_hash,
+                continues: false,
+                is_cycle: true,
+            };
+        }
+
+        on_extend(entryref);
+
+        // Flag the entry as already used
+        entryref.set_used();
+
+        output.push(Utils::decompress_base(base));
+
+        // Found a continuation into another bucket
+        let contig_break = (entryref.get_flags() == READ_FLAG_INCL_BEGIN)
+            || (entryref.get_flags() == READ_FLAG_INCL_END);
+        if contig_break {
+            return UnitigEnd {
+                hash: new_hash,
+                continues: true,
+                is_cycle: false,
+            };
+        }
+        current_hash = new_hash;
+    }
+}
+
 pub struct ParallelKmersMergeFinalExecutor<
     H: MinimizerHashFunctionFactory,
     MH: HashFunctionFactory,
@@ -44,6 +199,7 @@ pub struct ParallelKmersMergeFinalExecutor<
         <color_types::PartialUnitigsColorStructure<H, MH, CX> as SequenceExtraDataTempBufferManagement>::TempBuffer,
     bucket_counter: usize,
     bucket_change_threshold: usize,
+    stats: FinalStats,
     _phantom: PhantomData<H>,
 }
 
@@ -52,7 +208,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
 {
     pub fn new(global_data: &GlobalMergeData<H, MH, CX>) -> Self {
         let hashes_buffer =
-            BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, global_data.buckets_count);
+            BucketsThreadBuffer::new(global_data.output_buffer_size, global_data.buckets_count);
 
         Self {
             hashes_tmp: BucketsThreadDispatcher::new(&global_data.hashes_buckets, hashes_buffer),
@@ -65,10 +221,22 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                 color_types::PartialUnitigsColorStructure::<H, MH, CX>::new_temp_buffer(),
             bucket_counter: 0,
             bucket_change_threshold: 16, // TODO: Parametrize
+            stats: FinalStats::default(),
             _phantom: PhantomData,
         }
     }
 
+    #[inline(always)]
+    fn kmers_filter(
+        global_data: &GlobalMergeData<H, MH, CX>,
+    ) -> KmersFilter<'_, MH::HashTypeUnextendable> {
+        KmersFilter {
+            min_multiplicity: global_data.min_multiplicity,
+            max_multiplicity: global_data.max_multiplicity,
+            masked_kmers: global_data.masked_kmers.as_ref(),
+        }
+    }
+
     fn get_kmers(
         global_data: &<ParallelKmersMergeFactory<H, MH, CX> as KmersTransformExecutorFactory>::GlobalExtraData,
         map_struct: &ParallelKmersMergeMapPacket<H, MH, CX>,
@@ -79,11 +247,11 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         ),
     ) {
         let k = global_data.k;
+        let filter = Self::kmers_filter(global_data);
 
         if MH::INVERTIBLE {
             for (hash, rhentry) in map_struct.rhash_map.iter() {
-                let count = rhentry.get_kmer_multiplicity();
-                if count < global_data.min_multiplicity {
+                if !filter.is_kmer_valid(rhentry) {
                     continue;
                 }
 
@@ -139,8 +307,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                         }
                     };
 
-                    let count = rhentry.get_kmer_multiplicity();
-                    if count < global_data.min_multiplicity {
+                    if !filter.is_kmer_valid(rhentry) {
                         continue;
                     }
 
@@ -181,12 +348,26 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         global_data: &<ParallelKmersMergeFactory<H, MH, CX> as KmersTransformExecutorFactory>::GlobalExtraData,
         mut map_struct_packet: Packet<Self::MapStruct>,
     ) -> Packet<Self::MapStruct> {
+        let map_struct = map_struct_packet.deref_mut();
+
+        // Only the counters of a partial map are kept, the unitigs are built once the group is complete,
+        // so nothing is written for a group that fails after being flushed
+        if map_struct.is_partial {
+            map_struct.flushed_kmers.add_map(&map_struct.rhash_map);
+            map_struct.rhash_map.clear();
+            map_struct.is_partial = false;
+            return map_struct_packet;
+        }
+        map_struct.flushed_kmers.merge_into(
+            &mut map_struct.rhash_map,
+            global_data.min_multiplicity,
+            CX::ColorsMergeManagerType::<H, MH>::new_color_index,
+        );
+
         if self.current_bucket.is_none() {
             self.current_bucket = Some(global_data.output_results_buckets.pop().unwrap());
         }
 
-        let map_struct = map_struct_packet.deref_mut();
-
         let k = global_data.k;
         let buckets_count = global_data.buckets_count;
         let buckets_count_bits = buckets_count.ilog2() as usize;
@@ -201,6 +382,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                 &mut map_struct.rhash_map,
                 global_data.k,
                 global_data.min_multiplicity,
+                global_data.max_multiplicity,
             );
         }
 
@@ -209,6 +391,8 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
             return map_struct_packet;
         }
 
+        let filter = Self::kmers_filter(global_data);
+
         Self::get_kmers(global_data, map_struct, |hash, cread, rhentry| {
             let ignored_status = rhentry.get_flags();
 
@@ -253,6 +437,8 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                 last: first_count,
             };
 
+            let mut found_cycle = false;
+
             let mut try_extend_function =
                 |output: &mut Vec<u8>,
                  compute_hash_fw: fn(
@@ -272,82 +458,22 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                     entry: &MapEntry<color_types::HashMapTempColorIndex<H, MH, CX>>,
                 ),
                  #[cfg(feature = "support_kmer_counters")] is_forward: bool| {
-                    let mut temp_data = (hash, 0);
-                    let mut current_hash;
-
-                    return 'ext_loop: loop {
-                        let mut count = 0;
-                        current_hash = temp_data.0;
-                        #[cfg(feature = "support_kmer_counters")]
-                        let mut multiplicity = 0;
-                        for idx in 0..4 {
-                            let new_hash = compute_hash_fw(
-                                current_hash,
-                                k,
-                                Utils::compress_base(output[output.len() - k]),
-                                idx,
-                            );
-                            if let Some(hash) =
-                                map_struct.rhash_map.get(&new_hash.to_unextendable())
-                            {
-                                if hash.get_kmer_multiplicity() >= global_data.min_multiplicity {
-                                    // println!("Forward match extend read {:x?}!", new_hash);
-                                    #[cfg(feature = "support_kmer_counters")]
-                                    {
-                                        multiplicity = hash.get_kmer_multiplicity() as u64;
-                                    }
-                                    count += 1;
-                                    temp_data = (new_hash, idx);
-                                }
-                            }
-                        }
-
-                        if count == 1 {
-                            // Test for backward branches
-                            {
-                                let mut ocount = 0;
-                                let new_hash = temp_data.0;
-                                for idx in 0..4 {
-                                    let bw_hash = compute_hash_bw(new_hash, k, temp_data.1, idx);
-                                    if let Some(hash) =
-                                        map_struct.rhash_map.get(&bw_hash.to_unextendable())
-                                    {
-                                        if hash.get_kmer_multiplicity()
-                                            >= global_data.min_multiplicity
-                                        {
-                                            if ocount > 0 {
-                                                break 'ext_loop (current_hash, false);
-                                            }
-                                            ocount += 1;
-                                        }
-                                    }
-                                }
-                                assert_eq!(ocount, 1);
-                            }
-
-                            let entryref = map_struct
-                                .rhash_map
-                                .get(&temp_data.0.to_unextendable())
-                                .unwrap();
-
-                            let already_used = entryref.is_used();
-
-                            // Found a cycle unitig
-                            if already_used {
-                                break (temp_data.0, false);
-                            }
-
+                    let unitig_end = extend_unitig::<MH, _>(
+                        &map_struct.rhash_map,
+                        &filter,
+                        hash,
+                        k,
+                        output,
+                        compute_hash_fw,
+                        compute_hash_bw,
+                        |entryref| {
                             if CX::COLORS_ENABLED {
                                 colors_function(&mut self.unitigs_temp_colors, entryref);
                             }
 
-                            // Flag the entry as already used
-                            entryref.set_used();
-
-                            output.push(Utils::decompress_base(temp_data.1));
-
                             #[cfg(feature = "support_kmer_counters")]
                             {
+                                let multiplicity = entryref.get_kmer_multiplicity() as u64;
                                 counters.sum += multiplicity;
                                 if is_forward {
                                     counters.last = multiplicity;
@@ -355,17 +481,10 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                                     counters.first = multiplicity;
                                 }
                             }
-
-                            // Found a continuation into another bucket
-                            let contig_break = (entryref.get_flags() == READ_FLAG_INCL_BEGIN)
-                                || (entryref.get_flags() == READ_FLAG_INCL_END);
-                            if contig_break {
-                                break (temp_data.0, true);
-                            }
-                        } else {
-                            break (current_hash, false);
-                        }
-                    };
+                        },
+                    );
+                    found_cycle |= unitig_end.is_cycle;
+                    (unitig_end.hash, unitig_end.continues)
                 };
 
             let (fw_hash, fw_merge) = {
@@ -419,7 +538,18 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                 counters,
             };
 
-            let read_index = current_bucket.add_read(extra_data, out_seq, &self.temp_color_buffer);
+            let read_index = current_bucket.add_read(
+                extra_data,
+                out_seq,
+                if found_cycle {
+                    PARTIAL_UNITIG_FLAG_CIRCULAR
+                } else {
+                    0
+                },
+                &self.temp_color_buffer,
+            );
+            self.stats.sequences += 1;
+            self.stats.bases += out_seq.len() as u64;
 
             color_types::PartialUnitigsColorStructure::<H, MH, CX>::clear_temp_buffer(
                 &mut self.temp_color_buffer,
@@ -460,7 +590,121 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
     fn finalize(
         self,
         _global_data: &<ParallelKmersMergeFactory<H, MH, CX> as KmersTransformExecutorFactory>::GlobalExtraData,
-    ) {
+    ) -> FinalStats {
         self.hashes_tmp.finalize();
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::final_executor::{extend_unitig, KmersFilter};
+    use crate::masked_kmers::load_masked_kmers;
+    use hashbrown::{HashMap, HashSet};
+    use hashes::{ExtendableHashTraitType, HashFunction, HashFunctionFactory};
+    use std::io::Write;
+    use structs::map_entry::MapEntry;
+    use utils::Utils;
+
+    type TestHash = hashes::cn_seqhash::u64::CanonicalSeqHashFactory;
+
+    #[test]
+    fn masked_kmer_splits_path() {
+        let k = 15;
+        TestHash::initialize(k);
+
+        let path = b"ACGTTGCAAGGCTTACGATCGGATCCATGACCGTAGCTAGGCTAACGT";
+        let kmers_count = path.len() - k + 1;
+        let masked_position = kmers_count / 2;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mask_file = temp_dir.path().join("masked-path.txt");
+        {
+            let mut file = std::fs::File::create(&mask_file).unwrap();
+            file.write_all(&path[masked_position..masked_position + k])
+                .unwrap();
+            file.write_all(b"\n").unwrap();
+        }
+        let masked_kmers = load_masked_kmers::<TestHash>(&mask_file, k).unwrap();
+
+        let compressed: Vec<_> = path.iter().map(|b| Utils::compress_base(*b)).collect();
+        let hashes: Vec<_> = TestHash::new(compressed.as_slice(), k).iter().collect();
+
+        let new_map = || {
+            let mut map = HashMap::new();
+            for hash in hashes.iter() {
+                map.entry(hash.to_unextendable())
+                    .or_insert_with(|| MapEntry::new(()))
+                    .incr();
+            }
+            assert_eq!(map.len(), kmers_count);
+            map
+        };
+
+        let filter = KmersFilter {
+            min_multiplicity: 1,
+            max_multiplicity: None,
+            masked_kmers: Some(&masked_kmers),
+        };
+
+        // Extends forward from the kmer at start, returning the unitig built
+        let extend_forward =
+            |map: &HashMap<_, MapEntry<()>>, filter: &KmersFilter<_>, start: usize| {
+                let mut output = path[start..start + k].to_vec();
+                let unitig_end = extend_unitig::<TestHash, _>(
+                    map,
+                    filter,
+                    hashes[start],
+                    k,
+                    &mut output,
+                    TestHash::manual_roll_forward,
+                    TestHash::manual_roll_reverse,
+                    |_| {},
+                );
+                assert!(!unitig_end.continues && !unitig_end.is_cycle);
+                output
+            };
+
+        let map = new_map();
+        assert_eq!(
+            extend_forward(&map, &filter, 0),
+            &path[..masked_position - 1 + k]
+        );
+
+        // The masked kmer is kept in the graph, as a unitig on its own
+        let masked_entry = map.get(&hashes[masked_position].to_unextendable()).unwrap();
+        assert!(filter.is_kmer_valid(masked_entry));
+        assert!(!masked_entry.is_used());
+        assert_eq!(
+            extend_forward(&map, &filter, masked_position),
+            &path[masked_position..masked_position + k]
+        );
+
+        assert_eq!(
+            extend_forward(&map, &filter, masked_position + 1),
+            &path[masked_position + 1..]
+        );
+
+        // The backward extension stops before the masked kmer too
+        let mut output = path[masked_position + 1..masked_position + 1 + k].to_vec();
+        output.reverse();
+        extend_unitig::<TestHash, _>(
+            &map,
+            &filter,
+            hashes[masked_position + 1],
+            k,
+            &mut output,
+            TestHash::manual_roll_reverse,
+            TestHash::manual_roll_forward,
+            |_| {},
+        );
+        assert_eq!(output.len(), k);
+
+        let no_mask = KmersFilter {
+            min_multiplicity: 1,
+            max_multiplicity: None,
+            masked_kmers: None::<&HashSet<_>>,
+        };
+        assert_eq!(extend_forward(&new_map(), &no_mask, 0), path);
     }
 }
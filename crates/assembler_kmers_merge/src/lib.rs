@@ -1,6 +1,10 @@
 use crate::final_executor::ParallelKmersMergeFinalExecutor;
 use crate::map_processor::{ParallelKmersMergeMapProcessor, KMERGE_TEMP_DIR};
+use crate::masked_kmers::load_masked_kmers;
 use crate::preprocessor::ParallelKmersMergePreprocessor;
+use crate::resume::{
+    copy_previous_hashes, copy_previous_results, merge_output_paths, KMERS_MERGE_MANIFEST,
+};
 use crate::structs::{ResultsBucket, RetType};
 use assembler_minimizer_bucketing::AssemblerMinimizerBucketingExecutorFactory;
 use colors::colors_manager::color_types::{
@@ -8,16 +12,24 @@ use colors::colors_manager::color_types::{
 };
 use colors::colors_manager::{color_types, ColorsManager};
 use config::{
-    get_compression_level_info, get_memory_mode, BucketIndexType, SwapPriority,
-    MINIMUM_SUBBUCKET_KMERS_COUNT, RESPLITTING_MAX_K_M_DIFFERENCE,
+    get_compression_level_info, get_memory_mode, get_per_cpu_buffer_size, BucketIndexType,
+    SwapPriority, KEEP_FILES, KMERS_MERGE_MAP_FLUSH_SIZE, KMERS_MERGE_TIME_LIMIT_SECS,
+    MAX_MULTIPLICITY, MINIMUM_SUBBUCKET_KMERS_COUNT, RESPLITTING_MAX_K_M_DIFFERENCE,
 };
 use crossbeam::queue::*;
+use hashbrown::HashSet;
 use hashes::HashFunctionFactory;
 use hashes::MinimizerHashFunctionFactory;
+use io::generate_bucket_names;
 use io::structs::hash_entry::HashEntry;
 use io::structs::hash_entry::{Direction, HashEntrySerializer};
+use kmers_transform::builder::KmersTransformBuildError;
 use kmers_transform::processor::KmersTransformProcessor;
-use kmers_transform::{KmersTransform, KmersTransformExecutorFactory};
+use kmers_transform::resume_manifest::ResumeManifest;
+use kmers_transform::{
+    KmersTransform, KmersTransformError, KmersTransformExecutorFactory, KmersTransformResult,
+    KmersTransformStatus,
+};
 use minimizer_bucketing::{MinimizerBucketingCommonData, MinimizerBucketingExecutorFactory};
 use parallel_processor::buckets::bucket_writer::BucketItemSerializer;
 use parallel_processor::buckets::concurrent::BucketsThreadDispatcher;
@@ -27,17 +39,24 @@ use parallel_processor::buckets::{LockFreeBucket, MultiThreadBuckets};
 use parallel_processor::execution_manager::memory_tracker::MemoryTracker;
 #[cfg(feature = "mem-analysis")]
 use parallel_processor::mem_tracker::MemoryInfo;
+use parallel_processor::memory_data_size::MemoryDataSize;
+use parallel_processor::memory_fs::MemoryFs;
 use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use std::cmp::min;
+use std::fmt::{Display, Formatter};
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use utils::cancellation::CancellationToken;
 use utils::owned_drop::OwnedDrop;
 
 mod final_executor;
 mod map_processor;
+pub mod masked_kmers;
 mod preprocessor;
+pub mod resume;
 pub mod structs;
 
 pub struct GlobalMergeData<
@@ -49,10 +68,16 @@ pub struct GlobalMergeData<
     m: usize,
     buckets_count: usize,
     min_multiplicity: usize,
+    max_multiplicity: Option<usize>,
+    masked_kmers: Option<HashSet<MH::HashTypeUnextendable>>,
+    /// Size of the kmers map of a group above which its counters are flushed, if they can be
+    map_flush_size: Option<usize>,
     colors_global_table: Arc<GlobalColorsTableWriter<H, MH, CX>>,
     output_results_buckets:
         ArrayQueue<ResultsBucket<color_types::PartialUnitigsColorStructure<H, MH, CX>>>,
     hashes_buckets: Arc<MultiThreadBuckets<LockFreeBinaryWriter>>,
+    /// Size of the per thread buffers of the hashes buckets, the same of the transform sub-buckets
+    output_buffer_size: MemoryDataSize,
     global_resplit_data: Arc<MinimizerBucketingCommonData<()>>,
     sequences_size_total: AtomicU64,
     hasnmap_kmers_total: AtomicU64,
@@ -80,6 +105,14 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
     type FLAGS_COUNT = typenum::U2;
     const HAS_COLORS: bool = CX::COLORS_ENABLED;
 
+    fn min_multiplicity(global_data: &Self::GlobalExtraData) -> u64 {
+        global_data.min_multiplicity as u64
+    }
+
+    fn max_multiplicity(global_data: &Self::GlobalExtraData) -> Option<u64> {
+        global_data.max_multiplicity.map(|max| max as u64)
+    }
+
     fn new_resplitter(
         global_data: &Arc<Self::GlobalExtraData>,
     ) -> <Self::SequencesResplitterFactory as MinimizerBucketingExecutorFactory>::ExecutorType {
@@ -128,6 +161,35 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
     }
 }
 
+#[derive(Debug)]
+pub enum KmersMergeError {
+    ResumeManifest(std::io::Error),
+    MaskedKmers(std::io::Error),
+    /// The transform cannot be started with the given parameters
+    Build(KmersTransformBuildError),
+    Transform(KmersTransformError),
+}
+
+impl Display for KmersMergeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KmersMergeError::ResumeManifest(err) => {
+                write!(f, "Cannot update the kmers merge resume manifest: {}", err)
+            }
+            KmersMergeError::MaskedKmers(err) => {
+                write!(f, "Cannot read the masked kmers file: {}", err)
+            }
+            KmersMergeError::Build(err) => write!(f, "{}", err),
+            KmersMergeError::Transform(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for KmersMergeError {}
+
+/// Merges the kmers of the minimizer buckets. Without colors the merge can be interrupted by the
+/// cancellation token or KMERS_MERGE_TIME_LIMIT_SECS, and a new call with the same out_directory
+/// resumes it: the output is complete only if the returned status is Completed
 pub fn kmers_merge<
     H: MinimizerHashFunctionFactory,
     MH: HashFunctionFactory,
@@ -139,11 +201,13 @@ pub fn kmers_merge<
     colors_global_table: Arc<GlobalColorsTableWriter<H, MH, CX>>,
     buckets_count: usize,
     min_multiplicity: usize,
+    masked_kmers_file: Option<&Path>,
     out_directory: P,
     k: usize,
     m: usize,
     threads_count: usize,
-) -> RetType {
+    cancellation_token: &CancellationToken,
+) -> Result<(RetType, KmersTransformStatus), KmersMergeError> {
     PHASES_TIMES_MONITOR
         .write()
         .start_phase("phase: kmers merge".to_string());
@@ -152,20 +216,52 @@ pub fn kmers_merge<
     MH::initialize(k);
     *KMERGE_TEMP_DIR.write() = Some(out_directory.as_ref().to_path_buf());
 
+    let masked_kmers = masked_kmers_file
+        .map(|path| load_masked_kmers::<MH>(path, k))
+        .transpose()
+        .map_err(KmersMergeError::MaskedKmers)?;
+
+    // The colors table is written from scratch by each run, so a colored merge is always completed
+    let mut resume_manifest = if CX::COLORS_ENABLED {
+        None
+    } else {
+        Some(
+            ResumeManifest::open(out_directory.as_ref().join(KMERS_MERGE_MANIFEST))
+                .map_err(KmersMergeError::ResumeManifest)?,
+        )
+    };
+
+    // Each attempt writes a new generation of the output, starting with a copy of the committed one
+    let generation = resume_manifest
+        .as_ref()
+        .map_or(0, |manifest| manifest.generation());
+    let (result_path, hashes_path) = merge_output_paths(out_directory.as_ref(), generation);
+    let previous_output = generation.checked_sub(1).map(|previous_generation| {
+        let (previous_result, previous_hashes) =
+            merge_output_paths(out_directory.as_ref(), previous_generation);
+        (
+            generate_bucket_names(previous_result, buckets_count, None),
+            generate_bucket_names(previous_hashes, buckets_count, None),
+        )
+    });
+
     let hashes_buckets = Arc::new(MultiThreadBuckets::<LockFreeBinaryWriter>::new(
         buckets_count,
-        out_directory.as_ref().join("hashes"),
+        hashes_path,
         &(
             get_memory_mode(SwapPriority::HashBuckets),
             LockFreeBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
         ),
     ));
+    if let Some((_, previous_hashes)) = &previous_output {
+        copy_previous_hashes::<MH>(previous_hashes, &hashes_buckets);
+    }
 
     let mut sequences = Vec::new();
 
     let reads_buckets = MultiThreadBuckets::<CompressedBinaryWriter>::new(
         buckets_count,
-        out_directory.as_ref().join("result"),
+        result_path,
         &(
             get_memory_mode(SwapPriority::ResultBuckets),
             CompressedBinaryWriter::CHECKPOINT_SIZE_UNLIMITED,
@@ -175,7 +271,7 @@ pub fn kmers_merge<
 
     let output_results_buckets = ArrayQueue::new(reads_buckets.count());
     for (index, bucket) in reads_buckets.into_buckets().enumerate() {
-        let bucket_read = ResultsBucket::<color_types::PartialUnitigsColorStructure<H, MH, CX>> {
+        let mut bucket_read = ResultsBucket::<color_types::PartialUnitigsColorStructure<H, MH, CX>> {
             read_index: 0,
             reads_writer: OwnedDrop::new(bucket),
             temp_buffer: Vec::with_capacity(256),
@@ -183,19 +279,30 @@ pub fn kmers_merge<
             _phantom: PhantomData,
             serializer: BucketItemSerializer::new(),
         };
+        if let Some((previous_results, _)) = &previous_output {
+            copy_previous_results(&previous_results[index], &mut bucket_read);
+        }
         sequences.push(bucket_read.reads_writer.get_path());
         let res = output_results_buckets.push(bucket_read).is_ok();
         assert!(res);
     }
 
+    let output_buffer_size = get_per_cpu_buffer_size();
+
     let global_data = Arc::new(GlobalMergeData::<H, MH, CX> {
         k,
         m,
         buckets_count,
         min_multiplicity,
+        max_multiplicity: Some(MAX_MULTIPLICITY.load(Ordering::Relaxed))
+            .filter(|max| *max != usize::MAX),
+        masked_kmers,
+        map_flush_size: Some(KMERS_MERGE_MAP_FLUSH_SIZE.load(Ordering::Relaxed))
+            .filter(|size| *size != usize::MAX),
         colors_global_table,
         output_results_buckets,
         hashes_buckets: hashes_buckets.clone(),
+        output_buffer_size,
         global_resplit_data: Arc::new(MinimizerBucketingCommonData::new(
             k,
             if k > RESPLITTING_MAX_K_M_DIFFERENCE + 1 {
@@ -213,7 +320,7 @@ pub fn kmers_merge<
         kmer_batches_count: AtomicU64::new(0),
     });
 
-    KmersTransform::<ParallelKmersMergeFactory<H, MH, CX>>::new(
+    let mut transform = KmersTransform::<ParallelKmersMergeFactory<H, MH, CX>>::try_new(
         file_inputs,
         out_directory.as_ref(),
         buckets_counters_path,
@@ -223,27 +330,171 @@ pub fn kmers_merge<
         k,
         MINIMUM_SUBBUCKET_KMERS_COUNT as u64,
     )
-    .parallel_kmers_transform();
+    .map_err(KmersMergeError::Build)?
+    .with_output_buffer_size(output_buffer_size);
+
+    if let Some(resume_manifest) = &resume_manifest {
+        transform = transform
+            .with_resume_manifest(resume_manifest)
+            .with_cancellation_token(cancellation_token.clone());
+
+        let time_limit = KMERS_MERGE_TIME_LIMIT_SECS.load(Ordering::Relaxed);
+        if time_limit != u64::MAX {
+            transform = transform.with_deadline(Instant::now() + Duration::from_secs(time_limit));
+        }
+    }
+
+    let KmersTransformResult {
+        status,
+        global_extra_data,
+        completed_buckets,
+        final_stats,
+        ..
+    } = transform
+        .parallel_kmers_transform()
+        .map_err(KmersMergeError::Transform)?;
+
+    println!(
+        "Assembled {} partial unitigs totaling {} bp",
+        final_stats.sequences, final_stats.bases
+    );
 
-    RetType {
-        sequences,
-        hashes: hashes_buckets.finalize(),
+    // Finalizes the results buckets
+    drop(global_extra_data);
+    let hashes = hashes_buckets.finalize();
+
+    if let Some(resume_manifest) = &mut resume_manifest {
+        // The output, and the input buckets skipped by this attempt, must be on disk before committing
+        MemoryFs::flush_all_to_disk();
+        resume_manifest
+            .commit(&completed_buckets)
+            .map_err(KmersMergeError::ResumeManifest)?;
+
+        // The committed generation includes the previous one, and replaces its completed input buckets
+        if !KEEP_FILES.load(Ordering::Relaxed) {
+            let previous_files =
+                previous_output
+                    .iter()
+                    .flat_map(|(previous_results, previous_hashes)| {
+                        previous_results.iter().chain(previous_hashes.iter())
+                    });
+            for file in previous_files.chain(completed_buckets.iter()) {
+                let _ = std::fs::remove_file(file);
+            }
+        }
+    }
+
+    if status != KmersTransformStatus::Completed {
+        println!(
+            "Kmers merge interrupted, completed {} buckets in this attempt",
+            completed_buckets.len()
+        );
     }
+
+    Ok((RetType { sequences, hashes }, status))
 }
 
 #[cfg(test)]
 mod tests {
     use colors::colors_manager::{ColorsManager, ColorsMergeManager};
     use colors::non_colored::NonColoredManager;
-    use config::{FLUSH_QUEUE_FACTOR, KEEP_FILES, PREFER_MEMORY};
+    use config::{FLUSH_QUEUE_FACTOR, KEEP_FILES, KMERS_MERGE_TIME_LIMIT_SECS, PREFER_MEMORY};
     use io::generate_bucket_names;
+    use kmers_transform::KmersTransformStatus;
     use parallel_processor::memory_data_size::MemoryDataSize;
     use parallel_processor::memory_fs::MemoryFs;
     use rayon::ThreadPoolBuilder;
     use std::cmp::max;
     use std::path::Path;
     use std::sync::atomic::Ordering;
-    use std::sync::Arc;
+    use std::sync::{mpsc, Arc};
+    use std::time::Duration;
+    use utils::cancellation::CancellationToken;
+
+    #[test]
+    fn kmers_merge_without_inputs() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let temp_dir = test_dir.path();
+
+        let global_colors_table = Arc::new(
+            <<NonColoredManager as ColorsManager>::ColorsMergeManagerType<
+                hashes::cn_nthash::CanonicalNtHashIteratorFactory,
+                hashes::cn_seqhash::u128::CanonicalSeqHashFactory,
+            > as ColorsMergeManager<
+                hashes::cn_nthash::CanonicalNtHashIteratorFactory,
+                hashes::cn_seqhash::u128::CanonicalSeqHashFactory,
+            >>::create_colors_table("", &[])
+            .unwrap(),
+        );
+
+        MemoryFs::init(MemoryDataSize::from_mebioctets(64), 4, 1, 32768);
+
+        // Run the merge on its own thread, so that a hang fails the test instead of blocking it
+        let (sender, receiver) = mpsc::channel();
+        let merge_dir = temp_dir.to_path_buf();
+        let merge_colors_table = global_colors_table.clone();
+        std::thread::spawn(move || {
+            let merge_result = crate::kmers_merge::<
+                hashes::cn_nthash::CanonicalNtHashIteratorFactory,
+                hashes::cn_seqhash::u128::CanonicalSeqHashFactory,
+                NonColoredManager,
+                _,
+            >(
+                vec![],
+                merge_dir.join("missing-counters.dat"),
+                merge_colors_table,
+                4,
+                1,
+                None,
+                &merge_dir,
+                31,
+                12,
+                2,
+                &CancellationToken::new(),
+            );
+            let _ = sender.send(merge_result);
+        });
+
+        let (result, status) = receiver
+            .recv_timeout(Duration::from_secs(10))
+            .expect("the merge without inputs did not complete in time")
+            .unwrap();
+
+        assert_eq!(result.sequences.len(), 4);
+        assert_eq!(status, KmersTransformStatus::Completed);
+
+        // A cancellation or a deadline with no buckets left to process do not interrupt the merge
+        let cancelled_token = CancellationToken::new();
+        cancelled_token.cancel();
+        KMERS_MERGE_TIME_LIMIT_SECS.store(0, Ordering::Relaxed);
+        let (result, status) = crate::kmers_merge::<
+            hashes::cn_nthash::CanonicalNtHashIteratorFactory,
+            hashes::cn_seqhash::u128::CanonicalSeqHashFactory,
+            NonColoredManager,
+            _,
+        >(
+            vec![],
+            temp_dir.join("missing-counters.dat"),
+            global_colors_table,
+            4,
+            1,
+            None,
+            temp_dir,
+            31,
+            12,
+            2,
+            &cancelled_token,
+        )
+        .unwrap();
+        KMERS_MERGE_TIME_LIMIT_SECS.store(u64::MAX, Ordering::Relaxed);
+        assert_eq!(status, KmersTransformStatus::Completed);
+        assert_eq!(
+            result.sequences,
+            crate::resume::committed_merge_output(temp_dir, 4)
+                .unwrap()
+                .sequences
+        );
+    }
 
     #[ignore]
     #[test]
@@ -275,7 +526,8 @@ mod tests {
             > as ColorsMergeManager<
                 hashes::cn_nthash::CanonicalNtHashIteratorFactory,
                 hashes::cn_rkhash::u128::CanonicalRabinKarpHashFactory,
-            >>::create_colors_table("", &[]),
+            >>::create_colors_table("", &[])
+            .unwrap(),
         );
 
         let k = 63;
@@ -328,10 +580,13 @@ mod tests {
             global_colors_table.clone(),
             buckets_count,
             min_multiplicity,
+            None,
             Path::new(TEMP_DIR),
             k,
             m,
             threads_count,
-        );
+            &CancellationToken::new(),
+        )
+        .unwrap();
     }
 }
@@ -22,6 +22,7 @@ use parallel_processor::mt_debug_counters::counter::{AtomicCounter, AvgMode, Max
 use parallel_processor::mt_debug_counters::{declare_avg_counter_i64, declare_counter_i64};
 use parking_lot::RwLock;
 use std::cmp::{max, min};
+use std::hash::Hash;
 use std::mem::size_of;
 use std::ops::{Deref, DerefMut};
 use std::path::PathBuf;
@@ -32,6 +33,81 @@ instrumenter::use_instrumenter!();
 
 pub(crate) static KMERGE_TEMP_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
 
+/// Counters and flags of the kmers of the maps flushed for the current group, sorted by hash and
+/// combined across the flushes. They are merged back in the map when the group is finalized, so the
+/// kmers split across the flushes get the same counters as if the map was never flushed
+pub struct FlushedKmers<K> {
+    kmers: Vec<(K, usize, u8)>,
+}
+
+impl<K: Copy + Ord + Hash> FlushedKmers<K> {
+    pub fn new() -> Self {
+        Self { kmers: vec![] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.kmers.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.kmers.clear();
+    }
+
+    /// Adds the counters of a flushed map, that can then be cleared
+    pub fn add_map<CHI>(&mut self, map: &HashMap<K, MapEntry<CHI>>) {
+        let mut flushed: Vec<_> = map
+            .iter()
+            .map(|(hash, entry)| (*hash, entry.get_counter(), entry.get_flags()))
+            .collect();
+        flushed.sort_unstable_by_key(|(hash, _, _)| *hash);
+
+        let previous = std::mem::take(&mut self.kmers);
+        self.kmers.reserve(previous.len() + flushed.len());
+
+        let mut previous = previous.into_iter().peekable();
+        let mut flushed = flushed.into_iter().peekable();
+        loop {
+            let kmer = match (previous.peek(), flushed.peek()) {
+                (Some(a), Some(b)) if a.0 == b.0 => {
+                    let (a, b) = (previous.next().unwrap(), flushed.next().unwrap());
+                    (a.0, a.1 + b.1, a.2 | b.2)
+                }
+                (Some(a), Some(b)) if a.0 < b.0 => previous.next().unwrap(),
+                (Some(_), Some(_)) | (None, Some(_)) => flushed.next().unwrap(),
+                (Some(_), None) => previous.next().unwrap(),
+                (None, None) => break,
+            };
+            self.kmers.push(kmer);
+        }
+    }
+
+    /// Merges the flushed counters in the final map of the group. The kmers that are not in the map
+    /// are added only if their multiplicity reaches min_multiplicity, as the others are never used
+    pub fn merge_into<CHI>(
+        &mut self,
+        map: &mut HashMap<K, MapEntry<CHI>>,
+        min_multiplicity: usize,
+        mut new_color_index: impl FnMut() -> CHI,
+    ) {
+        for (hash, counter, flags) in self.kmers.drain(..) {
+            match map.get_mut(&hash) {
+                Some(entry) => {
+                    entry.set_counter_after_check(entry.get_counter() + counter);
+                    entry.update_flags(flags);
+                }
+                None => {
+                    let mut entry = MapEntry::new(new_color_index());
+                    entry.set_counter_after_check(counter);
+                    entry.update_flags(flags);
+                    if entry.get_kmer_multiplicity() >= min_multiplicity {
+                        map.insert(hash, entry);
+                    }
+                }
+            }
+        }
+    }
+}
+
 pub struct ParallelKmersMergeMapPacket<
     H: MinimizerHashFunctionFactory,
     MH: HashFunctionFactory,
@@ -42,6 +118,9 @@ pub struct ParallelKmersMergeMapPacket<
     pub saved_reads: Vec<u8>,
     pub encoded_saved_reads_indexes: Vec<u8>,
     pub temp_colors: color_types::ColorsBufferTempStructure<H, MH, CX>,
+    pub flushed_kmers: FlushedKmers<MH::HashTypeUnextendable>,
+    /// The map is a partial map of its group, flushed by try_flush
+    pub is_partial: bool,
     average_hasmap_size: u64,
     average_sequences_size: u64,
 }
@@ -71,6 +150,8 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
             temp_colors: CX::ColorsMergeManagerType::<H, MH>::allocate_temp_buffer_structure(
                 KMERGE_TEMP_DIR.read().deref().as_ref().unwrap(),
             ),
+            flushed_kmers: FlushedKmers::new(),
+            is_partial: false,
             average_hasmap_size: 0,
             average_sequences_size: 0,
         }
@@ -97,6 +178,9 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         }
 
         CX::ColorsMergeManagerType::<H, MH>::reinit_temp_buffer_structure(&mut self.temp_colors);
+        // Discards the counters flushed for the group too, if it failed
+        self.flushed_kmers.clear();
+        self.is_partial = false;
     }
 }
 
@@ -110,6 +194,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
                 MapEntry<color_types::HashMapTempColorIndex<H, MH, CX>>,
             )>() + 1)
             + self.saved_reads.len()
+            + self.flushed_kmers.len() * size_of::<(MH::HashTypeUnextendable, usize, u8)>()
     }
 }
 
@@ -258,6 +343,26 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         }
     }
 
+    /// Flushes the counters of the map when it is too big. Only the maps of invertible hashes
+    /// without colors can be flushed, as the other ones need the reads and the colors of the kmers
+    fn try_flush(
+        &mut self,
+        global_data: &<ParallelKmersMergeFactory<H, MH, CX> as KmersTransformExecutorFactory>::GlobalExtraData,
+    ) -> Option<Packet<Self::MapStruct>> {
+        let map_flush_size = global_data.map_flush_size?;
+        if !MH::INVERTIBLE || CX::COLORS_ENABLED {
+            return None;
+        }
+
+        let map_packet = self.map_packet.as_mut().unwrap();
+        if map_packet.rhash_map.len() * Self::MAP_SIZE < map_flush_size {
+            return None;
+        }
+
+        map_packet.is_partial = true;
+        self.map_packet.take()
+    }
+
     #[instrumenter::track]
     fn process_group_finalize(
         &mut self,
@@ -296,3 +401,79 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         map_packet
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::map_processor::FlushedKmers;
+    use config::{READ_FLAG_INCL_BEGIN, READ_FLAG_INCL_END};
+    use hashbrown::HashMap;
+    use structs::map_entry::MapEntry;
+
+    fn add_kmers(map: &mut HashMap<u64, MapEntry<()>>, kmers: &[(u64, u8)]) {
+        for (hash, flags) in kmers {
+            let entry = map.entry(*hash).or_insert_with(|| MapEntry::new(()));
+            entry.update_flags(*flags);
+            entry.incr();
+        }
+    }
+
+    #[test]
+    fn flushed_kmers_combine_counters() {
+        let batches: [&[(u64, u8)]; 3] = [
+            &[(1, 0), (2, READ_FLAG_INCL_BEGIN), (3, 0)],
+            &[(2, READ_FLAG_INCL_END), (3, 0), (4, 0)],
+            &[(3, 0), (5, 0), (5, 0), (1, 0)],
+        ];
+
+        let mut expected = HashMap::new();
+        for batch in batches {
+            add_kmers(&mut expected, batch);
+        }
+
+        // Flush the map after each batch but the last one
+        let mut flushed = FlushedKmers::new();
+        let mut map = HashMap::new();
+        for (index, batch) in batches.iter().enumerate() {
+            add_kmers(&mut map, batch);
+            if index < batches.len() - 1 {
+                flushed.add_map(&map);
+                map.clear();
+            }
+        }
+        flushed.merge_into(&mut map, 1, || ());
+        assert_eq!(flushed.len(), 0);
+
+        assert_eq!(map.len(), expected.len());
+        for (hash, entry) in expected.iter() {
+            assert_eq!(map[hash].get_counter(), entry.get_counter());
+            assert_eq!(map[hash].get_flags(), entry.get_flags());
+            assert_eq!(
+                map[hash].get_kmer_multiplicity(),
+                entry.get_kmer_multiplicity()
+            );
+        }
+        assert_eq!(
+            map[&2].get_flags(),
+            READ_FLAG_INCL_BEGIN | READ_FLAG_INCL_END
+        );
+        assert_eq!(map[&3].get_counter(), 3);
+
+        // The flushed kmers that do not reach the minimum multiplicity are not added back
+        let mut flushed = FlushedKmers::new();
+        let mut map = HashMap::new();
+        add_kmers(&mut map, &[(1, 0), (2, 0), (2, 0)]);
+        flushed.add_map(&map);
+        map.clear();
+        add_kmers(&mut map, &[(3, 0)]);
+        flushed.add_map(&map);
+        map.clear();
+        add_kmers(&mut map, &[(1, 0)]);
+        flushed.merge_into(&mut map, 2, || ());
+        let mut kmers: Vec<_> = map
+            .iter()
+            .map(|(hash, entry)| (*hash, entry.get_counter()))
+            .collect();
+        kmers.sort_unstable();
+        assert_eq!(kmers, vec![(1, 2), (2, 2)]);
+    }
+}
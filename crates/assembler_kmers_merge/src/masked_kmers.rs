@@ -0,0 +1,96 @@
+use hashbrown::HashSet;
+use hashes::{ExtendableHashTraitType, HashFunction, HashFunctionFactory};
+use std::fs::File;
+use std::hash::Hash;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use utils::Utils;
+
+/// Loads a set of k-mers that must act as hard stops for the unitigs extension.
+/// The file can be a plain list of sequences (one for each line) or a fasta file,
+/// every k-mer contained in each sequence is masked.
+pub fn load_masked_kmers<MH: HashFunctionFactory>(
+    path: impl AsRef<Path>,
+    k: usize,
+) -> io::Result<HashSet<MH::HashTypeUnextendable>> {
+    let mut masked_kmers = HashSet::new();
+
+    let mut add_sequence = |sequence: &mut Vec<u8>| {
+        if sequence.len() >= k {
+            for base in sequence.iter_mut() {
+                *base = Utils::compress_base(*base);
+            }
+            for hash in MH::new(sequence.as_slice(), k).iter() {
+                masked_kmers.insert(hash.to_unextendable());
+            }
+        }
+        sequence.clear();
+    };
+
+    let mut sequence = Vec::new();
+    let mut is_fasta = false;
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.starts_with('>') {
+            is_fasta = true;
+            add_sequence(&mut sequence);
+        } else {
+            sequence.extend_from_slice(line.as_bytes());
+            // Plain lists have exactly one sequence for each line
+            if !is_fasta {
+                add_sequence(&mut sequence);
+            }
+        }
+    }
+    add_sequence(&mut sequence);
+
+    Ok(masked_kmers)
+}
+
+#[inline(always)]
+pub fn is_kmer_masked<T: Hash + Eq>(masked_kmers: Option<&HashSet<T>>, hash: &T) -> bool {
+    masked_kmers.map_or(false, |masked| masked.contains(hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::masked_kmers::{is_kmer_masked, load_masked_kmers};
+    use hashes::{ExtendableHashTraitType, HashFunction, HashFunctionFactory};
+    use std::io::Write;
+    use utils::Utils;
+
+    type TestHash = hashes::cn_seqhash::u64::CanonicalSeqHashFactory;
+
+    #[test]
+    fn masked_kmers_loading() {
+        let k = 15;
+        TestHash::initialize(k);
+
+        let sequence = b"ACGTTGCAAGGCTTACGATCGGATCC";
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mask_file = temp_dir.path().join("masked-kmers.fa");
+        {
+            let mut file = std::fs::File::create(&mask_file).unwrap();
+            file.write_all(b">masked\n").unwrap();
+            file.write_all(&sequence[..13]).unwrap();
+            file.write_all(b"\n").unwrap();
+            file.write_all(&sequence[13..]).unwrap();
+            file.write_all(b"\n").unwrap();
+        }
+
+        let masked_kmers = load_masked_kmers::<TestHash>(&mask_file, k).unwrap();
+        assert_eq!(masked_kmers.len(), sequence.len() - k + 1);
+
+        let compressed: Vec<_> = sequence.iter().map(|b| Utils::compress_base(*b)).collect();
+        for hash in TestHash::new(compressed.as_slice(), k).iter() {
+            assert!(is_kmer_masked(Some(&masked_kmers), &hash.to_unextendable()));
+        }
+        let hash = TestHash::new(&compressed[..k], k).iter().next().unwrap();
+        assert!(!is_kmer_masked(None, &hash.to_unextendable()));
+
+        assert!(load_masked_kmers::<TestHash>(temp_dir.path().join("missing.fa"), k).is_err());
+    }
+}
@@ -1,12 +1,12 @@
 use crate::ParallelKmersMergeFactory;
 use colors::colors_manager::ColorsManager;
 use config::BucketIndexType;
-use config::READ_FLAG_INCL_END;
-use hashes::ExtendableHashTraitType;
-use hashes::HashFunction;
-use hashes::{HashFunctionFactory, HashableSequence, MinimizerHashFunctionFactory};
+use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
 use io::compressed_read::CompressedRead;
-use kmers_transform::{KmersTransformExecutorFactory, KmersTransformPreprocessor};
+use kmers_transform::{
+    get_sequence_bucket, KmersTransformBucketHasher, KmersTransformExecutorFactory,
+    KmersTransformPreprocessor,
+};
 use std::marker::PhantomData;
 
 pub struct ParallelKmersMergePreprocessor<
@@ -38,25 +38,13 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         used_hash_bits: usize,
         bucket_bits_count: usize,
     ) -> BucketIndexType {
-        let read = &seq_data.3;
-        let flags = seq_data.0;
-        let decr_val =
-            ((read.bases_count() == global_data.k) && (flags & READ_FLAG_INCL_END) == 0) as usize;
-
-        let hashes = H::new(
-            read.sub_slice((1 - decr_val)..(global_data.k - decr_val)),
+        get_sequence_bucket::<H, KmersTransformBucketHasher<ParallelKmersMergeFactory<H, MH, CX>>>(
+            global_data.k,
             global_data.m,
-        );
-
-        let minimizer = hashes
-            .iter()
-            .min_by_key(|k| H::get_full_minimizer(k.to_unextendable()))
-            .unwrap();
-
-        H::get_bucket(
+            &seq_data.3,
+            seq_data.0,
             used_hash_bits,
             bucket_bits_count,
-            minimizer.to_unextendable(),
         )
     }
 }
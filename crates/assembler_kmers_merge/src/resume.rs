@@ -0,0 +1,111 @@
+use crate::structs::{PartialUnitigExtraData, ResultsBucket, RetType};
+use config::{get_per_cpu_buffer_size, BucketIndexType, DEFAULT_PREFETCH_AMOUNT};
+use hashes::HashFunctionFactory;
+use io::concurrent::temp_reads::creads_utils::CompressedReadsBucketDataSerializer;
+use io::concurrent::temp_reads::extra_data::{
+    SequenceExtraDataConsecutiveCompression, SequenceExtraDataTempBufferManagement,
+};
+use io::generate_bucket_names;
+use io::structs::hash_entry::HashEntrySerializer;
+use kmers_transform::resume_manifest::ResumeManifest;
+use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
+use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
+use parallel_processor::buckets::readers::lock_free_binary_reader::LockFreeBinaryReader;
+use parallel_processor::buckets::readers::BucketReader;
+use parallel_processor::buckets::writers::lock_free_binary_writer::LockFreeBinaryWriter;
+use parallel_processor::buckets::MultiThreadBuckets;
+use parallel_processor::memory_fs::RemoveFileMode;
+use std::path::{Path, PathBuf};
+
+/// Manifest of the input buckets merged by the previous attempts of an interrupted run
+pub const KMERS_MERGE_MANIFEST: &str = "kmers-merge-manifest.txt";
+
+/// Base paths of the result and hashes buckets written by an attempt of the kmers merge.
+/// Each attempt of a resumed run writes a new generation, starting with a copy of the previous one
+pub fn merge_output_paths(out_directory: &Path, generation: usize) -> (PathBuf, PathBuf) {
+    if generation == 0 {
+        (out_directory.join("result"), out_directory.join("hashes"))
+    } else {
+        (
+            out_directory.join(format!("result-g{}", generation)),
+            out_directory.join(format!("hashes-g{}", generation)),
+        )
+    }
+}
+
+/// Result and hashes buckets of the last committed attempt of the kmers merge
+pub fn committed_merge_output(
+    out_directory: &Path,
+    buckets_count: usize,
+) -> std::io::Result<RetType> {
+    let manifest = ResumeManifest::open(out_directory.join(KMERS_MERGE_MANIFEST))?;
+    let (result_path, hashes_path) =
+        merge_output_paths(out_directory, manifest.generation().saturating_sub(1));
+
+    Ok(RetType {
+        sequences: generate_bucket_names(result_path, buckets_count, None),
+        hashes: generate_bucket_names(hashes_path, buckets_count, None),
+    })
+}
+
+/// Copies the unitigs of the previous generation in the same order, so that the read indexes
+/// referenced by the copied hashes do not change
+pub(crate) fn copy_previous_results<X: SequenceExtraDataConsecutiveCompression>(
+    previous_result: &Path,
+    results_bucket: &mut ResultsBucket<X>,
+) {
+    let mut decompress_buffer = Vec::new();
+    let mut extra_buffer = X::new_temp_buffer();
+
+    CompressedBinaryReader::new(
+        previous_result,
+        RemoveFileMode::Keep,
+        DEFAULT_PREFETCH_AMOUNT,
+    )
+    .decode_all_bucket_items::<CompressedReadsBucketDataSerializer<
+        PartialUnitigExtraData<X>,
+        typenum::U1,
+        false,
+    >, _>(
+        Vec::new(),
+        &mut extra_buffer,
+        |(flags, _, extra_data, seq), extra_buffer| {
+            if seq.bases_count() > decompress_buffer.len() {
+                decompress_buffer.resize(seq.bases_count(), 0);
+            }
+            seq.write_unpacked_to_slice(&mut decompress_buffer[..seq.bases_count()]);
+
+            results_bucket.add_read(
+                extra_data,
+                &decompress_buffer[..seq.bases_count()],
+                flags,
+                extra_buffer,
+            );
+            X::clear_temp_buffer(extra_buffer);
+        },
+    );
+}
+
+/// Copies the hashes of the previous generation, each one to the bucket it was read from
+pub(crate) fn copy_previous_hashes<MH: HashFunctionFactory>(
+    previous_hashes: &[PathBuf],
+    hashes_buckets: &MultiThreadBuckets<LockFreeBinaryWriter>,
+) {
+    let mut hashes_tmp =
+        BucketsThreadDispatcher::<_, HashEntrySerializer<MH::HashTypeUnextendable>>::new(
+            hashes_buckets,
+            BucketsThreadBuffer::new(get_per_cpu_buffer_size(), previous_hashes.len()),
+        );
+
+    for (bucket, path) in previous_hashes.iter().enumerate() {
+        LockFreeBinaryReader::new(path, RemoveFileMode::Keep, DEFAULT_PREFETCH_AMOUNT)
+            .decode_all_bucket_items::<HashEntrySerializer<MH::HashTypeUnextendable>, _>(
+            (),
+            &mut (),
+            |entry, _| {
+                hashes_tmp.add_element(bucket as BucketIndexType, &(), &entry);
+            },
+        );
+    }
+    hashes_tmp.finalize();
+}
@@ -14,13 +14,16 @@ use std::path::PathBuf;
 use structs::unitigs_counters::UnitigsCounters;
 use utils::owned_drop::OwnedDrop;
 
+/// The partial unitig is a complete cycle inside a single bucket
+pub const PARTIAL_UNITIG_FLAG_CIRCULAR: u8 = 1;
+
 pub struct ResultsBucket<X: SequenceExtraDataConsecutiveCompression> {
     pub read_index: u64,
     pub reads_writer: OwnedDrop<CompressedBinaryWriter>,
     pub temp_buffer: Vec<u8>,
     pub bucket_index: BucketIndexType,
     pub serializer:
-        CompressedReadsBucketDataSerializer<PartialUnitigExtraData<X>, typenum::U0, false>,
+        CompressedReadsBucketDataSerializer<PartialUnitigExtraData<X>, typenum::U1, false>,
     pub _phantom: PhantomData<X>,
 }
 
@@ -108,11 +111,12 @@ impl<X: SequenceExtraDataConsecutiveCompression> ResultsBucket<X> {
         &mut self,
         el: PartialUnitigExtraData<X>,
         read: &[u8],
+        flags: u8,
         extra_buffer: &X::TempBuffer,
     ) -> u64 {
         self.temp_buffer.clear();
         self.serializer.write_to(
-            &CompressedReadsBucketData::new(read, 0, 0),
+            &CompressedReadsBucketData::new(read, flags, 0),
             &mut self.temp_buffer,
             &el,
             extra_buffer,
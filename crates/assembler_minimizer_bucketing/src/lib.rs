@@ -12,9 +12,11 @@ use io::concurrent::temp_reads::extra_data::SequenceExtraDataTempBufferManagemen
 use io::sequences_reader::{DnaSequence, DnaSequencesFileType};
 use io::sequences_stream::general::{GeneralSequenceBlockData, GeneralSequencesStream};
 use io::sequences_stream::SequenceInfo;
+use minimizer_bucketing::bases_composition::BasesComposition;
+use minimizer_bucketing::minimizer_hasher::{DefaultMinimizerBucketHasher, MinimizerBucketHasher};
 use minimizer_bucketing::{
     GenericMinimizerBucketing, MinimizerBucketingCommonData, MinimizerBucketingExecutor,
-    MinimizerBucketingExecutorFactory, MinimizerInputSequence,
+    MinimizerBucketingExecutorFactory, MinimizerBucketingHasher, MinimizerInputSequence,
 };
 use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use std::cmp::max;
@@ -58,6 +60,10 @@ pub struct AssemblerMinimizerBucketingExecutorFactory<
     CX: ColorsManager,
 >(PhantomData<(H, CX)>);
 
+/// Bucket hasher used to write the assembler buckets
+pub type AssemblerBucketHasher<H, CX> =
+    MinimizerBucketingHasher<AssemblerMinimizerBucketingExecutorFactory<H, CX>>;
+
 impl<H: MinimizerHashFunctionFactory, CX: ColorsManager> MinimizerBucketingExecutorFactory
     for AssemblerMinimizerBucketingExecutorFactory<H, CX>
 {
@@ -70,6 +76,7 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager> MinimizerBucketingExecu
     type FLAGS_COUNT = typenum::U2;
 
     type ExecutorType = AssemblerMinimizerBucketingExecutor<H, CX>;
+    type Hasher = DefaultMinimizerBucketHasher;
 
     fn new(
         global_data: &Arc<MinimizerBucketingCommonData<Self::GlobalData>>,
@@ -179,8 +186,14 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
                 && (preprocess_info.include_last || end_index != index)
             {
                 push_sequence(
-                    H::get_bucket(used_bits, first_bits, last_hash),
-                    H::get_bucket(used_bits + first_bits, second_bits, last_hash),
+                    AssemblerBucketHasher::<H, CX>::get_bucket::<H>(
+                        used_bits, first_bits, last_hash,
+                    ),
+                    AssemblerBucketHasher::<H, CX>::get_bucket::<H>(
+                        used_bits + first_bits,
+                        second_bits,
+                        last_hash,
+                    ),
                     sequence.get_subslice((max(1, last_index) - 1)..(index + self.global_data.k)),
                     include_first as u8,
                     preprocess_info
@@ -197,8 +210,12 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
         let start_index = max(1, last_index) - 1;
         let include_last = preprocess_info.include_last; // Always include the last element of the sequence in the last entry
         push_sequence(
-            H::get_bucket(used_bits, first_bits, last_hash),
-            H::get_bucket(used_bits + first_bits, second_bits, last_hash),
+            AssemblerBucketHasher::<H, CX>::get_bucket::<H>(used_bits, first_bits, last_hash),
+            AssemblerBucketHasher::<H, CX>::get_bucket::<H>(
+                used_bits + first_bits,
+                second_bits,
+                last_hash,
+            ),
             sequence.get_subslice(start_index..sequence.seq_len()),
             include_first as u8 | ((include_last as u8) << 1),
             preprocess_info
@@ -223,7 +240,8 @@ pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
     threads_count: usize,
     k: usize,
     m: usize,
-) -> (Vec<PathBuf>, PathBuf) {
+    read_queue_multiplier: Option<usize>,
+) -> (Vec<PathBuf>, PathBuf, Option<BasesComposition>) {
     H::initialize(k);
 
     PHASES_TIMES_MONITOR
@@ -260,5 +278,199 @@ pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
         Some(k - 1),
         false,
         k,
+        read_queue_multiplier,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{AssemblerMinimizerBucketingExecutorFactory, AssemblerPreprocessInfo};
+    use colors::non_colored::NonColoredManager;
+    use hashes::cn_nthash::CanonicalNtHashIteratorFactory;
+    use hashes::MinimizerHashFunctionFactory;
+    use io::compressed_read::CompressedReadIndipendent;
+    use io::sequences_stream::general::GeneralSequenceBlockData;
+    use kmers_transform::buckets_export::write_fasta_read;
+    use kmers_transform::get_sequence_bucket;
+    use minimizer_bucketing::minimizer_hasher::DefaultMinimizerBucketHasher;
+    use minimizer_bucketing::{
+        MinimizerBucketingCommonData, MinimizerBucketingExecutor, MinimizerBucketingExecutorFactory,
+    };
+    use parallel_processor::memory_data_size::MemoryDataSize;
+    use parallel_processor::memory_fs::MemoryFs;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+
+    type TestFactory = AssemblerMinimizerBucketingExecutorFactory<
+        CanonicalNtHashIteratorFactory,
+        NonColoredManager,
+    >;
+
+    #[test]
+    fn exported_buckets_partition_reads() {
+        let k = 21;
+        let m = 11;
+        let buckets_count_bits = 3;
+        CanonicalNtHashIteratorFactory::initialize(k);
+
+        let mut state = 0x2545f4914f6cdd1du64;
+        let reads: Vec<Vec<u8>> = (0..50)
+            .map(|i| {
+                (0..(k + i * 7 % 180))
+                    .map(|_| {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        b"ACGT"[(state >> 62) as usize]
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let global_data = Arc::new(MinimizerBucketingCommonData::new(
+            k,
+            m,
+            1 << buckets_count_bits,
+            k - 1,
+            1,
+            (),
+        ));
+        let mut executor = TestFactory::new(&global_data);
+        let preprocess_info = AssemblerPreprocessInfo {
+            include_first: true,
+            include_last: true,
+            ..Default::default()
+        };
+
+        // Each super-kmer is written to the file of the bucket chosen by the bucketing,
+        // named after its read and its position in the read. The kmers transform must find
+        // the same bucket from the read and its flags
+        let mut buckets_files = vec![Vec::new(); 1 << buckets_count_bits];
+        let mut storage = Vec::new();
+        let mut line_buffer = Vec::new();
+        for (read_index, read) in reads.iter().enumerate() {
+            let mut part = 0;
+            executor.process_sequence(
+                &preprocess_info,
+                read.as_slice(),
+                0..read.len(),
+                0,
+                buckets_count_bits,
+                0,
+                |bucket, _, sequence: &[u8], flags, _, _| {
+                    storage.clear();
+                    let compressed = CompressedReadIndipendent::from_plain(sequence, &mut storage);
+                    assert_eq!(
+                        get_sequence_bucket::<
+                            CanonicalNtHashIteratorFactory,
+                            DefaultMinimizerBucketHasher,
+                        >(
+                            k,
+                            m,
+                            &compressed.as_reference(&storage),
+                            flags,
+                            0,
+                            buckets_count_bits,
+                        ),
+                        bucket
+                    );
+                    write_fasta_read(
+                        &mut buckets_files[bucket as usize],
+                        format_args!("{}_{}", read_index, part),
+                        &compressed.as_reference(&storage),
+                        &mut line_buffer,
+                    );
+                    part += 1;
+                },
+            );
+        }
+
+        let mut parts = HashMap::new();
+        let mut used_buckets = HashSet::new();
+        for (bucket, file) in buckets_files.iter().enumerate() {
+            for record in file.split(|c| *c == b'>').filter(|r| !r.is_empty()) {
+                let mut lines = record.split(|c| *c == b'\n');
+                let name = std::str::from_utf8(lines.next().unwrap()).unwrap();
+                let (read_index, part) = name.split_once('_').unwrap();
+                let key = (
+                    read_index.parse::<usize>().unwrap(),
+                    part.parse::<usize>().unwrap(),
+                );
+                let sequence = lines.next().unwrap().to_vec();
+                assert!(sequence.len() >= k);
+                assert!(parts.insert(key, sequence).is_none());
+                used_buckets.insert(bucket);
+            }
+        }
+        assert!(used_buckets.len() > 1);
+
+        // Consecutive super-kmers of a read overlap by k bases, their union is the read
+        for (read_index, read) in reads.iter().enumerate() {
+            let mut rebuilt = parts.remove(&(read_index, 0)).unwrap();
+            let mut part = 1;
+            while let Some(sequence) = parts.remove(&(read_index, part)) {
+                assert_eq!(&rebuilt[rebuilt.len() - k..], &sequence[..k]);
+                rebuilt.extend_from_slice(&sequence[k..]);
+                part += 1;
+            }
+            assert_eq!(&rebuilt, read);
+        }
+        assert!(parts.is_empty());
+    }
+
+    #[test]
+    fn bucketing_files_in_run_layout() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let run_id = "build_graph_layout-test";
+        let run_dir = test_dir.path().join(run_id);
+        std::fs::create_dir_all(&run_dir).unwrap();
+
+        let input = test_dir.path().join("input.fa");
+        let mut state = 0x9e3779b97f4a7c15u64;
+        let mut fasta = Vec::new();
+        for read_index in 0..20 {
+            fasta.extend_from_slice(format!(">read{}\n", read_index).as_bytes());
+            fasta.extend((0..200).map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                b"ACGT"[(state >> 62) as usize]
+            }));
+            fasta.push(b'\n');
+        }
+        std::fs::write(&input, fasta).unwrap();
+
+        MemoryFs::init(MemoryDataSize::from_mebioctets(64), 4, 1, 32768);
+
+        let (buckets, counters, _) = crate::static_dispatch::minimizer_bucketing::<
+            CanonicalNtHashIteratorFactory,
+            NonColoredManager,
+        >(
+            vec![GeneralSequenceBlockData::FASTA((input, None))],
+            &run_dir,
+            8,
+            2,
+            21,
+            11,
+            None,
+        );
+        MemoryFs::flush_all_to_disk();
+
+        // The buckets and their counters are written under <run_id>/buckets, prefixed with the run id
+        let spill_dir = run_dir.join("buckets");
+        assert_eq!(buckets.len(), 8);
+        for path in buckets.iter().chain([&counters]) {
+            assert_eq!(path.parent().unwrap(), spill_dir);
+            let file_name = path.file_name().unwrap().to_str().unwrap();
+            assert!(file_name.starts_with(&format!("{}-", run_id)));
+            assert!(path.exists());
+        }
+
+        // Nothing of the bucketing is left at the top of the run directory
+        let run_entries: Vec<_> = std::fs::read_dir(&run_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(run_entries, ["buckets"]);
+    }
+}
@@ -163,6 +163,7 @@ namespace ggcat
         static GGCATInstance *create(GGCATConfig config);
 
         /// Builds a new graph from the given input files, with the specified parameters
+        /// Throws rust::Error if the build fails or is cancelled
         std::string build_graph_from_files(
             // The input files
             Slice<std::string> input_files,
@@ -195,6 +196,7 @@ namespace ggcat
             size_t minimizer_length = -1);
 
         /// Builds a new graph from the given input streams, with the specified parameters
+        /// Throws rust::Error if the build fails or is cancelled
         template <typename S>
         std::string build_graph_from_streams(
             // The input streams
@@ -5,7 +5,9 @@ use std::{mem::transmute, path::PathBuf};
 use ggcat_api::{
     ColorIndexType, DnaSequence, DnaSequencesFileType, DynamicSequencesStream, SequenceInfo,
 };
-use ggcat_api::{ExtraElaboration, GGCATConfig, GGCATInstance, GeneralSequenceBlockData};
+use ggcat_api::{
+    ExtraElaboration, GGCATConfig, GGCATError, GGCATInstance, GeneralSequenceBlockData,
+};
 
 #[repr(transparent)]
 struct GGCATInstanceFFI(GGCATInstance);
@@ -62,7 +64,7 @@ fn ggcat_build(
 
     // Extra elaboration step
     extra_elab: usize,
-) -> String {
+) -> Result<String, GGCATError> {
     const EXTRA_ELABORATION_STEP_NONE: usize = 0;
     const EXTRA_ELABORATION_STEP_UNITIG_LINKS: usize = 1;
     const EXTRA_ELABORATION_STEP_GREEDY_MATCHTIGS: usize = 2;
@@ -98,9 +100,12 @@ fn ggcat_build(
                 _ => panic!("Invalid extra_elab value: {}", extra_elab),
             },
         )
-        .to_str()
-        .unwrap()
-        .to_string()
+        .map(|output_file| {
+            // Empty if the debug options stopped the build before writing the graph
+            output_file.map_or(String::new(), |output_file| {
+                output_file.to_str().unwrap().to_string()
+            })
+        })
 }
 
 fn ggcat_build_from_files(
@@ -131,7 +136,7 @@ fn ggcat_build_from_files(
 
     // Extra elaboration step
     extra_elab: usize,
-) -> String {
+) -> Result<String, GGCATError> {
     ggcat_build(
         instance,
         input_files
@@ -185,7 +190,7 @@ fn ggcat_build_from_streams(
 
     // Extra elaboration step
     extra_elab: usize,
-) -> String {
+) -> Result<String, GGCATError> {
     struct SequencesStreamFFI {
         // extern "C" void (*read_block)(uintptr_t block, bool copy_ident_data, size_t partial_read_copyback, uintptr_t callback, uintptr_t callback_context);
         //      with void callback(uintptr_t callback_context, DnaSequenceFFI sequence, SequenceInfoFFI info);
@@ -308,7 +313,7 @@ fn ggcat_query_graph(
 
     // Query output format
     color_output_format: usize,
-) -> String {
+) -> Result<String, GGCATError> {
     const COLORED_QUERY_OUTPUT_FORMAT_JSON_LINES_WITH_NUMBERS: usize = 0;
     const COLORED_QUERY_OUTPUT_FORMAT_JSON_LINES_WITH_NAMES: usize = 1;
 
@@ -337,9 +342,7 @@ fn ggcat_query_graph(
                 _ => panic!("Invalid color_output_format value: {}", color_output_format),
             },
         )
-        .to_str()
-        .unwrap()
-        .to_string()
+        .map(|output_file| output_file.to_str().unwrap().to_string())
 }
 
 /// Obtains the standard colormap file path from a graph file path
@@ -382,7 +385,7 @@ fn ggcat_dump_unitigs(
 
     output_function_context: usize,
     output_function_ptr: usize,
-) {
+) -> Result<(), GGCATError> {
     let output_function: extern "C" fn(usize, usize, usize, usize, usize, bool) =
         unsafe { transmute(output_function_ptr) };
 
@@ -510,7 +513,7 @@ mod ffi {
 
             // Extra elaboration step
             extra_elab: usize,
-        ) -> String;
+        ) -> Result<String>;
 
         /// Builds a new graph from the given input streams, with the specified parameters
         fn ggcat_build_from_streams(
@@ -541,7 +544,7 @@ mod ffi {
 
             // Extra elaboration step
             extra_elab: usize,
-        ) -> String;
+        ) -> Result<String>;
 
         /// Queries a (optionally) colored graph with a specific set of sequences as queries
         fn ggcat_query_graph(
@@ -569,7 +572,7 @@ mod ffi {
 
             // Query output format
             color_output_format: usize,
-        ) -> String;
+        ) -> Result<String>;
 
         fn ggcat_get_colormap_file(graph_file: String) -> String;
 
@@ -603,6 +606,6 @@ mod ffi {
             output_function_context: usize,
             // extern "C" fn(context: usize, seq_ptr: usize, seq_len: usize, col_ptr: usize, col_len: usize, same_colors: bool),
             output_function_ptr: usize,
-        );
+        ) -> Result<()>;
     }
 }
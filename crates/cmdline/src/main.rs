@@ -7,7 +7,7 @@ extern crate test;
 mod benchmarks;
 
 use ahash::HashMap;
-use ggcat_api::{ExtraElaboration, GGCATConfig, GGCATInstance};
+use ggcat_api::{ExtraElaboration, GGCATConfig, GGCATError, GGCATInstance};
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Write};
 use std::panic;
@@ -51,12 +51,52 @@ arg_enum! {
     }
 }
 
-use ::utils::compute_best_m;
+arg_enum! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum OutputSortOrder {
+        LengthDesc,
+        CoverageDesc,
+        GcContent,
+        ColorsCountDesc,
+    }
+}
+
+arg_enum! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum InputValidation {
+        FailFast,
+        SkipInvalid,
+    }
+}
+
+arg_enum! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum MaxColorsPolicy {
+        Error,
+        Approximate,
+    }
+}
+
+arg_enum! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum CanonicalPolicy {
+        MinSequence,
+        MinHash,
+    }
+}
+
+use ::utils::kmer_length::{validate_lengths, MAX_SEQHASH_KMER_LENGTH};
+use ::utils::spill_layout::create_run_dir;
+use colors::colors_limit::ColorsLimitPolicy;
 use colors::colors_manager::ColorMapReader;
 use colors::storage::deserializer::ColorsDeserializer;
 use colors::DefaultColorsSerializer;
 use config::ColorIndexType;
+use io::sequences_sort::{
+    by_colors_count_desc, by_coverage_desc, by_gc_content, by_length_desc, sort_unitigs_file,
+};
 use io::sequences_stream::general::GeneralSequenceBlockData;
+use io::sequences_validation::InputValidationPolicy;
 use parallel_processor::memory_fs::MemoryFs;
 use std::io::BufRead;
 use structopt::clap::{arg_enum, ArgGroup};
@@ -108,6 +148,46 @@ struct CommonArgs {
     #[structopt(short = "f", long)]
     pub forward_only: bool,
 
+    /// Rule used to choose the canonical orientation of a kmer
+    #[structopt(long = "canonical-policy", default_value = "MinSequence")]
+    pub canonical_policy: CanonicalPolicy,
+
+    /// Validate the structure and the bases of the input records, aborting or skipping the invalid ones
+    #[structopt(long = "validate-input")]
+    pub validate_input: Option<InputValidation>,
+
+    /// Maximum number of invalid input records that are reported
+    #[structopt(long = "max-reported-errors", default_value = "10")]
+    pub max_reported_errors: usize,
+
+    /// Try different reading parameters on the first buckets and use the fastest for the rest of the run
+    #[structopt(long = "auto-tune")]
+    pub auto_tune: bool,
+
+    /// Process the buckets in order with a single executor of each type, for reproducible debugging
+    #[structopt(long = "sequential-kmers-transform")]
+    pub sequential_kmers_transform: bool,
+
+    /// Maximum number of buckets readers of the kmers transform
+    #[structopt(long = "max-readers")]
+    pub max_readers: Option<usize>,
+
+    /// Maximum number of map processors of the kmers transform
+    #[structopt(long = "max-processors")]
+    pub max_processors: Option<usize>,
+
+    /// Maximum number of resplitters of the kmers transform
+    #[structopt(long = "max-resplitters")]
+    pub max_resplitters: Option<usize>,
+
+    /// Store the second level bucket of each read in the temporary buckets, instead of recomputing it when reading them
+    #[structopt(long = "use-second-bucket")]
+    pub use_second_bucket: bool,
+
+    /// Check that the reads of each temporary bucket match the counts recorded when writing it, aborting on mismatches
+    #[structopt(long = "verify")]
+    pub verify: bool,
+
     /// Maximum suggested memory usage (GB)
     /// The tool will try use only up to this GB of memory to store temporary files
     /// without writing to disk. This usage does not include the needed memory for the processing steps.
@@ -123,10 +203,22 @@ struct CommonArgs {
     #[structopt(short = "b", long = "buckets-count-log")]
     pub buckets_count_log: Option<usize>,
 
+    /// Read chunks queued for each compute thread during the minimizer bucketing
+    #[structopt(long = "read-queue-multiplier")]
+    pub read_queue_multiplier: Option<usize>,
+
     /// The level of lz4 compression to be used for the intermediate files
     #[structopt(long = "intermediate-compression-level")]
     pub intermediate_compression_level: Option<u32>,
 
+    /// Size of the buffers of the final output files (KiB)
+    #[structopt(long = "output-buffer-size")]
+    pub output_buffer_size: Option<usize>,
+
+    /// Size of the per thread buffers of the intermediate buckets written by the kmers merge (KiB)
+    #[structopt(long = "per-cpu-buffer-size")]
+    pub per_cpu_buffer_size: Option<usize>,
+
     #[structopt(long = "only-bstats", hidden = true)]
     pub only_bstats: bool,
 }
@@ -149,10 +241,42 @@ struct AssemblerArgs {
     #[structopt(short, long)]
     pub colors: bool,
 
+    /// Track only the presence of each color for a kmer, reducing the memory needed for large colors sets
+    #[structopt(long = "colors-presence-only")]
+    pub colors_presence_only: bool,
+
+    /// Maximum number of colors allowed in the colored graph
+    #[structopt(long = "max-colors")]
+    pub max_colors: Option<usize>,
+
+    /// What to do when the colors are more than the maximum: abort, or store approximate colors subsets
+    #[structopt(long = "max-colors-policy", default_value = "Error")]
+    pub max_colors_policy: MaxColorsPolicy,
+
     /// Minimum multiplicity required to keep a kmer
     #[structopt(short = "s", long = "min-multiplicity", default_value = "2")]
     pub min_multiplicity: usize,
 
+    /// Maximum multiplicity allowed to keep a kmer, to drop the highly repetitive ones
+    #[structopt(long = "max-multiplicity")]
+    pub max_multiplicity: Option<usize>,
+
+    /// Only run the minimizer bucketing, writing the reads of each bucket as a fasta file in this directory
+    #[structopt(long = "export-buckets-reads")]
+    pub export_buckets_reads: Option<PathBuf>,
+
+    /// File with a list of kmers (one sequence for each line or fasta) that must break the unitigs extension
+    #[structopt(long = "masked-kmers")]
+    pub masked_kmers: Option<PathBuf>,
+
+    /// Resume a cancelled build from its temporary directory, with the same inputs and parameters
+    #[structopt(long = "resume", conflicts_with = "colors")]
+    pub resume: Option<PathBuf>,
+
+    /// Stop starting new buckets in the kmers merge after this many seconds, leaving the build to be resumed
+    #[structopt(long = "kmers-merge-time-limit", conflicts_with = "colors")]
+    pub kmers_merge_time_limit: Option<u64>,
+
     // /// Minimum correctness probability for each kmer (using fastq quality checks)
     // #[structopt(short = "q", long = "quality-threshold")]
     // pub quality_threshold: Option<f64>,
@@ -188,6 +312,34 @@ struct AssemblerArgs {
     #[structopt(long = "pathtigs", group = "output-mode")]
     pub pathtigs: bool,
 
+    /// Write a sidecar file mapping each output unitig to the bucket it was assembled in (plain unitigs output only)
+    #[structopt(long = "write-provenance", conflicts_with = "output-mode")]
+    pub write_provenance: bool,
+
+    /// Sort the output unitigs (plain fasta output only), keeping the original order for ties
+    #[structopt(long = "sort-output")]
+    pub sort_output: Option<OutputSortOrder>,
+
+    /// Write the unitigs in the same order on every run, at the cost of spilling them to temporary files
+    #[structopt(long = "ordered-output")]
+    pub ordered_output: bool,
+
+    /// Sync the output file to disk before exiting, at the cost of waiting for the writes to complete
+    #[structopt(long = "durable-output")]
+    pub durable_output: bool,
+
+    /// Write the links between the maximal unitigs as a plain edge list
+    #[structopt(long = "write-edge-list", requires = "generate_maximal_unitigs_links")]
+    pub write_edge_list: bool,
+
+    /// Write the graph with its links as a compact binary dump, portable between machines
+    #[structopt(long = "write-graph-dump", requires = "generate_maximal_unitigs_links")]
+    pub write_graph_dump: bool,
+
+    /// Count the bases of the input reads while they are bucketed, printing their composition and GC fraction
+    #[structopt(long = "bases-composition")]
+    pub bases_composition: bool,
+
     #[structopt(flatten)]
     pub common_args: CommonArgs,
 }
@@ -225,6 +377,10 @@ struct QueryArgs {
     #[structopt(long = "colored-query-output-format")]
     pub colored_query_output_format: Option<ColoredQueryOutputFormat>,
 
+    /// Report the name of each query sequence in the output, along with its index
+    #[structopt(long = "preserve-read-names")]
+    pub preserve_read_names: bool,
+
     #[structopt(short = "x", long, default_value = "MinimizerBucketing")]
     pub step: QuerierStartingStep,
 
@@ -240,6 +396,16 @@ struct QueryArgs {
 // static DEBUG_ALLOCATOR: DebugAllocator = DebugAllocator::new();
 
 fn initialize(args: &CommonArgs, out_file: &PathBuf) -> &'static GGCATInstance {
+    let minimizer_length = validate_lengths(
+        args.kmer_length,
+        args.minimizer_length,
+        (args.hash_type == HashType::SeqHash).then_some(MAX_SEQHASH_KMER_LENGTH),
+    )
+    .unwrap_or_else(|err| {
+        println!("ERROR: {}", err);
+        exit(1);
+    });
+
     let instance = GGCATInstance::create(GGCATConfig {
         temp_dir: Some(args.temp_dir.clone()),
         memory: args.memory,
@@ -251,6 +417,7 @@ fn initialize(args: &CommonArgs, out_file: &PathBuf) -> &'static GGCATInstance {
 
     ggcat_api::debug::DEBUG_KEEP_FILES.store(args.keep_temp_files, Ordering::Relaxed);
     *ggcat_api::debug::BUCKETS_COUNT_LOG_FORCE.lock() = args.buckets_count_log;
+    *ggcat_api::debug::DEBUG_READ_QUEUE_MULTIPLIER.lock() = args.read_queue_multiplier;
     ggcat_api::debug::DEBUG_ONLY_BSTATS.store(args.only_bstats, Ordering::Relaxed);
     *ggcat_api::debug::DEBUG_HASH_TYPE.lock() = match args.hash_type {
         HashType::Auto => ggcat_api::HashType::Auto,
@@ -259,13 +426,46 @@ fn initialize(args: &CommonArgs, out_file: &PathBuf) -> &'static GGCATInstance {
         HashType::RabinKarp64 => ggcat_api::HashType::RabinKarp64,
         HashType::RabinKarp128 => ggcat_api::HashType::RabinKarp128,
     };
-
-    println!(
-        "Using m: {} with k: {}",
-        args.minimizer_length
-            .unwrap_or(compute_best_m(args.kmer_length)),
-        args.kmer_length
+    ggcat_api::debug::DEBUG_CANONICAL_ORIENTATION_MIN_HASH.store(
+        args.canonical_policy == CanonicalPolicy::MinHash,
+        Ordering::Relaxed,
+    );
+    ggcat_api::debug::DEBUG_INPUT_VALIDATION_MODE.store(
+        match args.validate_input {
+            None => InputValidationPolicy::Disabled,
+            Some(InputValidation::FailFast) => InputValidationPolicy::FailFast,
+            Some(InputValidation::SkipInvalid) => InputValidationPolicy::SkipInvalid,
+        }
+        .to_mode(),
+        Ordering::Relaxed,
+    );
+    ggcat_api::debug::DEBUG_INPUT_VALIDATION_MAX_REPORTED_ERRORS
+        .store(args.max_reported_errors, Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_AUTO_TUNE_KMERS_TRANSFORM.store(args.auto_tune, Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_SEQUENTIAL_KMERS_TRANSFORM
+        .store(args.sequential_kmers_transform, Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_KMERS_TRANSFORM_MAX_READERS
+        .store(args.max_readers.unwrap_or(usize::MAX), Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_KMERS_TRANSFORM_MAX_PROCESSORS
+        .store(args.max_processors.unwrap_or(usize::MAX), Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_KMERS_TRANSFORM_MAX_RESPLITTERS.store(
+        args.max_resplitters.unwrap_or(usize::MAX),
+        Ordering::Relaxed,
+    );
+    ggcat_api::debug::DEBUG_USE_SECOND_BUCKET.store(args.use_second_bucket, Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_VERIFY_BUCKETS_COUNTERS.store(args.verify, Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_OUTPUT_BUFFER_SIZE.store(
+        args.output_buffer_size
+            .map_or(usize::MAX, |size| size.max(1) * 1024),
+        Ordering::Relaxed,
     );
+    ggcat_api::debug::DEBUG_PER_CPU_BUFFER_SIZE.store(
+        args.per_cpu_buffer_size
+            .map_or(usize::MAX, |size| size.max(1) * 1024),
+        Ordering::Relaxed,
+    );
+
+    println!("Using m: {} with k: {}", minimizer_length, args.kmer_length);
 
     // #[cfg(feature = "mem-analysis")]
     // debug_print_allocations("/tmp/allocations", Duration::from_secs(5));
@@ -406,8 +606,49 @@ fn run_assembler_from_args(instance: &GGCATInstance, args: AssemblerArgs) {
     *ggcat_api::debug::DEBUG_ASSEMBLER_FIRST_STEP.lock() = convert_assembler_step(args.step);
     *ggcat_api::debug::DEBUG_ASSEMBLER_LAST_STEP.lock() = convert_assembler_step(args.last_step);
     ggcat_api::debug::DEBUG_LINK_PHASE_ITERATION_START_STEP.store(args.number, Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_WRITE_UNITIGS_PROVENANCE
+        .store(args.write_provenance, Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_WRITE_EDGE_LIST.store(args.write_edge_list, Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_WRITE_GRAPH_DUMP.store(args.write_graph_dump, Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_COUNT_BASES_COMPOSITION
+        .store(args.bases_composition, Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_DETERMINISTIC_OUTPUT.store(args.ordered_output, Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_DURABLE_OUTPUT.store(args.durable_output, Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_COLORS_PRESENCE_ONLY
+        .store(args.colors_presence_only, Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_MAX_COLORS
+        .store(args.max_colors.unwrap_or(usize::MAX), Ordering::Relaxed);
+    ggcat_api::debug::DEBUG_MAX_COLORS_POLICY.store(
+        match args.max_colors_policy {
+            MaxColorsPolicy::Error => ColorsLimitPolicy::Error,
+            MaxColorsPolicy::Approximate => ColorsLimitPolicy::Approximate,
+        }
+        .to_mode(),
+        Ordering::Relaxed,
+    );
+    if let Some(max_multiplicity) = args
+        .max_multiplicity
+        .filter(|max_multiplicity| *max_multiplicity < args.min_multiplicity)
+    {
+        println!(
+            "ERROR: the maximum multiplicity {} is lower than the minimum multiplicity {}",
+            max_multiplicity, args.min_multiplicity
+        );
+        exit(1);
+    }
+    ggcat_api::debug::DEBUG_MAX_MULTIPLICITY.store(
+        args.max_multiplicity.unwrap_or(usize::MAX),
+        Ordering::Relaxed,
+    );
+    *ggcat_api::debug::DEBUG_MASKED_KMERS_FILE.lock() = args.masked_kmers;
+    *ggcat_api::debug::DEBUG_EXPORT_BUCKETS_READS.lock() = args.export_buckets_reads;
+    *ggcat_api::debug::DEBUG_RESUME_RUN_DIR.lock() = args.resume;
+    ggcat_api::debug::DEBUG_KMERS_MERGE_TIME_LIMIT_SECS.store(
+        args.kmers_merge_time_limit.unwrap_or(u64::MAX),
+        Ordering::Relaxed,
+    );
 
-    let output_file = instance.build_graph(
+    let output_file = match instance.build_graph(
         inputs,
         args.output_file,
         Some(&color_names),
@@ -428,11 +669,82 @@ fn run_assembler_from_args(instance: &GGCATInstance, args: AssemblerArgs) {
         } else {
             ExtraElaboration::None
         },
-    );
+    ) {
+        Ok(Some(output_file)) => output_file,
+        // The debug options stopped the build before the output
+        Ok(None) => return,
+        Err(GGCATError::Cancelled { run_dir }) => {
+            println!(
+                "Build interrupted, it can be resumed by adding: --resume {}",
+                run_dir.display()
+            );
+            exit(1);
+        }
+        Err(err) => {
+            println!("ERROR: {}", err);
+            exit(1);
+        }
+    };
+
+    if let Some(sort_order) = args.sort_output {
+        sort_output_file(&output_file, &args.common_args, sort_order);
+    }
 
     println!("Final output saved to: {}", output_file.display());
 }
 
+fn sort_output_file(output_file: &Path, common_args: &CommonArgs, sort_order: OutputSortOrder) {
+    if output_file
+        .extension()
+        .map(|x| x == "gz" || x == "lz4")
+        .unwrap_or(false)
+    {
+        println!("Warning: sorting is supported only for plain fasta output, skipping");
+        return;
+    }
+
+    // Concurrent runs can share the same temp dir
+    let sort_dir = create_run_dir(&common_args.temp_dir, "sort_output");
+    let sorted_file = sort_dir.join("sorted-output.fa");
+    let max_memory = (common_args.memory * (1024 * 1024 * 1024) as f64) as usize;
+
+    match sort_order {
+        OutputSortOrder::LengthDesc => sort_unitigs_file(
+            output_file,
+            &sorted_file,
+            &sort_dir,
+            by_length_desc,
+            max_memory,
+        ),
+        OutputSortOrder::CoverageDesc => sort_unitigs_file(
+            output_file,
+            &sorted_file,
+            &sort_dir,
+            by_coverage_desc,
+            max_memory,
+        ),
+        OutputSortOrder::GcContent => sort_unitigs_file(
+            output_file,
+            &sorted_file,
+            &sort_dir,
+            by_gc_content,
+            max_memory,
+        ),
+        OutputSortOrder::ColorsCountDesc => sort_unitigs_file(
+            output_file,
+            &sorted_file,
+            &sort_dir,
+            by_colors_count_desc,
+            max_memory,
+        ),
+    }
+
+    std::fs::rename(&sorted_file, output_file)
+        .or_else(|_| std::fs::copy(&sorted_file, output_file).map(|_| ()))
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&sort_dir);
+}
+
 fn convert_querier_step(step: QuerierStartingStep) -> querier::QuerierStartingStep {
     match step {
         QuerierStartingStep::MinimizerBucketing => querier::QuerierStartingStep::MinimizerBucketing,
@@ -444,28 +756,34 @@ fn convert_querier_step(step: QuerierStartingStep) -> querier::QuerierStartingSt
 
 fn run_querier_from_args(instance: &GGCATInstance, args: QueryArgs) -> PathBuf {
     *ggcat_api::debug::DEBUG_QUERIER_FIRST_STEP.lock() = convert_querier_step(args.step);
+    ggcat_api::debug::DEBUG_PRESERVE_READ_NAMES.store(args.preserve_read_names, Ordering::Relaxed);
 
-    instance.query_graph(
-        args.input_graph,
-        args.input_query,
-        args.output_file_prefix,
-        args.common_args.kmer_length,
-        args.common_args.threads_count,
-        args.common_args.forward_only,
-        args.common_args.minimizer_length,
-        args.colors,
-        match args
-            .colored_query_output_format
-            .unwrap_or(ColoredQueryOutputFormat::JsonLinesWithNumbers)
-        {
-            ColoredQueryOutputFormat::JsonLinesWithNumbers => {
-                querier::ColoredQueryOutputFormat::JsonLinesWithNumbers
-            }
-            ColoredQueryOutputFormat::JsonLinesWithNames => {
-                querier::ColoredQueryOutputFormat::JsonLinesWithNames
-            }
-        },
-    )
+    instance
+        .query_graph(
+            args.input_graph,
+            args.input_query,
+            args.output_file_prefix,
+            args.common_args.kmer_length,
+            args.common_args.threads_count,
+            args.common_args.forward_only,
+            args.common_args.minimizer_length,
+            args.colors,
+            match args
+                .colored_query_output_format
+                .unwrap_or(ColoredQueryOutputFormat::JsonLinesWithNumbers)
+            {
+                ColoredQueryOutputFormat::JsonLinesWithNumbers => {
+                    querier::ColoredQueryOutputFormat::JsonLinesWithNumbers
+                }
+                ColoredQueryOutputFormat::JsonLinesWithNames => {
+                    querier::ColoredQueryOutputFormat::JsonLinesWithNames
+                }
+            },
+        )
+        .unwrap_or_else(|err| {
+            println!("ERROR: {}", err);
+            exit(1);
+        })
 }
 
 instrumenter::global_setup_instrumenter!();
@@ -491,6 +809,10 @@ fn main() {
         exit(1);
     }));
 
+    // On termination the running build is checkpointed instead of being lost
+    #[cfg(feature = "termination-handler")]
+    ::utils::cancellation::install_termination_handler();
+
     match args {
         CliArgs::Build(args) => {
             let _guard = instrumenter::initialize_tracing(
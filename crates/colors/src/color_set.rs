@@ -0,0 +1,225 @@
+use byteorder::ReadBytesExt;
+use config::ColorIndexType;
+use io::concurrent::temp_reads::extra_data::{HasEmptyExtraBuffer, SequenceExtraData};
+use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
+use std::cmp::Ordering;
+use std::io::{Read, Write};
+
+/// Colors reserved before decoding a set, as a corrupted count must not allocate a huge buffer
+const MAX_PREALLOCATED_COLORS: usize = 1024;
+
+/// Sorted set of color ids associated to a kmer, encoded as the varint deltas between consecutive colors.
+/// Can be used as the kmers extra data to merge the colors of identical kmers in the map processors
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ColorSet {
+    colors: Vec<ColorIndexType>,
+}
+
+impl ColorSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn single(color: ColorIndexType) -> Self {
+        Self {
+            colors: vec![color],
+        }
+    }
+
+    pub fn from_unsorted(mut colors: Vec<ColorIndexType>) -> Self {
+        colors.sort_unstable();
+        colors.dedup();
+        Self { colors }
+    }
+
+    pub fn colors(&self) -> &[ColorIndexType] {
+        &self.colors
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colors.is_empty()
+    }
+
+    pub fn contains(&self, color: ColorIndexType) -> bool {
+        self.colors.binary_search(&color).is_ok()
+    }
+
+    pub fn insert(&mut self, color: ColorIndexType) {
+        if let Err(position) = self.colors.binary_search(&color) {
+            self.colors.insert(position, color);
+        }
+    }
+
+    /// Adds the colors of other, merging the two sorted lists in linear time
+    pub fn union_with(&mut self, other: &ColorSet) {
+        // Fast path for the common case of the same color added multiple times
+        if other.colors.iter().all(|color| self.contains(*color)) {
+            return;
+        }
+
+        let mut merged = Vec::with_capacity(self.colors.len() + other.colors.len());
+        let mut first = self.colors.iter().peekable();
+        let mut second = other.colors.iter().peekable();
+        while let (Some(&&a), Some(&&b)) = (first.peek(), second.peek()) {
+            match a.cmp(&b) {
+                Ordering::Less => {
+                    merged.push(a);
+                    first.next();
+                }
+                Ordering::Greater => {
+                    merged.push(b);
+                    second.next();
+                }
+                Ordering::Equal => {
+                    merged.push(a);
+                    first.next();
+                    second.next();
+                }
+            }
+        }
+        merged.extend(first);
+        merged.extend(second);
+        self.colors = merged;
+    }
+
+    /// Keeps only the colors that are also in other
+    pub fn intersect_with(&mut self, other: &ColorSet) {
+        let mut other_colors = other.colors.iter().peekable();
+        self.colors.retain(|color| {
+            while other_colors.next_if(|other| *other < color).is_some() {}
+            other_colors.peek() == Some(&color)
+        });
+    }
+
+    pub fn union(&self, other: &ColorSet) -> ColorSet {
+        let mut result = self.clone();
+        result.union_with(other);
+        result
+    }
+
+    pub fn intersection(&self, other: &ColorSet) -> ColorSet {
+        let mut result = self.clone();
+        result.intersect_with(other);
+        result
+    }
+}
+
+impl HasEmptyExtraBuffer for ColorSet {}
+impl SequenceExtraData for ColorSet {
+    fn decode_extended(_: &mut Self::TempBuffer, reader: &mut impl Read) -> Option<Self> {
+        let count = decode_varint(|| reader.read_u8().ok())? as usize;
+        let mut colors = Vec::with_capacity(count.min(MAX_PREALLOCATED_COLORS));
+        let mut last_color = 0;
+        for index in 0..count {
+            // Corrupted deltas must not wrap around the colors range
+            let delta = ColorIndexType::try_from(decode_varint(|| reader.read_u8().ok())?).ok()?;
+            // The colors are strictly increasing, so only the first one can have a zero delta
+            if delta == 0 && index > 0 {
+                return None;
+            }
+            last_color = last_color.checked_add(delta)?;
+            colors.push(last_color);
+        }
+        Some(Self { colors })
+    }
+
+    fn encode_extended(&self, _: &Self::TempBuffer, writer: &mut impl Write) {
+        encode_varint(|b| writer.write_all(b), self.colors.len() as u64).unwrap();
+        let mut last_color = 0;
+        for color in self.colors.iter() {
+            encode_varint(|b| writer.write_all(b), (*color - last_color) as u64).unwrap();
+            last_color = *color;
+        }
+    }
+
+    #[inline(always)]
+    fn max_size(&self) -> usize {
+        (self.colors.len() + 1) * VARINT_MAX_SIZE
+    }
+
+    #[inline(always)]
+    fn max_size_hint(&self) -> usize {
+        self.max_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color_set::ColorSet;
+    use config::ColorIndexType;
+    use io::concurrent::temp_reads::extra_data::SequenceExtraData;
+    use io::varint::encode_varint;
+
+    #[test]
+    fn color_sets_algebra_and_encoding() {
+        let first = ColorSet::from_unsorted(vec![7, 1, 300, 7, 42]);
+        let second = ColorSet::from_unsorted(vec![42, 2, 1, 100000]);
+
+        assert_eq!(first.colors(), &[1, 7, 42, 300]);
+        assert_eq!(first.union(&second).colors(), &[1, 2, 7, 42, 300, 100000]);
+        assert_eq!(first.intersection(&second).colors(), &[1, 42]);
+        assert!(first.intersection(&ColorSet::new()).is_empty());
+
+        let mut merged = ColorSet::single(42);
+        merged.union_with(&ColorSet::single(42));
+        merged.insert(3);
+        assert_eq!(merged.colors(), &[3, 42]);
+
+        let mut encoded = vec![];
+        first.encode_extended(&(), &mut encoded);
+        assert!(encoded.len() <= first.max_size());
+        assert_eq!(
+            ColorSet::decode_from_slice_extended(&mut (), &encoded),
+            Some(first)
+        );
+    }
+
+    #[test]
+    fn overflowing_color_deltas() {
+        let encode = |values: &[u64]| {
+            let mut encoded = vec![];
+            for value in values {
+                encode_varint(|b| encoded.extend_from_slice(b), *value);
+            }
+            encoded
+        };
+        let max_color = ColorIndexType::MAX as u64;
+
+        assert_eq!(
+            ColorSet::decode_from_slice_extended(&mut (), &encode(&[2, 1, max_color - 1])),
+            Some(ColorSet::from_unsorted(vec![1, ColorIndexType::MAX]))
+        );
+        assert_eq!(
+            ColorSet::decode_from_slice_extended(&mut (), &encode(&[2, 1, max_color])),
+            None
+        );
+        assert_eq!(
+            ColorSet::decode_from_slice_extended(&mut (), &encode(&[1, max_color + 1])),
+            None
+        );
+
+        // Duplicated colors
+        assert_eq!(
+            ColorSet::decode_from_slice_extended(&mut (), &encode(&[2, 0, 0])),
+            None
+        );
+        assert_eq!(
+            ColorSet::decode_from_slice_extended(&mut (), &encode(&[3, 0, 4, 0])),
+            None
+        );
+        assert_eq!(
+            ColorSet::decode_from_slice_extended(&mut (), &encode(&[2, 0, 4])),
+            Some(ColorSet::from_unsorted(vec![0, 4]))
+        );
+
+        // A huge count with truncated colors
+        assert_eq!(
+            ColorSet::decode_from_slice_extended(&mut (), &encode(&[u32::MAX as u64, 1, 2])),
+            None
+        );
+    }
+}
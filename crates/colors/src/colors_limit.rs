@@ -0,0 +1,190 @@
+use config::{ColorIndexType, MAX_COLORS, MAX_COLORS_POLICY};
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::Ordering;
+
+/// Bits set in the approximate representation for each color
+const APPROXIMATE_COLORS_HASHES: u64 = 2;
+
+/// Policy applied when the number of colors exceeds the configured maximum
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+pub enum ColorsLimitPolicy {
+    /// Abort the run
+    #[default]
+    Error,
+    /// Store for each colors subset a bloom filter with the maximum number of colors as bits
+    Approximate,
+}
+
+impl ColorsLimitPolicy {
+    pub const fn to_mode(self) -> u8 {
+        match self {
+            ColorsLimitPolicy::Error => 0,
+            ColorsLimitPolicy::Approximate => 1,
+        }
+    }
+
+    /// Returns the policy selected for the current run
+    pub fn current() -> Self {
+        match MAX_COLORS_POLICY.load(Ordering::Relaxed) {
+            0 => ColorsLimitPolicy::Error,
+            _ => ColorsLimitPolicy::Approximate,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ColorsLimitError {
+    pub colors_count: usize,
+    pub max_colors: usize,
+}
+
+impl Display for ColorsLimitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the number of colors {} exceeds the maximum allowed of {}",
+            self.colors_count, self.max_colors
+        )
+    }
+}
+
+/// Approximate colors subsets, where each color is mapped to a fixed number of bits of a bloom filter
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ApproximateColors {
+    bits_count: u64,
+    hashes_count: u64,
+}
+
+impl ApproximateColors {
+    /// Bloom filter parameters, as stored in the colormap
+    pub fn new(bits_count: u64, hashes_count: u64) -> Self {
+        Self {
+            bits_count: bits_count.max(1),
+            hashes_count: hashes_count.max(1),
+        }
+    }
+
+    pub fn bits_count(&self) -> usize {
+        self.bits_count as usize
+    }
+
+    pub fn hashes_count(&self) -> usize {
+        self.hashes_count as usize
+    }
+
+    #[inline(always)]
+    fn color_bit(&self, color: ColorIndexType, hash_index: u64) -> ColorIndexType {
+        let hash = (color as u64 ^ (hash_index << 32)).wrapping_mul(0x9E3779B97F4A7C15);
+        ((hash >> 32) % self.bits_count) as ColorIndexType
+    }
+
+    /// Writes the sorted bits of the bloom filter representing the colors subset
+    pub fn map_subset(&self, colors: &[ColorIndexType], bits: &mut Vec<ColorIndexType>) {
+        bits.clear();
+        for color in colors {
+            for hash_index in 0..self.hashes_count {
+                bits.push(self.color_bit(*color, hash_index));
+            }
+        }
+        bits.sort_unstable();
+        bits.dedup();
+    }
+
+    /// Checks if the color may be in the subset represented by the bloom filter bits
+    pub fn may_contain(&self, bits: &[ColorIndexType], color: ColorIndexType) -> bool {
+        (0..self.hashes_count).all(|hash_index| {
+            bits.binary_search(&self.color_bit(color, hash_index))
+                .is_ok()
+        })
+    }
+
+    /// Writes the colors that may be in the subset represented by the bloom filter bits,
+    /// a superset of the colors it was built from
+    pub fn decode_subset(
+        &self,
+        bits: &[ColorIndexType],
+        colors_count: usize,
+        colors: &mut Vec<ColorIndexType>,
+    ) {
+        colors.extend(
+            (0..colors_count as ColorIndexType).filter(|color| self.may_contain(bits, *color)),
+        );
+    }
+}
+
+/// Checks the number of colors against the limit, returning the approximate representation to use if needed
+pub fn check_colors_limit(
+    colors_count: usize,
+    max_colors: usize,
+    policy: ColorsLimitPolicy,
+) -> Result<Option<ApproximateColors>, ColorsLimitError> {
+    if colors_count <= max_colors {
+        return Ok(None);
+    }
+
+    match policy {
+        ColorsLimitPolicy::Error => Err(ColorsLimitError {
+            colors_count,
+            max_colors,
+        }),
+        ColorsLimitPolicy::Approximate => Ok(Some(ApproximateColors::new(
+            max_colors as u64,
+            APPROXIMATE_COLORS_HASHES,
+        ))),
+    }
+}
+
+/// Checks the number of colors against the limit configured for the current run
+pub fn check_current_colors_limit(
+    colors_count: usize,
+) -> Result<Option<ApproximateColors>, ColorsLimitError> {
+    check_colors_limit(
+        colors_count,
+        MAX_COLORS.load(Ordering::Relaxed),
+        ColorsLimitPolicy::current(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::colors_limit::{
+        check_colors_limit, ApproximateColors, ColorsLimitError, ColorsLimitPolicy,
+    };
+    use config::ColorIndexType;
+
+    #[test]
+    fn colors_over_limit() {
+        assert!(check_colors_limit(100, 100, ColorsLimitPolicy::Error)
+            .unwrap()
+            .is_none());
+
+        assert_eq!(
+            check_colors_limit(101, 100, ColorsLimitPolicy::Error).unwrap_err(),
+            ColorsLimitError {
+                colors_count: 101,
+                max_colors: 100
+            }
+        );
+
+        let approximate = check_colors_limit(1000, 64, ColorsLimitPolicy::Approximate)
+            .unwrap()
+            .unwrap();
+        assert_eq!(approximate.bits_count(), 64);
+
+        let colors: Vec<ColorIndexType> = (0..1000).step_by(7).collect();
+        let mut bits = vec![];
+        approximate.map_subset(&colors, &mut bits);
+
+        assert!(bits.len() <= 64);
+        assert!(bits.iter().all(|b| *b < 64));
+        assert!(colors.iter().all(|c| approximate.may_contain(&bits, *c)));
+
+        let mut decoded = vec![];
+        approximate.decode_subset(&bits, 1000, &mut decoded);
+        assert!(decoded.windows(2).all(|w| w[0] < w[1]));
+        assert!(colors.iter().all(|c| decoded.binary_search(c).is_ok()));
+
+        assert_eq!(approximate, ApproximateColors::new(64, 2));
+        assert_eq!(ApproximateColors::new(0, 0).bits_count(), 1);
+    }
+}
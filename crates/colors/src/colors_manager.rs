@@ -1,3 +1,4 @@
+use crate::colors_limit::ColorsLimitError;
 use crate::parsers::SingleSequenceInfo;
 use config::{BucketIndexType, ColorCounterType, ColorIndexType};
 use dynamic_dispatch::dynamic_dispatch;
@@ -130,7 +131,7 @@ pub trait ColorsMergeManager<H: MinimizerHashFunctionFactory, MH: HashFunctionFa
     fn create_colors_table(
         path: impl AsRef<Path>,
         color_names: &[String],
-    ) -> Self::GlobalColorsTableWriter;
+    ) -> Result<Self::GlobalColorsTableWriter, ColorsLimitError>;
 
     /// Creates a new colors table at the given path
     fn open_colors_table(path: impl AsRef<Path>) -> Self::GlobalColorsTableReader;
@@ -168,6 +169,7 @@ pub trait ColorsMergeManager<H: MinimizerHashFunctionFactory, MH: HashFunctionFa
         map: &mut HashMap<MH::HashTypeUnextendable, MapEntry<Self::HashMapTempColorIndex>>,
         k: usize,
         min_multiplicity: usize,
+        max_multiplicity: Option<usize>,
     );
 
     /// Struct used to hold color information about unitigs
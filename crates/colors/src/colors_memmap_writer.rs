@@ -1,4 +1,5 @@
 // use crate::storage::roaring::ColorsStorage;
+use crate::colors_limit::{check_current_colors_limit, ApproximateColors, ColorsLimitError};
 use crate::storage::serializer::ColorsSerializer;
 use crate::storage::ColorsSerializerTrait;
 use config::ColorIndexType;
@@ -6,23 +7,34 @@ use dashmap::DashMap;
 use hashes::dummy_hasher::DummyHasherBuilder;
 use rand::{thread_rng, RngCore};
 use siphasher::sip128::{Hasher128, SipHasher13};
+use std::cell::UnsafeCell;
 use std::hash::Hash;
 use std::path::Path;
 
 pub struct ColorsMemMapWriter<C: ColorsSerializerTrait> {
     colors: DashMap<u128, ColorIndexType, DummyHasherBuilder>,
     colors_storage: ColorsSerializer<C>,
+    colors_count: usize,
+    approximate_colors: Option<ApproximateColors>,
     hash_keys: (u64, u64),
 }
 
+thread_local! {
+    static TEMP_APPROXIMATE_COLORS: UnsafeCell<Vec<ColorIndexType>> = const { UnsafeCell::new(Vec::new()) };
+}
+
 impl<C: ColorsSerializerTrait> ColorsMemMapWriter<C> {
-    pub fn new(file: impl AsRef<Path>, color_names: &[String]) -> Self {
+    pub fn new(file: impl AsRef<Path>, color_names: &[String]) -> Result<Self, ColorsLimitError> {
+        let approximate_colors = check_current_colors_limit(color_names.len())?;
+
         let mut rng = thread_rng();
-        Self {
+        Ok(Self {
             colors: DashMap::with_hasher(DummyHasherBuilder),
-            colors_storage: ColorsSerializer::new(file, color_names),
+            colors_storage: ColorsSerializer::new(file, color_names, approximate_colors.as_ref()),
+            colors_count: color_names.len(),
+            approximate_colors,
             hash_keys: (rng.next_u64(), rng.next_u64()),
-        }
+        })
     }
 
     fn hash_colors(&self, colors: &[ColorIndexType]) -> u128 {
@@ -33,6 +45,18 @@ impl<C: ColorsSerializerTrait> ColorsMemMapWriter<C> {
 
     #[inline(always)]
     pub fn get_id(&self, colors: &[ColorIndexType]) -> ColorIndexType {
+        match &self.approximate_colors {
+            None => self.get_exact_id(colors),
+            Some(approximate_colors) => TEMP_APPROXIMATE_COLORS.with(|bits| {
+                let bits = unsafe { &mut *bits.get() };
+                approximate_colors.map_subset(colors, bits);
+                self.get_exact_id(bits)
+            }),
+        }
+    }
+
+    #[inline(always)]
+    fn get_exact_id(&self, colors: &[ColorIndexType]) -> ColorIndexType {
         let hash = self.hash_colors(colors);
 
         match self.colors.get(&hash) {
@@ -45,6 +69,10 @@ impl<C: ColorsSerializerTrait> ColorsMemMapWriter<C> {
         }
     }
 
+    pub fn colors_count(&self) -> usize {
+        self.colors_count
+    }
+
     pub fn print_stats(&self) {
         self.colors_storage.print_stats();
     }
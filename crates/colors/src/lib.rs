@@ -1,11 +1,14 @@
 use crate::storage::run_length::RunLengthColorsSerializer;
 
 pub mod bundles;
+pub mod color_set;
+pub mod colors_limit;
 pub mod colors_manager;
 pub mod colors_memmap_writer;
 pub mod managers;
 pub mod non_colored;
 pub mod parsers;
+pub mod presence_bitset;
 pub mod storage;
 
 pub(crate) mod async_slice_queue;
@@ -1,12 +1,15 @@
+use crate::colors_limit::ColorsLimitError;
 use crate::colors_manager::ColorsMergeManager;
 use crate::colors_memmap_writer::ColorsMemMapWriter;
+use crate::presence_bitset::ColorsPresenceBitset;
 use crate::DefaultColorsSerializer;
 use atoi::{FromRadix10, FromRadix16};
 use bstr::ByteSlice;
 use byteorder::ReadBytesExt;
 use config::{
     get_compression_level_info, get_memory_mode, ColorCounterType, ColorIndexType, MinimizerType,
-    SwapPriority, PARTIAL_VECS_CHECKPOINT_SIZE, READ_FLAG_INCL_BEGIN, READ_FLAG_INCL_END,
+    SwapPriority, COLORS_PRESENCE_ONLY, PARTIAL_VECS_CHECKPOINT_SIZE, READ_FLAG_INCL_BEGIN,
+    READ_FLAG_INCL_END,
 };
 use hashbrown::HashMap;
 use hashes::ExtendableHashTraitType;
@@ -112,6 +115,10 @@ pub struct MultipleColorsManager<H: MinimizerHashFunctionFactory, MH: HashFuncti
     kmers_count: usize,
     sequences_count: usize,
     temp_colors_buffer: Vec<ColorIndexType>,
+    /// Occurrences not yet seen of each kmer with a presence bitset in temp_colors_buffer
+    presence_remaining: Vec<ColorIndexType>,
+    presence_colors: Vec<ColorIndexType>,
+    last_presence_colors: Vec<ColorIndexType>,
     temp_dir: PathBuf,
     _phantom: PhantomData<(H, MH)>,
 }
@@ -130,7 +137,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
     fn create_colors_table(
         path: impl AsRef<Path>,
         color_names: &[String],
-    ) -> Self::GlobalColorsTableWriter {
+    ) -> Result<Self::GlobalColorsTableWriter, ColorsLimitError> {
         ColorsMemMapWriter::new(path, color_names)
     }
 
@@ -153,6 +160,9 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
             kmers_count: 0,
             sequences_count: 0,
             temp_colors_buffer: vec![],
+            presence_remaining: vec![],
+            presence_colors: vec![],
+            last_presence_colors: vec![],
             temp_dir: temp_dir.to_path_buf(),
             _phantom: PhantomData,
         }
@@ -162,6 +172,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         data.sequences.iter_mut().for_each(|s| s.clear());
         data.temp_colors_buffer.clear();
         data.temp_colors_buffer.shrink_to(TEMP_BUFFER_START_SIZE);
+        data.presence_remaining.clear();
         data.kmers_count = 0;
         data.sequences_count = 0;
     }
@@ -232,9 +243,15 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         map: &mut HashMap<MH::HashTypeUnextendable, MapEntry<Self::HashMapTempColorIndex>>,
         k: usize,
         min_multiplicity: usize,
+        max_multiplicity: Option<usize>,
     ) {
+        let presence_only = COLORS_PRESENCE_ONLY.load(Ordering::Relaxed);
+        let presence_words = ColorsPresenceBitset::words_count(global_colors_table.colors_count());
+
         for buffer in data.sequences.iter_mut() {
             data.temp_colors_buffer.clear();
+            data.presence_remaining.clear();
+            data.last_presence_colors.clear();
 
             let mut stream = buffer.get_stream();
 
@@ -281,7 +298,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
                         tmp
                     };
 
-                    if entry.get_kmer_multiplicity() < min_multiplicity {
+                    if !entry.is_multiplicity_in_range(min_multiplicity, max_multiplicity) {
                         continue;
                     }
 
@@ -306,7 +323,12 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
 
                     if missing_temp_color {
                         let colors_count = entry_count;
-                        let start_temp_color_index = data.temp_colors_buffer.len();
+                        // In presence mode each kmer has a bitset of the same size, referenced by its index
+                        let start_temp_color_index = if presence_only {
+                            data.presence_remaining.len()
+                        } else {
+                            data.temp_colors_buffer.len()
+                        };
 
                         match () {
                             #[cfg(not(feature = "support_kmer_counters"))]
@@ -320,10 +342,16 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
                             }
                         };
 
-                        data.temp_colors_buffer
-                            .resize(data.temp_colors_buffer.len() + colors_count + 1, 0);
+                        if presence_only {
+                            data.temp_colors_buffer
+                                .resize(data.temp_colors_buffer.len() + presence_words, 0);
+                            data.presence_remaining.push(colors_count as ColorIndexType);
+                        } else {
+                            data.temp_colors_buffer
+                                .resize(data.temp_colors_buffer.len() + colors_count + 1, 0);
 
-                        data.temp_colors_buffer[start_temp_color_index] = 1;
+                            data.temp_colors_buffer[start_temp_color_index] = 1;
+                        }
                     }
 
                     let position = match () {
@@ -333,35 +361,67 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
                         () => entry.color_index & !VISITED_BIT,
                     };
 
-                    let col_count = data.temp_colors_buffer[position] as usize;
-                    data.temp_colors_buffer[position] += 1;
+                    let has_all_colors = if presence_only {
+                        let bitset_range =
+                            (position * presence_words)..((position + 1) * presence_words);
+                        ColorsPresenceBitset::set_color(
+                            &mut data.temp_colors_buffer[bitset_range.clone()],
+                            color,
+                        );
+                        data.presence_remaining[position] -= 1;
+
+                        let has_all_colors = data.presence_remaining[position] == 0;
+
+                        // All the occurrences were seen, decode the colors present
+                        if has_all_colors {
+                            ColorsPresenceBitset::decode_colors(
+                                &data.temp_colors_buffer[bitset_range],
+                                &mut data.presence_colors,
+                            );
+
+                            if data.presence_colors != data.last_presence_colors {
+                                last_color = global_colors_table.get_id(&data.presence_colors);
+                                std::mem::swap(
+                                    &mut data.presence_colors,
+                                    &mut data.last_presence_colors,
+                                );
+                            }
+                        }
+                        has_all_colors
+                    } else {
+                        let col_count = data.temp_colors_buffer[position] as usize;
+                        data.temp_colors_buffer[position] += 1;
 
-                    assert_eq!(data.temp_colors_buffer[position + col_count], 0);
-                    data.temp_colors_buffer[position + col_count] = color;
+                        assert_eq!(data.temp_colors_buffer[position + col_count], 0);
+                        data.temp_colors_buffer[position + col_count] = color;
 
-                    let has_all_colors = (position + col_count + 1)
-                        == data.temp_colors_buffer.len()
-                        || data.temp_colors_buffer[position + col_count + 1] != 0;
+                        let has_all_colors = (position + col_count + 1)
+                            == data.temp_colors_buffer.len()
+                            || data.temp_colors_buffer[position + col_count + 1] != 0;
 
-                    // All colors were added, let's assign the final color
-                    if has_all_colors {
-                        let colors_range = &mut data.temp_colors_buffer
-                            [(position + 1)..(position + col_count + 1)];
+                        // All colors were added, let's assign the final color
+                        if has_all_colors {
+                            let colors_range = &mut data.temp_colors_buffer
+                                [(position + 1)..(position + col_count + 1)];
 
-                        colors_range.sort_unstable();
+                            colors_range.sort_unstable();
 
-                        // Get the new partition indexes, start to dedup last element
-                        let new_partition = (position + 1)
-                            ..(position + 1 + colors_range.nq_partition_dedup().0.len());
+                            // Get the new partition indexes, start to dedup last element
+                            let new_partition = (position + 1)
+                                ..(position + 1 + colors_range.nq_partition_dedup().0.len());
 
-                        let unique_colors = &data.temp_colors_buffer[new_partition.clone()];
+                            let unique_colors = &data.temp_colors_buffer[new_partition.clone()];
 
-                        // Assign the subset color index to the current kmer
-                        if unique_colors != &data.temp_colors_buffer[last_partition.clone()] {
-                            last_color = global_colors_table.get_id(unique_colors);
-                            last_partition = new_partition;
+                            // Assign the subset color index to the current kmer
+                            if unique_colors != &data.temp_colors_buffer[last_partition.clone()] {
+                                last_color = global_colors_table.get_id(unique_colors);
+                                last_partition = new_partition;
+                            }
                         }
+                        has_all_colors
+                    };
 
+                    if has_all_colors {
                         match () {
                             #[cfg(not(feature = "support_kmer_counters"))]
                             () => {
@@ -1,3 +1,4 @@
+use crate::colors_limit::ColorsLimitError;
 use crate::colors_manager::ColorsMergeManager;
 use crate::storage::deserializer::ColorsDeserializer;
 use crate::DefaultColorsSerializer;
@@ -32,8 +33,8 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
     fn create_colors_table(
         _path: impl AsRef<Path>,
         _color_names: &[String],
-    ) -> Self::GlobalColorsTableWriter {
-        ()
+    ) -> Result<Self::GlobalColorsTableWriter, ColorsLimitError> {
+        Ok(())
     }
 
     fn open_colors_table(path: impl AsRef<Path>) -> Self::GlobalColorsTableReader {
@@ -87,6 +88,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         _map: &mut HashMap<MH::HashTypeUnextendable, MapEntry<Self::HashMapTempColorIndex>>,
         _k: usize,
         _min_multiplicity: usize,
+        _max_multiplicity: Option<usize>,
     ) {
     }
 
@@ -1,3 +1,4 @@
+use crate::colors_limit::ColorsLimitError;
 use crate::colors_manager::{
     ColorsManager, ColorsMergeManager, ColorsParser, MinimizerBucketingSeqColorData,
 };
@@ -120,8 +121,8 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
     fn create_colors_table(
         _path: impl AsRef<Path>,
         _color_names: &[String],
-    ) -> Self::GlobalColorsTableWriter {
-        ()
+    ) -> Result<Self::GlobalColorsTableWriter, ColorsLimitError> {
+        Ok(())
     }
 
     fn open_colors_table(_path: impl AsRef<Path>) -> Self::GlobalColorsTableReader {
@@ -176,6 +177,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory> ColorsMergeManage
         >,
         _k: usize,
         _min_multiplicity: usize,
+        _max_multiplicity: Option<usize>,
     ) {
         unreachable!()
     }
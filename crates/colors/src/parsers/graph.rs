@@ -217,6 +217,11 @@ impl SequenceExtraData for MinBkMultipleColors {
     fn max_size(&self) -> usize {
         self.buffer_slice.len() * (VARINT_MAX_SIZE * 2) + VARINT_MAX_SIZE
     }
+
+    #[inline(always)]
+    fn max_size_hint(&self) -> usize {
+        <Self as SequenceExtraData>::max_size(self)
+    }
 }
 
 // fn parse_colors(ident: &[u8], colors_buffer: &mut UnitigsSerializerTempBuffer) -> Range<usize> {
@@ -0,0 +1,83 @@
+use config::ColorIndexType;
+
+const WORD_BITS: usize = ColorIndexType::BITS as usize;
+
+/// Per-kmer presence bitset of the colors, stored inline in the temporary colors buffer.
+/// Only the membership of each color is kept, regardless of how many times it was seen
+pub struct ColorsPresenceBitset;
+
+impl ColorsPresenceBitset {
+    #[inline(always)]
+    pub fn words_count(colors_count: usize) -> usize {
+        (colors_count + WORD_BITS - 1) / WORD_BITS
+    }
+
+    #[inline(always)]
+    pub fn set_color(bitset: &mut [ColorIndexType], color: ColorIndexType) {
+        let color = color as usize;
+        bitset[color / WORD_BITS] |= 1 << (color % WORD_BITS);
+    }
+
+    /// Writes the sorted list of the colors present in the bitset
+    pub fn decode_colors(bitset: &[ColorIndexType], colors: &mut Vec<ColorIndexType>) {
+        colors.clear();
+        for (index, word) in bitset.iter().enumerate() {
+            let mut word = *word;
+            while word != 0 {
+                let bit = word.trailing_zeros() as usize;
+                colors.push((index * WORD_BITS + bit) as ColorIndexType);
+                word &= word - 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::presence_bitset::ColorsPresenceBitset;
+    use config::ColorIndexType;
+    use nightly_quirks::slice_partition_dedup::SlicePartitionDedup;
+
+    #[test]
+    fn presence_matches_counts_membership() {
+        let colors_count = 70;
+        let occurrences: Vec<ColorIndexType> = vec![65, 3, 3, 0, 31, 32, 3, 65, 69, 0, 31];
+
+        // Counts mode: one slot for each occurrence, sorted and deduplicated
+        let mut counts_slots = occurrences.clone();
+        counts_slots.sort_unstable();
+        let counts_colors = counts_slots.nq_partition_dedup().0.to_vec();
+
+        // Presence mode: one bit for each color
+        let mut bitset = vec![0; ColorsPresenceBitset::words_count(colors_count)];
+        assert_eq!(bitset.len(), 3);
+        for color in occurrences.iter() {
+            ColorsPresenceBitset::set_color(&mut bitset, *color);
+        }
+
+        let mut presence_colors = vec![];
+        ColorsPresenceBitset::decode_colors(&bitset, &mut presence_colors);
+
+        assert_eq!(presence_colors, counts_colors);
+        assert!(bitset.len() < counts_slots.len());
+    }
+
+    #[test]
+    fn words_count_boundaries() {
+        assert_eq!(ColorsPresenceBitset::words_count(1), 1);
+        assert_eq!(ColorsPresenceBitset::words_count(32), 1);
+        assert_eq!(ColorsPresenceBitset::words_count(33), 2);
+
+        for colors_count in [32, 33] {
+            let mut bitset = vec![0; ColorsPresenceBitset::words_count(colors_count)];
+            let all_colors: Vec<ColorIndexType> = (0..colors_count as ColorIndexType).collect();
+            for color in all_colors.iter() {
+                ColorsPresenceBitset::set_color(&mut bitset, *color);
+            }
+
+            let mut colors = vec![];
+            ColorsPresenceBitset::decode_colors(&bitset, &mut colors);
+            assert_eq!(colors, all_colors);
+        }
+    }
+}
@@ -1,5 +1,9 @@
+use crate::colors_limit::ApproximateColors;
 use crate::colors_manager::ColorMapReader;
-use crate::storage::serializer::{ColorsFileHeader, ColorsIndexEntry, ColorsIndexMap};
+use crate::storage::serializer::{
+    ApproximateColorsParams, ColorsFileHeader, ColorsIndexEntry, ColorsIndexMap,
+    APPROXIMATE_STORAGE_VERSION, STORAGE_VERSION,
+};
 use crate::storage::ColorsSerializerTrait;
 use config::ColorIndexType;
 use desse::Desse;
@@ -18,6 +22,9 @@ pub struct ColorsDeserializer<DS: ColorsSerializerTrait> {
     current_chunk: ColorsIndexEntry,
     current_chunk_size: ColorIndexType,
     current_index: ColorIndexType,
+    colors_count: usize,
+    /// Set if the subsets are stored as bloom filter bits, decoded to the colors they may contain
+    approximate_colors: Option<(ApproximateColors, Vec<ColorIndexType>)>,
     _phantom: PhantomData<DS>,
 }
 
@@ -34,6 +41,20 @@ impl<DS: ColorsSerializerTrait> ColorsDeserializer<DS> {
         let header: ColorsFileHeader = ColorsFileHeader::deserialize_from(&header_buffer);
         assert_eq!(header.magic, DS::MAGIC);
 
+        let approximate_colors = match header.version {
+            STORAGE_VERSION => None,
+            APPROXIMATE_STORAGE_VERSION => {
+                let mut params_buffer = [0; ApproximateColorsParams::SIZE];
+                file.read_exact(&mut params_buffer).unwrap();
+                let params = ApproximateColorsParams::deserialize_from(&params_buffer);
+                Some((
+                    ApproximateColors::new(params.bits_count, params.hashes_count),
+                    Vec::new(),
+                ))
+            }
+            version => panic!("Unsupported colormap version: {}", version),
+        };
+
         let color_names = if read_color_names {
             let mut compressed_stream = lz4::Decoder::new(BufReader::new(file)).unwrap();
 
@@ -73,6 +94,8 @@ impl<DS: ColorsSerializerTrait> ColorsDeserializer<DS> {
             current_chunk: first_chunk,
             current_chunk_size,
             current_index: first_chunk.start_index,
+            colors_count: header.colors_count as usize,
+            approximate_colors,
             _phantom: Default::default(),
         }
     }
@@ -127,7 +150,14 @@ impl<DS: ColorsSerializerTrait> ColorsDeserializer<DS> {
         }
 
         // Decode the requested color
-        DS::decode_color(&mut self.colormap_file, Some(out_vec));
+        match &mut self.approximate_colors {
+            None => DS::decode_color(&mut self.colormap_file, Some(out_vec)),
+            Some((approximate_colors, bits)) => {
+                bits.clear();
+                DS::decode_color(&mut self.colormap_file, Some(bits));
+                approximate_colors.decode_subset(bits, self.colors_count, out_vec);
+            }
+        }
         self.current_index += 1;
     }
 }
@@ -149,3 +179,46 @@ impl<DS: ColorsSerializerTrait> ColorMapReader for ColorsDeserializer<DS> {
         self.colors_index.subsets_count as u64
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::colors_limit::ApproximateColors;
+    use crate::colors_manager::ColorMapReader;
+    use crate::storage::deserializer::ColorsDeserializer;
+    use crate::storage::serializer::ColorsSerializer;
+    use crate::DefaultColorsSerializer;
+
+    #[test]
+    fn approximate_colormap_decoding() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("approximate-colormap");
+        let color_names: Vec<_> = (0..20).map(|color| format!("color{}", color)).collect();
+        let approximate_colors = ApproximateColors::new(8, 2);
+
+        let mut bits = vec![];
+        approximate_colors.map_subset(&[1, 5], &mut bits);
+        let subset = {
+            let serializer = ColorsSerializer::<DefaultColorsSerializer>::new(
+                &path,
+                &color_names,
+                Some(&approximate_colors),
+            );
+            serializer.serialize_colors(&bits)
+        };
+
+        let mut deserializer = ColorsDeserializer::<DefaultColorsSerializer>::new(&path, true);
+        let mut colors = vec![];
+        deserializer.get_color_mappings(subset, &mut colors);
+
+        // The stored bits are decoded to the colors that may be in the subset
+        assert!(colors.contains(&1) && colors.contains(&5));
+        assert!(colors.iter().all(|color| *color < 20));
+        assert_eq!(
+            colors,
+            (0..20)
+                .filter(|color| approximate_colors.may_contain(&bits, *color))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(deserializer.get_color_name(5, false), "color5");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::colors_limit::ApproximateColors;
 use crate::storage::ColorsSerializerTrait;
 use config::DEFAULT_OUTPUT_BUFFER_SIZE;
 use config::{ColorIndexType, COLORS_SINGLE_BATCH_SIZE};
@@ -12,7 +13,9 @@ use std::ops::{Deref, DerefMut};
 use std::path::Path;
 use std::sync::atomic::{AtomicU64, Ordering};
 
-const STORAGE_VERSION: u64 = 1;
+pub(crate) const STORAGE_VERSION: u64 = 1;
+/// The colors subsets are stored as the bits of a bloom filter, whose parameters follow the header
+pub(crate) const APPROXIMATE_STORAGE_VERSION: u64 = 2;
 
 #[derive(Debug, Desse, DesseSized, Default)]
 pub(crate) struct ColorsFileHeader {
@@ -25,6 +28,12 @@ pub(crate) struct ColorsFileHeader {
     pub total_uncompressed_size: u64,
 }
 
+#[derive(Debug, Desse, DesseSized, Default)]
+pub(crate) struct ApproximateColorsParams {
+    pub bits_count: u64,
+    pub hashes_count: u64,
+}
+
 #[derive(Clone, Copy, Serialize, Deserialize, Ord, PartialOrd, Eq, PartialEq)]
 pub struct ColorsIndexEntry {
     pub start_index: ColorIndexType,
@@ -39,17 +48,35 @@ pub(crate) struct ColorsIndexMap {
 
 pub struct ColorsSerializer<SI: ColorsSerializerTrait> {
     colors_count: u64,
+    is_approximate: bool,
     serializer_impl: ManuallyDrop<SI>,
 }
 
 impl<SI: ColorsSerializerTrait> ColorsSerializer<SI> {
-    pub fn new(file: impl AsRef<Path>, color_names: &[String]) -> Self {
+    /// With approximate colors the serialized subsets are the bloom filter bits
+    pub fn new(
+        file: impl AsRef<Path>,
+        color_names: &[String],
+        approximate_colors: Option<&ApproximateColors>,
+    ) -> Self {
         let mut colormap_file = File::create(file).unwrap();
 
         colormap_file
             .write_all(&ColorsFileHeader::default().serialize()[..])
             .unwrap();
 
+        if let Some(approximate_colors) = approximate_colors {
+            colormap_file
+                .write_all(
+                    &ApproximateColorsParams {
+                        bits_count: approximate_colors.bits_count() as u64,
+                        hashes_count: approximate_colors.hashes_count() as u64,
+                    }
+                    .serialize()[..],
+                )
+                .unwrap();
+        }
+
         colormap_file = {
             let mut color_names_stream = lz4::EncoderBuilder::new()
                 .level(4)
@@ -80,6 +107,7 @@ impl<SI: ColorsSerializerTrait> ColorsSerializer<SI> {
 
         Self {
             colors_count,
+            is_approximate: approximate_colors.is_some(),
             serializer_impl: ManuallyDrop::new(SI::new(
                 color_processor,
                 COLORS_SINGLE_BATCH_SIZE as usize,
@@ -128,7 +156,11 @@ impl<SI: ColorsSerializerTrait> Drop for ColorsSerializer<SI> {
             .write_all(
                 &ColorsFileHeader {
                     magic: SI::MAGIC,
-                    version: STORAGE_VERSION,
+                    version: if self.is_approximate {
+                        APPROXIMATE_STORAGE_VERSION
+                    } else {
+                        STORAGE_VERSION
+                    },
                     index_offset: index_position,
                     colors_count: self.colors_count,
                     subsets_count,
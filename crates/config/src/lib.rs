@@ -4,7 +4,7 @@ use parallel_processor::buckets::writers::compressed_binary_writer::{
 };
 use parallel_processor::memory_data_size::MemoryDataSize;
 use parallel_processor::memory_fs::file::internal::MemoryFileMode;
-use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use std::time::Duration;
 
 pub type BucketIndexType = u16;
@@ -21,6 +21,7 @@ pub const PACKETS_PRIORITY_FILES: usize = 1;
 // pub type DefaultColorsSerializer = RunLengthColorsSerializer;
 
 pub const READ_INTERMEDIATE_CHUNKS_SIZE: usize = 1024 * 512 * 1;
+/// Read chunks queued for each compute thread, used when the bucketing is not given its own multiplier
 pub static READ_INTERMEDIATE_QUEUE_MULTIPLIER: AtomicUsize = AtomicUsize::new(2);
 
 pub const KMERS_TRANSFORM_READS_CHUNKS_SIZE: usize = 1024 * 24;
@@ -38,6 +39,11 @@ pub const MINIMIZER_BUCKETS_CHECKPOINT_SIZE: CompressedCheckpointSize =
 
 pub const DEFAULT_OUTPUT_BUFFER_SIZE: usize = 1024 * 1024 * 4;
 pub const DEFAULT_PER_CPU_BUFFER_SIZE: MemoryDataSize = MemoryDataSize::from_kibioctets(4);
+/// Size in bytes of the buffers of the final output files, usize::MAX for DEFAULT_OUTPUT_BUFFER_SIZE
+pub static OUTPUT_BUFFER_SIZE: AtomicUsize = AtomicUsize::new(usize::MAX);
+/// Size in bytes of the per thread buffers of the kmers merge intermediate buckets,
+/// usize::MAX for DEFAULT_PER_CPU_BUFFER_SIZE
+pub static PER_CPU_BUFFER_SIZE: AtomicUsize = AtomicUsize::new(usize::MAX);
 
 pub const MINIMUM_LOG_DELTA_TIME: Duration = Duration::from_secs(10);
 
@@ -49,8 +55,6 @@ pub const MAX_RESPLIT_BUCKETS_COUNT_LOG: usize = 9;
 
 pub const MIN_BUCKET_CHUNKS_FOR_READING_THREAD: usize = 2;
 
-pub const USE_SECOND_BUCKET: bool = false;
-
 pub const RESPLITTING_MAX_K_M_DIFFERENCE: usize = 10;
 
 pub const MINIMUM_SUBBUCKET_KMERS_COUNT: usize = 1024 * 32;
@@ -88,6 +92,54 @@ pub static KEEP_FILES: AtomicBool = AtomicBool::new(false);
 pub static INTERMEDIATE_COMPRESSION_LEVEL_SLOW: AtomicU32 = AtomicU32::new(3);
 pub static INTERMEDIATE_COMPRESSION_LEVEL_FAST: AtomicU32 = AtomicU32::new(0);
 pub static PREFER_MEMORY: AtomicBool = AtomicBool::new(false);
+pub static WRITE_UNITIGS_PROVENANCE: AtomicBool = AtomicBool::new(false);
+pub static WRITE_EDGE_LIST: AtomicBool = AtomicBool::new(false);
+/// Write a compact binary dump of the compacted graph, that can be loaded back with GraphDumpReader
+pub static WRITE_GRAPH_DUMP: AtomicBool = AtomicBool::new(false);
+/// Writes the output unitigs in ascending bucket order, so that the same input always gives the same output
+pub static DETERMINISTIC_OUTPUT: AtomicBool = AtomicBool::new(false);
+/// Syncs the output file to disk before returning, so that it is durable once the build completes
+pub static DURABLE_OUTPUT: AtomicBool = AtomicBool::new(false);
+/// Track only the presence of each color for a kmer, instead of one entry for each of its occurrences
+pub static COLORS_PRESENCE_ONLY: AtomicBool = AtomicBool::new(false);
+/// Keep the names of the query sequences, to report them in the query output
+pub static PRESERVE_READ_NAMES: AtomicBool = AtomicBool::new(false);
+/// Count the bases composition of the input reads while they are bucketed
+pub static COUNT_BASES_COMPOSITION: AtomicBool = AtomicBool::new(false);
+/// Maximum number of colors, and policy when it is exceeded: 0 = error, 1 = approximate colors
+pub static MAX_COLORS: AtomicUsize = AtomicUsize::new(usize::MAX);
+pub static MAX_COLORS_POLICY: AtomicU8 = AtomicU8::new(0);
+/// Maximum multiplicity of the kmers kept in the graph, usize::MAX to keep all of them
+pub static MAX_MULTIPLICITY: AtomicUsize = AtomicUsize::new(usize::MAX);
+pub static AUTO_TUNE_KMERS_TRANSFORM: AtomicBool = AtomicBool::new(false);
+/// Buckets processed with each candidate configuration before choosing the best one
+pub const AUTO_TUNE_WARMUP_BUCKETS: usize = 2;
+/// Maximum number of executors of each type of the kmers transform, overriding the counts derived
+/// from the threads. usize::MAX for no maximum
+pub static KMERS_TRANSFORM_MAX_READERS: AtomicUsize = AtomicUsize::new(usize::MAX);
+pub static KMERS_TRANSFORM_MAX_PROCESSORS: AtomicUsize = AtomicUsize::new(usize::MAX);
+pub static KMERS_TRANSFORM_MAX_RESPLITTERS: AtomicUsize = AtomicUsize::new(usize::MAX);
+/// Runs the kmers transform with a single executor of each type, processing the buckets in order
+pub static SEQUENTIAL_KMERS_TRANSFORM: AtomicBool = AtomicBool::new(false);
+/// Stores the second level bucket of each read in the minimizer buckets, so that it is not recomputed when reading them.
+/// Must not change between the minimizer bucketing and the kmers transform of the same run
+pub static USE_SECOND_BUCKET: AtomicBool = AtomicBool::new(false);
+/// Checks that the reads found in each bucket match the counters recorded by the minimizer bucketing
+pub static VERIFY_BUCKETS_COUNTERS: AtomicBool = AtomicBool::new(false);
+/// Seconds after which the kmers merge stops starting new buckets, leaving the run to be resumed.
+/// u64::MAX for no limit
+pub static KMERS_MERGE_TIME_LIMIT_SECS: AtomicU64 = AtomicU64::new(u64::MAX);
+/// Size in bytes of the kmers map of a bucket above which the kmers merge flushes its counters,
+/// usize::MAX to never flush
+pub static KMERS_MERGE_MAP_FLUSH_SIZE: AtomicUsize = AtomicUsize::new(usize::MAX);
+/// Memory in bytes available to the kmers transform map processors, usize::MAX to use only the
+/// minimum processors count
+pub static PROCESSORS_MEMORY_LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+/// Selects the canonical orientation of a kmer by its hash value instead of the lexicographically smaller sequence
+pub static CANONICAL_ORIENTATION_MIN_HASH: AtomicBool = AtomicBool::new(false);
+/// Validation of the input records: 0 = disabled, 1 = fail fast, 2 = skip invalid records
+pub static INPUT_VALIDATION_MODE: AtomicU8 = AtomicU8::new(0);
+pub static INPUT_VALIDATION_MAX_REPORTED_ERRORS: AtomicUsize = AtomicUsize::new(10);
 
 pub fn get_memory_mode(swap_priority: usize) -> MemoryFileMode {
     if PREFER_MEMORY.load(Ordering::Relaxed) {
@@ -97,6 +149,20 @@ pub fn get_memory_mode(swap_priority: usize) -> MemoryFileMode {
     }
 }
 
+pub fn get_output_buffer_size() -> usize {
+    match OUTPUT_BUFFER_SIZE.load(Ordering::Relaxed) {
+        usize::MAX => DEFAULT_OUTPUT_BUFFER_SIZE,
+        size => size,
+    }
+}
+
+pub fn get_per_cpu_buffer_size() -> MemoryDataSize {
+    match PER_CPU_BUFFER_SIZE.load(Ordering::Relaxed) {
+        usize::MAX => DEFAULT_PER_CPU_BUFFER_SIZE,
+        size => MemoryDataSize::from_bytes(size),
+    }
+}
+
 pub fn get_compression_level_info() -> CompressionLevelInfo {
     CompressionLevelInfo {
         fast_disk: INTERMEDIATE_COMPRESSION_LEVEL_FAST.load(Ordering::Relaxed),
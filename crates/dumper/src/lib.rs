@@ -13,6 +13,7 @@ use pipeline::dumper_colormap_reading::colormap_reading;
 use std::fs::remove_file;
 use std::path::PathBuf;
 use std::sync::atomic::Ordering;
+use utils::spill_layout::create_run_dir;
 
 mod pipeline;
 
@@ -39,9 +40,11 @@ pub fn dump_unitigs(
     threads_count: usize,
     single_thread_output_function: bool,
     default_compression_level: Option<u32>,
+    read_queue_multiplier: Option<usize>,
     output_function: impl Fn(&[u8], &[ColorIndexType], bool) + Send + Sync,
 ) {
-    let temp_dir = temp_dir.unwrap_or(PathBuf::new());
+    // Without a temp dir the run files are kept together in a new directory of the current one
+    let temp_dir = temp_dir.unwrap_or_else(|| create_run_dir("", "dump_colors"));
 
     PHASES_TIMES_MONITOR.write().init();
 
@@ -73,6 +76,7 @@ pub fn dump_unitigs(
         k,
         m,
         color_map.colors_subsets_count(),
+        read_queue_multiplier,
     );
     let _ = remove_file(buckets_stats);
 
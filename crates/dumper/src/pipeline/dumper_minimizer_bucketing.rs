@@ -11,6 +11,7 @@ use io::concurrent::temp_reads::extra_data::{
 use io::sequences_reader::{DnaSequence, DnaSequencesFileType};
 use io::sequences_stream::fasta::FastaFileSequencesStream;
 use io::sequences_stream::SequenceInfo;
+use minimizer_bucketing::minimizer_hasher::DefaultMinimizerBucketHasher;
 use minimizer_bucketing::{
     GenericMinimizerBucketing, MinimizerBucketingCommonData, MinimizerBucketingExecutor,
     MinimizerBucketingExecutorFactory, MinimizerInputSequence,
@@ -114,6 +115,7 @@ impl<CX: ColorsManager> MinimizerBucketingExecutorFactory
     type FLAGS_COUNT = typenum::U0;
 
     type ExecutorType = DumperMinimizerBucketingExecutor<CX>;
+    type Hasher = DefaultMinimizerBucketHasher;
 
     fn new(
         global_data: &Arc<MinimizerBucketingCommonData<Self::GlobalData>>,
@@ -254,6 +256,7 @@ pub fn minimizer_bucketing<CX: ColorsManager>(
     k: usize,
     m: usize,
     colors_count: u64,
+    read_queue_multiplier: Option<usize>,
 ) -> (Vec<PathBuf>, PathBuf) {
     PHASES_TIMES_MONITOR
         .write()
@@ -261,7 +264,7 @@ pub fn minimizer_bucketing<CX: ColorsManager>(
 
     let input_files = vec![((graph_file, None), ())];
 
-    GenericMinimizerBucketing::do_bucketing::<
+    let (buckets, counters, _) = GenericMinimizerBucketing::do_bucketing::<
         DumperMinimizerBucketingExecutorFactory<CX>,
         FastaFileSequencesStream,
     >(
@@ -278,5 +281,7 @@ pub fn minimizer_bucketing<CX: ColorsManager>(
         None,
         CX::COLORS_ENABLED,
         k,
-    )
+        read_queue_multiplier,
+    );
+    (buckets, counters)
 }
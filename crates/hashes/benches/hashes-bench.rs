@@ -1,6 +1,8 @@
 use criterion::*;
+use ggcat_hashes::fw_nthash::ForwardNtHashIteratorFactory;
+use ggcat_hashes::rolling::minqueue::RollingMinQueue;
 use ggcat_hashes::*;
-use io::compressed_read::CompressedRead;
+use io::compressed_read::{CompressedRead, CompressedReadIndipendent};
 use rand::{RngCore, SeedableRng};
 use utils::Utils;
 
@@ -38,6 +40,44 @@ pub fn criterion_benchmark(c: &mut Criterion) {
     }
 }
 
-criterion_group!(benches, criterion_benchmark);
+/// Compares the rolling minimizers queue with recomputing the minimum of each window, on a 10kb read
+pub fn minimizers_benchmark(c: &mut Criterion) {
+    const K: usize = 31;
+    const M: usize = 11;
+    const WINDOW: usize = K - M + 1;
+
+    let bases = generate_bases(10000, 1);
+    let mut storage = Vec::new();
+    let read = CompressedReadIndipendent::from_plain(&bases, &mut storage);
+    let read = read.as_reference(&storage);
+
+    let mut queue = RollingMinQueue::<ForwardNtHashIteratorFactory>::new(WINDOW);
+    c.bench_function("minimizers-10kb-rolling", |b| {
+        b.iter(|| {
+            let hashes = ForwardNtHashIteratorFactory::new(read, M);
+            for minimizer in queue.make_iter(hashes.iter().map(|h| h.to_unextendable())) {
+                black_box(minimizer);
+            }
+        })
+    });
+
+    c.bench_function("minimizers-10kb-per-window", |b| {
+        b.iter(|| {
+            let hashes: Vec<_> = ForwardNtHashIteratorFactory::new(read, M)
+                .iter()
+                .map(|h| h.to_unextendable())
+                .collect();
+            for window in hashes.windows(WINDOW) {
+                black_box(
+                    window
+                        .iter()
+                        .min_by_key(|h| ForwardNtHashIteratorFactory::get_full_minimizer(**h)),
+                );
+            }
+        })
+    });
+}
+
+criterion_group!(benches, criterion_benchmark, minimizers_benchmark);
 
 criterion_main!(benches);
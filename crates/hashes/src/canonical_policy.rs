@@ -0,0 +1,150 @@
+use crate::{ExtendableHashTraitType, HashFunction, HashFunctionFactory, HashableSequence};
+use config::CANONICAL_ORIENTATION_MIN_HASH;
+use std::sync::atomic::Ordering;
+
+const C_LETTERS: [u8; 4] = [b'A', b'C', b'T', b'G'];
+
+/// Rule used to choose which orientation of a kmer is the canonical one
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+pub enum CanonicalOrientationPolicy {
+    /// The canonical kmer is the lexicographically smaller between the forward and the reverse complement
+    #[default]
+    MinSequence,
+    /// The canonical kmer is the one with the smaller hash value
+    MinHash,
+}
+
+impl CanonicalOrientationPolicy {
+    /// Returns the policy selected for the current run
+    pub fn current() -> Self {
+        if CANONICAL_ORIENTATION_MIN_HASH.load(Ordering::Relaxed) {
+            Self::MinHash
+        } else {
+            Self::MinSequence
+        }
+    }
+
+    /// Rank of a compressed base in the lexicographic order of its letter
+    #[inline(always)]
+    pub fn lexicographic_rank(cbase: u8) -> u8 {
+        // A = 0, C = 1, T = 2, G = 3 => A = 0, C = 1, G = 2, T = 3
+        cbase ^ (cbase >> 1)
+    }
+
+    /// Returns true if the forward orientation of the compressed kmer is the canonical one.
+    /// Palindromic kmers are always considered forward
+    pub fn is_forward_canonical<H: HashFunctionFactory>(
+        &self,
+        kmer: impl HashableSequence,
+    ) -> bool {
+        let k = kmer.bases_count();
+        match self {
+            CanonicalOrientationPolicy::MinSequence => {
+                for i in 0..k {
+                    let (fw_base, rc_base) = unsafe {
+                        (
+                            kmer.get_unchecked_cbase(i),
+                            kmer.get_unchecked_cbase(k - 1 - i) ^ 2,
+                        )
+                    };
+                    if fw_base != rc_base {
+                        return Self::lexicographic_rank(fw_base)
+                            < Self::lexicographic_rank(rc_base);
+                    }
+                }
+                true
+            }
+            CanonicalOrientationPolicy::MinHash => {
+                let hash = H::new(kmer, k).iter().next().unwrap();
+                hash.is_forward() || hash.is_rc_symmetric()
+            }
+        }
+    }
+
+    /// Writes the canonical orientation of the compressed kmer as ascii bases
+    pub fn write_canonical_bases<H: HashFunctionFactory>(
+        &self,
+        kmer: impl HashableSequence,
+        output: &mut Vec<u8>,
+    ) {
+        let k = kmer.bases_count();
+        if self.is_forward_canonical::<H>(kmer.clone()) {
+            output
+                .extend((0..k).map(|i| C_LETTERS[unsafe { kmer.get_unchecked_cbase(i) } as usize]));
+        } else {
+            output.extend(
+                (0..k)
+                    .rev()
+                    .map(|i| C_LETTERS[(unsafe { kmer.get_unchecked_cbase(i) } ^ 2) as usize]),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::canonical_policy::CanonicalOrientationPolicy;
+    use crate::cn_seqhash::u64::CanonicalSeqHashFactory;
+    use crate::tests::rng;
+    use config::CANONICAL_ORIENTATION_MIN_HASH;
+    use rand::RngCore;
+    use std::sync::atomic::Ordering;
+    use utils::Utils;
+
+    fn reverse_complement(kmer: &[u8]) -> Vec<u8> {
+        kmer.iter()
+            .rev()
+            .map(|b| match b {
+                b'A' => b'T',
+                b'C' => b'G',
+                b'G' => b'C',
+                b'T' => b'A',
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn min_sequence_is_lexicographic_min() {
+        let mut rng = rng(492);
+        let policy = CanonicalOrientationPolicy::MinSequence;
+
+        let mut kmers: Vec<Vec<u8>> =
+            vec![b"ACGT".to_vec(), b"TTTTT".to_vec(), b"GATTACA".to_vec()];
+        for k in [15, 21, 31] {
+            for _ in 0..100 {
+                kmers.push(
+                    (0..k)
+                        .map(|_| b"ACGT"[(rng.next_u32() % 4) as usize])
+                        .collect(),
+                );
+            }
+        }
+
+        let mut canonical = Vec::new();
+        for kmer in kmers {
+            let compressed: Vec<_> = kmer.iter().map(|b| Utils::compress_base(*b)).collect();
+            canonical.clear();
+            policy.write_canonical_bases::<CanonicalSeqHashFactory>(
+                compressed.as_slice(),
+                &mut canonical,
+            );
+            let expected = kmer.clone().min(reverse_complement(&kmer));
+            assert_eq!(canonical, expected);
+        }
+    }
+
+    #[test]
+    fn current_policy_follows_config() {
+        assert_eq!(
+            CanonicalOrientationPolicy::current(),
+            CanonicalOrientationPolicy::MinSequence
+        );
+        CANONICAL_ORIENTATION_MIN_HASH.store(true, Ordering::Relaxed);
+        assert_eq!(
+            CanonicalOrientationPolicy::current(),
+            CanonicalOrientationPolicy::MinHash
+        );
+        CANONICAL_ORIENTATION_MIN_HASH.store(false, Ordering::Relaxed);
+    }
+}
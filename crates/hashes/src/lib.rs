@@ -1,5 +1,6 @@
 use dynamic_dispatch::dynamic_dispatch;
 
+pub mod canonical_policy;
 pub mod cn_nthash;
 pub mod cn_seqhash;
 pub mod fw_nthash;
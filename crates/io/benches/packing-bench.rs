@@ -0,0 +1,43 @@
+use criterion::*;
+use ggcat_io::compressed_read::packing::{
+    pack_bases, pack_bases_scalar, unpack_bases, unpack_bases_scalar,
+};
+use rand::{Rng, SeedableRng};
+
+fn generate_bases(len: usize, seed: u64) -> Vec<u8> {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    (0..len).map(|_| b"ACGT"[rng.gen_range(0..4)]).collect()
+}
+
+/// Compares the SIMD and the scalar 2-bit packing on a 100kb read
+pub fn packing_benchmark(c: &mut Criterion) {
+    let bases = generate_bases(100000, 0);
+    let mut packed = Vec::with_capacity(bases.len() / 4 + 4);
+
+    c.bench_function("pack-100kb-simd", |b| {
+        b.iter(|| {
+            packed.clear();
+            pack_bases(black_box(&bases), |p| packed.extend_from_slice(p));
+        })
+    });
+
+    c.bench_function("pack-100kb-scalar", |b| {
+        b.iter(|| {
+            packed.clear();
+            pack_bases_scalar(black_box(&bases), |p| packed.extend_from_slice(p));
+        })
+    });
+
+    let mut unpacked = vec![0; bases.len()];
+    c.bench_function("unpack-100kb-simd", |b| {
+        b.iter(|| unpack_bases(black_box(&packed), &mut unpacked))
+    });
+
+    c.bench_function("unpack-100kb-scalar", |b| {
+        b.iter(|| unpack_bases_scalar(black_box(&packed), &mut unpacked))
+    });
+}
+
+criterion_group!(benches, packing_benchmark);
+
+criterion_main!(benches);
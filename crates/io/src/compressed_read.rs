@@ -1,6 +1,8 @@
 use crate::varint::encode_varint_flags;
 use core::fmt::{Debug, Formatter};
-use hashes::HashableSequence;
+use hashes::canonical_policy::CanonicalOrientationPolicy;
+use hashes::{HashFunctionFactory, HashableSequence};
+use std::cmp::min;
 use std::io::Write;
 use std::iter::FromIterator;
 use std::marker::PhantomData;
@@ -8,6 +10,14 @@ use std::ops::Range;
 use std::slice::from_raw_parts;
 use utils::Utils;
 
+pub mod packing;
+
+/// Read packed with 2 bits per base, that can only represent A, C, T and G (in either case).
+/// Any other letter is packed as an arbitrary base. The minimizer bucketing splits the sequences
+/// at the N bases before packing them, so the bucketed reads contain ambiguous bases only if their
+/// source did not map them to N, as the sequences readers and streams do.
+/// Reads packed from other sources can use an ambiguous bases mask (see [`ambiguous_bases_mask`])
+/// with [`CompressedRead::valid_kmers`] to skip the kmers that overlap them
 #[derive(Copy, Clone)]
 pub struct CompressedRead<'a> {
     pub(crate) size: usize,
@@ -25,6 +35,21 @@ impl<'a> Debug for CompressedRead<'a> {
     }
 }
 
+/// Bitmask of the ambiguous (non ACGT, in any case) bases of a plain sequence, one bit per base.
+/// Returns None if the sequence has no ambiguous bases, so that no mask is needed for it
+pub fn ambiguous_bases_mask(plain: &[u8]) -> Option<Vec<u64>> {
+    let is_ambiguous = |base: &u8| !matches!(base.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T');
+
+    let first_ambiguous = plain.iter().position(is_ambiguous)?;
+    let mut mask = vec![0u64; (plain.len() + 63) / 64];
+    for (pos, base) in plain.iter().enumerate().skip(first_ambiguous) {
+        if is_ambiguous(base) {
+            mask[pos / 64] |= 1 << (pos % 64);
+        }
+    }
+    Some(mask)
+}
+
 #[derive(Copy, Clone)]
 pub struct CompressedReadIndipendent {
     start: usize,
@@ -107,14 +132,8 @@ impl<'a> CompressedRead<'a> {
     }
 
     #[inline(always)]
-    fn compress_from_plain(seq: &'a [u8], mut writer: impl FnMut(&[u8])) {
-        for chunk in seq.chunks(16) {
-            let mut value = 0;
-            for aa in chunk.iter().rev() {
-                value = (value << 2) | Utils::compress_base(*aa) as u32;
-            }
-            writer(&value.to_le_bytes()[..(chunk.len() + 3) / 4])
-        }
+    fn compress_from_plain(seq: &'a [u8], writer: impl FnMut(&[u8])) {
+        packing::pack_bases(seq, writer);
     }
 
     #[inline]
@@ -179,7 +198,7 @@ impl<'a> CompressedRead<'a> {
     pub fn sub_slice(&self, range: Range<usize>) -> CompressedRead<'a> {
         assert!(range.start <= range.end);
 
-        let start = ((self.start + range.start as u8) % 4) as u8;
+        let start = ((self.start as usize + range.start) % 4) as u8;
         let sbyte = (self.start as usize + range.start) / 4;
 
         CompressedRead {
@@ -197,6 +216,12 @@ impl<'a> CompressedRead<'a> {
     }
 
     pub fn write_unpacked_to_slice(&self, slice: &mut [u8]) {
+        if self.start == 0 {
+            let bases_count = min(slice.len(), self.size);
+            packing::unpack_bases(self.get_packed_slice(), &mut slice[..bases_count]);
+            return;
+        }
+
         for (val, letter) in slice.iter_mut().zip(self.as_bases_iter()) {
             *val = letter;
         }
@@ -212,6 +237,85 @@ impl<'a> CompressedRead<'a> {
             .map(move |i| unsafe { Utils::decompress_base(self.get_base_unchecked(i) ^ 2) })
     }
 
+    /// Iterates the compressed bases of the reverse complement, without decompressing them
+    pub fn rc_bases_iter(&self) -> impl Iterator<Item = u8> + 'a {
+        let read = *self;
+        (0..read.size)
+            .rev()
+            .map(move |i| unsafe { read.get_base_unchecked(i) ^ 2 })
+    }
+
+    /// Returns the compressed bases of the canonical orientation of the kmer starting at pos,
+    /// chosen by the policy, or None if the kmer does not fit in the read
+    pub fn canonical_kmer_at<H: HashFunctionFactory>(
+        &self,
+        pos: usize,
+        k: usize,
+        policy: CanonicalOrientationPolicy,
+    ) -> Option<impl Iterator<Item = u8> + 'a> {
+        if pos.checked_add(k)? > self.size {
+            return None;
+        }
+
+        let read = *self;
+        let is_forward = policy.is_forward_canonical::<H>(read.sub_slice(pos..pos + k));
+
+        Some((0..k).map(move |i| unsafe {
+            if is_forward {
+                read.get_base_unchecked(pos + i)
+            } else {
+                read.get_base_unchecked(pos + k - 1 - i) ^ 2
+            }
+        }))
+    }
+
+    /// Iterates all the kmers of the read, skipping the ones that overlap a base set in the
+    /// ambiguous bases mask. Without a mask every kmer is returned, with no additional checks.
+    /// Panics if the mask has less bits than the read bases
+    pub fn valid_kmers(
+        &self,
+        k: usize,
+        ambiguous_mask: Option<&'a [u64]>,
+    ) -> impl Iterator<Item = CompressedRead<'a>> + 'a {
+        assert!(k > 0);
+
+        let read = *self;
+        if let Some(mask) = ambiguous_mask {
+            assert!(
+                mask.len() * 64 >= read.size,
+                "Ambiguous bases mask of {} bits for a read of {} bases",
+                mask.len() * 64,
+                read.size
+            );
+        }
+        let is_ambiguous = |mask: &[u64], pos: usize| (mask[pos / 64] >> (pos % 64)) & 1 != 0;
+
+        let kmers_count = (read.size + 1).saturating_sub(k);
+
+        // First start position whose kmer does not include any of the ambiguous bases seen so far
+        let mut next_valid_start = 0;
+        if let Some(mask) = ambiguous_mask {
+            for pos in 0..(k - 1).min(read.size) {
+                if is_ambiguous(mask, pos) {
+                    next_valid_start = pos + 1;
+                }
+            }
+        }
+
+        (0..kmers_count).filter_map(move |start| {
+            if let Some(mask) = ambiguous_mask {
+                let last = start + k - 1;
+                if is_ambiguous(mask, last) {
+                    next_valid_start = last + 1;
+                }
+                if start < next_valid_start {
+                    return None;
+                }
+            }
+            Some(read.sub_slice(start..start + k))
+        })
+    }
+
     pub fn to_string(&self) -> String {
         String::from_iter(
             (0..self.size)
@@ -257,3 +361,126 @@ impl<'a> HashableSequence for CompressedRead<'a> {
         self.size
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::compressed_read::{ambiguous_bases_mask, CompressedRead, CompressedReadIndipendent};
+    use hashes::canonical_policy::CanonicalOrientationPolicy;
+    use hashes::cn_nthash::CanonicalNtHashIteratorFactory;
+    use hashes::{ExtendableHashTraitType, HashFunction, HashFunctionFactory};
+    use utils::Utils;
+
+    fn decompress(bases: impl Iterator<Item = u8>) -> Vec<u8> {
+        bases.map(Utils::decompress_base).collect()
+    }
+
+    #[test]
+    fn reverse_complement_and_canonical_kmers() {
+        let mut storage = vec![];
+        let read = CompressedReadIndipendent::from_plain(b"GATTACAG", &mut storage);
+        let read = read.as_reference(&storage);
+
+        assert_eq!(decompress(read.rc_bases_iter()), b"CTGTAATC");
+
+        let canonical_kmer_at = |read: &CompressedRead, pos, k| {
+            read.canonical_kmer_at::<CanonicalNtHashIteratorFactory>(
+                pos,
+                k,
+                CanonicalOrientationPolicy::MinSequence,
+            )
+            .map(decompress)
+        };
+
+        // AATC < GATT
+        assert_eq!(canonical_kmer_at(&read, 0, 4).unwrap(), b"AATC");
+        // ACAG < CTGT
+        assert_eq!(canonical_kmer_at(&read, 4, 4).unwrap(), b"ACAG");
+        assert!(canonical_kmer_at(&read, 5, 4).is_none());
+        assert!(canonical_kmer_at(&read, usize::MAX, 4).is_none());
+        assert!(canonical_kmer_at(&read, 0, 9).is_none());
+
+        let short = CompressedReadIndipendent::from_plain(b"", &mut storage);
+        let short = short.as_reference(&storage);
+        assert_eq!(short.rc_bases_iter().count(), 0);
+        assert!(canonical_kmer_at(&short, 0, 1).is_none());
+    }
+
+    #[test]
+    fn canonical_kmers_follow_policy() {
+        let mut storage = vec![];
+        let plain: Vec<_> = (0..300u32)
+            .map(|i| b"ACGT"[(i.wrapping_mul(2654435761) >> 7) as usize % 4])
+            .collect();
+        let read = CompressedReadIndipendent::from_plain(&plain, &mut storage);
+        let read = read.as_reference(&storage);
+        let k = 15;
+
+        for pos in 0..=(plain.len() - k) {
+            let canonical: Vec<_> = read
+                .canonical_kmer_at::<CanonicalNtHashIteratorFactory>(
+                    pos,
+                    k,
+                    CanonicalOrientationPolicy::MinHash,
+                )
+                .unwrap()
+                .collect();
+            let hash = CanonicalNtHashIteratorFactory::new(canonical.as_slice(), k)
+                .iter()
+                .next()
+                .unwrap();
+            assert!(hash.is_forward() || hash.is_rc_symmetric());
+
+            let forward: Vec<_> = plain[pos..pos + k]
+                .iter()
+                .map(|b| Utils::compress_base(*b))
+                .collect();
+            let reverse: Vec<_> = forward.iter().rev().map(|b| b ^ 2).collect();
+            let canonical: Vec<_> = read
+                .canonical_kmer_at::<CanonicalNtHashIteratorFactory>(
+                    pos,
+                    k,
+                    CanonicalOrientationPolicy::MinSequence,
+                )
+                .unwrap()
+                .collect();
+            assert_eq!(
+                decompress(canonical.into_iter()),
+                decompress(forward.into_iter()).min(decompress(reverse.into_iter()))
+            );
+        }
+    }
+
+    #[test]
+    fn valid_kmers_skip_ambiguous_bases() {
+        let plain = b"ACGTNACGTACNGTTTAC";
+        let mask = ambiguous_bases_mask(plain).unwrap();
+        let mut storage = vec![];
+        let read = CompressedReadIndipendent::from_plain(plain, &mut storage);
+        let read = read.as_reference(&storage);
+
+        let kmers: Vec<_> = read
+            .valid_kmers(4, Some(&mask))
+            .map(|kmer| kmer.to_string())
+            .collect();
+        assert_eq!(
+            kmers,
+            vec!["ACGT", "ACGT", "CGTA", "GTAC", "GTTT", "TTTA", "TTAC"]
+        );
+
+        assert!(ambiguous_bases_mask(b"ACGTTGCA").is_none());
+        assert!(ambiguous_bases_mask(b"acgtTGCA").is_none());
+        assert_eq!(ambiguous_bases_mask(b"acgtnGCA"), Some(vec![1 << 4]));
+        assert_eq!(read.valid_kmers(4, None).count(), plain.len() - 3);
+        assert_eq!(read.valid_kmers(plain.len() + 1, None).count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn valid_kmers_short_mask() {
+        let plain = [b'A'; 70];
+        let mut storage = vec![];
+        let read = CompressedReadIndipendent::from_plain(&plain, &mut storage);
+        let mask = [0u64; 1];
+        let _ = read.as_reference(&storage).valid_kmers(4, Some(&mask));
+    }
+}
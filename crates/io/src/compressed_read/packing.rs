@@ -0,0 +1,149 @@
+use utils::Utils;
+
+/// Packs the bases 4 per byte, the first one in the lowest bits, passing the packed bytes to writer.
+/// Uses SSSE3 on the reads of at least 16 bases when the cpu supports it, with the same result as the scalar code
+#[inline(always)]
+pub fn pack_bases(seq: &[u8], mut writer: impl FnMut(&[u8])) {
+    #[cfg(target_arch = "x86_64")]
+    if seq.len() >= 16 && std::is_x86_feature_detected!("ssse3") {
+        let (simd_part, tail) = seq.split_at(seq.len() / 16 * 16);
+        let mut packed = [0; 64];
+        for chunk in simd_part.chunks(packed.len() * 4) {
+            unsafe { x86::pack_ssse3(chunk, &mut packed) };
+            writer(&packed[..chunk.len() / 4]);
+        }
+        pack_bases_scalar(tail, writer);
+        return;
+    }
+
+    pack_bases_scalar(seq, writer);
+}
+
+#[inline(always)]
+pub fn pack_bases_scalar(seq: &[u8], mut writer: impl FnMut(&[u8])) {
+    for chunk in seq.chunks(16) {
+        let mut value = 0;
+        for aa in chunk.iter().rev() {
+            value = (value << 2) | Utils::compress_base(*aa) as u32;
+        }
+        writer(&value.to_le_bytes()[..(chunk.len() + 3) / 4])
+    }
+}
+
+/// Unpacks the first bases.len() bases of packed, the inverse of pack_bases for the A, C, T and G letters
+#[inline(always)]
+pub fn unpack_bases(packed: &[u8], bases: &mut [u8]) {
+    assert!(packed.len() * 4 >= bases.len());
+
+    #[cfg(target_arch = "x86_64")]
+    if bases.len() >= 16 && std::is_x86_feature_detected!("ssse3") {
+        let simd_len = bases.len() / 16 * 16;
+        let (simd_part, tail) = bases.split_at_mut(simd_len);
+        unsafe { x86::unpack_ssse3(packed, simd_part) };
+        unpack_bases_scalar(&packed[simd_len / 4..], tail);
+        return;
+    }
+
+    unpack_bases_scalar(packed, bases);
+}
+
+#[inline(always)]
+pub fn unpack_bases_scalar(packed: &[u8], bases: &mut [u8]) {
+    for (i, base) in bases.iter_mut().enumerate() {
+        *base = Utils::decompress_base((packed[i / 4] >> ((i % 4) * 2)) & 0x3);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+    use utils::Utils;
+
+    /// Packs seq, with a length multiple of 16, in the first seq.len() / 4 bytes of packed
+    #[target_feature(enable = "ssse3")]
+    pub unsafe fn pack_ssse3(seq: &[u8], packed: &mut [u8]) {
+        debug_assert!(seq.len() % 16 == 0 && packed.len() * 4 >= seq.len());
+
+        let codes_mask = _mm_set1_epi8(0x3);
+        // Byte weights (1, 4) for the pairs of bases, then word weights (1, 16) for the pairs of pairs
+        let pairs_weights = _mm_set1_epi16(0x0401);
+        let quads_weights = _mm_set1_epi32(0x0010_0001);
+
+        for (i, chunk) in seq.chunks_exact(16).enumerate() {
+            let letters = _mm_loadu_si128(chunk.as_ptr() as *const __m128i);
+            // Same as Utils::compress_base, the bits shifted in from the next byte are masked out
+            let codes = _mm_and_si128(_mm_srli_epi16(letters, 1), codes_mask);
+            let pairs = _mm_maddubs_epi16(codes, pairs_weights);
+            let quads = _mm_madd_epi16(pairs, quads_weights);
+            let words = _mm_packs_epi32(quads, quads);
+            let bytes = _mm_packus_epi16(words, words);
+            packed[i * 4..(i + 1) * 4].copy_from_slice(&_mm_cvtsi128_si32(bytes).to_le_bytes());
+        }
+    }
+
+    /// Unpacks the first bases.len() bases of packed, with bases.len() multiple of 16
+    #[target_feature(enable = "ssse3")]
+    pub unsafe fn unpack_ssse3(packed: &[u8], bases: &mut [u8]) {
+        debug_assert!(bases.len() % 16 == 0 && packed.len() * 4 >= bases.len());
+
+        let letter = |code: u8| Utils::decompress_base(code) as i8;
+        let [a, c, t, g] = [letter(0), letter(1), letter(2), letter(3)];
+        // Letters of the low and of the high 2 bits of each nibble
+        let low_letters = _mm_setr_epi8(a, c, t, g, a, c, t, g, a, c, t, g, a, c, t, g);
+        let high_letters = _mm_setr_epi8(a, a, a, a, c, c, c, c, t, t, t, t, g, g, g, g);
+
+        // Each packed byte is spread to its 4 bases, that take the nibble and the half nibble given by their position
+        let spread = _mm_setr_epi8(0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3);
+        let high_nibble_lanes = _mm_set1_epi32(0xFFFF_0000u32 as i32);
+        let odd_lanes = _mm_set1_epi16(0xFF00u16 as i16);
+        let nibble_mask = _mm_set1_epi8(0xF);
+
+        for (i, chunk) in bases.chunks_exact_mut(16).enumerate() {
+            let value = u32::from_le_bytes(packed[i * 4..(i + 1) * 4].try_into().unwrap());
+            let bytes = _mm_shuffle_epi8(_mm_cvtsi32_si128(value as i32), spread);
+
+            let low = _mm_and_si128(bytes, nibble_mask);
+            let high = _mm_and_si128(_mm_srli_epi16(bytes, 4), nibble_mask);
+            let nibbles = _mm_or_si128(
+                _mm_andnot_si128(high_nibble_lanes, low),
+                _mm_and_si128(high_nibble_lanes, high),
+            );
+
+            let letters = _mm_or_si128(
+                _mm_andnot_si128(odd_lanes, _mm_shuffle_epi8(low_letters, nibbles)),
+                _mm_and_si128(odd_lanes, _mm_shuffle_epi8(high_letters, nibbles)),
+            );
+            _mm_storeu_si128(chunk.as_mut_ptr() as *mut __m128i, letters);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compressed_read::packing::{
+        pack_bases, pack_bases_scalar, unpack_bases, unpack_bases_scalar,
+    };
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn simd_packing_matches_scalar() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+
+        for len in (0..80).chain([255, 256, 257, 1000, 4099]) {
+            let bases: Vec<u8> = (0..len).map(|_| b"ACGTacgt"[rng.gen_range(0..8)]).collect();
+
+            let mut packed = Vec::new();
+            pack_bases(&bases, |b| packed.extend_from_slice(b));
+            let mut scalar_packed = Vec::new();
+            pack_bases_scalar(&bases, |b| scalar_packed.extend_from_slice(b));
+            assert_eq!(packed, scalar_packed);
+
+            let mut unpacked = vec![0; len];
+            unpack_bases(&packed, &mut unpacked);
+            let mut scalar_unpacked = vec![0; len];
+            unpack_bases_scalar(&packed, &mut scalar_unpacked);
+            assert_eq!(unpacked, scalar_unpacked);
+            assert_eq!(unpacked, bases.to_ascii_uppercase());
+        }
+    }
+}
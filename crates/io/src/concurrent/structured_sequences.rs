@@ -1,12 +1,21 @@
-use super::temp_reads::extra_data::SequenceExtraDataConsecutiveCompression;
+use super::temp_reads::extra_data::{
+    HasEmptyExtraBuffer, SequenceExtraData, SequenceExtraDataConsecutiveCompression,
+};
+use bstr::ByteSlice;
+use byteorder::ReadBytesExt;
 use parking_lot::{Condvar, Mutex};
-use std::io::Write;
+use std::io::{Read, Write};
 use std::marker::PhantomData;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub mod binary;
 pub mod concurrent;
 pub mod fasta;
+pub mod graph_dump;
+pub mod ordered;
+pub mod provenance;
+
+use ordered::OrderedSegments;
 
 pub trait IdentSequenceWriter: SequenceExtraDataConsecutiveCompression + Sized {
     fn write_as_ident(&self, stream: &mut impl Write, extra_buffer: &Self::TempBuffer);
@@ -31,6 +40,65 @@ impl IdentSequenceWriter for () {
     }
 }
 
+/// Links info that can carry per-unitig annotations computed while building the unitigs
+pub trait UnitigAnnotation: IdentSequenceWriter {
+    fn new_unitig(is_circular: bool) -> Self;
+}
+
+impl UnitigAnnotation for () {
+    #[inline(always)]
+    fn new_unitig(_is_circular: bool) -> Self {}
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CircularUnitigAnnotation {
+    pub is_circular: bool,
+}
+
+impl HasEmptyExtraBuffer for CircularUnitigAnnotation {}
+impl SequenceExtraData for CircularUnitigAnnotation {
+    fn decode_extended(_buffer: &mut Self::TempBuffer, reader: &mut impl Read) -> Option<Self> {
+        Some(Self {
+            is_circular: reader.read_u8().ok()? != 0,
+        })
+    }
+
+    fn encode_extended(&self, _buffer: &Self::TempBuffer, writer: &mut impl Write) {
+        writer.write_all(&[self.is_circular as u8]).unwrap();
+    }
+
+    fn max_size(&self) -> usize {
+        1
+    }
+}
+
+impl IdentSequenceWriter for CircularUnitigAnnotation {
+    fn write_as_ident(&self, stream: &mut impl Write, _extra_buffer: &Self::TempBuffer) {
+        if self.is_circular {
+            write!(stream, " circular=true").unwrap();
+        }
+    }
+
+    fn write_as_gfa(&self, _stream: &mut impl Write, _extra_buffer: &Self::TempBuffer) {}
+
+    fn parse_as_ident<'a>(ident: &[u8], _extra_buffer: &mut Self::TempBuffer) -> Option<Self> {
+        Some(Self {
+            is_circular: ident.find(b"circular=true").is_some(),
+        })
+    }
+
+    fn parse_as_gfa<'a>(_ident: &[u8], _extra_buffer: &mut Self::TempBuffer) -> Option<Self> {
+        Some(Self { is_circular: false })
+    }
+}
+
+impl UnitigAnnotation for CircularUnitigAnnotation {
+    #[inline(always)]
+    fn new_unitig(is_circular: bool) -> Self {
+        Self { is_circular }
+    }
+}
+
 #[derive(Clone, Debug)]
 #[cfg(feature = "support_kmer_counters")]
 pub struct SequenceAbundance {
@@ -81,6 +149,7 @@ pub struct StructuredSequenceWriter<
     k: usize,
     backend: Mutex<Backend>,
     index_condvar: Condvar,
+    ordered_segments: Option<OrderedSegments>,
     _phantom: PhantomData<(ColorInfo, LinksInfo, Backend)>,
 }
 
@@ -96,10 +165,23 @@ impl<
             k,
             backend: Mutex::new(backend),
             index_condvar: Condvar::new(),
+            ordered_segments: None,
             _phantom: PhantomData,
         }
     }
 
+    /// Spills the sequences of the buffers tagged with a bucket to segments in temp_dir, that are
+    /// written in ascending bucket order by merge_ordered_segments to make the output deterministic.
+    /// The name must be unique among the writers sharing temp_dir
+    pub fn with_ordered_segments(mut self, temp_dir: impl AsRef<Path>, name: &str) -> Self {
+        self.ordered_segments = Some(OrderedSegments::new(temp_dir, name));
+        self
+    }
+
+    pub fn ordered_segments(&self) -> Option<&OrderedSegments> {
+        self.ordered_segments.as_ref()
+    }
+
     fn write_sequences<'a>(
         &self,
         buffer: &mut Backend::SequenceTempBuffer,
@@ -166,3 +248,48 @@ impl<
         self.backend.into_inner().finalize();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::concurrent::structured_sequences::{CircularUnitigAnnotation, IdentSequenceWriter};
+    use crate::concurrent::temp_reads::extra_data::SequenceExtraData;
+
+    #[test]
+    fn circular_annotation_header() {
+        let mut header = b">0 LN:i:45".to_vec();
+        CircularUnitigAnnotation { is_circular: true }.write_as_ident(&mut header, &());
+        assert_eq!(header, b">0 LN:i:45 circular=true");
+        assert!(
+            CircularUnitigAnnotation::parse_as_ident(&header, &mut ())
+                .unwrap()
+                .is_circular
+        );
+
+        let mut header = b">1 LN:i:45".to_vec();
+        CircularUnitigAnnotation { is_circular: false }.write_as_ident(&mut header, &());
+        assert_eq!(header, b">1 LN:i:45");
+        assert!(
+            !CircularUnitigAnnotation::parse_as_ident(&header, &mut ())
+                .unwrap()
+                .is_circular
+        );
+    }
+
+    #[test]
+    fn circular_annotation_payload_roundtrip() {
+        let mut payload = Vec::new();
+        for is_circular in [true, false, true] {
+            let annotation = CircularUnitigAnnotation { is_circular };
+            let start = payload.len();
+            annotation.encode_extended(&(), &mut payload);
+            assert_eq!(payload.len() - start, annotation.max_size());
+        }
+
+        let mut reader = payload.as_slice();
+        for is_circular in [true, false, true] {
+            let decoded = CircularUnitigAnnotation::decode_extended(&mut (), &mut reader).unwrap();
+            assert_eq!(decoded.is_circular, is_circular);
+        }
+        assert!(CircularUnitigAnnotation::decode_extended(&mut (), &mut reader).is_none());
+    }
+}
@@ -1,6 +1,11 @@
+use crate::concurrent::structured_sequences::ordered::write_segment_entry;
+use crate::concurrent::structured_sequences::provenance::SequencesProvenanceWriter;
 use crate::concurrent::structured_sequences::{
     IdentSequenceWriter, StructuredSequenceBackend, StructuredSequenceWriter,
 };
+use config::BucketIndexType;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use utils::vec_slice::VecSlice;
 
 use super::SequenceAbundanceType;
@@ -18,6 +23,9 @@ pub struct FastaWriterConcurrentBuffer<
     temp_buffer: Backend::SequenceTempBuffer,
     current_index: Option<u64>,
     auto_flush: bool,
+    provenance: Option<(&'a SequencesProvenanceWriter, BucketIndexType)>,
+    output_bucket: Option<BucketIndexType>,
+    segment: Option<BufWriter<File>>,
 }
 
 impl<
@@ -40,14 +48,69 @@ impl<
             temp_buffer: Backend::alloc_temp_buffer(),
             current_index: None,
             auto_flush,
+            provenance: None,
+            output_bucket: None,
+            segment: None,
         }
     }
 
-    pub fn flush(&mut self) -> u64 {
+    /// Bucket of the written sequences, if the target keeps the output ordered they are
+    /// written to the bucket segment and merged later in ascending bucket order.
+    /// Only one buffer for each bucket should be used between two merges
+    pub fn with_output_bucket(mut self, bucket_index: BucketIndexType) -> Self {
+        self.output_bucket = Some(bucket_index);
+        self
+    }
+
+    /// Records the bucket index of every flushed sequence in the provenance sidecar
+    pub fn with_provenance(
+        mut self,
+        provenance: Option<&'a SequencesProvenanceWriter>,
+        bucket_index: BucketIndexType,
+    ) -> Self {
+        self.provenance = provenance.map(|p| (p, bucket_index));
+        self
+    }
+
+    /// Writes the buffered sequences, returning the index of the first one.
+    /// Returns None if there are no sequences or if they are written to an ordered segment,
+    /// as their index is assigned only when merging it
+    pub fn flush(&mut self) -> Option<u64> {
         if self.sequences.len() == 0 {
-            return 0;
+            return None;
         }
 
+        if let (Some(segments), Some(bucket_index)) =
+            (self.target.ordered_segments(), self.output_bucket)
+        {
+            assert!(
+                self.current_index.is_none(),
+                "Explicit sequences indexes cannot be ordered by bucket"
+            );
+
+            let segment = self
+                .segment
+                .get_or_insert_with(|| segments.open_segment(bucket_index));
+            for (slice, color, links, abundance) in self.sequences.drain(..) {
+                write_segment_entry(
+                    segment,
+                    slice.get_slice(&self.seq_buf),
+                    &color,
+                    &links,
+                    &abundance,
+                    &self.extra_buffers,
+                );
+            }
+            segment.flush().unwrap();
+
+            ColorInfo::clear_temp_buffer(&mut self.extra_buffers.0);
+            LinksInfo::clear_temp_buffer(&mut self.extra_buffers.1);
+            self.seq_buf.clear();
+            return None;
+        }
+
+        let sequences_count = self.sequences.len() as u64;
+
         let first_read_index = self.target.write_sequences(
             &mut self.temp_buffer,
             self.current_index.map(|c| c - self.sequences.len() as u64),
@@ -63,7 +126,11 @@ impl<
         LinksInfo::clear_temp_buffer(&mut self.extra_buffers.1);
         self.seq_buf.clear();
 
-        first_read_index
+        if let Some((provenance, bucket_index)) = self.provenance {
+            provenance.add_range(bucket_index, first_read_index, sequences_count);
+        }
+
+        Some(first_read_index)
     }
 
     #[inline(always)]
@@ -86,7 +153,7 @@ impl<
 
         if let Some(sequence_index) = sequence_index {
             if Some(sequence_index) != self.current_index {
-                result = Some(self.flush());
+                result = self.flush();
                 self.current_index = Some(sequence_index);
                 different_index = true;
             }
@@ -94,7 +161,7 @@ impl<
 
         if !different_index && self.auto_flush && Self::will_overflow(&self.seq_buf, sequence.len())
         {
-            result = Some(self.flush());
+            result = self.flush();
         }
 
         let color =
@@ -121,7 +188,7 @@ impl<
         result
     }
 
-    pub fn finalize(mut self) -> u64 {
+    pub fn finalize(mut self) -> Option<u64> {
         self.flush()
     }
 }
@@ -137,3 +204,183 @@ impl<
         self.flush();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::concurrent::structured_sequences::concurrent::FastaWriterConcurrentBuffer;
+    use crate::concurrent::structured_sequences::provenance::SequencesProvenanceWriter;
+    use crate::concurrent::structured_sequences::{
+        StructuredSequenceBackend, StructuredSequenceWriter,
+    };
+    use config::BucketIndexType;
+    use parking_lot::Mutex;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    #[cfg(feature = "support_kmer_counters")]
+    use crate::concurrent::structured_sequences::SequenceAbundance;
+
+    struct MemoryBackend {
+        sequences: Arc<Mutex<Vec<(u64, Vec<u8>)>>>,
+    }
+
+    impl StructuredSequenceBackend<(), ()> for MemoryBackend {
+        type SequenceTempBuffer = Vec<(u64, Vec<u8>)>;
+
+        fn alloc_temp_buffer() -> Self::SequenceTempBuffer {
+            vec![]
+        }
+
+        fn write_sequence(
+            _k: usize,
+            buffer: &mut Self::SequenceTempBuffer,
+            sequence_index: u64,
+            sequence: &[u8],
+            _color_info: (),
+            _links_info: (),
+            _extra_buffers: &((), ()),
+            #[cfg(feature = "support_kmer_counters")] _abundance: SequenceAbundance,
+        ) {
+            buffer.push((sequence_index, sequence.to_vec()));
+        }
+
+        fn get_path(&self) -> PathBuf {
+            PathBuf::new()
+        }
+
+        fn flush_temp_buffer(&mut self, buffer: &mut Self::SequenceTempBuffer) {
+            self.sequences.lock().extend(buffer.drain(..));
+        }
+
+        fn finalize(self) {}
+    }
+
+    #[test]
+    fn provenance_matches_bucket() {
+        let sequences = Arc::new(Mutex::new(vec![]));
+        let writer = StructuredSequenceWriter::new(
+            MemoryBackend {
+                sequences: sequences.clone(),
+            },
+            3,
+        );
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let provenance_path = temp_dir.path().join("provenance-test.tsv");
+        let provenance = SequencesProvenanceWriter::new(&provenance_path).unwrap();
+
+        std::thread::scope(|s| {
+            for bucket in 0..4 as BucketIndexType {
+                let writer = &writer;
+                let provenance = &provenance;
+                s.spawn(move || {
+                    // Small buffer to force multiple flushes for each bucket
+                    let mut buffer = FastaWriterConcurrentBuffer::new(writer, 16, true)
+                        .with_provenance(Some(provenance), bucket);
+                    for i in 0..50 {
+                        buffer.add_read(
+                            format!("{}:{}", bucket, i).as_bytes(),
+                            None,
+                            (),
+                            &(),
+                            (),
+                            &(),
+                            #[cfg(feature = "support_kmer_counters")]
+                            SequenceAbundance {
+                                first: 0,
+                                sum: 0,
+                                last: 0,
+                            },
+                        );
+                    }
+                    buffer.finalize();
+                });
+            }
+        });
+
+        provenance.finalize().unwrap();
+        let recorded = SequencesProvenanceWriter::read_from_file(&provenance_path).unwrap();
+
+        let mut sequences = sequences.lock().clone();
+        sequences.sort_unstable();
+
+        assert_eq!(recorded.len(), sequences.len());
+        for ((index, bucket), (seq_index, sequence)) in recorded.iter().zip(sequences.iter()) {
+            assert_eq!(index, seq_index);
+            let expected_bucket: BucketIndexType = std::str::from_utf8(sequence)
+                .unwrap()
+                .split_once(':')
+                .unwrap()
+                .0
+                .parse()
+                .unwrap();
+            assert_eq!(*bucket, expected_bucket);
+        }
+
+        std::fs::write(&provenance_path, "0\t1\n1 2\n").unwrap();
+        assert_eq!(
+            SequencesProvenanceWriter::read_from_file(&provenance_path)
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn ordered_segments_follow_buckets() {
+        let sequences = Arc::new(Mutex::new(vec![]));
+        let test_dir = tempfile::tempdir().unwrap();
+        let temp_dir = test_dir.path();
+
+        let writer = StructuredSequenceWriter::new(
+            MemoryBackend {
+                sequences: sequences.clone(),
+            },
+            3,
+        )
+        .with_ordered_segments(temp_dir, "test");
+
+        std::thread::scope(|s| {
+            for bucket in (0..4 as BucketIndexType).rev() {
+                let writer = &writer;
+                s.spawn(move || {
+                    let mut buffer = FastaWriterConcurrentBuffer::new(writer, 16, true)
+                        .with_output_bucket(bucket);
+                    for i in 0..50 {
+                        buffer.add_read(
+                            format!("{}:{}", bucket, i).as_bytes(),
+                            None,
+                            (),
+                            &(),
+                            (),
+                            &(),
+                            #[cfg(feature = "support_kmer_counters")]
+                            SequenceAbundance {
+                                first: 0,
+                                sum: 0,
+                                last: 0,
+                            },
+                        );
+                    }
+                    buffer.finalize();
+                });
+            }
+        });
+        assert!(sequences.lock().is_empty());
+
+        writer.merge_ordered_segments(None).unwrap();
+        let expected: Vec<_> = (0..4)
+            .flat_map(|bucket| (0..50).map(move |i| format!("{}:{}", bucket, i).into_bytes()))
+            .enumerate()
+            .map(|(index, sequence)| (index as u64, sequence))
+            .collect();
+        assert_eq!(*sequences.lock(), expected);
+        assert_eq!(std::fs::read_dir(temp_dir).unwrap().count(), 0);
+
+        // A segment truncated in the middle of an entry is an error, not the end of the segment
+        let mut segment = writer.ordered_segments().unwrap().open_segment(0);
+        segment.write_all(&[10, b'A', b'C']).unwrap();
+        drop(segment);
+        assert!(writer.merge_ordered_segments(None).is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use crate::concurrent::structured_sequences::{IdentSequenceWriter, StructuredSequenceBackend};
-use config::{DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PER_CPU_BUFFER_SIZE};
+use config::{get_output_buffer_size, DEFAULT_PER_CPU_BUFFER_SIZE, DURABLE_OUTPUT};
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use lz4::{BlockMode, BlockSize, ContentChecksum};
@@ -7,12 +7,47 @@ use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
 
 #[cfg(feature = "support_kmer_counters")]
 use super::SequenceAbundance;
 
+/// Output stream that must be finished to complete the file, writing the end of the compressed streams
+trait FinishWrite: Write {
+    fn finish(self: Box<Self>) -> std::io::Result<()>;
+}
+
+impl FinishWrite for BufWriter<File> {
+    fn finish(self: Box<Self>) -> std::io::Result<()> {
+        (*self).into_inner().map_err(|err| err.into_error())?;
+        Ok(())
+    }
+}
+
+impl FinishWrite for BufWriter<GzEncoder<BufWriter<File>>> {
+    fn finish(self: Box<Self>) -> std::io::Result<()> {
+        let compress_stream = (*self).into_inner().map_err(|err| err.into_error())?;
+        compress_stream.finish()?.flush()
+    }
+}
+
+impl FinishWrite for BufWriter<lz4::Encoder<BufWriter<File>>> {
+    fn finish(self: Box<Self>) -> std::io::Result<()> {
+        let compress_stream = (*self).into_inner().map_err(|err| err.into_error())?;
+        let (mut file_stream, result) = compress_stream.finish();
+        result?;
+        file_stream.flush()
+    }
+}
+
+impl FinishWrite for std::io::Sink {
+    fn finish(self: Box<Self>) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 pub struct FastaWriter<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter> {
-    writer: Box<dyn Write>,
+    writer: Box<dyn FinishWrite>,
     path: PathBuf,
     _phantom: PhantomData<(ColorInfo, LinksInfo)>,
 }
@@ -32,13 +67,13 @@ impl<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter>
 {
     pub fn new_compressed_gzip(path: impl AsRef<Path>, level: u32) -> Self {
         let compress_stream = GzEncoder::new(
-            BufWriter::with_capacity(DEFAULT_OUTPUT_BUFFER_SIZE, File::create(&path).unwrap()),
+            BufWriter::with_capacity(get_output_buffer_size(), File::create(&path).unwrap()),
             Compression::new(level),
         );
 
         FastaWriter {
             writer: Box::new(BufWriter::with_capacity(
-                DEFAULT_OUTPUT_BUFFER_SIZE,
+                get_output_buffer_size(),
                 compress_stream,
             )),
             path: path.as_ref().to_path_buf(),
@@ -53,14 +88,14 @@ impl<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter>
             .block_mode(BlockMode::Linked)
             .block_size(BlockSize::Max1MB)
             .build(BufWriter::with_capacity(
-                DEFAULT_OUTPUT_BUFFER_SIZE,
+                get_output_buffer_size(),
                 File::create(&path).unwrap(),
             ))
             .unwrap();
 
         FastaWriter {
             writer: Box::new(BufWriter::with_capacity(
-                DEFAULT_OUTPUT_BUFFER_SIZE,
+                get_output_buffer_size(),
                 compress_stream,
             )),
             path: path.as_ref().to_path_buf(),
@@ -71,7 +106,7 @@ impl<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter>
     pub fn new_plain(path: impl AsRef<Path>) -> Self {
         FastaWriter {
             writer: Box::new(BufWriter::with_capacity(
-                DEFAULT_OUTPUT_BUFFER_SIZE,
+                get_output_buffer_size(),
                 File::create(&path).unwrap(),
             )),
             path: path.as_ref().to_path_buf(),
@@ -131,7 +166,31 @@ impl<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter>
         buffer.clear();
     }
 
-    fn finalize(self) {}
+    fn finalize(mut self) {
+        // The compressed streams must be finished before the sync
+        std::mem::replace(&mut self.writer, Box::new(std::io::sink()))
+            .finish()
+            .unwrap_or_else(|err| {
+                panic!("Cannot write output file {}: {}", self.path.display(), err)
+            });
+
+        if DURABLE_OUTPUT.load(Ordering::Relaxed) {
+            File::open(&self.path)
+                .and_then(|file| file.sync_all())
+                .unwrap_or_else(|err| {
+                    panic!("Cannot sync output file {}: {}", self.path.display(), err)
+                });
+            // Also persist the directory entry of the newly created file, where supported
+            if let Some(parent) = self.path.parent() {
+                let parent = if parent.as_os_str().is_empty() {
+                    Path::new(".")
+                } else {
+                    parent
+                };
+                let _ = File::open(parent).and_then(|dir| dir.sync_all());
+            }
+        }
+    }
 }
 
 impl<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter> Drop
@@ -141,3 +200,40 @@ impl<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter> Drop
         self.writer.flush().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::concurrent::structured_sequences::fasta::FastaWriter;
+    use crate::concurrent::structured_sequences::StructuredSequenceBackend;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn fasta_writer_finishes_compressed_streams() {
+        let contents = b">0 LN:i:8\nACGTTGCA\n".repeat(1000);
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("fasta.fa.lz4");
+        let mut writer = FastaWriter::<(), ()>::new_compressed_lz4(&path, 2);
+        writer.flush_temp_buffer(&mut contents.clone());
+        writer.finalize();
+
+        let mut decoded = vec![];
+        lz4::Decoder::new(std::fs::File::open(&path).unwrap())
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, contents);
+
+        let path = path.with_extension("gz");
+        let mut writer = FastaWriter::<(), ()>::new_compressed_gzip(&path, 2);
+        writer.flush_temp_buffer(&mut contents.clone());
+        writer.finalize();
+
+        let mut decoded = vec![];
+        GzDecoder::new(std::fs::File::open(&path).unwrap())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, contents);
+    }
+}
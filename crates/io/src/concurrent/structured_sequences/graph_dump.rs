@@ -0,0 +1,334 @@
+use crate::concurrent::structured_sequences::{IdentSequenceWriter, SequenceAbundanceType};
+use crate::varint::{decode_varint, encode_varint};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use config::get_output_buffer_size;
+use parking_lot::Mutex;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use utils::Utils;
+
+#[cfg(feature = "support_kmer_counters")]
+use crate::concurrent::structured_sequences::SequenceAbundance;
+
+const GRAPH_DUMP_MAGIC: [u8; 8] = *b"GGCATGRF";
+pub const GRAPH_DUMP_VERSION: u32 = 1;
+
+/// Header of a graph dump file. All the fixed size fields are little endian,
+/// the variable size ones are LEB128 varints, so that the files are portable between machines
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct GraphDumpHeader {
+    pub version: u32,
+    pub k: u64,
+    pub with_abundances: bool,
+}
+
+#[derive(Debug)]
+pub enum GraphDumpError {
+    Io(PathBuf, std::io::Error),
+    /// The file is not a graph dump
+    InvalidMagic(PathBuf),
+    /// The graph dump was written with an incompatible format version
+    VersionMismatch {
+        path: PathBuf,
+        found: u32,
+    },
+    /// The graph dump abundances do not match the support_kmer_counters feature of this build
+    AbundancesMismatch(PathBuf),
+    /// The graph dump ends in the middle of an entry
+    Truncated(PathBuf),
+}
+
+impl Display for GraphDumpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphDumpError::Io(path, err) => {
+                write!(f, "Cannot access graph dump {}: {}", path.display(), err)
+            }
+            GraphDumpError::InvalidMagic(path) => {
+                write!(f, "File {} is not a graph dump", path.display())
+            }
+            GraphDumpError::VersionMismatch { path, found } => write!(
+                f,
+                "Graph dump {} has format version {}, expected version {}",
+                path.display(),
+                found,
+                GRAPH_DUMP_VERSION
+            ),
+            GraphDumpError::AbundancesMismatch(path) => write!(
+                f,
+                "Graph dump {} abundances do not match the kmer counters support of this build",
+                path.display()
+            ),
+            GraphDumpError::Truncated(path) => {
+                write!(f, "Graph dump {} is truncated", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphDumpError {}
+
+/// Compact binary dump of the compacted graph, with the unitigs 2-bit encoded
+/// alongside their colors, links and abundances
+pub struct GraphDumpWriter {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl GraphDumpWriter {
+    pub fn new(path: impl AsRef<Path>, k: usize) -> Self {
+        let mut writer =
+            BufWriter::with_capacity(get_output_buffer_size(), File::create(path).unwrap());
+        writer.write_all(&GRAPH_DUMP_MAGIC).unwrap();
+        writer
+            .write_u32::<LittleEndian>(GRAPH_DUMP_VERSION)
+            .unwrap();
+        writer.write_u64::<LittleEndian>(k as u64).unwrap();
+        writer
+            .write_u8(cfg!(feature = "support_kmer_counters") as u8)
+            .unwrap();
+
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Encodes an unitig in the buffer, the buffers are then written with write_entries
+    pub fn encode_entry<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter>(
+        buffer: &mut Vec<u8>,
+        index: u64,
+        sequence: &[u8],
+        color: &ColorInfo,
+        links: &LinksInfo,
+        _abundance: &SequenceAbundanceType,
+        extra_buffers: (&ColorInfo::TempBuffer, &LinksInfo::TempBuffer),
+    ) {
+        encode_varint(|b| buffer.extend_from_slice(b), index);
+        encode_varint(|b| buffer.extend_from_slice(b), sequence.len() as u64);
+        buffer.extend(sequence.chunks(4).map(|bases| {
+            bases.iter().enumerate().fold(0, |packed, (i, base)| {
+                packed | (Utils::compress_base(*base) << (i * 2))
+            })
+        }));
+        color.encode_extended(extra_buffers.0, buffer, Default::default());
+        links.encode_extended(extra_buffers.1, buffer, Default::default());
+
+        #[cfg(feature = "support_kmer_counters")]
+        for value in [_abundance.first, _abundance.sum, _abundance.last] {
+            encode_varint(|b| buffer.extend_from_slice(b), value);
+        }
+    }
+
+    pub fn write_entries(&self, entries: &[u8]) {
+        self.writer.lock().write_all(entries).unwrap();
+    }
+
+    pub fn finalize(self) {
+        self.writer.into_inner().flush().unwrap();
+    }
+}
+
+/// Unitig loaded from a graph dump, its bases are stored in the sequence buffer passed to read_entry
+pub struct GraphDumpEntry<ColorInfo, LinksInfo> {
+    pub index: u64,
+    pub color: ColorInfo,
+    pub links: LinksInfo,
+    pub abundance: SequenceAbundanceType,
+}
+
+pub struct GraphDumpReader {
+    reader: BufReader<File>,
+    path: PathBuf,
+    header: GraphDumpHeader,
+}
+
+impl GraphDumpReader {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, GraphDumpError> {
+        let path = path.as_ref().to_path_buf();
+        let io_error = |err| GraphDumpError::Io(path.clone(), err);
+
+        let mut reader = BufReader::with_capacity(
+            get_output_buffer_size(),
+            File::open(&path).map_err(io_error)?,
+        );
+
+        let mut magic = [0; GRAPH_DUMP_MAGIC.len()];
+        if reader.read_exact(&mut magic).is_err() || magic != GRAPH_DUMP_MAGIC {
+            return Err(GraphDumpError::InvalidMagic(path.clone()));
+        }
+
+        let version = reader.read_u32::<LittleEndian>().map_err(io_error)?;
+        if version != GRAPH_DUMP_VERSION {
+            return Err(GraphDumpError::VersionMismatch {
+                path: path.clone(),
+                found: version,
+            });
+        }
+
+        let header = GraphDumpHeader {
+            version,
+            k: reader.read_u64::<LittleEndian>().map_err(io_error)?,
+            with_abundances: reader.read_u8().map_err(io_error)? != 0,
+        };
+
+        if header.with_abundances != cfg!(feature = "support_kmer_counters") {
+            return Err(GraphDumpError::AbundancesMismatch(path.clone()));
+        }
+
+        Ok(Self {
+            reader,
+            path,
+            header,
+        })
+    }
+
+    pub fn header(&self) -> &GraphDumpHeader {
+        &self.header
+    }
+
+    /// Reads the next unitig, decoding its bases in the sequence buffer. Returns None at the end of the dump
+    pub fn read_entry<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter>(
+        &mut self,
+        sequence: &mut Vec<u8>,
+        extra_buffers: &mut (ColorInfo::TempBuffer, LinksInfo::TempBuffer),
+    ) -> Result<Option<GraphDumpEntry<ColorInfo, LinksInfo>>, GraphDumpError> {
+        let is_finished = self
+            .reader
+            .fill_buf()
+            .map_err(|err| GraphDumpError::Io(self.path.clone(), err))?
+            .is_empty();
+        if is_finished {
+            return Ok(None);
+        }
+
+        self.decode_entry(sequence, extra_buffers)
+            .map(Some)
+            .ok_or_else(|| GraphDumpError::Truncated(self.path.clone()))
+    }
+
+    fn decode_entry<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter>(
+        &mut self,
+        sequence: &mut Vec<u8>,
+        extra_buffers: &mut (ColorInfo::TempBuffer, LinksInfo::TempBuffer),
+    ) -> Option<GraphDumpEntry<ColorInfo, LinksInfo>> {
+        let reader = &mut self.reader;
+
+        let index = decode_varint(|| reader.read_u8().ok())?;
+        let length = decode_varint(|| reader.read_u8().ok())? as usize;
+
+        let mut packed = vec![0; (length + 3) / 4];
+        reader.read_exact(&mut packed).ok()?;
+        sequence.clear();
+        sequence.extend(
+            (0..length).map(|i| Utils::decompress_base((packed[i / 4] >> ((i % 4) * 2)) & 0x3)),
+        );
+
+        let color = ColorInfo::decode_extended(&mut extra_buffers.0, reader, Default::default())?;
+        let links = LinksInfo::decode_extended(&mut extra_buffers.1, reader, Default::default())?;
+
+        #[cfg(feature = "support_kmer_counters")]
+        let abundance = {
+            let mut read_value = || decode_varint(|| reader.read_u8().ok());
+            SequenceAbundance {
+                first: read_value()?,
+                sum: read_value()?,
+                last: read_value()?,
+            }
+        };
+        #[cfg(not(feature = "support_kmer_counters"))]
+        let abundance = ();
+
+        Some(GraphDumpEntry {
+            index,
+            color,
+            links,
+            abundance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::concurrent::structured_sequences::graph_dump::{
+        GraphDumpError, GraphDumpReader, GraphDumpWriter, GRAPH_DUMP_VERSION,
+    };
+    use crate::concurrent::structured_sequences::{
+        CircularUnitigAnnotation, SequenceAbundanceType,
+    };
+
+    #[test]
+    fn graph_dump_roundtrip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("graph-dump.ggraph");
+
+        #[cfg(feature = "support_kmer_counters")]
+        let abundance = SequenceAbundanceType {
+            first: 3,
+            sum: 12,
+            last: 5,
+        };
+        #[cfg(not(feature = "support_kmer_counters"))]
+        let abundance: SequenceAbundanceType = ();
+
+        let unitigs: [&[u8]; 3] = [b"ACGTTGCAA", b"GATTACA", b"CCCCGGGGT"];
+
+        let writer = GraphDumpWriter::new(&path, 5);
+        let mut buffer = vec![];
+        for (index, unitig) in unitigs.iter().enumerate() {
+            GraphDumpWriter::encode_entry(
+                &mut buffer,
+                index as u64,
+                unitig,
+                &(),
+                &CircularUnitigAnnotation {
+                    is_circular: index == 1,
+                },
+                &abundance,
+                (&(), &()),
+            );
+        }
+        writer.write_entries(&buffer);
+        writer.finalize();
+
+        let mut reader = GraphDumpReader::open(&path).unwrap();
+        assert_eq!(reader.header().version, GRAPH_DUMP_VERSION);
+        assert_eq!(reader.header().k, 5);
+
+        let mut sequence = vec![];
+        for (index, unitig) in unitigs.iter().enumerate() {
+            let entry = reader
+                .read_entry::<(), CircularUnitigAnnotation>(&mut sequence, &mut ((), ()))
+                .unwrap()
+                .unwrap();
+            assert_eq!(entry.index, index as u64);
+            assert_eq!(sequence.as_slice(), *unitig);
+            assert_eq!(entry.links.is_circular, index == 1);
+        }
+        assert!(reader
+            .read_entry::<(), CircularUnitigAnnotation>(&mut sequence, &mut ((), ()))
+            .unwrap()
+            .is_none());
+
+        // A dump cut in the middle of an entry is reported as truncated
+        let mut truncated = std::fs::read(&path).unwrap();
+        truncated.pop();
+        std::fs::write(&path, truncated).unwrap();
+        let mut reader = GraphDumpReader::open(&path).unwrap();
+        for _ in 0..unitigs.len() - 1 {
+            reader
+                .read_entry::<(), CircularUnitigAnnotation>(&mut sequence, &mut ((), ()))
+                .unwrap();
+        }
+        assert!(matches!(
+            reader.read_entry::<(), CircularUnitigAnnotation>(&mut sequence, &mut ((), ())),
+            Err(GraphDumpError::Truncated(_))
+        ));
+
+        std::fs::write(&path, b"GGCATCNT").unwrap();
+        assert!(matches!(
+            GraphDumpReader::open(&path),
+            Err(GraphDumpError::InvalidMagic(_))
+        ));
+    }
+}
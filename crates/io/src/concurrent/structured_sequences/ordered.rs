@@ -0,0 +1,191 @@
+use crate::concurrent::structured_sequences::provenance::SequencesProvenanceWriter;
+use crate::concurrent::structured_sequences::{
+    IdentSequenceWriter, SequenceAbundanceType, StructuredSequenceBackend, StructuredSequenceWriter,
+};
+use crate::varint::{decode_varint, encode_varint};
+use byteorder::ReadBytesExt;
+use config::{get_output_buffer_size, BucketIndexType};
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use utils::vec_slice::VecSlice;
+
+#[cfg(feature = "support_kmer_counters")]
+use crate::concurrent::structured_sequences::SequenceAbundance;
+
+/// Sequences of each bucket, spilled to a temporary segment file until they are merged into the
+/// output in ascending bucket order, so that the output does not depend on the threads scheduling
+pub struct OrderedSegments {
+    temp_dir: PathBuf,
+    /// Prefix of the segments names, to keep apart the segments of writers sharing temp_dir
+    name: String,
+    segments: Mutex<BTreeMap<BucketIndexType, PathBuf>>,
+}
+
+impl OrderedSegments {
+    pub fn new(temp_dir: impl AsRef<Path>, name: &str) -> Self {
+        Self {
+            temp_dir: temp_dir.as_ref().to_path_buf(),
+            name: name.to_string(),
+            segments: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Opens the segment of the bucket for appending, its sequences are merged in the order they are written
+    pub fn open_segment(&self, bucket_index: BucketIndexType) -> BufWriter<File> {
+        let path = self.temp_dir.join(format!(
+            "{}-ordered-segment-{}.tmp",
+            self.name, bucket_index
+        ));
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .unwrap_or_else(|err| panic!("Cannot open segment {}: {}", path.display(), err));
+        self.segments.lock().insert(bucket_index, path);
+        BufWriter::new(file)
+    }
+
+    fn take_segments(&self) -> BTreeMap<BucketIndexType, PathBuf> {
+        std::mem::take(&mut *self.segments.lock())
+    }
+}
+
+pub(crate) fn write_segment_entry<
+    ColorInfo: IdentSequenceWriter,
+    LinksInfo: IdentSequenceWriter,
+>(
+    writer: &mut impl Write,
+    sequence: &[u8],
+    color: &ColorInfo,
+    links: &LinksInfo,
+    _abundance: &SequenceAbundanceType,
+    extra_buffers: &(ColorInfo::TempBuffer, LinksInfo::TempBuffer),
+) {
+    encode_varint(|b| writer.write_all(b), sequence.len() as u64).unwrap();
+    writer.write_all(sequence).unwrap();
+    color.encode_extended(&extra_buffers.0, writer, Default::default());
+    links.encode_extended(&extra_buffers.1, writer, Default::default());
+
+    #[cfg(feature = "support_kmer_counters")]
+    for value in [_abundance.first, _abundance.sum, _abundance.last] {
+        encode_varint(|b| writer.write_all(b), value).unwrap();
+    }
+}
+
+/// Reads the next entry of a segment, returning None once the segment is finished
+fn read_segment_entry<ColorInfo: IdentSequenceWriter, LinksInfo: IdentSequenceWriter>(
+    reader: &mut impl BufRead,
+    seq_buf: &mut Vec<u8>,
+    extra_buffers: &mut (ColorInfo::TempBuffer, LinksInfo::TempBuffer),
+) -> std::io::Result<Option<(VecSlice<u8>, ColorInfo, LinksInfo, SequenceAbundanceType)>> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+
+    let truncated = || std::io::Error::new(ErrorKind::UnexpectedEof, "Truncated segment entry");
+
+    let length = decode_varint(|| reader.read_u8().ok()).ok_or_else(truncated)? as usize;
+
+    let start = seq_buf.len();
+    seq_buf.resize(start + length, 0);
+    reader.read_exact(&mut seq_buf[start..])?;
+
+    let color = ColorInfo::decode_extended(&mut extra_buffers.0, reader, Default::default())
+        .ok_or_else(truncated)?;
+    let links = LinksInfo::decode_extended(&mut extra_buffers.1, reader, Default::default())
+        .ok_or_else(truncated)?;
+
+    #[cfg(feature = "support_kmer_counters")]
+    let abundance = {
+        let mut read_value = || decode_varint(|| reader.read_u8().ok()).ok_or_else(truncated);
+        SequenceAbundance {
+            first: read_value()?,
+            sum: read_value()?,
+            last: read_value()?,
+        }
+    };
+    #[cfg(not(feature = "support_kmer_counters"))]
+    let abundance = ();
+
+    Ok(Some((
+        VecSlice::new(start, length),
+        color,
+        links,
+        abundance,
+    )))
+}
+
+impl<
+        ColorInfo: IdentSequenceWriter,
+        LinksInfo: IdentSequenceWriter,
+        Backend: StructuredSequenceBackend<ColorInfo, LinksInfo>,
+    > StructuredSequenceWriter<ColorInfo, LinksInfo, Backend>
+{
+    /// Writes the segments of the buckets flushed so far in ascending bucket order, streaming each
+    /// segment in batches. Must be called after all the buffers writing to the segments are finalized
+    pub fn merge_ordered_segments(
+        &self,
+        provenance: Option<&SequencesProvenanceWriter>,
+    ) -> std::io::Result<()> {
+        let Some(ordered_segments) = &self.ordered_segments else {
+            return Ok(());
+        };
+
+        let mut temp_buffer = Backend::alloc_temp_buffer();
+        let mut extra_buffers = (ColorInfo::new_temp_buffer(), LinksInfo::new_temp_buffer());
+        let output_buffer_size = get_output_buffer_size();
+        let mut seq_buf = Vec::with_capacity(output_buffer_size);
+        let mut sequences = Vec::new();
+
+        for (bucket_index, path) in ordered_segments.take_segments() {
+            let mut reader = BufReader::new(File::open(&path)?);
+
+            loop {
+                let entry = read_segment_entry::<ColorInfo, LinksInfo>(
+                    &mut reader,
+                    &mut seq_buf,
+                    &mut extra_buffers,
+                )
+                .map_err(|err| {
+                    std::io::Error::new(
+                        err.kind(),
+                        format!("Cannot read segment {}: {}", path.display(), err),
+                    )
+                })?;
+                let is_finished = entry.is_none();
+                sequences.extend(entry);
+
+                if !sequences.is_empty() && (is_finished || seq_buf.len() >= output_buffer_size) {
+                    let sequences_count = sequences.len() as u64;
+                    let first_index = self.write_sequences(
+                        &mut temp_buffer,
+                        None,
+                        sequences.drain(..).map(|(slice, color, links, abundance)| {
+                            (slice.get_slice(&seq_buf), color, links, abundance)
+                        }),
+                        &extra_buffers,
+                    );
+                    if let Some(provenance) = provenance {
+                        provenance.add_range(bucket_index, first_index, sequences_count);
+                    }
+
+                    ColorInfo::clear_temp_buffer(&mut extra_buffers.0);
+                    LinksInfo::clear_temp_buffer(&mut extra_buffers.1);
+                    seq_buf.clear();
+                }
+
+                if is_finished {
+                    break;
+                }
+            }
+
+            drop(reader);
+            let _ = std::fs::remove_file(path);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use config::BucketIndexType;
+use parking_lot::Mutex;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Sidecar file that maps each output sequence index to the bucket it was assembled in,
+/// one `<sequence_index>\t<bucket_index>` line per sequence.
+pub struct SequencesProvenanceWriter {
+    path: PathBuf,
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl SequencesProvenanceWriter {
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            writer: Mutex::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    pub fn add_range(&self, bucket_index: BucketIndexType, first_index: u64, count: u64) {
+        let mut writer = self.writer.lock();
+        for index in first_index..(first_index + count) {
+            writeln!(writer, "{}\t{}", index, bucket_index).unwrap();
+        }
+    }
+
+    pub fn get_path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    pub fn finalize(self) -> io::Result<()> {
+        self.writer.into_inner().flush()
+    }
+
+    pub fn read_from_file(path: impl AsRef<Path>) -> io::Result<Vec<(u64, BucketIndexType)>> {
+        let mut provenance = BufReader::new(File::open(path)?)
+            .lines()
+            .map(|line| {
+                let line = line?;
+                line.split_once('\t')
+                    .and_then(|(index, bucket)| Some((index.parse().ok()?, bucket.parse().ok()?)))
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            ErrorKind::InvalidData,
+                            format!("Invalid provenance line: '{}'", line),
+                        )
+                    })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        provenance.sort_unstable();
+        Ok(provenance)
+    }
+}
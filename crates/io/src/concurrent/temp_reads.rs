@@ -1,2 +1,4 @@
 pub mod creads_utils;
 pub mod extra_data;
+pub mod read_names;
+pub mod read_pairs;
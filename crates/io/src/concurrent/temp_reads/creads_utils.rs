@@ -82,6 +82,8 @@ impl<
         extra_data: &Self::ExtraData,
         extra_data_buffer: &Self::ExtraDataBuffer,
     ) {
+        bucket.reserve(extra_data.max_size_hint() + WITH_SECOND_BUCKET as usize);
+
         if WITH_SECOND_BUCKET {
             bucket.push(element.extra_bucket);
         }
@@ -4,6 +4,9 @@ use config::ColorIndexType;
 use core::fmt::Debug;
 use std::io::{Cursor, Read, Write};
 
+/// Bytes reserved by default before encoding an extra data value
+pub const DEFAULT_EXTRA_DATA_SIZE_HINT: usize = VARINT_MAX_SIZE;
+
 struct PointerDecoder {
     ptr: *const u8,
 }
@@ -96,6 +99,13 @@ pub trait SequenceExtraDataConsecutiveCompression: SequenceExtraDataTempBufferMa
     fn obtain_last_data(&self, last_data: Self::LastData) -> Self::LastData;
 
     fn max_size(&self) -> usize;
+
+    /// Bytes to reserve in the output buffer before encoding, to avoid growing it multiple times
+    /// while encoding large values. Unlike max_size it does not need to be an upper bound
+    #[inline(always)]
+    fn max_size_hint(&self) -> usize {
+        DEFAULT_EXTRA_DATA_SIZE_HINT
+    }
 }
 
 pub trait SequenceExtraData: SequenceExtraDataTempBufferManagement {
@@ -118,6 +128,13 @@ pub trait SequenceExtraData: SequenceExtraDataTempBufferManagement {
     fn encode_extended(&self, buffer: &Self::TempBuffer, writer: &mut impl Write);
 
     fn max_size(&self) -> usize;
+
+    /// Bytes to reserve in the output buffer before encoding, to avoid growing it multiple times
+    /// while encoding large values. Unlike max_size it does not need to be an upper bound
+    #[inline(always)]
+    fn max_size_hint(&self) -> usize {
+        DEFAULT_EXTRA_DATA_SIZE_HINT
+    }
 }
 
 pub trait SequenceExtraDataOwned: SequenceExtraDataConsecutiveCompression {
@@ -175,6 +192,11 @@ impl<T: SequenceExtraData> SequenceExtraDataConsecutiveCompression for T {
         <Self as SequenceExtraData>::max_size(self)
     }
 
+    #[inline(always)]
+    fn max_size_hint(&self) -> usize {
+        <Self as SequenceExtraData>::max_size_hint(self)
+    }
+
     fn obtain_last_data(&self, _last_data: Self::LastData) -> Self::LastData {
         ()
     }
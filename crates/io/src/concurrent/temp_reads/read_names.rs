@@ -0,0 +1,101 @@
+use parking_lot::Mutex;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Table of the read names, filled while reading the input and written once to disk,
+/// so that only the reads indexes need to be stored in the buckets
+pub struct ReadNamesTable {
+    names: Mutex<Vec<String>>,
+}
+
+impl ReadNamesTable {
+    pub fn new() -> Self {
+        Self {
+            names: Mutex::new(vec![]),
+        }
+    }
+
+    /// Extracts the read name from a sequence header, without the leading marker and the description
+    pub fn get_read_name(ident: &[u8]) -> &[u8] {
+        let ident = match ident.first() {
+            Some(b'>') | Some(b'@') => &ident[1..],
+            _ => ident,
+        };
+        ident
+            .split(|c| c.is_ascii_whitespace())
+            .next()
+            .unwrap_or(&[])
+    }
+
+    pub fn add_name(&self, index: usize, ident: &[u8]) {
+        let mut names = self.names.lock();
+        if names.len() <= index {
+            names.resize(index + 1, String::new());
+        }
+        names[index] = String::from_utf8_lossy(Self::get_read_name(ident)).into_owned();
+    }
+
+    pub fn write_to_file(&self, path: impl AsRef<Path>) {
+        let mut writer = BufWriter::new(File::create(path).unwrap());
+        for name in self.names.lock().iter() {
+            writeln!(writer, "{}", name).unwrap();
+        }
+    }
+
+    pub fn load_from_file(path: impl AsRef<Path>) -> Self {
+        let names = BufReader::new(File::open(path).unwrap())
+            .lines()
+            .map(|line| line.unwrap())
+            .collect();
+        Self {
+            names: Mutex::new(names),
+        }
+    }
+
+    pub fn into_names(self) -> Vec<String> {
+        self.names.into_inner()
+    }
+
+    /// Escapes a read name to be written as a json string
+    pub fn json_escape(name: &str) -> String {
+        let mut escaped = String::with_capacity(name.len());
+        for c in name.chars() {
+            match c {
+                '"' => escaped.push_str("\\\""),
+                '\\' => escaped.push_str("\\\\"),
+                c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::concurrent::temp_reads::read_names::ReadNamesTable;
+
+    #[test]
+    fn read_name_recovered_from_table() {
+        let table = ReadNamesTable::new();
+        table.add_name(1, b">read_two some description");
+        table.add_name(0, b">read_one");
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("read-names");
+        table.write_to_file(&path);
+        let names = ReadNamesTable::load_from_file(&path).into_names();
+
+        assert_eq!(names, vec!["read_one", "read_two"]);
+    }
+
+    #[test]
+    fn read_name_json_escape() {
+        assert_eq!(ReadNamesTable::json_escape("read_1"), "read_1");
+        assert_eq!(
+            ReadNamesTable::json_escape("a\"b\\c\u{1}"),
+            "a\\\"b\\\\c\\u0001"
+        );
+    }
+}
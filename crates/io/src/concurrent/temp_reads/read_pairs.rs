@@ -0,0 +1,174 @@
+use crate::concurrent::temp_reads::extra_data::{
+    HasEmptyExtraBuffer, SequenceExtraData, SequenceExtraDataConsecutiveCompression,
+    SequenceExtraDataTempBufferManagement,
+};
+use crate::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
+use byteorder::ReadBytesExt;
+use std::io::{Read, Write};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ReadMate {
+    First,
+    Second,
+}
+
+/// Pair of a paired-end read, shared by both its mates
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ReadPairId {
+    pub pair_id: u64,
+    pub mate: ReadMate,
+}
+
+impl ReadPairId {
+    /// Pair of a read in an interleaved input, where the mates of each pair are consecutive
+    pub fn from_interleaved_index(read_index: u64) -> Self {
+        Self {
+            pair_id: read_index / 2,
+            mate: if read_index % 2 == 0 {
+                ReadMate::First
+            } else {
+                ReadMate::Second
+            },
+        }
+    }
+
+    pub fn is_mate_of(&self, other: &ReadPairId) -> bool {
+        self.pair_id == other.pair_id && self.mate != other.mate
+    }
+}
+
+impl HasEmptyExtraBuffer for ReadPairId {}
+impl SequenceExtraData for ReadPairId {
+    #[inline(always)]
+    fn decode_extended(_: &mut (), reader: &mut impl Read) -> Option<Self> {
+        let value = decode_varint(|| reader.read_u8().ok())?;
+        Some(Self {
+            pair_id: value >> 1,
+            mate: if value & 1 == 0 {
+                ReadMate::First
+            } else {
+                ReadMate::Second
+            },
+        })
+    }
+
+    #[inline(always)]
+    fn encode_extended(&self, _: &(), writer: &mut impl Write) {
+        let value = (self.pair_id << 1) | (self.mate == ReadMate::Second) as u64;
+        encode_varint(|b| writer.write_all(b), value).unwrap();
+    }
+
+    #[inline(always)]
+    fn max_size(&self) -> usize {
+        VARINT_MAX_SIZE
+    }
+}
+
+/// Extra data of a read carrying its pair id alongside the pipeline extra data,
+/// so that the mates can be matched after the reads are bucketed independently
+#[derive(Clone, Debug)]
+pub struct WithReadPair<E: SequenceExtraDataConsecutiveCompression> {
+    pub pair: ReadPairId,
+    pub data: E,
+}
+
+impl<E: SequenceExtraDataConsecutiveCompression> SequenceExtraDataTempBufferManagement
+    for WithReadPair<E>
+{
+    type TempBuffer = E::TempBuffer;
+
+    #[inline(always)]
+    fn new_temp_buffer() -> Self::TempBuffer {
+        E::new_temp_buffer()
+    }
+
+    #[inline(always)]
+    fn clear_temp_buffer(buffer: &mut Self::TempBuffer) {
+        E::clear_temp_buffer(buffer)
+    }
+
+    fn copy_temp_buffer(dest: &mut Self::TempBuffer, src: &Self::TempBuffer) {
+        E::copy_temp_buffer(dest, src)
+    }
+
+    #[inline(always)]
+    fn copy_extra_from(extra: Self, src: &Self::TempBuffer, dst: &mut Self::TempBuffer) -> Self {
+        Self {
+            pair: extra.pair,
+            data: E::copy_extra_from(extra.data, src, dst),
+        }
+    }
+}
+
+impl<E: SequenceExtraDataConsecutiveCompression> SequenceExtraDataConsecutiveCompression
+    for WithReadPair<E>
+{
+    type LastData = E::LastData;
+
+    #[inline(always)]
+    fn decode_extended(
+        buffer: &mut Self::TempBuffer,
+        reader: &mut impl Read,
+        last_data: Self::LastData,
+    ) -> Option<Self> {
+        let pair = <ReadPairId as SequenceExtraData>::decode_extended(&mut (), reader)?;
+        let data = E::decode_extended(buffer, reader, last_data)?;
+        Some(Self { pair, data })
+    }
+
+    #[inline(always)]
+    fn encode_extended(
+        &self,
+        buffer: &Self::TempBuffer,
+        writer: &mut impl Write,
+        last_data: Self::LastData,
+    ) {
+        <ReadPairId as SequenceExtraData>::encode_extended(&self.pair, &(), writer);
+        self.data.encode_extended(buffer, writer, last_data);
+    }
+
+    #[inline(always)]
+    fn obtain_last_data(&self, last_data: Self::LastData) -> Self::LastData {
+        self.data.obtain_last_data(last_data)
+    }
+
+    #[inline(always)]
+    fn max_size(&self) -> usize {
+        VARINT_MAX_SIZE + self.data.max_size()
+    }
+
+    #[inline(always)]
+    fn max_size_hint(&self) -> usize {
+        VARINT_MAX_SIZE + self.data.max_size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::concurrent::temp_reads::extra_data::SequenceExtraDataConsecutiveCompression;
+    use crate::concurrent::temp_reads::read_pairs::{ReadMate, ReadPairId, WithReadPair};
+    use config::ColorIndexType;
+
+    #[test]
+    fn read_pair_survives_bucket_encoding() {
+        let first = ReadPairId::from_interleaved_index(2 * 1000000 + 1);
+        assert_eq!(first.pair_id, 1000000);
+        assert_eq!(first.mate, ReadMate::Second);
+        assert!(first.is_mate_of(&ReadPairId::from_interleaved_index(2 * 1000000)));
+
+        let read = WithReadPair {
+            pair: first,
+            data: 42 as ColorIndexType,
+        };
+
+        let mut bucket = vec![];
+        read.encode_extended(&(), &mut bucket, ());
+        assert!(bucket.len() <= read.max_size());
+
+        let decoded =
+            WithReadPair::<ColorIndexType>::decode_from_slice_extended(&mut (), &bucket, ())
+                .unwrap();
+        assert_eq!(decoded.pair, first);
+        assert_eq!(decoded.data, 42);
+    }
+}
@@ -1,7 +1,9 @@
 use crate::sequences_stream::general::GeneralSequenceBlockData;
 use config::{BucketIndexType, MAX_BUCKETS_COUNT_LOG, MAX_BUCKET_SIZE, MIN_BUCKETS_COUNT_LOG};
+use parallel_processor::memory_data_size::MemoryDataSize;
 use std::cmp::{max, min};
 use std::path::{Path, PathBuf};
+use utils::kmer_length::{validate_k, KmerError};
 
 pub mod chunks_writer;
 pub mod compressed_read;
@@ -9,7 +11,9 @@ pub mod concurrent;
 pub mod lines_reader;
 // pub mod reads_writer;
 pub mod sequences_reader;
+pub mod sequences_sort;
 pub mod sequences_stream;
+pub mod sequences_validation;
 pub mod structs;
 pub mod varint;
 
@@ -75,3 +79,82 @@ pub fn compute_stats_from_input_blocks(blocks: &[GeneralSequenceBlockData]) -> F
         // best_lz4_compression_level: 0,
     }
 }
+
+/// Maximum expansion of a compressed input file when decompressed
+const TEMP_SPACE_COMPRESSED_INPUT_RATIO: u64 = 4;
+/// Space used by each bucket file besides its reads, for buffers flushes and headers
+const TEMP_SPACE_BUCKET_OVERHEAD: u64 = 64 * 1024;
+
+/// Estimates an upper bound of the peak temporary disk space needed to process the input files.
+/// Assumptions:
+/// - each byte of a plain input file is at most one base, compressed files (gz, lz4) expand at most 4 times
+/// - the reads are stored 2-bit encoded in the buckets, split in super-kmers that repeat k - 1 bases
+///   and contain on average (k - m + 2) / 2 kmers each
+/// - at the peak, the minimizer buckets and the kmers merge results are both on disk,
+///   with the results not bigger than the buckets
+/// - intermediate compression is not taken into account, so it only lowers the real usage
+///
+/// Returns an error if k is not supported, as the minimizer length is derived from it
+pub fn estimate_temp_space(
+    file_inputs: &[PathBuf],
+    buckets_count: usize,
+    k: usize,
+) -> Result<MemoryDataSize, KmerError> {
+    validate_k(k)?;
+
+    let bases_count: u64 = file_inputs
+        .iter()
+        .map(|file| {
+            let file_size = std::fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+            if file
+                .extension()
+                .map(|x| x == "gz" || x == "lz4")
+                .unwrap_or(false)
+            {
+                file_size * TEMP_SPACE_COMPRESSED_INPUT_RATIO
+            } else {
+                file_size
+            }
+        })
+        .sum();
+
+    let m = utils::compute_best_m(k);
+    let kmers_per_superkmer = max(1, (k.saturating_sub(m) + 2) / 2) as u64;
+    let superkmers_count = (bases_count + kmers_per_superkmer - 1) / kmers_per_superkmer;
+
+    // Each super-kmer stores its kmers bases plus the k - 1 overlapping ones, and a length header
+    let buckets_bytes =
+        (bases_count + superkmers_count * (k as u64 - 1)) / 4 + superkmers_count * 4;
+
+    Ok(MemoryDataSize::from_bytes(
+        (buckets_bytes * 2 + buckets_count as u64 * TEMP_SPACE_BUCKET_OVERHEAD) as usize,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::estimate_temp_space;
+    use utils::kmer_length::KmerError;
+
+    #[test]
+    fn temp_space_estimation_bounds() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let input = temp_dir.path().join("temp-space.fa");
+        let sequence = "ACGT".repeat(25000);
+        std::fs::write(&input, format!(">0\n{}\n", sequence)).unwrap();
+
+        let estimate = estimate_temp_space(&[input.clone()], 256, 31)
+            .unwrap()
+            .as_bytes();
+
+        // At least the 2-bit encoded input, stored twice
+        assert!(estimate >= sequence.len() / 4 * 2);
+        // Bounded by the super-kmers expansion
+        assert!(estimate <= sequence.len() * 4 + 256 * 64 * 1024);
+        assert_eq!(estimate_temp_space(&[], 0, 31).unwrap().as_bytes(), 0);
+        assert_eq!(
+            estimate_temp_space(&[input], 256, 0).unwrap_err(),
+            KmerError::TooShort { k: 0 }
+        );
+    }
+}
@@ -11,6 +11,37 @@ pub struct LinesReader {
     buffer: Vec<u8>,
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum InputCompression {
+    Plain,
+    /// Also covers the multi-member and bgzf files, that are concatenated gzip members
+    Gzip,
+    Lz4,
+}
+
+impl InputCompression {
+    const GZIP_MAGIC: &'static [u8] = &[0x1f, 0x8b];
+    const LZ4_MAGIC: &'static [u8] = &[0x04, 0x22, 0x4d, 0x18];
+
+    /// Detects the compression from the magic bytes, using the extension only if the file cannot be read
+    fn detect(path: &Path) -> Self {
+        let mut magic = Vec::with_capacity(Self::LZ4_MAGIC.len());
+        match File::open(path).and_then(|file| {
+            file.take(Self::LZ4_MAGIC.len() as u64)
+                .read_to_end(&mut magic)
+        }) {
+            Ok(_) if magic.starts_with(Self::GZIP_MAGIC) => InputCompression::Gzip,
+            Ok(_) if magic.starts_with(Self::LZ4_MAGIC) => InputCompression::Lz4,
+            Ok(_) => InputCompression::Plain,
+            Err(_) => match path.extension().and_then(|ext| ext.to_str()) {
+                Some("gz") => InputCompression::Gzip,
+                Some("lz4") => InputCompression::Lz4,
+                _ => InputCompression::Plain,
+            },
+        }
+    }
+}
+
 static COUNTER_THREADS_BUSY_READING: AtomicCounter<SumMode> =
     declare_counter_i64!("line_reading_threads", SumMode, false);
 
@@ -61,7 +92,9 @@ impl LinesReader {
         mut callback: impl FnMut(&[u8]),
         remove: bool,
     ) {
-        if path.as_ref().extension().filter(|x| *x == "gz").is_some() {
+        let compression = InputCompression::detect(path.as_ref());
+
+        if compression == InputCompression::Gzip {
             if let Err(_err) = decompress_file_buffered(
                 &path,
                 |data| {
@@ -76,7 +109,7 @@ impl LinesReader {
                 );
             }
             callback(&[]);
-        } else if path.as_ref().extension().filter(|x| *x == "lz4").is_some() {
+        } else if compression == InputCompression::Lz4 {
             let file = lz4::Decoder::new(
                 File::open(&path).expect(&format!("Cannot open file {}", path.as_ref().display())),
             )
@@ -179,3 +212,48 @@ impl LinesReader {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::lines_reader::{InputCompression, LinesReader};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn sniffed_multi_member_gzip() {
+        // Concatenated members without the .gz extension, as written by bgzip
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("lines.fa");
+        let mut contents = Vec::new();
+        for member in [">0\nACGT\n", ">1\nTTGCA\n"] {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(member.as_bytes()).unwrap();
+            contents.extend(encoder.finish().unwrap());
+        }
+        std::fs::write(&path, contents).unwrap();
+
+        assert_eq!(InputCompression::detect(&path), InputCompression::Gzip);
+
+        let mut lines = Vec::new();
+        LinesReader::new().process_lines(
+            &path,
+            |line, partial, finished| {
+                if !finished {
+                    assert!(!partial);
+                    lines.push(line.to_vec());
+                }
+            },
+            true,
+        );
+        assert_eq!(
+            lines,
+            vec![
+                b">0".to_vec(),
+                b"ACGT".to_vec(),
+                b">1".to_vec(),
+                b"TTGCA".to_vec()
+            ]
+        );
+    }
+}
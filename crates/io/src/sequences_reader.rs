@@ -1,4 +1,7 @@
 use crate::lines_reader::LinesReader;
+use crate::sequences_validation::{
+    InputValidationPolicy, SequencesValidator, ValidationError, ValidationErrorKind,
+};
 use config::DEFAULT_OUTPUT_BUFFER_SIZE;
 use nightly_quirks::branch_pred::unlikely;
 use std::cmp::max;
@@ -38,16 +41,27 @@ const SEQ_LETTERS_MAPPING: [u8; 256] = {
 
 pub struct SequencesReader {
     lines_reader: LinesReader,
+    validator: SequencesValidator,
 }
 
 impl SequencesReader {
     pub fn new() -> Self {
+        Self::with_validation(InputValidationPolicy::current())
+    }
+
+    pub fn with_validation(policy: InputValidationPolicy) -> Self {
         Self {
             lines_reader: LinesReader::new(),
+            validator: SequencesValidator::new(policy),
         }
     }
 
-    fn normalize_sequence(seq: &mut [u8]) {
+    pub fn get_validation_errors(&self) -> &[ValidationError] {
+        self.validator.get_errors()
+    }
+
+    /// Maps the bases to uppercase ACGT, any other byte to N
+    pub fn normalize_sequence(seq: &mut [u8]) {
         for el in seq.iter_mut() {
             *el = SEQ_LETTERS_MAPPING[*el as usize];
         }
@@ -121,15 +135,26 @@ impl SequencesReader {
             line_split_copyback.unwrap_or(0) * 2,
         );
 
+        let source = source.as_ref();
+        let validator = &mut self.validator;
+        let validate = validator.is_enabled();
+        let mut line_number = 0;
+
         self.lines_reader.process_lines(
             source,
             |line: &[u8], partial, finished| {
+                let current_line = line_number + 1;
+                if !partial {
+                    line_number += 1;
+                }
+
                 if on_comment {
                     on_comment = !partial;
                 }
                 // If a new ident line is found (or it's the last line)
                 else if finished || (new_line && line.len() > 0 && line[0] == b'>') {
-                    if intermediate[SEQ_STATE].len() > 0 {
+                    let is_valid = !validate || validator.finish_record();
+                    if intermediate[SEQ_STATE].len() > 0 && is_valid {
                         Self::normalize_sequence(&mut intermediate[SEQ_STATE]);
                         func(DnaSequence {
                             ident_data: &intermediate[IDENT_STATE],
@@ -156,17 +181,22 @@ impl SequencesReader {
                         state = SEQ_STATE;
                     }
                 } else {
+                    if validate {
+                        validator.check_bases(source, current_line, line);
+                    }
                     intermediate[SEQ_STATE].extend_from_slice(line);
                 }
 
                 if let Some(copyback) = line_split_copyback {
                     if intermediate[SEQ_STATE].len() >= flush_size {
                         Self::normalize_sequence(&mut intermediate[SEQ_STATE]);
-                        func(DnaSequence {
-                            ident_data: &intermediate[IDENT_STATE],
-                            seq: &intermediate[SEQ_STATE],
-                            format: DnaSequencesFileType::FASTQ,
-                        });
+                        if !validate || validator.is_record_valid() {
+                            func(DnaSequence {
+                                ident_data: &intermediate[IDENT_STATE],
+                                seq: &intermediate[SEQ_STATE],
+                                format: DnaSequencesFileType::FASTQ,
+                            });
+                        }
                         let copy_start = intermediate[SEQ_STATE].len() - copyback;
                         intermediate[SEQ_STATE].copy_within(copy_start.., 0);
                         intermediate[SEQ_STATE].truncate(copyback);
@@ -190,13 +220,76 @@ impl SequencesReader {
 
         let mut intermediate = [Vec::new(), Vec::new(), Vec::new()];
 
+        let source = source.as_ref();
+        let validator = &mut self.validator;
+        let validate = validator.is_enabled();
+        let mut line_number = 0;
+        let mut new_line = true;
+        let mut quality_length = 0;
+
         self.lines_reader.process_lines(
             source,
             |line: &[u8], partial, finished| {
                 if unlikely(finished) {
+                    if validate && (state != IDENT_STATE || intermediate[IDENT_STATE].len() > 0) {
+                        validator.add_error(
+                            source,
+                            line_number,
+                            ValidationErrorKind::TruncatedRecord,
+                        );
+                        validator.finish_record();
+                    }
                     return;
                 }
 
+                let current_line = line_number + 1;
+                let is_line_start = new_line;
+                if !partial {
+                    line_number += 1;
+                }
+                new_line = !partial;
+
+                if validate {
+                    // Skip empty lines between records
+                    if state == IDENT_STATE && is_line_start && !partial && line.len() == 0 {
+                        return;
+                    }
+
+                    match state {
+                        IDENT_STATE if is_line_start && line.first() != Some(&b'@') => {
+                            validator.add_error(
+                                source,
+                                current_line,
+                                ValidationErrorKind::MissingIdentMarker,
+                            );
+                        }
+                        SEQ_STATE => validator.check_bases(source, current_line, line),
+                        QUAL_STATE if !skipped_plus => {
+                            if is_line_start && line.first() != Some(&b'+') {
+                                validator.add_error(
+                                    source,
+                                    current_line,
+                                    ValidationErrorKind::MissingSeparator,
+                                );
+                            }
+                        }
+                        QUAL_STATE => {
+                            quality_length += line.len();
+                            if !partial && quality_length != intermediate[SEQ_STATE].len() {
+                                validator.add_error(
+                                    source,
+                                    current_line,
+                                    ValidationErrorKind::QualityLengthMismatch {
+                                        sequence: intermediate[SEQ_STATE].len(),
+                                        quality: quality_length,
+                                    },
+                                );
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+
                 if state == QUAL_STATE {
                     if !skipped_plus {
                         if !partial {
@@ -210,23 +303,26 @@ impl SequencesReader {
                     // }
 
                     if !partial {
-                        Self::normalize_sequence(&mut intermediate[SEQ_STATE]);
-                        func(DnaSequence {
-                            ident_data: &intermediate[IDENT_STATE],
-                            seq: &intermediate[SEQ_STATE],
-                            // qual: if get_quality {
-                            //     Some(&intermediate[QUAL_STATE])
-                            // } else {
-                            //     None
-                            // },
-                            format: DnaSequencesFileType::FASTQ,
-                        });
+                        if !validate || validator.finish_record() {
+                            Self::normalize_sequence(&mut intermediate[SEQ_STATE]);
+                            func(DnaSequence {
+                                ident_data: &intermediate[IDENT_STATE],
+                                seq: &intermediate[SEQ_STATE],
+                                // qual: if get_quality {
+                                //     Some(&intermediate[QUAL_STATE])
+                                // } else {
+                                //     None
+                                // },
+                                format: DnaSequencesFileType::FASTQ,
+                            });
+                        }
 
                         intermediate[IDENT_STATE].clear();
                         intermediate[SEQ_STATE].clear();
                         intermediate[QUAL_STATE].clear();
 
                         skipped_plus = false;
+                        quality_length = 0;
                     }
                 } else {
                     intermediate[state].extend_from_slice(line);
@@ -240,3 +336,52 @@ impl SequencesReader {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::sequences_reader::SequencesReader;
+    use crate::sequences_validation::{InputValidationPolicy, ValidationErrorKind};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::path::{Path, PathBuf};
+
+    const MALFORMED_FASTQ: &[u8] = b"@r1\nACGT\n+\nIIII\n@r2\nACGTAC\n+\nIII\n@r3\nGGCC\n+\nIIII\n";
+
+    fn write_test_file(dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(format!("validation-{}.fq", name));
+        std::fs::write(&path, MALFORMED_FASTQ).unwrap();
+        path
+    }
+
+    fn read_sequences(reader: &mut SequencesReader, path: &PathBuf) -> Vec<Vec<u8>> {
+        let mut sequences = vec![];
+        reader.process_file_extended(path, |x| sequences.push(x.seq.to_vec()), None, false, false);
+        sequences
+    }
+
+    #[test]
+    fn malformed_fastq_record() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = write_test_file(temp_dir.path(), "skip");
+        let mut reader = SequencesReader::with_validation(InputValidationPolicy::SkipInvalid);
+        let sequences = read_sequences(&mut reader, &path);
+
+        assert_eq!(sequences, vec![b"ACGT".to_vec(), b"GGCC".to_vec()]);
+        let errors = reader.get_validation_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 8);
+        assert_eq!(
+            errors[0].kind,
+            ValidationErrorKind::QualityLengthMismatch {
+                sequence: 6,
+                quality: 3
+            }
+        );
+
+        let path = write_test_file(temp_dir.path(), "abort");
+        let mut reader = SequencesReader::with_validation(InputValidationPolicy::FailFast);
+        let result = catch_unwind(AssertUnwindSafe(|| read_sequences(&mut reader, &path)));
+
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.ends_with(":8: quality length 3 does not match sequence length 6"));
+    }
+}
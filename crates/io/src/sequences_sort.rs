@@ -0,0 +1,277 @@
+use crate::sequences_reader::SequencesReader;
+use bstr::ByteSlice;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use utils::spill_layout::create_run_dir;
+
+/// Unitig properties that can be used to sort the output
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnitigMetadata {
+    pub index: u64,
+    pub length: usize,
+    pub gc_count: usize,
+    /// Average kmers abundance (km:f field), if present
+    pub coverage: Option<f64>,
+    /// Number of color runs (C: fields) of the unitig
+    pub colors_count: usize,
+}
+
+impl UnitigMetadata {
+    pub fn from_record(header: &[u8], sequence: &[u8]) -> Self {
+        let header = header.strip_prefix(b">").unwrap_or(header);
+
+        let index = header
+            .split(|c| *c == b' ')
+            .next()
+            .and_then(|i| std::str::from_utf8(i).ok())
+            .and_then(|i| i.parse().ok())
+            .unwrap_or(0);
+
+        let coverage = header.find(b"km:f:").and_then(|pos| {
+            let value = &header[pos + 5..];
+            let end = value.find_byte(b' ').unwrap_or(value.len());
+            std::str::from_utf8(&value[..end]).ok()?.parse().ok()
+        });
+
+        Self {
+            index,
+            length: sequence.len(),
+            gc_count: sequence
+                .iter()
+                .filter(|b| matches!(b, b'G' | b'C' | b'g' | b'c'))
+                .count(),
+            coverage,
+            colors_count: header.find_iter(b" C:").count(),
+        }
+    }
+
+    pub fn gc_content(&self) -> f64 {
+        self.gc_count as f64 / self.length.max(1) as f64
+    }
+}
+
+/// Ordering of the output unitigs, ties keep the original order
+pub trait UnitigsComparator: Sync + Send {
+    fn compare(&self, a: &UnitigMetadata, b: &UnitigMetadata) -> Ordering;
+}
+
+impl<F: Fn(&UnitigMetadata, &UnitigMetadata) -> Ordering + Sync + Send> UnitigsComparator for F {
+    fn compare(&self, a: &UnitigMetadata, b: &UnitigMetadata) -> Ordering {
+        self(a, b)
+    }
+}
+
+pub fn by_length_desc(a: &UnitigMetadata, b: &UnitigMetadata) -> Ordering {
+    b.length.cmp(&a.length)
+}
+
+pub fn by_coverage_desc(a: &UnitigMetadata, b: &UnitigMetadata) -> Ordering {
+    b.coverage
+        .unwrap_or(0.0)
+        .total_cmp(&a.coverage.unwrap_or(0.0))
+}
+
+pub fn by_gc_content(a: &UnitigMetadata, b: &UnitigMetadata) -> Ordering {
+    a.gc_content().total_cmp(&b.gc_content())
+}
+
+pub fn by_colors_count_desc(a: &UnitigMetadata, b: &UnitigMetadata) -> Ordering {
+    b.colors_count.cmp(&a.colors_count)
+}
+
+struct UnitigRecord {
+    metadata: UnitigMetadata,
+    header: Vec<u8>,
+    sequence: Vec<u8>,
+}
+
+impl UnitigRecord {
+    fn new(header: &[u8], sequence: &[u8]) -> Self {
+        Self {
+            metadata: UnitigMetadata::from_record(header, sequence),
+            header: header.to_vec(),
+            sequence: sequence.to_vec(),
+        }
+    }
+
+    fn memory_size(&self) -> usize {
+        self.header.len() + self.sequence.len() + std::mem::size_of::<Self>()
+    }
+
+    fn write_fasta(&self, writer: &mut impl Write) {
+        writer.write_all(&self.header).unwrap();
+        writer.write_all(b"\n").unwrap();
+        writer.write_all(&self.sequence).unwrap();
+        writer.write_all(b"\n").unwrap();
+    }
+
+    fn write_to_run(&self, writer: &mut impl Write) {
+        writer
+            .write_u32::<LittleEndian>(self.header.len() as u32)
+            .unwrap();
+        writer.write_all(&self.header).unwrap();
+        writer
+            .write_u32::<LittleEndian>(self.sequence.len() as u32)
+            .unwrap();
+        writer.write_all(&self.sequence).unwrap();
+    }
+
+    fn read_from_run(reader: &mut impl Read) -> Option<Self> {
+        let read_vec = |reader: &mut dyn Read| {
+            let len = reader.read_u32::<LittleEndian>().ok()? as usize;
+            let mut data = vec![0; len];
+            reader.read_exact(&mut data).unwrap();
+            Some(data)
+        };
+        let header = read_vec(reader)?;
+        let sequence = read_vec(reader).unwrap();
+        Some(Self {
+            metadata: UnitigMetadata::from_record(&header, &sequence),
+            header,
+            sequence,
+        })
+    }
+}
+
+/// Sorts the unitigs of a fasta file with an external merge sort, using at most max_memory bytes for each sorted run
+pub fn sort_unitigs_file(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    temp_dir: impl AsRef<Path>,
+    comparator: impl UnitigsComparator,
+    max_memory: usize,
+) {
+    let mut runs: Vec<PathBuf> = Vec::new();
+    let mut records = Vec::new();
+    let mut used_memory = 0;
+
+    // The runs are kept in their own directory, as other sorts can share the same temp dir
+    let runs_dir = create_run_dir(temp_dir, "unitigs_sort");
+
+    let write_run = |records: &mut Vec<UnitigRecord>, runs: &mut Vec<PathBuf>| {
+        records.sort_by(|a, b| comparator.compare(&a.metadata, &b.metadata));
+        let run_path = runs_dir.join(format!("unitigs-sort-run-{}.tmp", runs.len()));
+        let mut writer = BufWriter::new(File::create(&run_path).unwrap());
+        for record in records.drain(..) {
+            record.write_to_run(&mut writer);
+        }
+        writer.flush().unwrap();
+        runs.push(run_path);
+    };
+
+    SequencesReader::new().process_file_extended(
+        input,
+        |sequence| {
+            let record = UnitigRecord::new(sequence.ident_data, sequence.seq);
+            used_memory += record.memory_size();
+            records.push(record);
+            if used_memory >= max_memory {
+                write_run(&mut records, &mut runs);
+                used_memory = 0;
+            }
+        },
+        None,
+        true,
+        false,
+    );
+
+    let mut writer = BufWriter::new(File::create(output).unwrap());
+
+    if runs.is_empty() {
+        // Everything fits in memory, no merge is needed
+        records.sort_by(|a, b| comparator.compare(&a.metadata, &b.metadata));
+        for record in records {
+            record.write_fasta(&mut writer);
+        }
+    } else {
+        if !records.is_empty() {
+            write_run(&mut records, &mut runs);
+        }
+
+        let mut readers: Vec<_> = runs
+            .iter()
+            .map(|run| BufReader::new(File::open(run).unwrap()))
+            .collect();
+        let mut heads: Vec<_> = readers
+            .iter_mut()
+            .map(|reader| UnitigRecord::read_from_run(reader))
+            .collect();
+
+        loop {
+            // The first run wins the ties, keeping the sort stable
+            let mut best: Option<usize> = None;
+            for (index, head) in heads.iter().enumerate() {
+                if let Some(head) = head {
+                    if best.map_or(true, |best| {
+                        comparator.compare(&head.metadata, &heads[best].as_ref().unwrap().metadata)
+                            == Ordering::Less
+                    }) {
+                        best = Some(index);
+                    }
+                }
+            }
+
+            let best = match best {
+                None => break,
+                Some(best) => best,
+            };
+
+            heads[best].take().unwrap().write_fasta(&mut writer);
+            heads[best] = UnitigRecord::read_from_run(&mut readers[best]);
+        }
+    }
+
+    writer.flush().unwrap();
+    let _ = std::fs::remove_dir_all(&runs_dir);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sequences_reader::SequencesReader;
+    use crate::sequences_sort::{by_coverage_desc, sort_unitigs_file, UnitigMetadata};
+
+    #[test]
+    fn sort_by_coverage_desc() {
+        let test_dir = tempfile::tempdir().unwrap();
+        let temp_dir = test_dir.path();
+
+        let coverages = [3.0, 12.5, 1.0, 7.0, 12.5, 40.0, 2.5, 7.0, 0.5, 9.0];
+        let mut fasta = String::new();
+        for (index, coverage) in coverages.iter().enumerate() {
+            fasta.push_str(&format!(
+                ">{} LN:i:4 KC:i:4 km:f:{:.1}\nACGT\n",
+                index, coverage
+            ));
+        }
+
+        let input = temp_dir.join("input.fa");
+        let output = temp_dir.join("output.fa");
+        std::fs::write(&input, fasta).unwrap();
+
+        // Small memory limit to force multiple sorted runs
+        sort_unitigs_file(&input, &output, temp_dir, by_coverage_desc, 200);
+
+        let mut sorted = vec![];
+        SequencesReader::new().process_file_extended(
+            &output,
+            |x| sorted.push(UnitigMetadata::from_record(x.ident_data, x.seq)),
+            None,
+            true,
+            false,
+        );
+
+        assert_eq!(sorted.len(), coverages.len());
+        assert!(sorted
+            .windows(2)
+            .all(|w| w[0].coverage.unwrap() >= w[1].coverage.unwrap()));
+        // Equal coverages keep the original order
+        let indexes: Vec<_> = sorted.iter().map(|m| m.index).collect();
+        assert_eq!(indexes, vec![5, 1, 4, 9, 3, 7, 0, 6, 2, 8]);
+
+        // The sorted runs are removed with their directory
+        assert_eq!(std::fs::read_dir(temp_dir).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::sequences_reader::{DnaSequence, DnaSequencesFileType, SequencesReader};
+use crate::sequences_stream::general::{DynamicSequencesStream, GeneralSequenceBlockData};
+use crate::sequences_stream::SequenceInfo;
+use config::ColorIndexType;
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+pub type SequencesIterator = Box<dyn Iterator<Item = (Vec<u8>, Vec<u8>)> + Send>;
+
+/// Stream of (header, bases) pairs produced in memory, that are bucketed without being written to a file first.
+/// The bases are normalized like the ones read from files. The iterator is consumed by the first read of the block, as it cannot be restarted
+pub struct IteratorSequencesStream {
+    sequences: Mutex<Option<SequencesIterator>>,
+    color: Option<ColorIndexType>,
+    estimated_bases_count: u64,
+}
+
+impl IteratorSequencesStream {
+    /// The estimated bases count is only used to balance the reading threads and to choose the buckets count
+    pub fn new_block(
+        sequences: impl Iterator<Item = (Vec<u8>, Vec<u8>)> + Send + 'static,
+        color: Option<ColorIndexType>,
+        estimated_bases_count: u64,
+    ) -> GeneralSequenceBlockData {
+        GeneralSequenceBlockData::Dynamic((
+            Arc::new(Self {
+                sequences: Mutex::new(Some(Box::new(sequences))),
+                color,
+                estimated_bases_count,
+            }),
+            0,
+        ))
+    }
+}
+
+impl DynamicSequencesStream for IteratorSequencesStream {
+    fn read_block(
+        &self,
+        _block: usize,
+        copy_ident_data: bool,
+        _partial_read_copyback: Option<usize>,
+        callback: &mut dyn FnMut(DnaSequence, SequenceInfo),
+    ) {
+        let Some(sequences) = self.sequences.lock().take() else {
+            return;
+        };
+
+        for (header, mut sequence) in sequences {
+            SequencesReader::normalize_sequence(&mut sequence);
+            callback(
+                DnaSequence {
+                    ident_data: if copy_ident_data { &header } else { &[] },
+                    seq: &sequence,
+                    format: DnaSequencesFileType::FASTA,
+                },
+                SequenceInfo { color: self.color },
+            );
+        }
+    }
+
+    fn estimated_base_count(&self, _block: usize) -> u64 {
+        self.estimated_bases_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sequences_stream::general::{GeneralSequenceBlockData, GeneralSequencesStream};
+    use crate::sequences_stream::iterator::IteratorSequencesStream;
+    use crate::sequences_stream::GenericSequencesStream;
+
+    #[test]
+    fn iterator_stream_reads_once() {
+        let reads = vec![
+            (b">r0".to_vec(), b"ACGTACGT".to_vec()),
+            (b">r1".to_vec(), b"GGATTACA".to_vec()),
+        ];
+        let block = IteratorSequencesStream::new_block(reads.clone().into_iter(), Some(3), 16);
+        assert!(matches!(block, GeneralSequenceBlockData::Dynamic(_)));
+        assert_eq!(block.estimated_bases_count(), 16);
+
+        let mut stream = GeneralSequencesStream::new();
+        let mut read_back = Vec::new();
+        stream.read_block(&block, true, None, |sequence, info| {
+            assert_eq!(info.color, Some(3));
+            read_back.push((sequence.ident_data.to_vec(), sequence.seq.to_vec()));
+        });
+        assert_eq!(read_back, reads);
+
+        let mut reads_count = 0;
+        stream.read_block(&block, true, None, |_, _| reads_count += 1);
+        assert_eq!(reads_count, 0);
+    }
+
+    #[test]
+    fn iterator_stream_normalizes_bases() {
+        let block = IteratorSequencesStream::new_block(
+            vec![(b">r0".to_vec(), b"acgtRYacGT-".to_vec())].into_iter(),
+            None,
+            11,
+        );
+
+        let mut read_back = Vec::new();
+        GeneralSequencesStream::new().read_block(&block, false, None, |sequence, _| {
+            read_back.push(sequence.seq.to_vec());
+        });
+        assert_eq!(read_back, vec![b"ACGTNNACGTN".to_vec()]);
+    }
+}
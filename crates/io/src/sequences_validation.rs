@@ -0,0 +1,159 @@
+use config::{INPUT_VALIDATION_MAX_REPORTED_ERRORS, INPUT_VALIDATION_MODE};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Policy applied to the input records that fail the validation
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+pub enum InputValidationPolicy {
+    /// No validation is done
+    #[default]
+    Disabled,
+    /// Abort the run at the first invalid record
+    FailFast,
+    /// Report and skip the invalid records
+    SkipInvalid,
+}
+
+impl InputValidationPolicy {
+    pub const fn to_mode(self) -> u8 {
+        match self {
+            InputValidationPolicy::Disabled => 0,
+            InputValidationPolicy::FailFast => 1,
+            InputValidationPolicy::SkipInvalid => 2,
+        }
+    }
+
+    /// Returns the policy selected for the current run
+    pub fn current() -> Self {
+        match INPUT_VALIDATION_MODE.load(Ordering::Relaxed) {
+            0 => InputValidationPolicy::Disabled,
+            1 => InputValidationPolicy::FailFast,
+            _ => InputValidationPolicy::SkipInvalid,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ValidationErrorKind {
+    MissingIdentMarker,
+    MissingSeparator,
+    InvalidBase(u8),
+    QualityLengthMismatch { sequence: usize, quality: usize },
+    TruncatedRecord,
+}
+
+impl Display for ValidationErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationErrorKind::MissingIdentMarker => write!(f, "missing record header marker"),
+            ValidationErrorKind::MissingSeparator => write!(f, "missing '+' separator line"),
+            ValidationErrorKind::InvalidBase(base) => {
+                write!(f, "invalid base '{}'", base.escape_ascii())
+            }
+            ValidationErrorKind::QualityLengthMismatch { sequence, quality } => write!(
+                f,
+                "quality length {} does not match sequence length {}",
+                quality, sequence
+            ),
+            ValidationErrorKind::TruncatedRecord => write!(f, "truncated record"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValidationError {
+    pub file: PathBuf,
+    pub line: u64,
+    pub kind: ValidationErrorKind,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.file.display(), self.line, self.kind)
+    }
+}
+
+const VALID_BASES: [bool; 256] = {
+    let mut lookup = [false; 256];
+    let bases = b"ACGTNRYSWKMBDHVacgtnryswkmbdhv";
+    let mut i = 0;
+    while i < bases.len() {
+        lookup[bases[i] as usize] = true;
+        i += 1;
+    }
+    lookup
+};
+
+/// Errors already reported by all the readers, used to limit the output to the first errors
+static REPORTED_ERRORS: AtomicUsize = AtomicUsize::new(0);
+
+/// Checks the structure and the alphabet of the records while they are parsed
+pub struct SequencesValidator {
+    policy: InputValidationPolicy,
+    record_error: Option<ValidationError>,
+    errors: Vec<ValidationError>,
+}
+
+impl SequencesValidator {
+    pub fn new(policy: InputValidationPolicy) -> Self {
+        Self {
+            policy,
+            record_error: None,
+            errors: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_enabled(&self) -> bool {
+        self.policy != InputValidationPolicy::Disabled
+    }
+
+    /// Marks the current record as invalid, only the first error of each record is kept
+    pub fn add_error(&mut self, file: &Path, line: u64, kind: ValidationErrorKind) {
+        if self.record_error.is_none() {
+            let error = ValidationError {
+                file: file.to_path_buf(),
+                line,
+                kind,
+            };
+            if self.policy == InputValidationPolicy::FailFast {
+                panic!("Invalid input record at {}", error);
+            }
+            self.record_error = Some(error);
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_record_valid(&self) -> bool {
+        self.record_error.is_none()
+    }
+
+    #[inline]
+    pub fn check_bases(&mut self, file: &Path, line: u64, bases: &[u8]) {
+        if let Some(base) = bases.iter().find(|b| !VALID_BASES[**b as usize]) {
+            self.add_error(file, line, ValidationErrorKind::InvalidBase(*base));
+        }
+    }
+
+    /// Ends the current record, returning true if it should be processed
+    pub fn finish_record(&mut self) -> bool {
+        let error = match self.record_error.take() {
+            None => return true,
+            Some(error) => error,
+        };
+
+        let max_reported = INPUT_VALIDATION_MAX_REPORTED_ERRORS.load(Ordering::Relaxed);
+        if REPORTED_ERRORS.fetch_add(1, Ordering::Relaxed) < max_reported {
+            println!("Warning: skipping invalid input record at {}", error);
+        }
+        if self.errors.len() < max_reported {
+            self.errors.push(error);
+        }
+        false
+    }
+
+    pub fn get_errors(&self) -> &[ValidationError] {
+        &self.errors
+    }
+}
@@ -0,0 +1,155 @@
+use config::DEFAULT_PREFETCH_AMOUNT;
+use parking_lot::Mutex;
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Reader parameters that can be changed for each bucket
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct KmersTransformTuning {
+    pub prefetch_amount: Option<usize>,
+    /// Multiplier of the packets allocated for each reader task
+    pub packets_pool_factor: f64,
+}
+
+impl Default for KmersTransformTuning {
+    fn default() -> Self {
+        Self {
+            prefetch_amount: DEFAULT_PREFETCH_AMOUNT,
+            packets_pool_factor: 1.0,
+        }
+    }
+}
+
+impl KmersTransformTuning {
+    pub fn default_candidates() -> Vec<Self> {
+        vec![
+            Self::default(),
+            Self {
+                prefetch_amount: DEFAULT_PREFETCH_AMOUNT.map(|p| p * 4),
+                packets_pool_factor: 2.0,
+            },
+            Self {
+                prefetch_amount: DEFAULT_PREFETCH_AMOUNT.map(|p| (p / 4).max(1)),
+                packets_pool_factor: 0.5,
+            },
+        ]
+    }
+}
+
+#[derive(Clone, Default)]
+struct CandidateStats {
+    work: u64,
+    elapsed: Duration,
+    runs: usize,
+}
+
+impl CandidateStats {
+    fn throughput(&self) -> f64 {
+        self.work as f64 / self.elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
+
+struct AutoTunerState {
+    stats: Vec<CandidateStats>,
+    next_candidate: usize,
+    selected: Option<usize>,
+}
+
+/// Tries each candidate configuration on the first buckets (warmup), measuring the throughput,
+/// and then selects the best one for the remainder of the run
+pub struct AutoTuner<C: Clone + Debug> {
+    candidates: Vec<C>,
+    warmup_runs: usize,
+    state: Mutex<AutoTunerState>,
+}
+
+impl<C: Clone + Debug> AutoTuner<C> {
+    pub fn new(candidates: Vec<C>, warmup_runs: usize) -> Self {
+        assert!(candidates.len() > 0);
+        Self {
+            state: Mutex::new(AutoTunerState {
+                stats: vec![CandidateStats::default(); candidates.len()],
+                next_candidate: 0,
+                selected: None,
+            }),
+            candidates,
+            warmup_runs,
+        }
+    }
+
+    /// Returns the configuration to be used for the next run, with its index
+    pub fn next_config(&self) -> (usize, C) {
+        let mut state = self.state.lock();
+        let index = match state.selected {
+            Some(selected) => selected,
+            None => {
+                let index = state.next_candidate;
+                state.next_candidate = (index + 1) % self.candidates.len();
+                index
+            }
+        };
+        (index, self.candidates[index].clone())
+    }
+
+    /// Records the amount of work done in a run and its duration
+    pub fn record(&self, index: usize, work: u64, elapsed: Duration) {
+        let mut state = self.state.lock();
+        if state.selected.is_some() {
+            return;
+        }
+
+        let stats = &mut state.stats[index];
+        stats.work += work;
+        stats.elapsed += elapsed;
+        stats.runs += 1;
+
+        if state.stats.iter().all(|s| s.runs >= self.warmup_runs) {
+            let best = (0..self.candidates.len()).max_by(|a, b| {
+                state.stats[*a]
+                    .throughput()
+                    .total_cmp(&state.stats[*b].throughput())
+            });
+            state.selected = best;
+        }
+    }
+
+    pub fn get_selected(&self) -> Option<C> {
+        self.state
+            .lock()
+            .selected
+            .map(|index| self.candidates[index].clone())
+    }
+
+    pub fn print_summary(&self) {
+        match self.get_selected() {
+            Some(selected) => println!("Auto-tuned parameters: {:?}", selected),
+            None => println!("Auto-tuning not completed, too few buckets for the warmup"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::auto_tuner::AutoTuner;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn selects_faster_configuration() {
+        // The configurations are the time spent for each unit of work
+        let tuner = AutoTuner::new(
+            vec![Duration::from_millis(4), Duration::from_micros(200)],
+            2,
+        );
+
+        for _ in 0..10 {
+            let (index, unit_time) = tuner.next_config();
+            let start = Instant::now();
+            let work = 5;
+            std::thread::sleep(unit_time * work);
+            tuner.record(index, work as u64, start.elapsed());
+        }
+
+        assert_eq!(tuner.get_selected(), Some(Duration::from_micros(200)));
+        assert_eq!(tuner.next_config().0, 1);
+    }
+}
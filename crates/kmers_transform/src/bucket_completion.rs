@@ -0,0 +1,101 @@
+use config::BucketIndexType;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+
+/// Tracks the pending work of each input bucket, including the sub-buckets created by
+/// resplitting or rewriting it, to detect when an input bucket is fully processed.
+/// Every file or executor address holding data of a bucket holds a pending unit for it
+pub struct BucketCompletionTracker {
+    pending: Mutex<HashMap<BucketIndexType, usize>>,
+    /// Buckets completed outside of a map processor, waiting to be notified by one
+    completed: Mutex<Vec<BucketIndexType>>,
+    /// All the buckets completed so far
+    done: Mutex<Vec<BucketIndexType>>,
+}
+
+impl BucketCompletionTracker {
+    pub fn new() -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+            completed: Mutex::new(Vec::new()),
+            done: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn acquire(&self, bucket: BucketIndexType, count: usize) {
+        if count > 0 {
+            *self.pending.lock().entry(bucket).or_insert(0) += count;
+        }
+    }
+
+    /// Releases a pending unit, returns true if it was the last one of the bucket
+    pub fn release(&self, bucket: BucketIndexType) -> bool {
+        let mut pending = self.pending.lock();
+        let count = pending
+            .get_mut(&bucket)
+            .expect("Released a bucket without pending work");
+        *count -= 1;
+        if *count == 0 {
+            pending.remove(&bucket);
+            self.done.lock().push(bucket);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Releases a pending unit, deferring the completion notification to a map processor
+    pub fn release_deferred(&self, bucket: BucketIndexType) {
+        if self.release(bucket) {
+            self.completed.lock().push(bucket);
+        }
+    }
+
+    pub fn take_completed(&self) -> Vec<BucketIndexType> {
+        std::mem::take(&mut *self.completed.lock())
+    }
+
+    /// Buckets with all their work completed, in completion order
+    pub fn into_done(self) -> Vec<BucketIndexType> {
+        self.done.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bucket_completion::BucketCompletionTracker;
+
+    #[test]
+    fn bucket_completed_once() {
+        let tracker = BucketCompletionTracker::new();
+
+        // Main bucket read into two processors and a resplitter
+        tracker.acquire(3, 1);
+        tracker.acquire(3, 3);
+        tracker.release_deferred(3);
+        assert!(!tracker.release(3));
+
+        // The resplitter creates two sub-buckets, each read into a processor
+        tracker.acquire(3, 2);
+        tracker.release_deferred(3);
+        for _ in 0..2 {
+            tracker.acquire(3, 1);
+            tracker.release_deferred(3);
+        }
+        assert!(!tracker.release(3));
+        assert!(!tracker.release(3));
+        assert!(tracker.release(3));
+        assert!(tracker.take_completed().is_empty());
+
+        // Empty bucket completed by its reader
+        tracker.acquire(5, 1);
+        tracker.release_deferred(5);
+        assert_eq!(tracker.take_completed(), vec![5]);
+        assert!(tracker.take_completed().is_empty());
+
+        // Started but not completed
+        tracker.acquire(7, 2);
+        tracker.release(7);
+        assert_eq!(tracker.into_done(), vec![3, 5]);
+    }
+}
@@ -0,0 +1,123 @@
+use parking_lot::Mutex;
+use std::any::Any;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+
+/// Policy applied when the processing of a single bucket fails
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+pub enum ContinueOrAbort {
+    /// Log the failing bucket, skip it and continue with the other ones
+    Continue,
+    /// Abort the whole run
+    #[default]
+    Abort,
+}
+
+#[derive(Clone, Debug)]
+pub struct FailedBucket {
+    pub bucket_path: PathBuf,
+    pub sub_bucket: usize,
+    pub message: String,
+}
+
+pub struct BucketErrorsTracker {
+    policy: ContinueOrAbort,
+    failed_buckets: Mutex<Vec<FailedBucket>>,
+}
+
+impl BucketErrorsTracker {
+    pub fn new(policy: ContinueOrAbort) -> Self {
+        Self {
+            policy,
+            failed_buckets: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn panic_message(payload: &Box<dyn Any + Send>) -> String {
+        if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown error".to_string()
+        }
+    }
+
+    /// Runs a step of the processing of a bucket, returning None if it failed and the policy allows to continue
+    pub fn run_guarded<T>(
+        &self,
+        bucket_path: &PathBuf,
+        sub_bucket: usize,
+        function: impl FnOnce() -> T,
+    ) -> Option<T> {
+        match self.policy {
+            ContinueOrAbort::Abort => Some(function()),
+            ContinueOrAbort::Continue => match catch_unwind(AssertUnwindSafe(function)) {
+                Ok(result) => Some(result),
+                Err(payload) => {
+                    let message = Self::panic_message(&payload);
+                    println!(
+                        "Warning: skipping bucket {} (sub-bucket {}) after error: {}",
+                        bucket_path.display(),
+                        sub_bucket,
+                        message
+                    );
+                    self.failed_buckets.lock().push(FailedBucket {
+                        bucket_path: bucket_path.clone(),
+                        sub_bucket,
+                        message,
+                    });
+                    None
+                }
+            },
+        }
+    }
+
+    pub fn get_failed_buckets(&self) -> Vec<FailedBucket> {
+        self.failed_buckets.lock().clone()
+    }
+
+    pub fn print_summary(&self) {
+        let failed_buckets = self.failed_buckets.lock();
+        if failed_buckets.len() > 0 {
+            println!("Failed buckets: {}", failed_buckets.len());
+            for failed in failed_buckets.iter() {
+                println!(
+                    "\t{} (sub-bucket {}): {}",
+                    failed.bucket_path.display(),
+                    failed.sub_bucket,
+                    failed.message
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bucket_errors::{BucketErrorsTracker, ContinueOrAbort};
+    use std::path::PathBuf;
+
+    #[test]
+    fn continue_after_bucket_failure() {
+        let tracker = BucketErrorsTracker::new(ContinueOrAbort::Continue);
+
+        let results: Vec<_> = (0..5)
+            .map(|bucket| {
+                tracker.run_guarded(&PathBuf::from(format!("bucket.{}", bucket)), 0, || {
+                    if bucket == 2 {
+                        panic!("Injected failure");
+                    }
+                    bucket
+                })
+            })
+            .collect();
+
+        assert_eq!(results, vec![Some(0), Some(1), None, Some(3), Some(4)]);
+
+        let failed = tracker.get_failed_buckets();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].bucket_path, PathBuf::from("bucket.2"));
+        assert_eq!(failed[0].message, "Injected failure");
+    }
+}
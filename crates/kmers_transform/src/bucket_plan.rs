@@ -0,0 +1,172 @@
+use crate::BucketsSchedulingOrder;
+use std::path::PathBuf;
+
+/// Why a bucket was placed at its position of the reading order
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BucketPlanDecision {
+    /// One of the smallest buckets, read first to estimate the unique kmers ratio
+    UniqueEstimator,
+    /// Largest remaining bucket, starting a new balancing group
+    LargestRemaining,
+    /// Smallest remaining bucket, added to balance the size of the last largest one
+    SmallestRemaining,
+    /// Placed by decreasing cost, with the largest first scheduling order
+    LargestFirst,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlannedBucket {
+    pub path: PathBuf,
+    pub file_size: usize,
+    pub decision: BucketPlanDecision,
+}
+
+/// Buckets in the order they will be read, with the current costs and scheduling order
+#[derive(Clone, Debug)]
+pub struct BucketsPlan {
+    pub buckets: Vec<PlannedBucket>,
+    pub unique_estimator_buckets: usize,
+    pub total_buckets_size: usize,
+}
+
+/// Orders the buckets, sorted by decreasing size, by first taking the smallest ones to estimate the
+/// unique kmers ratio and then alternating the largest remaining bucket with enough of the
+/// smallest ones to match its size. Returns the indices of the buckets in the reading order.
+/// It only depends on the sizes, so it can balance any list of files sorted by decreasing size
+pub fn interleave_buckets(
+    sorted_sizes: &[usize],
+    mut unique_estimator_buckets_count: usize,
+) -> Vec<(usize, BucketPlanDecision)> {
+    let mut buckets_order = Vec::with_capacity(sorted_sizes.len());
+    let mut start_idx = 0;
+    let mut end_idx = sorted_sizes.len();
+
+    let mut matched_size = 0i64;
+
+    while start_idx != end_idx && unique_estimator_buckets_count > 0 {
+        end_idx -= 1;
+        unique_estimator_buckets_count -= 1;
+        buckets_order.push((end_idx, BucketPlanDecision::UniqueEstimator));
+    }
+
+    while start_idx != end_idx {
+        if matched_size <= 0 {
+            matched_size = sorted_sizes[start_idx] as i64;
+            buckets_order.push((start_idx, BucketPlanDecision::LargestRemaining));
+            start_idx += 1;
+        } else {
+            matched_size -= sorted_sizes[end_idx - 1] as i64;
+            end_idx -= 1;
+            buckets_order.push((end_idx, BucketPlanDecision::SmallestRemaining));
+        }
+    }
+
+    buckets_order
+}
+
+/// Reading order of the buckets sorted by decreasing size with the given scheduling order.
+/// The unique kmers estimation buckets are always read first
+pub fn schedule_buckets(
+    sorted_sizes: &[usize],
+    unique_estimator_buckets_count: usize,
+    scheduling_order: BucketsSchedulingOrder,
+) -> Vec<(usize, BucketPlanDecision)> {
+    match scheduling_order {
+        BucketsSchedulingOrder::Interleaved => {
+            interleave_buckets(sorted_sizes, unique_estimator_buckets_count)
+        }
+        BucketsSchedulingOrder::LargestFirst => {
+            let scheduled_count =
+                sorted_sizes.len() - unique_estimator_buckets_count.min(sorted_sizes.len());
+            (scheduled_count..sorted_sizes.len())
+                .rev()
+                .map(|index| (index, BucketPlanDecision::UniqueEstimator))
+                .chain((0..scheduled_count).map(|index| (index, BucketPlanDecision::LargestFirst)))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bucket_plan::BucketPlanDecision::*;
+    use crate::bucket_plan::{interleave_buckets, schedule_buckets};
+    use crate::BucketsSchedulingOrder;
+
+    #[test]
+    fn interleaved_buckets_order() {
+        let sizes = [100, 60, 40, 30, 20, 10, 5];
+
+        assert_eq!(
+            interleave_buckets(&sizes, 1),
+            vec![
+                (6, UniqueEstimator),
+                (0, LargestRemaining),
+                (5, SmallestRemaining),
+                (4, SmallestRemaining),
+                (3, SmallestRemaining),
+                (2, SmallestRemaining),
+                (1, LargestRemaining),
+            ]
+        );
+
+        // Every bucket is planned exactly once, even when the estimators take all of them
+        let all_estimators = interleave_buckets(&sizes[..2], 4);
+        assert_eq!(
+            all_estimators,
+            vec![(1, UniqueEstimator), (0, UniqueEstimator)]
+        );
+        assert!(interleave_buckets(&[], 2).is_empty());
+    }
+
+    #[test]
+    fn interleaved_buckets_edge_sizes() {
+        // Equal sizes alternate a largest and a smallest bucket
+        assert_eq!(
+            interleave_buckets(&[10, 10, 10, 10], 0),
+            vec![
+                (0, LargestRemaining),
+                (3, SmallestRemaining),
+                (1, LargestRemaining),
+                (2, SmallestRemaining),
+            ]
+        );
+
+        // A single huge bucket is balanced by all the remaining ones
+        assert_eq!(
+            interleave_buckets(&[1000, 10, 10, 10], 1),
+            vec![
+                (3, UniqueEstimator),
+                (0, LargestRemaining),
+                (2, SmallestRemaining),
+                (1, SmallestRemaining),
+            ]
+        );
+    }
+
+    #[test]
+    fn scheduled_buckets_order() {
+        let sizes = [100, 60, 40, 30, 20, 10, 5];
+
+        assert_eq!(
+            schedule_buckets(&sizes, 1, BucketsSchedulingOrder::Interleaved),
+            interleave_buckets(&sizes, 1)
+        );
+        assert_eq!(
+            schedule_buckets(&sizes, 2, BucketsSchedulingOrder::LargestFirst),
+            vec![
+                (6, UniqueEstimator),
+                (5, UniqueEstimator),
+                (0, LargestFirst),
+                (1, LargestFirst),
+                (2, LargestFirst),
+                (3, LargestFirst),
+                (4, LargestFirst),
+            ]
+        );
+        assert_eq!(
+            schedule_buckets(&sizes[..2], 4, BucketsSchedulingOrder::LargestFirst),
+            vec![(1, UniqueEstimator), (0, UniqueEstimator)]
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use config::BucketIndexType;
+use std::fmt::{Display, Formatter};
+
+/// Largest buckets count whose indexes are all representable as a BucketIndexType.
+/// The second level buckets and the resplit sub-buckets have their own indexes, so they do not add to it
+pub const MAX_BUCKETS_COUNT: usize = BucketIndexType::MAX as usize + 1;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BucketsCountError {
+    Empty,
+    TooMany { buckets_count: usize },
+}
+
+impl Display for BucketsCountError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BucketsCountError::Empty => write!(f, "The buckets count must be greater than 0"),
+            BucketsCountError::TooMany { buckets_count } => write!(
+                f,
+                "Buckets count {} does not fit the buckets indexes, the maximum supported is {}",
+                buckets_count, MAX_BUCKETS_COUNT
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BucketsCountError {}
+
+/// Checks that every bucket index fits in a BucketIndexType, as they would otherwise wrap and route the reads to the wrong buckets
+pub fn validate_buckets_count(buckets_count: usize) -> Result<(), BucketsCountError> {
+    if buckets_count == 0 {
+        Err(BucketsCountError::Empty)
+    } else if buckets_count > MAX_BUCKETS_COUNT {
+        Err(BucketsCountError::TooMany { buckets_count })
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::buckets_count::{validate_buckets_count, BucketsCountError, MAX_BUCKETS_COUNT};
+
+    #[test]
+    fn buckets_count_bounds() {
+        assert_eq!(validate_buckets_count(0), Err(BucketsCountError::Empty));
+        assert_eq!(validate_buckets_count(1), Ok(()));
+        assert_eq!(validate_buckets_count(MAX_BUCKETS_COUNT), Ok(()));
+        assert_eq!(
+            validate_buckets_count(MAX_BUCKETS_COUNT + 1),
+            Err(BucketsCountError::TooMany {
+                buckets_count: MAX_BUCKETS_COUNT + 1
+            })
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use config::{DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, USE_SECOND_BUCKET};
+use io::compressed_read::CompressedRead;
+use io::concurrent::temp_reads::creads_utils::CompressedReadsBucketDataSerializer;
+use io::concurrent::temp_reads::extra_data::{
+    SequenceExtraDataConsecutiveCompression, SequenceExtraDataTempBufferManagement,
+};
+use parallel_processor::buckets::readers::async_binary_reader::{
+    AsyncBinaryReader, AsyncReaderThread,
+};
+use parallel_processor::memory_fs::RemoveFileMode;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+pub fn write_fasta_read(
+    writer: &mut impl Write,
+    name: impl Display,
+    read: &CompressedRead,
+    line_buffer: &mut Vec<u8>,
+) {
+    line_buffer.clear();
+    line_buffer.extend(read.as_bases_iter());
+    writeln!(writer, ">{}", name).unwrap();
+    writer.write_all(line_buffer).unwrap();
+    writer.write_all(b"\n").unwrap();
+}
+
+/// Decodes all the reads of a minimizer bucket and writes them as a fasta file inside the output directory,
+/// allowing external tools to run their own per-bucket algorithms
+pub fn export_bucket_reads<
+    E: SequenceExtraDataConsecutiveCompression,
+    FlagsCount: typenum::Unsigned,
+>(
+    bucket: PathBuf,
+    bucket_index: usize,
+    output_dir: &Path,
+) -> PathBuf {
+    if USE_SECOND_BUCKET.load(Ordering::Relaxed) {
+        export_bucket_reads_with_format::<E, FlagsCount, true>(bucket, bucket_index, output_dir)
+    } else {
+        export_bucket_reads_with_format::<E, FlagsCount, false>(bucket, bucket_index, output_dir)
+    }
+}
+
+fn export_bucket_reads_with_format<
+    E: SequenceExtraDataConsecutiveCompression,
+    FlagsCount: typenum::Unsigned,
+    const WITH_SECOND_BUCKET: bool,
+>(
+    bucket: PathBuf,
+    bucket_index: usize,
+    output_dir: &Path,
+) -> PathBuf {
+    let reader = AsyncBinaryReader::new(
+        &bucket,
+        true,
+        RemoveFileMode::Remove { remove_fs: false },
+        DEFAULT_PREFETCH_AMOUNT,
+    );
+
+    let reader_thread = AsyncReaderThread::new(DEFAULT_OUTPUT_BUFFER_SIZE, 4);
+
+    let output_path = output_dir.join(format!("bucket{}.fasta", bucket_index));
+    let mut writer = BufWriter::new(File::create(&output_path).unwrap());
+
+    let mut items_iterator = reader.get_items_stream::<CompressedReadsBucketDataSerializer<
+        E,
+        FlagsCount,
+        WITH_SECOND_BUCKET,
+    >>(reader_thread.clone(), Vec::new(), E::new_temp_buffer());
+
+    let mut line_buffer = Vec::new();
+    let mut read_index = 0;
+    while let Some((read_info, extra_buffer)) = items_iterator.next() {
+        write_fasta_read(
+            &mut writer,
+            format_args!("{}_{}", bucket_index, read_index),
+            &read_info.3,
+            &mut line_buffer,
+        );
+        read_index += 1;
+        E::clear_temp_buffer(extra_buffer);
+    }
+
+    writer.flush().unwrap();
+    output_path
+}
@@ -0,0 +1,238 @@
+use crate::buckets_count::{validate_buckets_count, BucketsCountError};
+use crate::progress::ProgressReporter;
+use crate::thread_split::{ThreadSplitError, ThreadSplitPolicy};
+use crate::{KmersTransform, KmersTransformExecutorFactory, KmersTransformExecutorsLimits};
+use minimizer_bucketing::counters_analyzer::CountersError;
+use parallel_processor::memory_data_size::MemoryDataSize;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::sync::Arc;
+use utils::kmer_length::KmerError;
+use utils::spill_layout::SpillLayoutError;
+
+#[derive(Debug)]
+pub enum KmersTransformBuildError {
+    MissingField(&'static str),
+    ThreadSplit(ThreadSplitError),
+    ZeroBucketsCount,
+    /// The buckets indexes do not fit the BucketIndexType
+    BucketsCount(BucketsCountError),
+    /// The counters file exists but cannot be used
+    Counters(CountersError),
+    /// The temp dir cannot name the spill files of the run
+    SpillLayout(SpillLayoutError),
+    /// The k-mers length is not supported
+    Kmer(KmerError),
+}
+
+impl Display for KmersTransformBuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KmersTransformBuildError::MissingField(field) => {
+                write!(f, "Missing required kmers transform field '{}'", field)
+            }
+            KmersTransformBuildError::ThreadSplit(err) => write!(f, "{}", err),
+            KmersTransformBuildError::ZeroBucketsCount => {
+                write!(f, "The buckets count must be greater than 0")
+            }
+            KmersTransformBuildError::BucketsCount(err) => write!(f, "{}", err),
+            KmersTransformBuildError::Counters(err) => write!(f, "{}", err),
+            KmersTransformBuildError::SpillLayout(err) => write!(f, "{}", err),
+            KmersTransformBuildError::Kmer(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for KmersTransformBuildError {}
+
+impl From<BucketsCountError> for KmersTransformBuildError {
+    fn from(err: BucketsCountError) -> Self {
+        match err {
+            BucketsCountError::Empty => KmersTransformBuildError::ZeroBucketsCount,
+            err => KmersTransformBuildError::BucketsCount(err),
+        }
+    }
+}
+
+/// Builds a KmersTransform from named parameters, checking that all the required ones are set
+pub struct KmersTransformBuilder<F: KmersTransformExecutorFactory> {
+    inputs: Option<Vec<PathBuf>>,
+    temp_dir: Option<PathBuf>,
+    counters_path: Option<PathBuf>,
+    buckets_count: Option<usize>,
+    global_data: Option<Arc<F::GlobalExtraData>>,
+    threads_count: Option<usize>,
+    k: Option<usize>,
+    min_bucket_size: u64,
+    max_memory: Option<MemoryDataSize>,
+    max_buffered_reads: Option<MemoryDataSize>,
+    thread_split: Option<ThreadSplitPolicy>,
+    progress_reporter: Option<Arc<dyn ProgressReporter>>,
+    spill_dirs: Vec<PathBuf>,
+    output_buffer_size: Option<MemoryDataSize>,
+    executors_limits: Option<KmersTransformExecutorsLimits>,
+}
+
+impl<F: KmersTransformExecutorFactory> Default for KmersTransformBuilder<F> {
+    fn default() -> Self {
+        Self {
+            inputs: None,
+            temp_dir: None,
+            counters_path: None,
+            buckets_count: None,
+            global_data: None,
+            threads_count: None,
+            k: None,
+            min_bucket_size: 0,
+            max_memory: None,
+            max_buffered_reads: None,
+            thread_split: None,
+            progress_reporter: None,
+            spill_dirs: Vec::new(),
+            output_buffer_size: None,
+            executors_limits: None,
+        }
+    }
+}
+
+impl<F: KmersTransformExecutorFactory> KmersTransformBuilder<F> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn inputs(mut self, inputs: Vec<PathBuf>) -> Self {
+        self.inputs = Some(inputs);
+        self
+    }
+
+    pub fn temp_dir(mut self, temp_dir: impl Into<PathBuf>) -> Self {
+        self.temp_dir = Some(temp_dir.into());
+        self
+    }
+
+    pub fn counters_path(mut self, counters_path: impl Into<PathBuf>) -> Self {
+        self.counters_path = Some(counters_path.into());
+        self
+    }
+
+    pub fn buckets_count(mut self, buckets_count: usize) -> Self {
+        self.buckets_count = Some(buckets_count);
+        self
+    }
+
+    pub fn global_data(mut self, global_data: Arc<F::GlobalExtraData>) -> Self {
+        self.global_data = Some(global_data);
+        self
+    }
+
+    pub fn threads_count(mut self, threads_count: usize) -> Self {
+        self.threads_count = Some(threads_count);
+        self
+    }
+
+    pub fn k(mut self, k: usize) -> Self {
+        self.k = Some(k);
+        self
+    }
+
+    /// Minimum kmers count of a sub-bucket, defaults to 0
+    pub fn min_bucket_size(mut self, min_bucket_size: u64) -> Self {
+        self.min_bucket_size = min_bucket_size;
+        self
+    }
+
+    pub fn max_memory(mut self, max_memory: MemoryDataSize) -> Self {
+        self.max_memory = Some(max_memory);
+        self
+    }
+
+    pub fn max_buffered_reads(mut self, max_buffered_reads: MemoryDataSize) -> Self {
+        self.max_buffered_reads = Some(max_buffered_reads);
+        self
+    }
+
+    pub fn thread_split(mut self, thread_split: ThreadSplitPolicy) -> Self {
+        self.thread_split = Some(thread_split);
+        self
+    }
+
+    pub fn progress_reporter(mut self, progress_reporter: Arc<dyn ProgressReporter>) -> Self {
+        self.progress_reporter = Some(progress_reporter);
+        self
+    }
+
+    /// Directories where the spilled sub-buckets are striped, in addition to the temp dir
+    pub fn spill_dirs(mut self, spill_dirs: Vec<PathBuf>) -> Self {
+        self.spill_dirs = spill_dirs;
+        self
+    }
+
+    /// Size of the per thread buffers of the written sub-buckets
+    pub fn output_buffer_size(mut self, output_buffer_size: MemoryDataSize) -> Self {
+        self.output_buffer_size = Some(output_buffer_size);
+        self
+    }
+
+    /// Maximum executors of each type, and of concurrent executors for a single bucket
+    pub fn executors_limits(mut self, executors_limits: KmersTransformExecutorsLimits) -> Self {
+        self.executors_limits = Some(executors_limits);
+        self
+    }
+
+    pub fn build(self) -> Result<KmersTransform<F>, KmersTransformBuildError> {
+        use KmersTransformBuildError::MissingField;
+
+        let inputs = self.inputs.ok_or(MissingField("inputs"))?;
+        let temp_dir = self.temp_dir.ok_or(MissingField("temp_dir"))?;
+        let counters_path = self.counters_path.ok_or(MissingField("counters_path"))?;
+        let buckets_count = self.buckets_count.ok_or(MissingField("buckets_count"))?;
+        validate_buckets_count(buckets_count)?;
+        let global_data = self.global_data.ok_or(MissingField("global_data"))?;
+        let threads_count = self.threads_count.ok_or(MissingField("threads_count"))?;
+        let k = self.k.ok_or(MissingField("k"))?;
+
+        // Validate before loading the counters, as they can be removed once loaded
+        if let Some(thread_split) = &self.thread_split {
+            thread_split
+                .split(threads_count)
+                .map_err(KmersTransformBuildError::ThreadSplit)?;
+        }
+
+        let mut transform = KmersTransform::try_new(
+            inputs,
+            &temp_dir,
+            counters_path,
+            buckets_count,
+            global_data,
+            threads_count,
+            k,
+            self.min_bucket_size,
+        )?;
+
+        if let Some(thread_split) = self.thread_split {
+            transform = transform
+                .with_thread_split(thread_split)
+                .map_err(KmersTransformBuildError::ThreadSplit)?;
+        }
+        if let Some(max_memory) = self.max_memory {
+            transform = transform.with_max_memory(max_memory);
+        }
+        if let Some(max_buffered_reads) = self.max_buffered_reads {
+            transform = transform.with_max_buffered_reads(max_buffered_reads);
+        }
+        if let Some(progress_reporter) = self.progress_reporter {
+            transform = transform.with_progress_reporter(progress_reporter);
+        }
+        if !self.spill_dirs.is_empty() {
+            transform = transform.with_spill_dirs(self.spill_dirs);
+        }
+        if let Some(output_buffer_size) = self.output_buffer_size {
+            transform = transform.with_output_buffer_size(output_buffer_size);
+        }
+        if let Some(executors_limits) = self.executors_limits {
+            transform = transform.with_executors_limits(executors_limits);
+        }
+
+        Ok(transform)
+    }
+}
@@ -0,0 +1,140 @@
+use minimizer_bucketing::counters_analyzer::BucketCounter;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Reads found in a bucket file, compared with the counters recorded for it when it was written
+pub struct BucketCountersCheck {
+    expected: Vec<u64>,
+    found: Vec<AtomicU64>,
+}
+
+#[derive(Debug)]
+pub struct CountersMismatch {
+    pub path: PathBuf,
+    /// Sub-bucket, expected and found reads of each mismatching sub-bucket
+    pub sub_buckets: Vec<(usize, u64, u64)>,
+}
+
+impl Display for CountersMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Bucket {} does not match its counters:",
+            self.path.display()
+        )?;
+        for (sub_bucket, expected, found) in &self.sub_buckets {
+            write!(
+                f,
+                " [sub-bucket {}: expected {} reads, found {}]",
+                sub_bucket, expected, found
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CountersMismatch {}
+
+impl BucketCountersCheck {
+    /// Checks each sub-bucket when the reads store their sub-bucket, otherwise only the total reads
+    pub fn new(counters: &[BucketCounter], per_sub_bucket: bool) -> Self {
+        let expected: Vec<_> = if per_sub_bucket {
+            counters.iter().map(|counter| counter.count).collect()
+        } else {
+            vec![counters.iter().map(|counter| counter.count).sum()]
+        };
+
+        Self {
+            found: expected.iter().map(|_| AtomicU64::new(0)).collect(),
+            expected,
+        }
+    }
+
+    /// Local tallies of a reader, to be added with add_found
+    pub fn new_tallies(&self) -> Vec<u64> {
+        vec![0; self.expected.len()]
+    }
+
+    #[inline(always)]
+    pub fn tally_read(tallies: &mut [u64], second_bucket: u8) {
+        let index = if tallies.len() > 1 {
+            second_bucket as usize
+        } else {
+            0
+        };
+        tallies[index] += 1;
+    }
+
+    pub fn add_found(&self, tallies: &[u64]) {
+        for (found, tally) in self.found.iter().zip(tallies) {
+            found.fetch_add(*tally, Ordering::Relaxed);
+        }
+    }
+
+    pub fn check(&self, path: &Path) -> Result<(), CountersMismatch> {
+        let sub_buckets: Vec<_> = self
+            .expected
+            .iter()
+            .zip(self.found.iter())
+            .enumerate()
+            .map(|(sub_bucket, (expected, found))| {
+                (sub_bucket, *expected, found.load(Ordering::Relaxed))
+            })
+            .filter(|(_, expected, found)| expected != found)
+            .collect();
+
+        if sub_buckets.is_empty() {
+            Ok(())
+        } else {
+            Err(CountersMismatch {
+                path: path.to_path_buf(),
+                sub_buckets,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::counters_check::BucketCountersCheck;
+    use minimizer_bucketing::counters_analyzer::BucketCounter;
+    use std::path::Path;
+
+    #[test]
+    fn counters_check_mismatch() {
+        let counters: Vec<_> = [2, 0, 1]
+            .into_iter()
+            .map(|count| BucketCounter { count })
+            .collect();
+        let path = Path::new("bucket.0");
+
+        let check = BucketCountersCheck::new(&counters, true);
+        let mut tallies = check.new_tallies();
+        for second_bucket in [0, 2, 0] {
+            BucketCountersCheck::tally_read(&mut tallies, second_bucket);
+        }
+        check.add_found(&tallies);
+        assert!(check.check(path).is_ok());
+
+        let check = BucketCountersCheck::new(&counters, true);
+        let mut tallies = check.new_tallies();
+        for second_bucket in [0, 1, 2] {
+            BucketCountersCheck::tally_read(&mut tallies, second_bucket);
+        }
+        check.add_found(&tallies);
+        assert_eq!(
+            check.check(path).unwrap_err().sub_buckets,
+            vec![(0, 2, 1), (1, 0, 1)]
+        );
+
+        // Without the stored sub-buckets only the total reads are compared
+        let check = BucketCountersCheck::new(&counters, false);
+        let mut tallies = check.new_tallies();
+        for second_bucket in [0, 1, 2] {
+            BucketCountersCheck::tally_read(&mut tallies, second_bucket);
+        }
+        check.add_found(&tallies);
+        assert!(check.check(path).is_ok());
+    }
+}
@@ -1,46 +1,58 @@
-use config::{
-    BucketIndexType, DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, READ_FLAG_INCL_END,
-    USE_SECOND_BUCKET,
-};
+use crate::get_sequence_bucket;
+use config::{DEFAULT_OUTPUT_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, USE_SECOND_BUCKET};
 use hashes::{
-    ExtendableHashTraitType, HashFunction, HashFunctionFactory, HashableSequence,
-    MinimizerHashFunctionFactory,
+    ExtendableHashTraitType, HashFunction, HashFunctionFactory, MinimizerHashFunctionFactory,
 };
-use io::compressed_read::CompressedRead;
 use io::concurrent::temp_reads::creads_utils::CompressedReadsBucketDataSerializer;
+use minimizer_bucketing::minimizer_hasher::MinimizerBucketHasher;
 use parallel_processor::buckets::readers::async_binary_reader::{
     AsyncBinaryReader, AsyncReaderThread,
 };
 use parallel_processor::memory_fs::RemoveFileMode;
 use std::collections::HashSet;
 use std::path::PathBuf;
-
-fn get_sequence_bucket<C, H: MinimizerHashFunctionFactory>(
+use std::sync::atomic::Ordering;
+
+/// Prints the sub-buckets statistics of a bucket, BH must be the hasher used to write it
+pub fn compute_stats_for_bucket<
+    H: MinimizerHashFunctionFactory,
+    MH: HashFunctionFactory,
+    BH: MinimizerBucketHasher,
+>(
+    bucket: PathBuf,
+    bucket_index: usize,
+    buckets_count: usize,
+    second_buckets_log_max: usize,
     k: usize,
     m: usize,
-    seq_data: &(u8, u8, C, CompressedRead),
-    used_hash_bits: usize,
-    bucket_bits_count: usize,
-) -> BucketIndexType {
-    let read = &seq_data.3;
-    let flags = seq_data.0;
-    let decr_val = ((read.bases_count() == k) && (flags & READ_FLAG_INCL_END) == 0) as usize;
-
-    let hashes = H::new(read.sub_slice((1 - decr_val)..(k - decr_val)), m);
-
-    let minimizer = hashes
-        .iter()
-        .min_by_key(|k| H::get_full_minimizer(k.to_unextendable()))
-        .unwrap();
-
-    H::get_bucket(
-        used_hash_bits,
-        bucket_bits_count,
-        minimizer.to_unextendable(),
-    )
+) {
+    if USE_SECOND_BUCKET.load(Ordering::Relaxed) {
+        compute_stats_for_bucket_with_format::<H, MH, BH, true>(
+            bucket,
+            bucket_index,
+            buckets_count,
+            second_buckets_log_max,
+            k,
+            m,
+        )
+    } else {
+        compute_stats_for_bucket_with_format::<H, MH, BH, false>(
+            bucket,
+            bucket_index,
+            buckets_count,
+            second_buckets_log_max,
+            k,
+            m,
+        )
+    }
 }
 
-pub fn compute_stats_for_bucket<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory>(
+fn compute_stats_for_bucket_with_format<
+    H: MinimizerHashFunctionFactory,
+    MH: HashFunctionFactory,
+    BH: MinimizerBucketHasher,
+    const WITH_SECOND_BUCKET: bool,
+>(
     bucket: PathBuf,
     bucket_index: usize,
     buckets_count: usize,
@@ -68,16 +80,17 @@ pub fn compute_stats_for_bucket<H: MinimizerHashFunctionFactory, MH: HashFunctio
     let mut items_iterator = reader.get_items_stream::<CompressedReadsBucketDataSerializer<
         (),
         typenum::U2,
-        { USE_SECOND_BUCKET },
+        WITH_SECOND_BUCKET,
     >>(reader_thread.clone(), Vec::new(), ());
 
     let mut total_counters = vec![0; second_buckets_max];
 
     while let Some((read_info, _)) = items_iterator.next() {
-        let orig_bucket = get_sequence_bucket::<(), H>(
+        let orig_bucket = get_sequence_bucket::<H, BH>(
             k,
             m,
-            &read_info,
+            &read_info.3,
+            read_info.0,
             buckets_count.ilog2() as usize,
             second_buckets_log_max,
         ) as usize;
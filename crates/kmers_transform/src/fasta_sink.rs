@@ -0,0 +1,104 @@
+use io::compressed_read::CompressedRead;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// Receives the sequences produced by a final executor while the maps are processed,
+/// allowing them to be streamed out without buffering. Object safe, so that an executor can own
+/// a boxed sink chosen by the caller
+pub trait SequencesSink: Send {
+    fn push(&mut self, header: &dyn Display, read: &CompressedRead) -> std::io::Result<()>;
+}
+
+/// Writes the sequences as fasta records, decoding the bases on the fly
+pub struct FastaSink<W: Write> {
+    writer: W,
+    /// Maximum bases on each line, None to write each sequence on a single line
+    line_width: Option<usize>,
+    line_buffer: Vec<u8>,
+}
+
+impl<W: Write> FastaSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            line_width: None,
+            line_buffer: Vec::new(),
+        }
+    }
+
+    pub fn with_line_width(mut self, line_width: usize) -> Self {
+        self.line_width = Some(line_width).filter(|width| *width > 0);
+        self
+    }
+
+    pub fn into_inner(mut self) -> std::io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl FastaSink<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write + Send> SequencesSink for FastaSink<W> {
+    fn push(&mut self, header: &dyn Display, read: &CompressedRead) -> std::io::Result<()> {
+        self.line_buffer.clear();
+        self.line_buffer.extend(read.as_bases_iter());
+
+        writeln!(self.writer, ">{}", header)?;
+        match self.line_width {
+            Some(line_width) => {
+                for line in self.line_buffer.chunks(line_width) {
+                    self.writer.write_all(line)?;
+                    self.writer.write_all(b"\n")?;
+                }
+            }
+            None => {
+                self.writer.write_all(&self.line_buffer)?;
+                self.writer.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fasta_sink::{FastaSink, SequencesSink};
+    use io::compressed_read::CompressedReadIndipendent;
+
+    #[test]
+    fn fasta_sink_line_wrapping() {
+        let mut storage = Vec::new();
+        let read = CompressedReadIndipendent::from_plain(b"ACGTTGCAAC", &mut storage);
+
+        let mut wrapped = FastaSink::new(Vec::new()).with_line_width(4);
+        wrapped.push(&0, &read.as_reference(&storage)).unwrap();
+        wrapped.push(&"last", &read.as_reference(&storage)).unwrap();
+        assert_eq!(
+            wrapped.into_inner().unwrap(),
+            b">0\nACGT\nTGCA\nAC\n>last\nACGT\nTGCA\nAC\n"
+        );
+
+        let mut single_line = FastaSink::new(Vec::new());
+        single_line.push(&1, &read.as_reference(&storage)).unwrap();
+        assert_eq!(single_line.into_inner().unwrap(), b">1\nACGTTGCAAC\n");
+
+        // Also usable as a trait object, writing to a file
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("fasta-sink");
+        let mut sink: Box<dyn SequencesSink> = Box::new(FastaSink::create(&path).unwrap());
+        sink.push(
+            &format_args!("{} len={}", 2, 10),
+            &read.as_reference(&storage),
+        )
+        .unwrap();
+        drop(sink);
+        assert_eq!(std::fs::read(&path).unwrap(), b">2 len=10\nACGTTGCAAC\n");
+    }
+}
@@ -1,39 +1,95 @@
 mod reader;
 
+use crate::auto_tuner::{AutoTuner, KmersTransformTuning};
+use crate::bucket_completion::BucketCompletionTracker;
+use crate::bucket_errors::{BucketErrorsTracker, ContinueOrAbort};
+use crate::bucket_plan::{schedule_buckets, BucketPlanDecision, BucketsPlan, PlannedBucket};
+use crate::buckets_count::validate_buckets_count;
+use crate::builder::KmersTransformBuildError;
+use crate::open_buckets::OpenBucketsLimit;
 use crate::processor::KmersTransformProcessor;
+use crate::processors_admission::ProcessorsAdmissionMode;
+use crate::processors_memory::ProcessorsMemoryLimit;
+use crate::progress::{
+    detect_buckets_skew, report_progress, BucketsSkew, DefaultProgressReporter, ProcessedBuckets,
+    ProgressReporter, TotalBuckets,
+};
+use crate::read_throughput::ReadThroughput;
 use crate::reader::{InputBucketDesc, KmersTransformReader};
+use crate::reads_backpressure::ReadsBytesLimit;
 use crate::resplitter::KmersTransformResplitter;
+use crate::resume_manifest::ResumeManifest;
+use crate::thread_split::{ThreadSplitError, ThreadSplitPolicy};
 use config::{
-    BucketIndexType, KEEP_FILES, KMERS_TRANSFORM_READS_CHUNKS_SIZE, MAXIMUM_JIT_PROCESSED_BUCKETS,
-    MAXIMUM_SECOND_BUCKETS_COUNT, MINIMUM_LOG_DELTA_TIME, PACKETS_PRIORITY_FILES,
+    get_per_cpu_buffer_size, BucketIndexType, AUTO_TUNE_KMERS_TRANSFORM, AUTO_TUNE_WARMUP_BUCKETS,
+    DEFAULT_OUTPUT_BUFFER_SIZE, KEEP_FILES, KMERS_TRANSFORM_MAX_PROCESSORS,
+    KMERS_TRANSFORM_MAX_READERS, KMERS_TRANSFORM_MAX_RESPLITTERS,
+    KMERS_TRANSFORM_READS_CHUNKS_SIZE, MAXIMUM_JIT_PROCESSED_BUCKETS, MAXIMUM_SECOND_BUCKETS_COUNT,
+    MAX_INTERMEDIATE_MAP_SIZE, MINIMUM_LOG_DELTA_TIME, PACKETS_PRIORITY_FILES,
+    PROCESSORS_MEMORY_LIMIT, READ_FLAG_INCL_END, SEQUENTIAL_KMERS_TRANSFORM, USE_SECOND_BUCKET,
+    VERIFY_BUCKETS_COUNTERS,
+};
+use hashes::{
+    ExtendableHashTraitType, HashFunction, HashableSequence, MinimizerHashFunctionFactory,
 };
 use io::compressed_read::{CompressedRead, CompressedReadIndipendent};
 use io::concurrent::temp_reads::extra_data::{
     SequenceExtraDataConsecutiveCompression, SequenceExtraDataTempBufferManagement,
 };
 use io::get_bucket_index;
-use minimizer_bucketing::counters_analyzer::CountersAnalyzer;
-use minimizer_bucketing::MinimizerBucketingExecutorFactory;
+use minimizer_bucketing::counters_analyzer::{BucketCounter, CountersAnalyzer, CountersError};
+use minimizer_bucketing::minimizer_hasher::MinimizerBucketHasher;
+use minimizer_bucketing::{MinimizerBucketingExecutorFactory, MinimizerBucketingHasher};
 use parallel_processor::execution_manager::execution_context::{ExecutionContext, PoolAllocMode};
 use parallel_processor::execution_manager::memory_tracker::MemoryTracker;
 use parallel_processor::execution_manager::objects_pool::PoolObjectTrait;
 use parallel_processor::execution_manager::packet::{Packet, PacketTrait};
 use parallel_processor::execution_manager::thread_pool::ExecThreadPool;
 use parallel_processor::execution_manager::units_io::{ExecutorInput, ExecutorInputAddressMode};
+use parallel_processor::memory_data_size::MemoryDataSize;
 use parallel_processor::memory_fs::MemoryFs;
 use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use parking_lot::Mutex;
-use std::cmp::{max, min};
+use std::cmp::{max, min, Reverse};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 use std::marker::PhantomData;
+use std::ops::AddAssign;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-
+use utils::cancellation::CancellationToken;
+use utils::kmer_length::validate_k;
+use utils::open_files::default_max_open_files;
+use utils::spill_layout::SpillLayout;
+
+pub mod auto_tuner;
+mod bucket_completion;
+pub mod bucket_errors;
+pub mod bucket_plan;
+pub mod buckets_count;
+pub mod buckets_export;
+pub mod builder;
+mod counters_check;
 pub mod debug_bucket_stats;
+pub mod fasta_sink;
+mod open_buckets;
 pub mod processor;
+pub mod processors_admission;
+pub mod processors_memory;
+pub mod progress;
+pub mod read_throughput;
+mod reads_backpressure;
 mod reads_buffer;
 mod resplitter;
+pub mod resume_manifest;
+pub mod small_buckets;
+pub mod thread_split;
+
+/// Bucket hasher used to resplit the buckets of a kmers transform factory
+pub type KmersTransformBucketHasher<F> =
+    MinimizerBucketingHasher<<F as KmersTransformExecutorFactory>::SequencesResplitterFactory>;
 
 pub trait KmersTransformExecutorFactory: Sized + 'static + Sync + Send {
     type SequencesResplitterFactory: MinimizerBucketingExecutorFactory<
@@ -53,6 +109,19 @@ pub trait KmersTransformExecutorFactory: Sized + 'static + Sync + Send {
 
     const HAS_COLORS: bool;
 
+    /// Minimum occurrences of a kmer for the final executor to emit it, 1 keeps all the kmers.
+    /// The threshold can be checked on each map independently, as all the occurrences of a kmer
+    /// share its minimizer and are always assigned to the same bucket and resplit sub-bucket
+    fn min_multiplicity(_global_data: &Self::GlobalExtraData) -> u64 {
+        1
+    }
+
+    /// Maximum occurrences of a kmer for the final executor to emit it, inclusive.
+    /// None keeps all the kmers, without checking their multiplicity
+    fn max_multiplicity(_global_data: &Self::GlobalExtraData) -> Option<u64> {
+        None
+    }
+
     fn new_resplitter(
         global_data: &Arc<Self::GlobalExtraData>,
     ) -> <Self::SequencesResplitterFactory as MinimizerBucketingExecutorFactory>::ExecutorType;
@@ -77,6 +146,35 @@ pub trait KmersTransformPreprocessor<F: KmersTransformExecutorFactory>:
     ) -> BucketIndexType;
 }
 
+/// Bucket of a read of the minimizer buckets, from the minimizer shared by its (k-1)-mers.
+/// flags are the READ_FLAG_* stored with the read, and BH must be the hasher used to write the buckets.
+/// With used_hash_bits set to 0 and bucket_bits_count to the log2 of the buckets count it returns
+/// the minimizer bucket of the read. With used_hash_bits set to the log2 of the buckets count it
+/// returns its sub-bucket instead, using up to MAXIMUM_SECOND_BUCKETS_LOG bits
+pub fn get_sequence_bucket<H: MinimizerHashFunctionFactory, BH: MinimizerBucketHasher>(
+    k: usize,
+    m: usize,
+    read: &CompressedRead,
+    flags: u8,
+    used_hash_bits: usize,
+    bucket_bits_count: usize,
+) -> BucketIndexType {
+    let decr_val = ((read.bases_count() == k) && (flags & READ_FLAG_INCL_END) == 0) as usize;
+
+    let hashes = H::new(read.sub_slice((1 - decr_val)..(k - decr_val)), m);
+
+    let minimizer = hashes
+        .iter()
+        .min_by_key(|k| H::get_full_minimizer(k.to_unextendable()))
+        .unwrap();
+
+    BH::get_bucket::<H>(
+        used_hash_bits,
+        bucket_bits_count,
+        minimizer.to_unextendable(),
+    )
+}
+
 pub struct GroupProcessStats {
     pub total_kmers: u64,
     pub unique_kmers: u64,
@@ -104,6 +202,15 @@ pub trait KmersTransformMapProcessor<F: KmersTransformExecutorFactory>:
         &mut self,
         global_data: &F::GlobalExtraData,
     ) -> Packet<Self::MapStruct>;
+
+    /// Called after each batch, returns the partial map of the group when it must be flushed to bound
+    /// the memory usage. The flushed map is passed to process_map and then, without being reset, back
+    /// to process_group_start to continue the group, so the final executor must combine the partial
+    /// results of the k-mers split across the flushes. It must also hold them until the last map of
+    /// the group, as a group that fails is discarded by resetting its map
+    fn try_flush(&mut self, _global_data: &F::GlobalExtraData) -> Option<Packet<Self::MapStruct>> {
+        None
+    }
 }
 
 pub trait KmersTransformFinalExecutor<F: KmersTransformExecutorFactory>:
@@ -111,28 +218,336 @@ pub trait KmersTransformFinalExecutor<F: KmersTransformExecutorFactory>:
 {
     type MapStruct: PacketTrait + PoolObjectTrait<InitData = ()>;
 
+    /// The resulting sequences can be streamed out with a SequencesSink owned by the executor.
+    /// Called more than once for a group if its map processor flushes partial maps with try_flush
     fn process_map(
         &mut self,
         global_data: &F::GlobalExtraData,
         map_struct: Packet<Self::MapStruct>,
     ) -> Packet<Self::MapStruct>;
 
-    fn finalize(self, global_data: &F::GlobalExtraData);
+    /// Called once for each input bucket after all its maps were processed, including
+    /// the ones of its resplitted sub-buckets. Each call is made on only one of the final executors
+    fn on_bucket_done(&mut self, _bucket: BucketIndexType, _global_data: &F::GlobalExtraData) {}
+
+    /// Returns the statistics of the sequences written by this executor,
+    /// the ones of all the final executors are summed in KmersTransformResult
+    fn finalize(self, global_data: &F::GlobalExtraData) -> FinalStats;
+}
+
+/// Output of the final executors, summed over all of them
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct FinalStats {
+    pub sequences: u64,
+    pub bases: u64,
+}
+
+impl AddAssign for FinalStats {
+    fn add_assign(&mut self, other: Self) {
+        self.sequences += other.sequences;
+        self.bases += other.bases;
+    }
+}
+
+/// Absolute maximum number of executors for each type, overriding the values derived from the threads count
+#[derive(Copy, Clone, Debug, Default)]
+pub struct KmersTransformExecutorsLimits {
+    pub max_readers: Option<usize>,
+    pub max_processors: Option<usize>,
+    pub max_resplitters: Option<usize>,
+    /// Executors spawned for a single bucket by a reader or a resplitter, each one holding its own buffers
+    pub max_bucket_concurrency: Option<usize>,
+}
+
+impl KmersTransformExecutorsLimits {
+    fn limit(max_count: Option<usize>, derived_count: usize) -> usize {
+        match max_count {
+            Some(max_count) => max(1, min(max_count, derived_count)),
+            None => derived_count,
+        }
+    }
+
+    pub fn readers_count(&self, derived_count: usize) -> usize {
+        Self::limit(self.max_readers, derived_count)
+    }
+
+    pub fn processors_count(&self, derived_count: usize) -> usize {
+        Self::limit(self.max_processors, derived_count)
+    }
+
+    pub fn resplitters_count(&self, derived_count: usize) -> usize {
+        Self::limit(self.max_resplitters, derived_count)
+    }
+
+    pub fn bucket_concurrency(&self, derived_count: usize) -> usize {
+        Self::limit(self.max_bucket_concurrency, derived_count)
+    }
+
+    /// A single reader and resplitter, so that the buckets are read in order
+    pub fn sequential() -> Self {
+        Self {
+            max_readers: Some(1),
+            max_processors: None,
+            max_resplitters: Some(1),
+            max_bucket_concurrency: None,
+        }
+    }
+
+    /// Limits set with the KMERS_TRANSFORM_MAX_* settings
+    pub fn from_config() -> Self {
+        let load = |max_count: &AtomicUsize| match max_count.load(Ordering::Relaxed) {
+            usize::MAX => None,
+            max_count => Some(max_count),
+        };
+        Self {
+            max_readers: load(&KMERS_TRANSFORM_MAX_READERS),
+            max_processors: load(&KMERS_TRANSFORM_MAX_PROCESSORS),
+            max_resplitters: load(&KMERS_TRANSFORM_MAX_RESPLITTERS),
+            max_bucket_concurrency: None,
+        }
+    }
+
+    /// Processors to register for the maps. The sub-buckets of the buckets processed just in time
+    /// need a map each, so without a maximum the count never goes below them plus two,
+    /// or the readers would stall. A maximum is always honored, see online_sub_buckets_limit
+    pub fn processors_pool_size(&self, maps_count: usize, compute_threads_count: usize) -> usize {
+        match self.max_processors {
+            Some(max_processors) => max(1, max_processors),
+            None => {
+                let min_maps_count = max(MAXIMUM_JIT_PROCESSED_BUCKETS, compute_threads_count);
+                max(min_maps_count + 2, maps_count)
+            }
+        }
+    }
+
+    /// Sub-buckets of a bucket that can be processed just in time, the buckets with more are
+    /// rewritten. With a maximum of processors they are shared among the readers, so that a
+    /// reader can always complete its bucket with the processors left by the others
+    pub fn online_sub_buckets_limit(
+        &self,
+        processors_count: usize,
+        readers_count: usize,
+    ) -> Option<usize> {
+        self.max_processors
+            .map(|_| max(1, processors_count / max(1, readers_count)))
+    }
+}
+
+/// Parameters of the threads reading the buckets files
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AsyncReaderConfig {
+    /// Size of each read buffer
+    pub buffer_size: usize,
+    /// Number of buffers that can be read ahead, increase it on high latency storage
+    pub prefetch_slots: usize,
+}
+
+impl Default for AsyncReaderConfig {
+    fn default() -> Self {
+        Self {
+            buffer_size: DEFAULT_OUTPUT_BUFFER_SIZE / 2,
+            prefetch_slots: 4,
+        }
+    }
+}
+
+impl AsyncReaderConfig {
+    /// The readers need at least one buffer to read ahead, and a non empty one
+    pub fn validate(&self) -> Result<(), AsyncReaderConfigError> {
+        if self.buffer_size == 0 {
+            Err(AsyncReaderConfigError::ZeroBufferSize)
+        } else if self.prefetch_slots == 0 {
+            Err(AsyncReaderConfigError::ZeroPrefetchSlots)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AsyncReaderConfigError {
+    ZeroBufferSize,
+    ZeroPrefetchSlots,
+}
+
+impl Display for AsyncReaderConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsyncReaderConfigError::ZeroBufferSize => {
+                write!(f, "The async readers buffer size must be greater than 0")
+            }
+            AsyncReaderConfigError::ZeroPrefetchSlots => {
+                write!(f, "The async readers prefetch slots must be at least 1")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AsyncReaderConfigError {}
+
+/// Controls which buckets can have their oversized sub-buckets resplitted
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ResplitPolicy {
+    #[default]
+    Always,
+    /// No resplitters are created, the oversized sub-buckets are processed as they are
+    Never,
+    /// Only the buckets with a file of at least bucket_bytes can be resplitted
+    Threshold { bucket_bytes: u64 },
+}
+
+impl ResplitPolicy {
+    pub fn allows_resplit(&self, bucket_file_size: usize) -> bool {
+        match self {
+            ResplitPolicy::Always => true,
+            ResplitPolicy::Never => false,
+            ResplitPolicy::Threshold { bucket_bytes } => bucket_file_size as u64 >= *bucket_bytes,
+        }
+    }
+}
+
+/// Names of the resplit and rewritten sub-buckets files
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SpillNaming {
+    /// Names with only a global counter, to keep the paths short
+    #[default]
+    Short,
+    /// Names with the main bucket and the resplit depth of the sub-buckets,
+    /// to map each file of the temp dir back to its bucket and stage
+    Descriptive,
+}
+
+impl SpillNaming {
+    /// Prefix of the sub-buckets of a resplitted bucket, followed by the sub-bucket index
+    pub fn resplit_name(&self, origin_bucket: BucketIndexType, resplit_index: usize) -> String {
+        match self {
+            SpillNaming::Short => format!("resplit-bucket{}", resplit_index),
+            SpillNaming::Descriptive => {
+                format!("resplit-b{}-n{}", origin_bucket, resplit_index)
+            }
+        }
+    }
+
+    /// Prefix of a rewritten group of sub-buckets, followed by the rewrite counter.
+    /// A group combines one or more sub-buckets, so its index is not a sub-bucket index
+    pub fn rewrite_name(
+        &self,
+        origin_bucket: BucketIndexType,
+        resplitted: bool,
+        group_index: usize,
+    ) -> String {
+        match self {
+            SpillNaming::Short => "bucket-rewrite-".to_string(),
+            SpillNaming::Descriptive => format!(
+                "rewrite-b{}-d{}-g{}-",
+                origin_bucket, resplitted as u8, group_index
+            ),
+        }
+    }
+}
+
+/// Order in which the input buckets are started
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum BucketsSchedulingOrder {
+    /// Alternates each large bucket with small ones of a similar total size
+    #[default]
+    Interleaved,
+    /// Starts the largest buckets first, so that they do not delay the end of the run
+    LargestFirst,
 }
 
 pub struct KmersTransform<F: KmersTransformExecutorFactory> {
     global_context: Arc<KmersTransformContext<F>>,
-    normal_buckets_list: Vec<InputBucketDesc>,
+    threads_count: usize,
+    /// Buckets in reading order, with the balancing decision that placed them
+    normal_buckets_list: Vec<(InputBucketDesc, BucketPlanDecision)>,
+    total_buckets_size: usize,
+    /// Small buckets placed at the start of the list to estimate the unique kmers ratio
+    unique_estimator_buckets: usize,
+    scheduling_order: BucketsSchedulingOrder,
+    /// Reported to the progress reporter when the processing starts
+    buckets_skew: Option<BucketsSkew>,
+    max_memory: Option<MemoryDataSize>,
+    /// Kept until the transform is completed, as it is needed again to resume the run
+    buckets_counters_path: PathBuf,
     // oversized_buckets_list: Vec<InputBucketDesc>,
     last_info_log: Mutex<Instant>,
+    progress_reporter: Arc<dyn ProgressReporter>,
     _phantom: PhantomData<F>,
 }
 
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum KmersTransformStatus {
+    Completed,
+    /// The cancellation token was triggered, the main buckets not yet started were left unprocessed.
+    /// The output of all the other buckets is complete, a run with a resume manifest can process the rest
+    Cancelled {
+        unprocessed_buckets: Vec<PathBuf>,
+    },
+    /// The deadline was reached, the main buckets not yet started were left unprocessed.
+    /// The output of all the other buckets is complete, a run with a resume manifest can process the rest
+    PartialCompletion {
+        unprocessed_buckets: Vec<PathBuf>,
+    },
+}
+
+impl KmersTransformStatus {
+    /// The run is completed if no bucket was skipped, even if the token was cancelled after the last one started
+    fn from_unprocessed_buckets(is_cancelled: bool, mut unprocessed_buckets: Vec<PathBuf>) -> Self {
+        unprocessed_buckets.sort();
+        if unprocessed_buckets.is_empty() {
+            KmersTransformStatus::Completed
+        } else if is_cancelled {
+            KmersTransformStatus::Cancelled {
+                unprocessed_buckets,
+            }
+        } else {
+            KmersTransformStatus::PartialCompletion {
+                unprocessed_buckets,
+            }
+        }
+    }
+}
+
+/// State of the transform returned after all the executors completed
+pub struct KmersTransformResult<F: KmersTransformExecutorFactory> {
+    pub status: KmersTransformStatus,
+    pub global_extra_data: Arc<F::GlobalExtraData>,
+    /// Processed buckets, including the ones created by resplitting
+    pub processed_buckets_count: usize,
+    /// Main buckets processed along with all their sub-buckets, sorted by path.
+    /// Their output is complete once the final executors are finalized
+    pub completed_buckets: Vec<PathBuf>,
+    pub final_stats: FinalStats,
+}
+
+#[derive(Debug)]
+pub enum KmersTransformError {
+    /// The transform context is still referenced after all the executors joined
+    ContextStillShared { references_count: usize },
+}
+
+impl Display for KmersTransformError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KmersTransformError::ContextStillShared { references_count } => write!(
+                f,
+                "Kmers transform context still has {} references after completion",
+                references_count
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KmersTransformError {}
+
 pub struct KmersTransformContext<F: KmersTransformExecutorFactory> {
     k: usize,
     min_bucket_size: u64,
     buckets_count: usize,
     max_buckets: usize,
+    /// Buckets added by the resplitters, kept apart from buckets_count so that
+    /// the processed main buckets never exceed their total
     extra_buckets_count: AtomicUsize,
     rewritten_buckets_count: AtomicUsize,
     processed_buckets_count: AtomicUsize,
@@ -148,16 +563,66 @@ pub struct KmersTransformContext<F: KmersTransformExecutorFactory> {
     compute_threads_count: usize,
     read_threads_count: usize,
     max_second_buckets_count_log2: usize,
-    temp_dir: PathBuf,
+    /// Layout of the resplit and rewritten sub-buckets, inside the temp dir and the stripe dirs
+    spill_layout: SpillLayout,
+    /// Size of the per thread buffers of the resplit and rewritten sub-buckets
+    output_buffer_size: MemoryDataSize,
 
     total_sequences: AtomicU64,
     total_kmers: AtomicU64,
     unique_kmers: AtomicU64,
 
     reader_init_lock: tokio::sync::Mutex<()>,
+    processors_admission: ProcessorsAdmissionMode,
+    bucket_errors: BucketErrorsTracker,
+    small_buckets_aggregation: Option<u64>,
+    /// Bucket files up to this size are processed as a single group
+    tiny_buckets_threshold: Option<usize>,
+    /// Maximum estimated size of the sub-buckets created by the resplitters
+    target_subbucket_bytes: Option<u64>,
+    /// Sub-buckets of a bucket above which it is rewritten instead of processed just in time
+    online_sub_buckets_limit: Option<usize>,
+    auto_tuner: Option<AutoTuner<KmersTransformTuning>>,
+    cancellation_token: Option<CancellationToken>,
+    deadline: Option<Instant>,
+    /// Main buckets skipped because they were reached after the deadline
+    unprocessed_buckets: Mutex<Vec<PathBuf>>,
+    async_reader_config: AsyncReaderConfig,
+    resplit_policy: ResplitPolicy,
+    spill_naming: SpillNaming,
+    /// The main buckets files are kept after being read, as they are needed again if the run is
+    /// interrupted before the resume manifest is committed
+    keep_main_buckets: bool,
+    main_buckets_paths: HashMap<BucketIndexType, PathBuf>,
+    bucket_completion: BucketCompletionTracker,
+    /// Cached value of USE_SECOND_BUCKET, as it selects the format of the buckets files
+    use_second_bucket: bool,
+    reads_bytes_limit: Option<ReadsBytesLimit>,
+    open_buckets_limit: OpenBucketsLimit,
+    read_throughput: ReadThroughput,
+    executors_limits: KmersTransformExecutorsLimits,
+    final_stats: Mutex<FinalStats>,
+    /// Checks that the reads of each bucket match its counters, see VERIFY_BUCKETS_COUNTERS
+    verify_counters: bool,
+}
+
+impl<F: KmersTransformExecutorFactory> KmersTransformContext<F> {
+    #[inline(always)]
+    fn is_cancelled(&self) -> bool {
+        self.cancellation_token
+            .as_ref()
+            .is_some_and(|token| token.is_cancelled())
+    }
+
+    #[inline(always)]
+    fn is_past_deadline(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
 }
 
 impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
+    /// See KmersTransformBuilder for setting the parameters by name
     pub fn new(
         file_inputs: Vec<PathBuf>,
         temp_dir: &Path,
@@ -168,14 +633,55 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
         k: usize,
         min_bucket_size: u64,
     ) -> Self {
-        let counters = CountersAnalyzer::load_from_file(
+        Self::try_new(
+            file_inputs,
+            temp_dir,
             buckets_counters_path,
-            !KEEP_FILES.load(Ordering::Relaxed),
-        );
+            buckets_count,
+            global_extra_data,
+            threads_count,
+            k,
+            min_bucket_size,
+        )
+        .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like new, but returns an error if k or the buckets count are not supported, if the temp dir
+    /// cannot name the spill files, or if the counters file exists and cannot be used.
+    /// A missing counters file is not an error, uniform buckets weights are used instead
+    pub fn try_new(
+        file_inputs: Vec<PathBuf>,
+        temp_dir: &Path,
+        buckets_counters_path: PathBuf,
+        buckets_count: usize,
+        global_extra_data: Arc<F::GlobalExtraData>,
+        threads_count: usize,
+        k: usize,
+        min_bucket_size: u64,
+    ) -> Result<Self, KmersTransformBuildError> {
+        validate_k(k).map_err(KmersTransformBuildError::Kmer)?;
+        validate_buckets_count(buckets_count)?;
+        let spill_layout =
+            SpillLayout::new(temp_dir).map_err(KmersTransformBuildError::SpillLayout)?;
+
+        // Only the counters of the input buckets are read, without loading the whole file
+        let counters = CountersAnalyzer::open_streaming_if_exists(
+            &buckets_counters_path,
+            false,
+            buckets_count,
+            k,
+        )
+        .map_err(KmersTransformBuildError::Counters)?;
+        let has_real_counters = counters.is_some();
+
+        let verify_counters = VERIFY_BUCKETS_COUNTERS.load(Ordering::Relaxed);
+        if verify_counters && !has_real_counters {
+            println!("Warning: the buckets counters are not available, they will not be verified");
+        }
 
         let mut total_buckets_size = 0;
 
-        let mut files_with_sizes: Vec<_> = file_inputs
+        let files_with_sizes: Vec<_> = file_inputs
             .into_iter()
             .map(|f| {
                 let file_size = MemoryFs::get_file_size(&f).unwrap_or(0);
@@ -184,62 +690,41 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
             })
             .collect();
 
-        files_with_sizes.sort_by_key(|x| x.1);
-        files_with_sizes.reverse();
-
-        let normal_buckets_list = {
-            let mut buckets_list = Vec::with_capacity(files_with_sizes.len());
-            let mut start_idx = 0;
-            let mut end_idx = files_with_sizes.len();
-
-            let mut matched_size = 0i64;
-
-            let mut unique_estimator_buckets_count = min(buckets_count / 8, threads_count * 2);
-
-            while start_idx != end_idx && unique_estimator_buckets_count > 0 {
-                end_idx -= 1;
-                unique_estimator_buckets_count -= 1;
-                let file_entry = files_with_sizes[end_idx].0.clone();
-                let bucket_index = get_bucket_index(&file_entry);
-                buckets_list.push(InputBucketDesc {
-                    path: file_entry,
-                    sub_bucket_counters: counters.get_counters_for_bucket(bucket_index).clone(),
-                    resplitted: false,
-                    rewritten: false,
-                    used_hash_bits: buckets_count.ilog2() as usize,
-                });
-            }
-
-            while start_idx != end_idx {
-                let file_entry = if matched_size <= 0 {
-                    let target_file = &files_with_sizes[start_idx];
-                    let entry = target_file.0.clone();
-                    matched_size = target_file.1 as i64;
-                    start_idx += 1;
-                    entry
-                } else {
-                    let target_file = &files_with_sizes[end_idx - 1];
-                    let entry = target_file.0.clone();
-                    matched_size -= target_file.1 as i64;
-                    end_idx -= 1;
-                    entry
-                };
+        let buckets_skew = detect_buckets_skew(&files_with_sizes, buckets_count);
+        let main_buckets_paths = files_with_sizes
+            .iter()
+            .map(|(path, _)| (get_bucket_index(path), path.clone()))
+            .collect();
 
+        let buckets = files_with_sizes
+            .into_iter()
+            .map(|(file_entry, file_size)| {
                 let bucket_index = get_bucket_index(&file_entry);
-
-                buckets_list.push(InputBucketDesc {
+                Ok(InputBucketDesc {
                     path: file_entry,
-                    sub_bucket_counters: counters.get_counters_for_bucket(bucket_index).clone(),
+                    sub_bucket_counters: match &counters {
+                        Some(counters) => counters.get_counters_for_bucket(bucket_index)?,
+                        None => vec![BucketCounter { count: 1 }; MAXIMUM_SECOND_BUCKETS_COUNT],
+                    },
                     resplitted: false,
                     rewritten: false,
                     used_hash_bits: buckets_count.ilog2() as usize,
+                    file_size,
+                    cost_weight: None,
+                    origin_bucket: bucket_index,
                 })
-            }
-            buckets_list
-        };
+            })
+            .collect::<Result<_, CountersError>>()
+            .map_err(KmersTransformBuildError::Counters)?;
+        let (normal_buckets_list, unique_estimator_buckets) = Self::order_buckets(
+            buckets,
+            min(buckets_count / 8, threads_count * 2),
+            BucketsSchedulingOrder::default(),
+        );
 
-        let compute_threads_count = max(1, threads_count / 4);
-        let read_threads_count = max(1, threads_count / 4 * 3);
+        let threads_split = ThreadSplitPolicy::Auto.split(threads_count).unwrap();
+        let compute_threads_count = threads_split.compute_threads_count;
+        let read_threads_count = threads_split.read_threads_count;
 
         let max_buckets = max(MAXIMUM_SECOND_BUCKETS_COUNT, compute_threads_count);
 
@@ -259,26 +744,423 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
             compute_threads_count,
             read_threads_count,
             max_second_buckets_count_log2: MAXIMUM_SECOND_BUCKETS_COUNT.ilog2() as usize,
-            temp_dir: temp_dir.to_path_buf(),
+            spill_layout,
+            output_buffer_size: get_per_cpu_buffer_size(),
             total_sequences: AtomicU64::new(0),
             total_kmers: AtomicU64::new(0),
             unique_kmers: AtomicU64::new(0),
             reader_init_lock: tokio::sync::Mutex::new(()),
+            processors_admission: ProcessorsAdmissionMode::default(),
+            bucket_errors: BucketErrorsTracker::new(ContinueOrAbort::default()),
+            small_buckets_aggregation: None,
+            tiny_buckets_threshold: None,
+            target_subbucket_bytes: None,
+            online_sub_buckets_limit: None,
+            auto_tuner: if AUTO_TUNE_KMERS_TRANSFORM.load(Ordering::Relaxed) {
+                Some(AutoTuner::new(
+                    KmersTransformTuning::default_candidates(),
+                    AUTO_TUNE_WARMUP_BUCKETS,
+                ))
+            } else {
+                None
+            },
+            cancellation_token: None,
+            deadline: None,
+            unprocessed_buckets: Mutex::new(Vec::new()),
+            async_reader_config: AsyncReaderConfig::default(),
+            resplit_policy: ResplitPolicy::default(),
+            spill_naming: SpillNaming::default(),
+            keep_main_buckets: false,
+            main_buckets_paths,
+            bucket_completion: BucketCompletionTracker::new(),
+            use_second_bucket: USE_SECOND_BUCKET.load(Ordering::Relaxed),
+            reads_bytes_limit: None,
+            open_buckets_limit: OpenBucketsLimit::new(default_max_open_files()),
+            read_throughput: ReadThroughput::new(),
+            executors_limits: KmersTransformExecutorsLimits::from_config(),
+            final_stats: Mutex::new(FinalStats::default()),
+            verify_counters: verify_counters && has_real_counters,
         });
 
-        Self {
+        let transform = Self {
             global_context: execution_context,
+            threads_count,
             normal_buckets_list,
+            unique_estimator_buckets,
+            scheduling_order: BucketsSchedulingOrder::default(),
+            buckets_skew,
+            total_buckets_size,
+            max_memory: None,
+            buckets_counters_path,
             // oversized_buckets_list,
             last_info_log: Mutex::new(Instant::now()),
+            progress_reporter: Arc::new(DefaultProgressReporter),
             _phantom: Default::default(),
+        };
+
+        Ok(if SEQUENTIAL_KMERS_TRANSFORM.load(Ordering::Relaxed) {
+            transform.with_sequential_execution()
+        } else {
+            transform
+        })
+    }
+
+    /// Uses a single thread and a single reader and resplitter, so that the buckets are processed in order.
+    /// The processors keep their minimum count, as the sub-buckets of a bucket need a map each.
+    /// Auto-tuning is disabled as it depends on the timings
+    pub fn with_sequential_execution(mut self) -> Self {
+        let global_context = Arc::get_mut(&mut self.global_context).unwrap();
+        global_context.executors_limits = KmersTransformExecutorsLimits::sequential();
+        global_context.read_threads_count = 1;
+        global_context.compute_threads_count = 1;
+        global_context.max_buckets = MAXIMUM_SECOND_BUCKETS_COUNT;
+        global_context.auto_tuner = None;
+        self
+    }
+
+    pub fn with_executors_limits(
+        mut self,
+        executors_limits: KmersTransformExecutorsLimits,
+    ) -> Self {
+        Arc::get_mut(&mut self.global_context)
+            .unwrap()
+            .executors_limits = executors_limits;
+        self
+    }
+
+    /// Changes how the threads are split between the bucket readers and the compute executors
+    pub fn with_thread_split(
+        mut self,
+        thread_split: ThreadSplitPolicy,
+    ) -> Result<Self, ThreadSplitError> {
+        let threads_split = thread_split.split(self.threads_count)?;
+        let global_context = Arc::get_mut(&mut self.global_context).unwrap();
+        global_context.read_threads_count = threads_split.read_threads_count;
+        global_context.compute_threads_count = threads_split.compute_threads_count;
+        global_context.max_buckets = max(
+            MAXIMUM_SECOND_BUCKETS_COUNT,
+            threads_split.compute_threads_count,
+        );
+        Ok(self)
+    }
+
+    pub fn with_async_reader_config(
+        mut self,
+        async_reader_config: AsyncReaderConfig,
+    ) -> Result<Self, AsyncReaderConfigError> {
+        async_reader_config.validate()?;
+        Arc::get_mut(&mut self.global_context)
+            .unwrap()
+            .async_reader_config = async_reader_config;
+        Ok(self)
+    }
+
+    pub fn with_resplit_policy(mut self, resplit_policy: ResplitPolicy) -> Self {
+        Arc::get_mut(&mut self.global_context)
+            .unwrap()
+            .resplit_policy = resplit_policy;
+        self
+    }
+
+    /// Skips the buckets already processed according to the manifest, counting them as processed.
+    /// The main buckets files are kept after being read, the caller commits the completed_buckets
+    /// of the result to the manifest once the output is durable, and can then remove them
+    pub fn with_resume_manifest(mut self, resume_manifest: &ResumeManifest) -> Self {
+        let global_context = Arc::get_mut(&mut self.global_context).unwrap();
+
+        let mut skipped_count = 0;
+        let mut skipped_size = 0;
+        let mut skipped_estimator_buckets = 0;
+
+        let mut index = 0;
+        self.normal_buckets_list.retain(|(bucket, _)| {
+            let is_processed = resume_manifest.is_processed(&bucket.path);
+            if is_processed {
+                skipped_count += 1;
+                skipped_size += bucket.file_size;
+                if index < self.unique_estimator_buckets {
+                    skipped_estimator_buckets += 1;
+                }
+            }
+            index += 1;
+            !is_processed
+        });
+        self.unique_estimator_buckets -= skipped_estimator_buckets;
+
+        *global_context.processed_buckets_count.get_mut() += skipped_count;
+        *global_context.processed_buckets_size.get_mut() += skipped_size;
+        global_context.keep_main_buckets = true;
+        self
+    }
+
+    /// Sorts the buckets by decreasing cost and schedules them, returning them
+    /// in reading order along with the count of the unique kmers estimation ones
+    fn order_buckets(
+        mut buckets: Vec<InputBucketDesc>,
+        unique_estimator_buckets_count: usize,
+        scheduling_order: BucketsSchedulingOrder,
+    ) -> (Vec<(InputBucketDesc, BucketPlanDecision)>, usize) {
+        // Stable, so that the buckets with the same cost keep the input order
+        buckets.sort_by_key(|bucket| Reverse(bucket.scheduling_cost()));
+
+        let sorted_costs: Vec<_> = buckets
+            .iter()
+            .map(|bucket| bucket.scheduling_cost())
+            .collect();
+        let mut buckets: Vec<_> = buckets.into_iter().map(Some).collect();
+        let ordered_buckets: Vec<_> = schedule_buckets(
+            &sorted_costs,
+            unique_estimator_buckets_count,
+            scheduling_order,
+        )
+        .into_iter()
+        .map(|(index, decision)| (buckets[index].take().unwrap(), decision))
+        .collect();
+
+        let unique_estimator_buckets = ordered_buckets
+            .iter()
+            .filter(|(_, decision)| *decision == BucketPlanDecision::UniqueEstimator)
+            .count();
+        (ordered_buckets, unique_estimator_buckets)
+    }
+
+    /// Weights the file size of the given buckets when ordering them, for the buckets known to be
+    /// more expensive than their size (e.g. repeat-heavy ones). The weights are relative to the size,
+    /// so that the buckets without a weight stay comparable. The reading order is computed again,
+    /// keeping the scheduling order
+    pub fn with_buckets_weights(mut self, weights: &HashMap<PathBuf, f64>) -> Self {
+        for (bucket, _) in &mut self.normal_buckets_list {
+            bucket.cost_weight = weights.get(&bucket.path).copied();
+        }
+        self.reorder_buckets();
+        self
+    }
+
+    /// Returns the buckets in the order they will be read, after the costs and the scheduling order
+    /// are applied. It only inspects the planned buckets, no file is read or removed
+    pub fn plan(&self) -> BucketsPlan {
+        BucketsPlan {
+            buckets: self
+                .normal_buckets_list
+                .iter()
+                .map(|(bucket, decision)| PlannedBucket {
+                    path: bucket.path.clone(),
+                    file_size: bucket.file_size,
+                    decision: *decision,
+                })
+                .collect(),
+            unique_estimator_buckets: self.unique_estimator_buckets,
+            total_buckets_size: self.total_buckets_size,
         }
     }
 
-    pub fn parallel_kmers_transform(mut self) {
+    pub fn with_buckets_scheduling(mut self, order: BucketsSchedulingOrder) -> Self {
+        self.scheduling_order = order;
+        self.reorder_buckets();
+        self
+    }
+
+    /// Computes the reading order again from the current costs and scheduling order
+    fn reorder_buckets(&mut self) {
+        let buckets = std::mem::take(&mut self.normal_buckets_list)
+            .into_iter()
+            .map(|(bucket, _)| bucket)
+            .collect();
+
+        let (normal_buckets_list, unique_estimator_buckets) = Self::order_buckets(
+            buckets,
+            min(
+                self.global_context.buckets_count / 8,
+                self.threads_count * 2,
+            ),
+            self.scheduling_order,
+        );
+        self.normal_buckets_list = normal_buckets_list;
+        self.unique_estimator_buckets = unique_estimator_buckets;
+    }
+
+    /// Memory available to the map processors, by default the processors memory limit of the config.
+    /// Without any limit only the minimum processors are used
+    pub fn with_max_memory(mut self, max_memory: MemoryDataSize) -> Self {
+        self.max_memory = Some(max_memory);
+        self
+    }
+
+    /// Blocks the readers while the reads buffers waiting to be processed exceed max_buffered_reads,
+    /// by default only the count of the buffers is limited
+    pub fn with_max_buffered_reads(mut self, max_buffered_reads: MemoryDataSize) -> Self {
+        Arc::get_mut(&mut self.global_context)
+            .unwrap()
+            .reads_bytes_limit = Some(ReadsBytesLimit::new(max_buffered_reads.as_bytes()));
+        self
+    }
+
+    /// Maximum bucket files open at the same time, counting the buckets being read and the
+    /// rewritten and resplitted sub-buckets being written. By default a fraction of the open files
+    /// limit of the process
+    pub fn with_max_open_buckets(mut self, max_open_buckets: usize) -> Self {
+        Arc::get_mut(&mut self.global_context)
+            .unwrap()
+            .open_buckets_limit = OpenBucketsLimit::new(max_open_buckets);
+        self
+    }
+
+    /// Spreads the resplit and rewritten sub-buckets round-robin across the temp dir and
+    /// these directories, to split their I/O over multiple disks once they are spilled
+    pub fn with_spill_dirs(mut self, spill_dirs: Vec<PathBuf>) -> Self {
+        let global_context = Arc::get_mut(&mut self.global_context).unwrap();
+        global_context.spill_layout = global_context
+            .spill_layout
+            .clone()
+            .with_stripe_dirs(&spill_dirs);
+        self
+    }
+
+    /// Names the resplit and rewritten sub-buckets after their main bucket and stage, see SpillNaming
+    pub fn with_spill_naming(mut self, spill_naming: SpillNaming) -> Self {
+        Arc::get_mut(&mut self.global_context).unwrap().spill_naming = spill_naming;
+        self
+    }
+
+    /// Size of the per thread buffers used to write the resplit and rewritten sub-buckets,
+    /// defaults to the PER_CPU_BUFFER_SIZE setting. Larger buffers reduce the write calls,
+    /// at the cost of more memory for each writing thread
+    pub fn with_output_buffer_size(mut self, output_buffer_size: MemoryDataSize) -> Self {
+        Arc::get_mut(&mut self.global_context)
+            .unwrap()
+            .output_buffer_size = output_buffer_size;
+        self
+    }
+
+    /// Receives the buckets progress instead of printing it to stdout
+    pub fn with_progress_reporter(mut self, progress_reporter: Arc<dyn ProgressReporter>) -> Self {
+        self.progress_reporter = progress_reporter;
+        self
+    }
+
+    pub fn with_processors_admission(mut self, admission_mode: ProcessorsAdmissionMode) -> Self {
+        Arc::get_mut(&mut self.global_context)
+            .unwrap()
+            .processors_admission = admission_mode;
+        self
+    }
+
+    pub fn with_bucket_error_policy(mut self, on_bucket_error: ContinueOrAbort) -> Self {
+        Arc::get_mut(&mut self.global_context)
+            .unwrap()
+            .bucket_errors = BucketErrorsTracker::new(on_bucket_error);
+        self
+    }
+
+    /// Combines the sub-buckets with less than max_combined_size sequences, sending them to a shared processor
+    pub fn with_small_buckets_aggregation(mut self, max_combined_size: u64) -> Self {
+        Arc::get_mut(&mut self.global_context)
+            .unwrap()
+            .small_buckets_aggregation = Some(max_combined_size);
+        self
+    }
+
+    /// Processes the bucket files up to max_file_size bytes as a single group, read by one task and sent
+    /// to one processor, instead of allocating a processor address for each of their sub-buckets
+    pub fn with_tiny_buckets_threshold(mut self, max_file_size: usize) -> Self {
+        Arc::get_mut(&mut self.global_context)
+            .unwrap()
+            .tiny_buckets_threshold = Some(max_file_size);
+        self
+    }
+
+    /// Resplits the oversized buckets in enough sub-buckets for each one to be estimated below
+    /// target_subbucket_bytes, so that they fit the memory budget of a single processor.
+    /// By default it is the memory share of a processor, when the processors memory is limited
+    pub fn with_target_subbucket_bytes(mut self, target_subbucket_bytes: u64) -> Self {
+        Arc::get_mut(&mut self.global_context)
+            .unwrap()
+            .target_subbucket_bytes = Some(max(1, target_subbucket_bytes));
+        self
+    }
+
+    /// Tries the candidate reader parameters on the first buckets, keeping the fastest for the rest of the run
+    pub fn with_auto_tuning(
+        mut self,
+        candidates: Vec<KmersTransformTuning>,
+        warmup_buckets_per_candidate: usize,
+    ) -> Self {
+        Arc::get_mut(&mut self.global_context).unwrap().auto_tuner =
+            Some(AutoTuner::new(candidates, warmup_buckets_per_candidate));
+        self
+    }
+
+    /// Stops starting new main buckets once the token is cancelled, while the ones already started are
+    /// completed along with their sub-buckets. The skipped buckets are kept and returned in the Cancelled status
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        Arc::get_mut(&mut self.global_context)
+            .unwrap()
+            .cancellation_token = Some(cancellation_token);
+        self
+    }
+
+    /// Soft deadline of the transform: the main buckets not yet started when it is reached are skipped,
+    /// while the ones already started are completed along with their sub-buckets. The skipped buckets
+    /// are kept and returned in the PartialCompletion status
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        Arc::get_mut(&mut self.global_context).unwrap().deadline = Some(deadline);
+        self
+    }
+
+    pub fn parallel_kmers_transform(
+        mut self,
+    ) -> Result<KmersTransformResult<F>, KmersTransformError> {
+        // The executors would wait forever for buckets that never come
+        if self.normal_buckets_list.is_empty() {
+            return self.into_result();
+        }
+
+        if let Some(buckets_skew) = &self.buckets_skew {
+            self.progress_reporter.on_buckets_skew(buckets_skew);
+        }
+
         let compute_threads_count = self.global_context.compute_threads_count;
         let read_threads_count = self.global_context.read_threads_count;
 
+        let min_maps_count = max(MAXIMUM_JIT_PROCESSED_BUCKETS, compute_threads_count) + 2;
+
+        let max_memory = self.max_memory.or_else(|| {
+            let limit = PROCESSORS_MEMORY_LIMIT.load(Ordering::Relaxed);
+            (limit != usize::MAX).then(|| MemoryDataSize::from_bytes(limit))
+        });
+        let maps_count = match max_memory {
+            // Each processor map is bounded by the intermediate map size only if every oversized
+            // sub-bucket is resplitted, otherwise its footprint is unknown
+            Some(max_memory) if self.global_context.resplit_policy == ResplitPolicy::Always => {
+                ProcessorsMemoryLimit::new(max_memory).processors_count(
+                    MAX_INTERMEDIATE_MAP_SIZE,
+                    min_maps_count,
+                    compute_threads_count * 4,
+                )
+            }
+            _ => min_maps_count,
+        };
+
+        let executors_limits = self.global_context.executors_limits;
+        let processors_count =
+            executors_limits.processors_pool_size(maps_count, compute_threads_count);
+        let readers_count = executors_limits.readers_count(read_threads_count);
+        Arc::get_mut(&mut self.global_context)
+            .unwrap()
+            .online_sub_buckets_limit =
+            executors_limits.online_sub_buckets_limit(processors_count, readers_count);
+
+        // Without an explicit target, the sub-buckets are sized to fit the memory share of a processor
+        if let Some(max_memory) = max_memory {
+            let global_context = Arc::get_mut(&mut self.global_context).unwrap();
+            if global_context.target_subbucket_bytes.is_none() {
+                global_context.target_subbucket_bytes = Some(max(
+                    1,
+                    ProcessorsMemoryLimit::new(max_memory).processor_budget(processors_count),
+                ));
+            }
+        }
+
         let execution_context = ExecutionContext::new();
 
         let disk_thread_pool =
@@ -287,7 +1169,9 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
             ExecThreadPool::new(&execution_context, compute_threads_count, "km_comp");
 
         let mut normal_input_buckets = ExecutorInput::from_iter(
-            std::mem::take(&mut self.normal_buckets_list).into_iter(),
+            std::mem::take(&mut self.normal_buckets_list)
+                .into_iter()
+                .map(|(bucket, _)| bucket),
             ExecutorInputAddressMode::Multiple,
         );
 
@@ -297,7 +1181,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
         // );
 
         let bucket_readers = disk_thread_pool.register_executors::<KmersTransformReader<F>>(
-            read_threads_count,
+            readers_count,
             PoolAllocMode::Distinct {
                 capacity: self.global_context.max_buckets,
             },
@@ -308,25 +1192,27 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
             &self.global_context,
         );
 
-        let min_maps_count = max(MAXIMUM_JIT_PROCESSED_BUCKETS, compute_threads_count);
-
         let bucket_sequences_processors = compute_thread_pool
             .register_executors::<KmersTransformProcessor<F>>(
-                min_maps_count + 2,
+                processors_count,
                 PoolAllocMode::Shared {
-                    capacity: min_maps_count + 2,
+                    capacity: processors_count,
                 },
                 (),
                 &self.global_context,
             );
 
-        let bucket_resplitters = compute_thread_pool
-            .register_executors::<KmersTransformResplitter<F>>(
-                compute_threads_count,
-                PoolAllocMode::None,
-                (),
-                &self.global_context,
-            );
+        let bucket_resplitters =
+            (self.global_context.resplit_policy != ResplitPolicy::Never).then(|| {
+                compute_thread_pool.register_executors::<KmersTransformResplitter<F>>(
+                    self.global_context
+                        .executors_limits
+                        .resplitters_count(compute_threads_count),
+                    PoolAllocMode::None,
+                    (),
+                    &self.global_context,
+                )
+            });
 
         normal_input_buckets.set_output_executor::<KmersTransformReader<F>>(
             &execution_context,
@@ -354,10 +1240,12 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
         // Wait for the main buckets to be processed
         execution_context.wait_for_completion(bucket_readers);
 
-        // Wait for the resplitting to be complete
-        execution_context.wait_for_completion(bucket_resplitters);
-        // Wait for the new buckets reading
-        execution_context.wait_for_completion(bucket_readers);
+        if let Some(bucket_resplitters) = bucket_resplitters {
+            // Wait for the resplitting to be complete
+            execution_context.wait_for_completion(bucket_resplitters);
+            // Wait for the new buckets reading
+            execution_context.wait_for_completion(bucket_readers);
+        }
 
         // Wait for the maps to be complete
         execution_context.wait_for_completion(bucket_sequences_processors);
@@ -368,6 +1256,64 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
         // // Wait for the final writer to finish
         // execution_context.wait_for_completion(bucket_writers);
         execution_context.join_all();
+
+        self.global_context.spill_layout.remove_empty_stripe_dirs();
+        self.global_context.bucket_errors.print_summary();
+        if let Some(auto_tuner) = &self.global_context.auto_tuner {
+            auto_tuner.print_summary();
+        }
+
+        // Release the executors references to the context before unwrapping it
+        drop(disk_thread_pool);
+        drop(compute_thread_pool);
+        drop(execution_context);
+
+        self.into_result()
+    }
+
+    fn into_result(self) -> Result<KmersTransformResult<F>, KmersTransformError> {
+        let processed_buckets_count = self
+            .global_context
+            .processed_buckets_count
+            .load(Ordering::Relaxed)
+            + self
+                .global_context
+                .processed_extra_buckets_count
+                .load(Ordering::Relaxed);
+
+        let is_cancelled = self.global_context.is_cancelled();
+
+        let mut global_context = Arc::try_unwrap(self.global_context).map_err(|context| {
+            KmersTransformError::ContextStillShared {
+                references_count: Arc::strong_count(&context),
+            }
+        })?;
+
+        let status = KmersTransformStatus::from_unprocessed_buckets(
+            is_cancelled,
+            global_context.unprocessed_buckets.into_inner(),
+        );
+
+        // The counters are needed again to process the skipped buckets
+        if status == KmersTransformStatus::Completed && !KEEP_FILES.load(Ordering::Relaxed) {
+            let _ = std::fs::remove_file(&self.buckets_counters_path);
+        }
+
+        let mut completed_buckets: Vec<_> = global_context
+            .bucket_completion
+            .into_done()
+            .into_iter()
+            .filter_map(|bucket| global_context.main_buckets_paths.remove(&bucket))
+            .collect();
+        completed_buckets.sort();
+
+        Ok(KmersTransformResult {
+            status,
+            global_extra_data: global_context.global_extra_data,
+            processed_buckets_count,
+            completed_buckets,
+            final_stats: global_context.final_stats.into_inner(),
+        })
     }
 
     fn maybe_log_completed_buckets(&self, extra_debug: impl FnOnce()) -> bool {
@@ -416,45 +1362,184 @@ impl<F: KmersTransformExecutorFactory> KmersTransform<F> {
                 .processed_extra_buckets_size
                 .load(Ordering::Relaxed);
 
-            let eta_processed_size = eta_standard_processed_size + eta_extra_processed_size;
+            let phase_elapsed = monitor.get_phase_timer();
+            drop(monitor);
 
-            let eta_remaining_size =
-                self.global_context.total_buckets_size - eta_standard_processed_size;
-            let eta_total_buckets_size =
-                self.global_context.total_buckets_size + eta_extra_processed_size;
-
-            let eta = Duration::from_secs(
-                (monitor.get_phase_timer().as_secs_f64() / (eta_processed_size as f64)
-                    * (eta_remaining_size as f64)) as u64,
-            );
-
-            let est_tot = Duration::from_secs(
-                (monitor.get_phase_timer().as_secs_f64() / (eta_processed_size as f64)
-                    * (eta_total_buckets_size as f64)) as u64,
-            );
-
-            println!(
-                "Processing bucket {}{} of [{}{}[R:{}]] {} phase eta: {:.0?} est. tot: {:.0?}",
-                processed_count,
-                if extra_processed_buckets_count > 0 {
-                    format!("(+{})", extra_processed_buckets_count)
-                } else {
-                    String::new()
+            report_progress(
+                self.progress_reporter.as_ref(),
+                ProcessedBuckets {
+                    buckets: processed_count,
+                    extra_buckets: extra_processed_buckets_count,
+                    read_bytes: self.global_context.read_throughput.total_bytes(),
+                    read_bytes_per_sec: self.global_context.read_throughput.sample(),
                 },
-                buckets_count,
-                if extra_buckets_count > 0 {
-                    format!("(+{})", extra_buckets_count)
-                } else {
-                    String::new()
+                TotalBuckets {
+                    buckets: buckets_count,
+                    extra_buckets: extra_buckets_count,
+                    rewritten_buckets: rewritten_buckets_count,
                 },
-                rewritten_buckets_count,
-                monitor.get_formatted_counter_without_memory(),
-                eta,
-                est_tot
-            );
-            true
+                phase_elapsed,
+                self.global_context.total_buckets_size,
+                eta_standard_processed_size,
+                eta_extra_processed_size,
+            )
         } else {
             false
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        AsyncReaderConfig, AsyncReaderConfigError, FinalStats, KmersTransformExecutorsLimits,
+        KmersTransformStatus, ResplitPolicy, SpillNaming,
+    };
+    use config::MAXIMUM_JIT_PROCESSED_BUCKETS;
+    use std::path::PathBuf;
+
+    #[test]
+    fn executors_limits() {
+        let limits = KmersTransformExecutorsLimits {
+            max_readers: None,
+            max_processors: Some(2),
+            max_resplitters: Some(0),
+            max_bucket_concurrency: Some(2),
+        };
+
+        assert_eq!(limits.readers_count(12), 12);
+        assert_eq!(limits.processors_count(34), 2);
+        assert_eq!(limits.processors_count(1), 1);
+        assert_eq!(limits.resplitters_count(8), 1);
+        assert_eq!(limits.bucket_concurrency(4), 2);
+        assert_eq!(limits.bucket_concurrency(1), 1);
+    }
+
+    #[test]
+    fn processors_pool_lower_bound() {
+        let min_count = MAXIMUM_JIT_PROCESSED_BUCKETS + 2;
+
+        let sequential = KmersTransformExecutorsLimits::sequential();
+        assert_eq!(sequential.processors_pool_size(min_count, 1), min_count);
+        assert_eq!(sequential.processors_pool_size(1, 1), min_count);
+        assert_eq!(sequential.processors_pool_size(64, 1), 64);
+
+        let unlimited = KmersTransformExecutorsLimits::default();
+        assert_eq!(unlimited.processors_pool_size(64, 40), 64);
+        assert_eq!(unlimited.processors_pool_size(1, 40), 42);
+        assert_eq!(unlimited.online_sub_buckets_limit(42, 4), None);
+    }
+
+    #[test]
+    fn processors_maximum_honored() {
+        let limited = KmersTransformExecutorsLimits {
+            max_processors: Some(2),
+            ..Default::default()
+        };
+
+        // The maximum holds even below the count needed by the buckets processed just in time
+        for (maps_count, compute_threads_count) in [(1, 1), (64, 1), (64, 40)] {
+            assert_eq!(
+                limited.processors_pool_size(maps_count, compute_threads_count),
+                2
+            );
+        }
+
+        // The readers share the processors, with at least one sub-bucket each so that the
+        // rewritten sub-buckets can still be processed
+        assert_eq!(limited.online_sub_buckets_limit(2, 1), Some(2));
+        assert_eq!(limited.online_sub_buckets_limit(2, 2), Some(1));
+        assert_eq!(limited.online_sub_buckets_limit(2, 8), Some(1));
+
+        let zero = KmersTransformExecutorsLimits {
+            max_processors: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(zero.processors_pool_size(64, 4), 1);
+    }
+
+    #[test]
+    fn final_stats_sum() {
+        let mut stats = FinalStats::default();
+        for (sequences, bases) in [(3, 120), (0, 0), (5, 47)] {
+            stats += FinalStats { sequences, bases };
+        }
+        assert_eq!(
+            stats,
+            FinalStats {
+                sequences: 8,
+                bases: 167
+            }
+        );
+    }
+
+    #[test]
+    fn transform_status() {
+        assert_eq!(
+            KmersTransformStatus::from_unprocessed_buckets(true, vec![]),
+            KmersTransformStatus::Completed
+        );
+        assert_eq!(
+            KmersTransformStatus::from_unprocessed_buckets(
+                true,
+                vec![PathBuf::from("bucket.3"), PathBuf::from("bucket.1")]
+            ),
+            KmersTransformStatus::Cancelled {
+                unprocessed_buckets: vec![PathBuf::from("bucket.1"), PathBuf::from("bucket.3")]
+            }
+        );
+        assert_eq!(
+            KmersTransformStatus::from_unprocessed_buckets(false, vec![PathBuf::from("bucket.2")]),
+            KmersTransformStatus::PartialCompletion {
+                unprocessed_buckets: vec![PathBuf::from("bucket.2")]
+            }
+        );
+    }
+
+    #[test]
+    fn resplit_policy() {
+        let threshold = ResplitPolicy::Threshold { bucket_bytes: 1024 };
+        assert!(!threshold.allows_resplit(1023));
+        assert!(threshold.allows_resplit(1024));
+        assert!(ResplitPolicy::Always.allows_resplit(0));
+        assert!(!ResplitPolicy::Never.allows_resplit(usize::MAX));
+    }
+
+    #[test]
+    fn spill_naming() {
+        assert_eq!(SpillNaming::Short.resplit_name(12, 3), "resplit-bucket3");
+        assert_eq!(
+            SpillNaming::Short.rewrite_name(12, true, 5),
+            "bucket-rewrite-"
+        );
+        assert_eq!(
+            SpillNaming::Descriptive.resplit_name(12, 3),
+            "resplit-b12-n3"
+        );
+        assert_eq!(
+            SpillNaming::Descriptive.rewrite_name(12, true, 5),
+            "rewrite-b12-d1-g5-"
+        );
+    }
+
+    #[test]
+    fn async_reader_config_validation() {
+        assert_eq!(AsyncReaderConfig::default().validate(), Ok(()));
+        assert_eq!(
+            AsyncReaderConfig {
+                prefetch_slots: 0,
+                ..Default::default()
+            }
+            .validate(),
+            Err(AsyncReaderConfigError::ZeroPrefetchSlots)
+        );
+        assert_eq!(
+            AsyncReaderConfig {
+                buffer_size: 0,
+                ..Default::default()
+            }
+            .validate(),
+            Err(AsyncReaderConfigError::ZeroBufferSize)
+        );
+    }
+}
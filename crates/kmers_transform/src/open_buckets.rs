@@ -0,0 +1,30 @@
+use std::cmp::{max, min};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Bucket files that can be open at the same time, read or written, to stay below the open files limit
+pub struct OpenBucketsLimit {
+    permits: Arc<Semaphore>,
+    max_open_buckets: usize,
+}
+
+impl OpenBucketsLimit {
+    pub fn new(max_open_buckets: usize) -> Self {
+        let max_open_buckets = max(1, max_open_buckets);
+        Self {
+            permits: Arc::new(Semaphore::new(max_open_buckets)),
+            max_open_buckets,
+        }
+    }
+
+    /// Waits until files_count more bucket files can be opened, they are released when the permit is dropped.
+    /// A request bigger than the whole limit waits for all the files instead, so that it is always satisfied
+    pub async fn acquire(&self, files_count: usize) -> OwnedSemaphorePermit {
+        let files_count = min(max(1, files_count), self.max_open_buckets);
+        self.permits
+            .clone()
+            .acquire_many_owned(files_count as u32)
+            .await
+            .unwrap()
+    }
+}
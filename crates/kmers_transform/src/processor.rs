@@ -3,6 +3,7 @@ use crate::{
     KmersTransformContext, KmersTransformExecutorFactory, KmersTransformFinalExecutor,
     KmersTransformMapProcessor,
 };
+use config::BucketIndexType;
 use parallel_processor::execution_manager::executor::{AsyncExecutor, ExecutorReceiver};
 use parallel_processor::execution_manager::memory_tracker::MemoryTracker;
 use parallel_processor::execution_manager::objects_pool::PoolObjectTrait;
@@ -29,6 +30,16 @@ pub struct KmersProcessorInitData {
     pub sub_bucket: usize,
     pub is_resplitted: bool,
     pub bucket_path: PathBuf,
+    pub origin_bucket: BucketIndexType,
+}
+
+impl<F: KmersTransformExecutorFactory> KmersTransformProcessor<F> {
+    fn new_map_packet() -> Packet<<F::MapProcessorType as KmersTransformMapProcessor<F>>::MapStruct>
+    {
+        Packet::new_simple(
+            <F::MapProcessorType as KmersTransformMapProcessor<F>>::MapStruct::allocate_new(&()),
+        )
+    }
 }
 
 impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformProcessor<F> {
@@ -52,31 +63,74 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformProcessor
                 F::new_map_processor(&global_context.global_extra_data, memory_tracker.clone());
             let mut final_executor = F::new_final_executor(&global_context.global_extra_data);
 
-            let mut packet = Packet::new_simple(
-                <F::MapProcessorType as KmersTransformMapProcessor<F>>::MapStruct::allocate_new(&()),
-            );
+            let mut packet = Self::new_map_packet();
 
             while let Ok((address, proc_info)) =
                 track!(receiver.obtain_address().await, ADDR_WAITING_COUNTER)
             {
                 map_processor.process_group_start(packet, &global_context.global_extra_data);
 
+                let bucket_errors = &global_context.bucket_errors;
+
                 let mut real_size = 0;
                 let mut total_kmers = 0;
                 let mut unique_kmers = 0;
+                let mut failed = false;
 
                 while let Some(input_packet) =
                     track!(address.receive_packet().await, PACKET_WAITING_COUNTER)
                 {
+                    // The packets being processed are already bounded by the processors count
+                    if let Some(reads_bytes_limit) = &global_context.reads_bytes_limit {
+                        reads_bytes_limit.release(input_packet.get_size());
+                    }
                     real_size += input_packet.reads.len();
-                    let stats = map_processor.process_group_batch_sequences(
-                        &global_context.global_extra_data,
-                        &input_packet.reads,
-                        &input_packet.extra_buffer,
-                        &input_packet.reads_buffer,
-                    );
-                    total_kmers += stats.total_kmers;
-                    unique_kmers += stats.unique_kmers;
+                    // Keep receiving the packets of a failed bucket, without processing them
+                    if failed {
+                        continue;
+                    }
+                    match bucket_errors.run_guarded(
+                        &proc_info.bucket_path,
+                        proc_info.sub_bucket,
+                        || {
+                            map_processor.process_group_batch_sequences(
+                                &global_context.global_extra_data,
+                                &input_packet.reads,
+                                &input_packet.extra_buffer,
+                                &input_packet.reads_buffer,
+                            )
+                        },
+                    ) {
+                        Some(stats) => {
+                            total_kmers += stats.total_kmers;
+                            unique_kmers += stats.unique_kmers;
+                        }
+                        None => failed = true,
+                    }
+
+                    if failed {
+                        continue;
+                    }
+
+                    // Stream out the partial map if the map processor asks for it, to bound the memory of huge buckets
+                    let flushed_packet = bucket_errors
+                        .run_guarded(&proc_info.bucket_path, proc_info.sub_bucket, || {
+                            map_processor
+                                .try_flush(&global_context.global_extra_data)
+                                .map(|packet| {
+                                    final_executor
+                                        .process_map(&global_context.global_extra_data, packet)
+                                })
+                        })
+                        .unwrap_or_else(|| {
+                            failed = true;
+                            Some(Self::new_map_packet())
+                        });
+
+                    if let Some(packet) = flushed_packet {
+                        map_processor
+                            .process_group_start(packet, &global_context.global_extra_data);
+                    }
                 }
 
                 if !proc_info.is_resplitted {
@@ -91,7 +145,17 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformProcessor
                         .fetch_add(unique_kmers, Ordering::Relaxed);
                 }
 
-                packet = map_processor.process_group_finalize(&global_context.global_extra_data);
+                packet = match bucket_errors.run_guarded(
+                    &proc_info.bucket_path,
+                    proc_info.sub_bucket,
+                    || map_processor.process_group_finalize(&global_context.global_extra_data),
+                ) {
+                    Some(packet) => packet,
+                    None => {
+                        failed = true;
+                        Self::new_map_packet()
+                    }
+                };
 
                 // static MAX_PACKET_SIZE: AtomicUsize = AtomicUsize::new(0);
                 let current_size = packet.get_size();
@@ -108,8 +172,28 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformProcessor
                     );
                 }
 
-                packet = final_executor.process_map(&global_context.global_extra_data, packet);
-                packet.reset();
+                if failed {
+                    // Discard the partial results of the failed bucket
+                    packet.reset();
+                } else {
+                    packet = bucket_errors
+                        .run_guarded(&proc_info.bucket_path, proc_info.sub_bucket, || {
+                            final_executor.process_map(&global_context.global_extra_data, packet)
+                        })
+                        .unwrap_or_else(Self::new_map_packet);
+                    packet.reset();
+                }
+
+                if global_context
+                    .bucket_completion
+                    .release(proc_info.origin_bucket)
+                {
+                    final_executor
+                        .on_bucket_done(proc_info.origin_bucket, &global_context.global_extra_data);
+                }
+                for bucket in global_context.bucket_completion.take_completed() {
+                    final_executor.on_bucket_done(bucket, &global_context.global_extra_data);
+                }
                 // address.packet_send(
                 //     global_context
                 //         .finalizer_address
@@ -120,7 +204,11 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformProcessor
                 //     packet,
                 // );
             }
-            final_executor.finalize(&global_context.global_extra_data);
+            for bucket in global_context.bucket_completion.take_completed() {
+                final_executor.on_bucket_done(bucket, &global_context.global_extra_data);
+            }
+            let final_stats = final_executor.finalize(&global_context.global_extra_data);
+            *global_context.final_stats.lock() += final_stats;
         }
     }
 }
@@ -0,0 +1,80 @@
+use config::MAXIMUM_JIT_PROCESSED_BUCKETS;
+use std::sync::Arc;
+
+/// Estimates the memory (in bytes) required by a map processor for a sub-bucket,
+/// given its sequences count from the counters and the current unique kmers per sequence ratio
+pub type ProcessorMemoryEstimator = Arc<dyn Fn(u64, f64) -> u64 + Sync + Send>;
+
+/// Decides if the sub-buckets of a bucket can be assigned directly to the map processors
+#[derive(Clone)]
+pub enum ProcessorsAdmissionMode {
+    /// At most this number of sub-buckets can be processed online
+    FixedCount(usize),
+    /// The sub-buckets are processed online only if their total estimated memory fits the budget
+    MemoryLimited {
+        budget: u64,
+        estimator: ProcessorMemoryEstimator,
+    },
+}
+
+impl Default for ProcessorsAdmissionMode {
+    fn default() -> Self {
+        Self::FixedCount(MAXIMUM_JIT_PROCESSED_BUCKETS)
+    }
+}
+
+impl ProcessorsAdmissionMode {
+    pub fn memory_limited_with_map_size(budget: u64, map_entry_size: usize) -> Self {
+        Self::MemoryLimited {
+            budget,
+            estimator: Arc::new(move |sequences_count, unique_estimator_factor| {
+                (sequences_count as f64 * unique_estimator_factor) as u64 * map_entry_size as u64
+            }),
+        }
+    }
+
+    /// Returns how many of the given sub-buckets (in order) can be admitted for online processing
+    pub fn admitted_count(
+        &self,
+        sequences_counts: impl Iterator<Item = u64>,
+        unique_estimator_factor: f64,
+    ) -> usize {
+        match self {
+            ProcessorsAdmissionMode::FixedCount(max_count) => {
+                sequences_counts.take(*max_count).count()
+            }
+            ProcessorsAdmissionMode::MemoryLimited { budget, estimator } => {
+                let mut used_memory = 0;
+                sequences_counts
+                    .take_while(|count| {
+                        used_memory += estimator(*count, unique_estimator_factor);
+                        used_memory <= *budget
+                    })
+                    .count()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::processors_admission::ProcessorsAdmissionMode;
+
+    #[test]
+    fn memory_limited_admission() {
+        let mode = ProcessorsAdmissionMode::memory_limited_with_map_size(1 << 20, 16);
+
+        let small_buckets = vec![1000u64; 64];
+        let large_buckets = vec![20000u64; 64];
+
+        let small_admitted = mode.admitted_count(small_buckets.iter().copied(), 1.0);
+        let large_admitted = mode.admitted_count(large_buckets.iter().copied(), 1.0);
+
+        assert_eq!(small_admitted, 64);
+        assert_eq!(large_admitted, 3);
+        assert!(small_admitted > large_admitted);
+
+        let fixed = ProcessorsAdmissionMode::FixedCount(4);
+        assert_eq!(fixed.admitted_count(large_buckets.iter().copied(), 1.0), 4);
+    }
+}
@@ -0,0 +1,64 @@
+use parallel_processor::memory_data_size::MemoryDataSize;
+use std::cmp::{max, min};
+
+/// Memory limit for the map processors, deciding how many of them can run at the same time
+#[derive(Copy, Clone, Debug)]
+pub struct ProcessorsMemoryLimit {
+    max_memory: MemoryDataSize,
+}
+
+impl ProcessorsMemoryLimit {
+    pub fn new(max_memory: MemoryDataSize) -> Self {
+        Self { max_memory }
+    }
+
+    /// Number of processors whose estimated footprint fits the memory limit, between min_count and
+    /// max_count. The min_count processors are always allowed, to avoid stalling the pipeline
+    pub fn processors_count(
+        &self,
+        processor_footprint: u64,
+        min_count: usize,
+        max_count: usize,
+    ) -> usize {
+        let fitting_count = self.max_memory.as_bytes() as u64 / max(1, processor_footprint);
+        max(min_count, min(fitting_count as usize, max_count))
+    }
+    /// Memory share of each processor when processors_count of them run at the same time
+    pub fn processor_budget(&self, processors_count: usize) -> u64 {
+        self.max_memory.as_bytes() as u64 / max(1, processors_count) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::processors_memory::ProcessorsMemoryLimit;
+    use parallel_processor::memory_data_size::MemoryDataSize;
+
+    #[test]
+    fn processors_count_scaling() {
+        let footprint = 32 * 1024 * 1024;
+
+        let small = ProcessorsMemoryLimit::new(MemoryDataSize::from_mebioctets(256));
+        assert_eq!(small.processors_count(footprint, 4, 64), 8);
+
+        let large = ProcessorsMemoryLimit::new(MemoryDataSize::from_mebioctets(64 * 1024));
+        assert_eq!(large.processors_count(footprint, 4, 64), 64);
+
+        // Zero or tiny limits must still allow the minimum processors
+        let zero = ProcessorsMemoryLimit::new(MemoryDataSize::from_bytes(0));
+        assert_eq!(zero.processors_count(footprint, 4, 64), 4);
+        let tiny = ProcessorsMemoryLimit::new(MemoryDataSize::from_bytes(1024));
+        assert_eq!(tiny.processors_count(footprint, 4, 64), 4);
+        assert_eq!(tiny.processors_count(0, 4, 64), 64);
+
+        // The minimum wins over a smaller maximum
+        assert_eq!(large.processors_count(footprint, 18, 16), 18);
+    }
+
+    #[test]
+    fn processor_memory_budget() {
+        let limit = ProcessorsMemoryLimit::new(MemoryDataSize::from_mebioctets(256));
+        assert_eq!(limit.processor_budget(8), 32 * 1024 * 1024);
+        assert_eq!(limit.processor_budget(0), 256 * 1024 * 1024);
+    }
+}
@@ -0,0 +1,307 @@
+use config::MAX_BUCKETS_COUNT_LOG;
+use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
+use std::cmp::min;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Ratio between the biggest and the median bucket above which the run is likely bottlenecked
+const BUCKETS_SKEW_WARNING_RATIO: usize = 20;
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ProcessedBuckets {
+    pub buckets: usize,
+    /// Buckets created by resplitting the oversized ones
+    pub extra_buckets: usize,
+    /// Bytes of the bucket files read, and their recent rate
+    pub read_bytes: u64,
+    pub read_bytes_per_sec: f64,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TotalBuckets {
+    pub buckets: usize,
+    /// Buckets created by resplitting the oversized ones
+    pub extra_buckets: usize,
+    /// Sub-buckets rewritten to be processed again
+    pub rewritten_buckets: usize,
+}
+
+/// Bucket much bigger than the median one, that will likely bottleneck the run
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BucketsSkew {
+    pub bucket: PathBuf,
+    pub bucket_size: usize,
+    pub median_size: usize,
+    pub buckets_count: usize,
+    /// Buckets count that should bring the biggest bucket below the warning ratio,
+    /// unless it is caused by a single very frequent minimizer
+    pub suggested_buckets_count: usize,
+}
+
+/// Checks if the biggest bucket is more than BUCKETS_SKEW_WARNING_RATIO times the median non empty one
+pub fn detect_buckets_skew(
+    files_with_sizes: &[(PathBuf, usize)],
+    buckets_count: usize,
+) -> Option<BucketsSkew> {
+    let mut sizes: Vec<_> = files_with_sizes
+        .iter()
+        .map(|(_, size)| *size)
+        .filter(|size| *size > 0)
+        .collect();
+    if sizes.is_empty() {
+        return None;
+    }
+    sizes.sort_unstable();
+    let median_size = sizes[sizes.len() / 2];
+
+    let (bucket, bucket_size) = files_with_sizes.iter().max_by_key(|(_, size)| *size)?;
+    if *bucket_size <= median_size * BUCKETS_SKEW_WARNING_RATIO {
+        return None;
+    }
+
+    let split_factor = (bucket_size / (median_size * BUCKETS_SKEW_WARNING_RATIO)) + 1;
+    Some(BucketsSkew {
+        bucket: bucket.clone(),
+        bucket_size: *bucket_size,
+        median_size,
+        buckets_count,
+        suggested_buckets_count: min(
+            (buckets_count * split_factor).next_power_of_two(),
+            1 << MAX_BUCKETS_COUNT_LOG,
+        ),
+    })
+}
+
+/// Receives the progress of the kmers transform, at most once every MINIMUM_LOG_DELTA_TIME
+pub trait ProgressReporter: Send + Sync {
+    fn on_bucket_completed(
+        &self,
+        processed: ProcessedBuckets,
+        total: TotalBuckets,
+        eta: Duration,
+        est_total: Duration,
+    );
+
+    /// Called once before the processing starts, if the buckets sizes are heavily skewed
+    fn on_buckets_skew(&self, skew: &BucketsSkew) {
+        println!(
+            "Warning: bucket {} is {} bytes, {:.1}x the median bucket size of {} bytes, the run will likely be bottlenecked by it. \
+            Consider increasing the buckets count from {} to {}, or using a different minimizer length",
+            skew.bucket.display(),
+            skew.bucket_size,
+            skew.bucket_size as f64 / skew.median_size as f64,
+            skew.median_size,
+            skew.buckets_count,
+            skew.suggested_buckets_count
+        );
+    }
+}
+
+/// Estimates the remaining and the total time of the phase from the size of the processed buckets.
+/// Returns None if no bucket was processed yet or the sizes are inconsistent
+pub fn estimate_eta(
+    phase_elapsed: Duration,
+    total_size: usize,
+    processed_size: usize,
+    extra_processed_size: usize,
+) -> Option<(Duration, Duration)> {
+    let done_size = processed_size.checked_add(extra_processed_size)?;
+    if done_size == 0 || processed_size > total_size {
+        return None;
+    }
+
+    let remaining_size = total_size - processed_size;
+    let estimated_total_size = total_size.saturating_add(extra_processed_size);
+    let seconds_per_byte = phase_elapsed.as_secs_f64() / done_size as f64;
+
+    Some((
+        Duration::from_secs((seconds_per_byte * remaining_size as f64) as u64),
+        Duration::from_secs((seconds_per_byte * estimated_total_size as f64) as u64),
+    ))
+}
+
+/// Reports the progress of the buckets, unless the ETA cannot be estimated from the processed sizes.
+/// Returns true if the progress was reported
+pub fn report_progress(
+    reporter: &dyn ProgressReporter,
+    processed: ProcessedBuckets,
+    total: TotalBuckets,
+    phase_elapsed: Duration,
+    total_size: usize,
+    processed_size: usize,
+    extra_processed_size: usize,
+) -> bool {
+    // Skip the logging instead of reporting meaningless times
+    let Some((eta, est_total)) = estimate_eta(
+        phase_elapsed,
+        total_size,
+        processed_size,
+        extra_processed_size,
+    ) else {
+        return false;
+    };
+
+    reporter.on_bucket_completed(processed, total, eta, est_total);
+    true
+}
+
+/// Prints the progress to stdout
+pub struct DefaultProgressReporter;
+
+impl ProgressReporter for DefaultProgressReporter {
+    fn on_bucket_completed(
+        &self,
+        processed: ProcessedBuckets,
+        total: TotalBuckets,
+        eta: Duration,
+        est_total: Duration,
+    ) {
+        println!(
+            "Processing bucket {}{} of [{}{}[R:{}]] {} phase eta: {:.0?} est. tot: {:.0?} read: {:.2} MB/s",
+            processed.buckets,
+            if processed.extra_buckets > 0 {
+                format!("(+{})", processed.extra_buckets)
+            } else {
+                String::new()
+            },
+            total.buckets,
+            if total.extra_buckets > 0 {
+                format!("(+{})", total.extra_buckets)
+            } else {
+                String::new()
+            },
+            total.rewritten_buckets,
+            PHASES_TIMES_MONITOR
+                .read()
+                .get_formatted_counter_without_memory(),
+            eta,
+            est_total,
+            processed.read_bytes_per_sec / (1024.0 * 1024.0)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::progress::{
+        detect_buckets_skew, estimate_eta, report_progress, ProcessedBuckets, ProgressReporter,
+        TotalBuckets,
+    };
+    use parking_lot::Mutex;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct RecordingReporter {
+        reports: Mutex<Vec<(usize, usize, Duration, Duration)>>,
+    }
+
+    impl ProgressReporter for RecordingReporter {
+        fn on_bucket_completed(
+            &self,
+            processed: ProcessedBuckets,
+            total: TotalBuckets,
+            eta: Duration,
+            est_total: Duration,
+        ) {
+            self.reports
+                .lock()
+                .push((processed.buckets, total.buckets, eta, est_total));
+        }
+    }
+
+    #[test]
+    fn eta_with_inconsistent_counts() {
+        let elapsed = Duration::from_secs(100);
+
+        assert_eq!(
+            estimate_eta(elapsed, 1000, 250, 0),
+            Some((Duration::from_secs(300), Duration::from_secs(400)))
+        );
+        // Resplitted buckets extend the total
+        assert_eq!(
+            estimate_eta(elapsed, 1000, 500, 500),
+            Some((Duration::from_secs(50), Duration::from_secs(150)))
+        );
+
+        // Nothing processed yet
+        assert_eq!(estimate_eta(elapsed, 1000, 0, 0), None);
+        // Processed more than the total
+        assert_eq!(estimate_eta(elapsed, 1000, 1001, 0), None);
+        assert_eq!(estimate_eta(elapsed, 1000, 1, usize::MAX), None);
+    }
+
+    #[test]
+    fn progress_past_buckets_count() {
+        let reporter = RecordingReporter::default();
+        let elapsed = Duration::from_secs(100);
+        let total = TotalBuckets {
+            buckets: 4,
+            extra_buckets: 0,
+            rewritten_buckets: 0,
+        };
+        let processed = |buckets| ProcessedBuckets {
+            buckets,
+            ..Default::default()
+        };
+
+        assert!(report_progress(
+            &reporter,
+            processed(1),
+            total,
+            elapsed,
+            1000,
+            250,
+            0
+        ));
+
+        // The resplit buckets are counted apart, but the processed sizes can still overflow the total
+        for buckets in 5..10 {
+            assert!(!report_progress(
+                &reporter,
+                processed(buckets),
+                total,
+                elapsed,
+                1000,
+                250 * buckets,
+                0
+            ));
+        }
+        assert!(!report_progress(
+            &reporter,
+            processed(5),
+            total,
+            elapsed,
+            1000,
+            1000,
+            usize::MAX
+        ));
+
+        assert_eq!(
+            *reporter.reports.lock(),
+            vec![(1, 4, Duration::from_secs(300), Duration::from_secs(400))]
+        );
+    }
+
+    #[test]
+    fn skewed_buckets_warning() {
+        let buckets = |sizes: &[usize]| -> Vec<_> {
+            sizes
+                .iter()
+                .enumerate()
+                .map(|(i, size)| (PathBuf::from(format!("bucket.{}", i)), *size))
+                .collect()
+        };
+
+        assert_eq!(
+            detect_buckets_skew(&buckets(&[100, 120, 90, 2000]), 4),
+            None
+        );
+        assert_eq!(detect_buckets_skew(&buckets(&[0, 0, 0]), 4), None);
+
+        let skew = detect_buckets_skew(&buckets(&[100, 0, 5000, 110, 90]), 256).unwrap();
+        assert_eq!(skew.bucket, PathBuf::from("bucket.2"));
+        assert_eq!(skew.median_size, 110);
+        assert_eq!(skew.suggested_buckets_count, 1024);
+    }
+}
@@ -0,0 +1,102 @@
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// Weight of the newest sample in the moving average of the read rate
+const THROUGHPUT_SMOOTHING: f64 = 0.3;
+
+struct ThroughputAverage {
+    last_sample: Instant,
+    last_total_bytes: u64,
+    bytes_per_sec: Option<f64>,
+}
+
+/// Bytes of the bucket files read, added once each bucket is fully read, with an exponentially
+/// weighted moving average of their rate, sampled when the progress is logged
+pub struct ReadThroughput {
+    total_bytes: AtomicU64,
+    average: Mutex<ThroughputAverage>,
+}
+
+impl ReadThroughput {
+    pub fn new() -> Self {
+        Self {
+            total_bytes: AtomicU64::new(0),
+            average: Mutex::new(ThroughputAverage {
+                last_sample: Instant::now(),
+                last_total_bytes: 0,
+                bytes_per_sec: None,
+            }),
+        }
+    }
+
+    #[inline(always)]
+    pub fn add_bytes(&self, bytes: u64) {
+        self.total_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Updates the moving average with the bytes read since the last sample, returning the new rate
+    pub fn sample(&self) -> f64 {
+        self.sample_at(Instant::now())
+    }
+
+    fn sample_at(&self, now: Instant) -> f64 {
+        let total_bytes = self.total_bytes();
+        let mut average = self.average.lock();
+
+        let elapsed = now.saturating_duration_since(average.last_sample);
+        if elapsed.is_zero() {
+            return average.bytes_per_sec.unwrap_or(0.0);
+        }
+
+        let rate = (total_bytes - average.last_total_bytes) as f64 / elapsed.as_secs_f64();
+        let bytes_per_sec = match average.bytes_per_sec {
+            None => rate,
+            Some(last) => last + THROUGHPUT_SMOOTHING * (rate - last),
+        };
+
+        average.last_sample = now;
+        average.last_total_bytes = total_bytes;
+        average.bytes_per_sec = Some(bytes_per_sec);
+        bytes_per_sec
+    }
+
+    /// Rate computed by the last sample, without updating it
+    pub fn throughput_bytes_per_sec(&self) -> f64 {
+        self.average.lock().bytes_per_sec.unwrap_or(0.0)
+    }
+}
+
+impl Default for ReadThroughput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::read_throughput::ReadThroughput;
+    use std::time::Duration;
+
+    #[test]
+    fn read_throughput_average() {
+        let throughput = ReadThroughput::new();
+        let start = throughput.average.lock().last_sample;
+        assert_eq!(throughput.throughput_bytes_per_sec(), 0.0);
+
+        throughput.add_bytes(1000);
+        assert_eq!(throughput.sample_at(start + Duration::from_secs(1)), 1000.0);
+
+        // A stall only lowers the average gradually
+        assert_eq!(throughput.sample_at(start + Duration::from_secs(2)), 700.0);
+
+        throughput.add_bytes(3000);
+        assert_eq!(throughput.sample_at(start + Duration::from_secs(4)), 940.0);
+        assert_eq!(throughput.throughput_bytes_per_sec(), 940.0);
+        assert_eq!(throughput.total_bytes(), 4000);
+    }
+}
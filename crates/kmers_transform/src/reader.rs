@@ -1,16 +1,17 @@
+use crate::auto_tuner::KmersTransformTuning;
+use crate::counters_check::BucketCountersCheck;
 use crate::processor::{KmersProcessorInitData, KmersTransformProcessor};
 use crate::reads_buffer::ReadsBuffer;
 use crate::resplitter::{KmersTransformResplitter, ResplitterInitData};
+use crate::small_buckets::SmallBucketsAggregator;
 use crate::{
     KmersTransformContext, KmersTransformExecutorFactory, KmersTransformMapProcessor,
     KmersTransformPreprocessor,
 };
 use config::{
-    get_compression_level_info, get_memory_mode, SwapPriority, DEFAULT_OUTPUT_BUFFER_SIZE,
-    DEFAULT_PER_CPU_BUFFER_SIZE, DEFAULT_PREFETCH_AMOUNT, KEEP_FILES,
-    MAXIMUM_JIT_PROCESSED_BUCKETS, MAX_INTERMEDIATE_MAP_SIZE, MIN_BUCKET_CHUNKS_FOR_READING_THREAD,
-    PACKETS_PRIORITY_DEFAULT, PACKETS_PRIORITY_REWRITTEN, PARTIAL_VECS_CHECKPOINT_SIZE,
-    USE_SECOND_BUCKET,
+    get_compression_level_info, get_memory_mode, BucketIndexType, SwapPriority, KEEP_FILES,
+    MAX_INTERMEDIATE_MAP_SIZE, MIN_BUCKET_CHUNKS_FOR_READING_THREAD, PACKETS_PRIORITY_DEFAULT,
+    PACKETS_PRIORITY_REWRITTEN, PARTIAL_VECS_CHECKPOINT_SIZE,
 };
 use instrumenter::local_setup_instrumenter;
 use io::compressed_read::CompressedReadIndipendent;
@@ -33,7 +34,7 @@ use parallel_processor::execution_manager::executor_address::ExecutorAddress;
 use parallel_processor::execution_manager::memory_tracker::MemoryTracker;
 use parallel_processor::execution_manager::objects_pool::{PoolObject, PoolObjectTrait};
 use parallel_processor::execution_manager::packet::{Packet, PacketTrait, PacketsPool};
-use parallel_processor::memory_fs::RemoveFileMode;
+use parallel_processor::memory_fs::{MemoryFs, RemoveFileMode};
 use parallel_processor::mt_debug_counters::counter::{AtomicCounter, SumMode};
 use parallel_processor::mt_debug_counters::declare_counter_i64;
 use parallel_processor::utils::replace_with_async::replace_with_async;
@@ -44,6 +45,9 @@ use std::marker::PhantomData;
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::OwnedSemaphorePermit;
+use utils::spill_layout::SpillKind;
 use utils::track;
 
 local_setup_instrumenter!();
@@ -58,6 +62,21 @@ pub struct InputBucketDesc {
     pub(crate) resplitted: bool,
     pub(crate) rewritten: bool,
     pub(crate) used_hash_bits: usize,
+    /// Size of the bucket file, used as an estimate of the work needed to process it
+    pub(crate) file_size: usize,
+    /// Weight of the bucket known in advance, multiplying file_size when ordering the buckets
+    pub(crate) cost_weight: Option<f64>,
+    /// Input bucket from which this file was derived
+    pub(crate) origin_bucket: BucketIndexType,
+}
+
+impl InputBucketDesc {
+    /// Work needed to process the bucket, in weighted bytes
+    pub(crate) fn scheduling_cost(&self) -> usize {
+        self.cost_weight.map_or(self.file_size, |cost_weight| {
+            (self.file_size as f64 * cost_weight.max(0.0)) as usize
+        })
+    }
 }
 
 impl PoolObjectTrait for InputBucketDesc {
@@ -70,11 +89,16 @@ impl PoolObjectTrait for InputBucketDesc {
             resplitted: false,
             rewritten: false,
             used_hash_bits: 0,
+            file_size: 0,
+            cost_weight: None,
+            origin_bucket: 0,
         }
     }
 
     fn reset(&mut self) {
         self.resplitted = false;
+        self.file_size = 0;
+        self.cost_weight = None;
         self.sub_bucket_counters.clear();
     }
 }
@@ -116,27 +140,26 @@ struct BucketsInfo {
     second_buckets_log_max: usize,
     file_size: usize,
     used_hash_bits: usize,
+    /// The file format stores the second bucket with each read
+    has_second_bucket: bool,
+    /// The second bucket stored with each read can be used instead of recomputing it
+    use_stored_second_bucket: bool,
+    counters_check: Option<BucketCountersCheck>,
+    /// Held until the bucket is read and its rewritten sub-buckets are finalized
+    _open_files_permit: OwnedSemaphorePermit,
 }
 
 impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
-    fn compute_buckets(
+    async fn compute_buckets(
         global_context: &KmersTransformContext<F>,
         file: Packet<InputBucketDesc>,
+        tuning: &KmersTransformTuning,
     ) -> BucketsInfo {
         let second_buckets_log_max = min(
             file.sub_bucket_counters.len().ilog2() as usize,
             global_context.max_second_buckets_count_log2,
         );
 
-        let reader = AsyncBinaryReader::new(
-            &file.path,
-            true,
-            RemoveFileMode::Remove {
-                remove_fs: file.rewritten || !KEEP_FILES.load(Ordering::Relaxed),
-            },
-            DEFAULT_PREFETCH_AMOUNT,
-        );
-
         let second_buckets_max = 1 << second_buckets_log_max;
 
         let mut buckets_remapping = vec![0; second_buckets_max];
@@ -153,7 +176,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
             })
             .collect();
 
-        let file_size = reader.get_file_size();
+        let file_size = file.file_size;
 
         bucket_sizes.make_contiguous().sort();
 
@@ -181,6 +204,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
             let biggest_sub_bucket = bucket_sizes.pop_back().unwrap();
 
             let is_outlier = !file.resplitted
+                && global_context.resplit_policy.allows_resplit(file.file_size)
                 && (total_sequences > 0)
                 && (biggest_sub_bucket.0.count as f64 * unique_estimator_factor
                     >= (MAX_INTERMEDIATE_MAP_SIZE / F::MapProcessorType::MAP_SIZE as u64) as f64);
@@ -220,12 +244,90 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
             queue.push(smallest_bucket);
         }
 
+        // Tiny buckets are processed as a single group, with one processor address and one reading task
+        let is_tiny_bucket = !has_outliers
+            && global_context
+                .tiny_buckets_threshold
+                .is_some_and(|threshold| file_size <= threshold);
+        if is_tiny_bucket {
+            buckets_remapping.fill(0);
+            queue = BinaryHeap::from([(Reverse(sequences_count), 0, false)]);
+        }
+
+        let allow_online_processing = !has_outliers
+            && global_context
+                .online_sub_buckets_limit
+                .map_or(true, |limit| queue.len() <= limit)
+            && global_context.processors_admission.admitted_count(
+                queue.iter().map(|(count, _, _)| count.0),
+                unique_estimator_factor,
+            ) == queue.len();
+
+        let mut queue = queue.into_vec();
+
+        // Combine the tiny buckets so that they share the same buffer and are sent as a single packets stream
+        if let (true, Some(max_combined_size)) = (
+            allow_online_processing,
+            global_context.small_buckets_aggregation,
+        ) {
+            queue.sort_unstable_by_key(|(_, index, _)| *index);
+
+            let mut aggregator = SmallBucketsAggregator::new(max_combined_size);
+            let mut queue_remapping = vec![0; queue.len()];
+            let mut combined_queue: Vec<(Reverse<u64>, usize, bool)> =
+                Vec::with_capacity(queue.len());
+            let mut groups_indexes = Vec::new();
+
+            for (count, index, outlier) in queue {
+                match aggregator.add_bucket(count.0) {
+                    Some(group) if group < groups_indexes.len() => {
+                        let combined_index = groups_indexes[group];
+                        combined_queue[combined_index].0 .0 += count.0;
+                        queue_remapping[index] = combined_index;
+                    }
+                    group => {
+                        if group.is_some() {
+                            groups_indexes.push(combined_queue.len());
+                        }
+                        queue_remapping[index] = combined_queue.len();
+                        combined_queue.push((count, combined_queue.len(), outlier));
+                    }
+                }
+            }
+
+            for bucket in buckets_remapping.iter_mut() {
+                *bucket = queue_remapping[*bucket];
+            }
+            queue = combined_queue;
+        }
+
+        // The bucket and its rewritten sub-buckets are opened only once there is room for all of them
+        let rewritten_count = if allow_online_processing {
+            0
+        } else {
+            queue.iter().filter(|(_, _, outlier)| !outlier).count()
+        };
+        let open_files_permit = global_context
+            .open_buckets_limit
+            .acquire(1 + rewritten_count)
+            .await;
+
+        let is_main_bucket = !file.resplitted && !file.rewritten;
+        let reader = AsyncBinaryReader::new(
+            &file.path,
+            true,
+            RemoveFileMode::Remove {
+                remove_fs: file.rewritten
+                    || !(KEEP_FILES.load(Ordering::Relaxed)
+                        || (is_main_bucket && global_context.keep_main_buckets)),
+            },
+            tuning.prefetch_amount,
+        );
+
         let mut addresses: Vec<_> = (0..queue.len()).map(|_| None).collect();
         let mut register_addresses = Vec::new();
         let mut dbg_counters: Vec<_> = vec![0; queue.len()];
 
-        let allow_online_processing = !has_outliers && queue.len() <= MAXIMUM_JIT_PROCESSED_BUCKETS;
-
         for (count, index, outlier) in queue.into_iter() {
             dbg_counters[index] = count.0;
             addresses[index] = if outlier {
@@ -233,6 +335,12 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
                 let new_address =
                     KmersTransformResplitter::<F>::generate_new_address(ResplitterInitData {
                         bucket_size: count.0 as usize,
+                        estimated_bytes: ResplitterInitData::estimate_bytes(
+                            file_size,
+                            count.0,
+                            sequences_count,
+                        ),
+                        origin_bucket: file.origin_bucket,
                     });
                 register_addresses.push(new_address.clone());
                 Some(AddressMode::Send(new_address))
@@ -244,6 +352,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
                             sub_bucket: index,
                             is_resplitted: file.resplitted,
                             bucket_path: file.path.clone(),
+                            origin_bucket: file.origin_bucket,
                         },
                     );
                     register_addresses.push(new_address.clone());
@@ -251,14 +360,23 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
                 } else {
                     static SUBSPLIT_INDEX: AtomicUsize = AtomicUsize::new(0);
 
+                    let subsplit_index = SUBSPLIT_INDEX.fetch_add(1, Ordering::Relaxed);
                     let writer = CompressedBinaryWriter::new(
-                        &global_context.temp_dir.join(&format!("bucket-rewrite-",)),
+                        &global_context.spill_layout.striped_spill_path(
+                            SpillKind::Rewrite,
+                            &global_context.spill_naming.rewrite_name(
+                                file.origin_bucket,
+                                file.resplitted,
+                                index,
+                            ),
+                            subsplit_index,
+                        ),
                         &(
                             get_memory_mode(SwapPriority::ResultBuckets),
                             PARTIAL_VECS_CHECKPOINT_SIZE,
                             get_compression_level_info(),
                         ),
-                        SUBSPLIT_INDEX.fetch_add(1, Ordering::Relaxed),
+                        subsplit_index,
                     );
 
                     Some(AddressMode::Rewrite(
@@ -284,10 +402,14 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
             reader.get_chunks_count() / MIN_BUCKET_CHUNKS_FOR_READING_THREAD,
         );
 
-        let concurrency = min(
-            min(4, global_context.read_threads_count),
-            min(addr_concurrency, chunks_concurrency),
-        );
+        let concurrency = if is_tiny_bucket {
+            1
+        } else {
+            global_context.executors_limits.bucket_concurrency(min(
+                min(4, global_context.read_threads_count),
+                min(addr_concurrency, chunks_concurrency),
+            ))
+        };
 
         //     println!(
         //     "File:{}\nChunks {} concurrency: {} REMAPPINGS: {:?} // {:?} // {:?} RATIO: {:.2} ADDR_COUNT: {}",
@@ -304,6 +426,13 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
         //     addresses.len()
         // );
 
+        // Only the main buckets store the second bucket, computed with all the counters bits,
+        // while the resplitted and rewritten buckets are written without it
+        let has_second_bucket =
+            global_context.use_second_bucket && !file.resplitted && !file.rewritten;
+        let use_stored_second_bucket =
+            has_second_bucket && file.sub_bucket_counters.len() == second_buckets_max;
+
         BucketsInfo {
             reader,
             concurrency,
@@ -313,6 +442,12 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
             second_buckets_log_max,
             file_size,
             used_hash_bits: file.used_hash_bits,
+            has_second_bucket,
+            use_stored_second_bucket,
+            counters_check: global_context.verify_counters.then(|| {
+                BucketCountersCheck::new(&file.sub_bucket_counters, use_stored_second_bucket)
+            }),
+            _open_files_permit: open_files_permit,
         }
     }
 
@@ -365,7 +500,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
     }
 
     #[instrumenter::track]
-    async fn read_bucket(
+    async fn read_bucket<const WITH_SECOND_BUCKET: bool>(
         global_context: &KmersTransformContext<F>,
         ops: &ExecutorAddressOperations<'_, Self>,
         bucket_info: &BucketsInfo,
@@ -378,7 +513,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
 
         let mut buffers = Vec::with_capacity(bucket_info.addresses.len());
 
-        let mut rewrite_buffer = Vec::with_capacity(DEFAULT_PER_CPU_BUFFER_SIZE.as_bytes());
+        let mut rewrite_buffer = Vec::with_capacity(global_context.output_buffer_size.as_bytes());
 
         track!(
             {
@@ -400,16 +535,23 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
             .get_items_stream::<CompressedReadsBucketDataSerializer<
                 F::AssociatedExtraData,
                 F::FLAGS_COUNT,
-                { USE_SECOND_BUCKET },
+                WITH_SECOND_BUCKET,
             >>(
                 async_reader_thread.clone(),
                 Vec::new(),
                 F::AssociatedExtraData::new_temp_buffer(),
             );
 
+        let mut counters_tallies = bucket_info
+            .counters_check
+            .as_ref()
+            .map(|counters_check| counters_check.new_tallies());
+
         while let Some((read_info, extra_buffer)) = items_iterator.next() {
             let bucket = if has_single_addr {
                 0
+            } else if WITH_SECOND_BUCKET && bucket_info.use_stored_second_bucket {
+                bucket_info.buckets_remapping[read_info.1 as usize]
             } else {
                 let orig_bucket = preprocessor.get_sequence_bucket(
                     global_extra_data,
@@ -421,7 +563,10 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
                 bucket_info.buckets_remapping[orig_bucket]
             };
 
-            let (flags, _second_bucket, mut extra_data, read) = read_info;
+            let (flags, second_bucket, mut extra_data, read) = read_info;
+            if let Some(counters_tallies) = &mut counters_tallies {
+                BucketCountersCheck::tally_read(counters_tallies, second_bucket);
+            }
 
             let ind_read =
                 CompressedReadIndipendent::from_read(&read, &mut buffers[bucket].reads_buffer);
@@ -439,6 +584,9 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
                     AddressMode::Send(address) => {
                         replace_with_async(&mut buffers[bucket], |mut buffer| async move {
                             buffer.sub_bucket = bucket;
+                            if let Some(reads_bytes_limit) = &global_context.reads_bytes_limit {
+                                reads_bytes_limit.acquire(buffer.get_size()).await;
+                            }
                             ops.packet_send(address.clone(), buffer);
                             track!(packets_pool.alloc_packet().await, PACKET_ALLOC_COUNTER)
                         })
@@ -456,6 +604,11 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
             }
             F::AssociatedExtraData::clear_temp_buffer(extra_buffer);
         }
+        if let (Some(counters_check), Some(counters_tallies)) =
+            (&bucket_info.counters_check, &counters_tallies)
+        {
+            counters_check.add_found(counters_tallies);
+        }
 
         for (bucket, (mut packet, address)) in buffers
             .drain(..)
@@ -466,6 +619,9 @@ impl<F: KmersTransformExecutorFactory> KmersTransformReader<F> {
                 packet.sub_bucket = bucket;
                 match address {
                     AddressMode::Send(address) => {
+                        if let Some(reads_bytes_limit) = &global_context.reads_bytes_limit {
+                            reads_bytes_limit.acquire(packet.get_size()).await;
+                        }
                         ops.packet_send(address.clone(), packet);
                     }
                     AddressMode::Rewrite(writer, seq_count, _) => {
@@ -510,11 +666,48 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformReader<F>
                     address.receive_packet().await.unwrap(),
                     PACKET_WAITING_COUNTER
                 );
+
                 let is_main_bucket = !file.resplitted && !file.rewritten;
                 let is_resplitted = file.resplitted;
-                let buckets_info = Self::compute_buckets(global_context, file);
+                let origin_bucket = file.origin_bucket;
+
+                // Only the main buckets not yet started are skipped, keeping their files,
+                // while the sub-buckets are processed so that the started buckets are complete
+                if is_main_bucket
+                    && (global_context.is_cancelled() || global_context.is_past_deadline())
+                {
+                    global_context
+                        .unprocessed_buckets
+                        .lock()
+                        .push(file.path.clone());
+                    assert!(track!(
+                        address.receive_packet().await.is_none(),
+                        PACKET_WAITING_COUNTER
+                    ));
+                    continue;
+                }
+
+                let tuning = match &global_context.auto_tuner {
+                    Some(auto_tuner) if is_main_bucket => Some(auto_tuner.next_config()),
+                    _ => None,
+                };
+                let tuning_params = tuning.map(|(_, params)| params).unwrap_or_default();
+                let bucket_path = file.path.clone();
+
+                let buckets_info =
+                    Self::compute_buckets(global_context, file, &tuning_params).await;
+
+                // Each output address holds the bucket until it is processed,
+                // the rewritten buckets take over the units of their rewrite addresses
+                if is_main_bucket {
+                    global_context.bucket_completion.acquire(origin_bucket, 1);
+                }
+                global_context
+                    .bucket_completion
+                    .acquire(origin_bucket, buckets_info.addresses.len());
 
                 let reader_lock = global_context.reader_init_lock.lock().await;
+                let start_time = Instant::now();
 
                 address.declare_addresses(
                     buckets_info.register_addresses.clone(),
@@ -523,7 +716,10 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformReader<F>
 
                 // FIXME: Better threads management
                 while async_threads.len() < buckets_info.concurrency {
-                    async_threads.push(AsyncReaderThread::new(DEFAULT_OUTPUT_BUFFER_SIZE / 2, 4));
+                    async_threads.push(AsyncReaderThread::new(
+                        global_context.async_reader_config.buffer_size,
+                        global_context.async_reader_config.prefetch_slots,
+                    ));
                 }
 
                 let mut spawner = address.make_spawner();
@@ -542,26 +738,61 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformReader<F>
                     let buckets_info = &buckets_info;
                     let packets_pool = address
                         .pool_alloc_await(max(
-                            global_context.max_buckets / 2,
-                            2 * buckets_info.addresses.len(),
+                            buckets_info.addresses.len() + 1,
+                            (max(
+                                global_context.max_buckets / 2,
+                                2 * buckets_info.addresses.len(),
+                            ) as f64
+                                * tuning_params.packets_pool_factor)
+                                as usize,
                         ))
                         .await;
 
                     spawner.spawn_executor(async move {
-                        Self::read_bucket(
-                            global_context,
-                            address,
-                            buckets_info,
-                            async_thread,
-                            packets_pool,
-                        )
-                        .await;
+                        if buckets_info.has_second_bucket {
+                            Self::read_bucket::<true>(
+                                global_context,
+                                address,
+                                buckets_info,
+                                async_thread,
+                                packets_pool,
+                            )
+                            .await;
+                        } else {
+                            Self::read_bucket::<false>(
+                                global_context,
+                                address,
+                                buckets_info,
+                                async_thread,
+                                packets_pool,
+                            )
+                            .await;
+                        }
                     });
                 }
 
                 drop(reader_lock);
                 spawner.executors_await().await;
                 drop(spawner);
+                global_context
+                    .read_throughput
+                    .add_bytes(buckets_info.file_size as u64);
+
+                if let Some(counters_check) = &buckets_info.counters_check {
+                    if let Err(mismatch) = counters_check.check(&bucket_path) {
+                        panic!("{}", mismatch);
+                    }
+                }
+
+                if let (Some(auto_tuner), Some((tuning_index, _))) =
+                    (&global_context.auto_tuner, tuning)
+                {
+                    auto_tuner.record(
+                        tuning_index,
+                        buckets_info.file_size as u64,
+                        start_time.elapsed(),
+                    );
+                }
 
                 for addr in buckets_info.addresses {
                     if let AddressMode::Rewrite(writer, seq_count, init_data) = addr {
@@ -576,14 +807,14 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformReader<F>
                             vec![new_bucket_address.clone()],
                             PACKETS_PRIORITY_REWRITTEN,
                         );
+                        let path = writer.get_path();
+                        writer.finalize();
+                        let file_size = MemoryFs::get_file_size(&path).unwrap_or(0);
+
                         address.get_context().send_packet(
                             new_bucket_address,
                             Packet::new_simple(InputBucketDesc {
-                                path: {
-                                    let path = writer.get_path();
-                                    writer.finalize();
-                                    path
-                                },
+                                path,
                                 sub_bucket_counters: vec![BucketCounter {
                                     count: seq_count.into_inner(),
                                 }],
@@ -591,6 +822,9 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformReader<F>
                                 rewritten: true,
                                 used_hash_bits: init_data.used_hash_bits
                                     + init_data.buckets_hash_bits,
+                                file_size,
+                                cost_weight: None,
+                                origin_bucket,
                             }),
                         );
                     }
@@ -612,6 +846,10 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformReader<F>
                         .fetch_add(buckets_info.file_size, Ordering::Relaxed);
                 }
 
+                global_context
+                    .bucket_completion
+                    .release_deferred(origin_bucket);
+
                 assert!(track!(
                     address.receive_packet().await.is_none(),
                     PACKET_WAITING_COUNTER
@@ -0,0 +1,37 @@
+use std::cmp::min;
+use tokio::sync::Semaphore;
+
+/// Limits the bytes of the reads buffers sent by the readers and not yet received by
+/// the processors or the resplitters, blocking the readers once the limit is reached
+pub struct ReadsBytesLimit {
+    max_bytes: usize,
+    semaphore: Semaphore,
+}
+
+impl ReadsBytesLimit {
+    pub fn new(max_bytes: usize) -> Self {
+        let max_bytes = max_bytes.clamp(1, min(Semaphore::MAX_PERMITS, u32::MAX as usize));
+        Self {
+            max_bytes,
+            semaphore: Semaphore::new(max_bytes),
+        }
+    }
+
+    /// A buffer larger than the limit is charged the whole limit, so that it can still be sent
+    fn charged_bytes(&self, bytes: usize) -> u32 {
+        min(bytes, self.max_bytes) as u32
+    }
+
+    pub async fn acquire(&self, bytes: usize) {
+        self.semaphore
+            .acquire_many(self.charged_bytes(bytes))
+            .await
+            .unwrap()
+            .forget();
+    }
+
+    pub fn release(&self, bytes: usize) {
+        self.semaphore
+            .add_permits(self.charged_bytes(bytes) as usize);
+    }
+}
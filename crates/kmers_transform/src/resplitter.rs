@@ -3,7 +3,7 @@ use crate::reads_buffer::ReadsBuffer;
 use crate::{KmersTransformContext, KmersTransformExecutorFactory};
 use config::{
     get_compression_level_info, get_memory_mode, BucketIndexType, SwapPriority,
-    DEFAULT_PER_CPU_BUFFER_SIZE, MAXIMUM_JIT_PROCESSED_BUCKETS, MAX_RESPLIT_BUCKETS_COUNT_LOG,
+    MAXIMUM_JIT_PROCESSED_BUCKETS, MAX_RESPLIT_BUCKETS_COUNT_LOG,
     MINIMIZER_BUCKETS_CHECKPOINT_SIZE, PACKETS_PRIORITY_DONE_RESPLIT,
 };
 use hashes::HashableSequence;
@@ -21,7 +21,8 @@ use parallel_processor::execution_manager::executor::{
 };
 use parallel_processor::execution_manager::executor_address::ExecutorAddress;
 use parallel_processor::execution_manager::memory_tracker::MemoryTracker;
-use parallel_processor::execution_manager::packet::Packet;
+use parallel_processor::execution_manager::packet::{Packet, PacketTrait};
+use parallel_processor::memory_fs::MemoryFs;
 use parallel_processor::mt_debug_counters::counter::{AtomicCounter, SumMode};
 use parallel_processor::mt_debug_counters::declare_counter_i64;
 use std::cmp::{max, min};
@@ -30,6 +31,7 @@ use std::marker::PhantomData;
 use std::ops::Deref;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use utils::spill_layout::SpillKind;
 use utils::track;
 
 local_setup_instrumenter!();
@@ -53,10 +55,10 @@ static PACKET_WAITING_COUNTER: AtomicCounter<SumMode> =
     declare_counter_i64!("kt_packet_wait_resplitter", SumMode, false);
 
 impl<F: KmersTransformExecutorFactory> KmersTransformResplitter<F> {
-    fn init_processing(
+    fn subsplit_buckets_count_log(
         global_context: &KmersTransformContext<F>,
         init_data: &ResplitterInitData,
-    ) -> BucketsResplitInfo {
+    ) -> usize {
         let total_sequences = global_context.total_sequences.load(Ordering::Relaxed);
         let unique_kmers = global_context.unique_kmers.load(Ordering::Relaxed);
 
@@ -77,12 +79,38 @@ impl<F: KmersTransformExecutorFactory> KmersTransformResplitter<F> {
             .ilog2() as usize,
         );
 
+        // Enough sub-buckets to keep each one below the target size, if the sequences are spread evenly
+        match global_context.target_subbucket_bytes {
+            Some(target_subbucket_bytes) => min(
+                MAX_RESPLIT_BUCKETS_COUNT_LOG,
+                max(
+                    subsplit_buckets_count_log,
+                    init_data
+                        .estimated_bytes
+                        .div_ceil(target_subbucket_bytes)
+                        .next_power_of_two()
+                        .ilog2() as usize,
+                ),
+            ),
+            None => subsplit_buckets_count_log,
+        }
+    }
+
+    fn init_processing(
+        global_context: &KmersTransformContext<F>,
+        init_data: &ResplitterInitData,
+        subsplit_buckets_count_log: usize,
+    ) -> BucketsResplitInfo {
+        let resplit_index = BUCKET_RESPLIT_COUNTER.fetch_add(1, Ordering::Relaxed);
         let buckets = Arc::new(MultiThreadBuckets::new(
             1 << subsplit_buckets_count_log,
-            global_context.temp_dir.join(format!(
-                "resplit-bucket{}",
-                BUCKET_RESPLIT_COUNTER.fetch_add(1, Ordering::Relaxed)
-            )),
+            global_context.spill_layout.striped_spill_path(
+                SpillKind::Resplit,
+                &global_context
+                    .spill_naming
+                    .resplit_name(init_data.origin_bucket, resplit_index),
+                resplit_index,
+            ),
             &(
                 get_memory_mode(SwapPriority::MinimizerBuckets),
                 MINIMIZER_BUCKETS_CHECKPOINT_SIZE,
@@ -95,7 +123,7 @@ impl<F: KmersTransformExecutorFactory> KmersTransformResplitter<F> {
             .collect();
 
         // TODO: Find best count of writing threads
-        let executors_count = 4; //global_context.read_threads_count;
+        let executors_count = global_context.executors_limits.bucket_concurrency(4);
         BucketsResplitInfo {
             // (
             //     (
@@ -126,7 +154,10 @@ impl<F: KmersTransformExecutorFactory> KmersTransformResplitter<F> {
             >,
         >::new(
             &resplit_info.buckets,
-            BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, resplit_info.buckets.count()),
+            BucketsThreadBuffer::new(
+                global_context.output_buffer_size,
+                resplit_info.buckets.count(),
+            ),
         );
 
         let mut local_counters = vec![0u8; resplit_info.global_counters.len()];
@@ -137,6 +168,9 @@ impl<F: KmersTransformExecutorFactory> KmersTransformResplitter<F> {
 
         while let Some(input_packet) = track!(ops.receive_packet().await, PACKET_WAITING_COUNTER) {
             let input_packet = input_packet.deref();
+            if let Some(reads_bytes_limit) = &global_context.reads_bytes_limit {
+                reads_bytes_limit.release(input_packet.get_size());
+            }
 
             let mut preprocess_info = Default::default();
 
@@ -189,6 +223,22 @@ impl<F: KmersTransformExecutorFactory> KmersTransformResplitter<F> {
 #[derive(Clone)]
 pub struct ResplitterInitData {
     pub bucket_size: usize,
+    /// Bytes of the bucket file belonging to the resplitted sub-buckets
+    pub estimated_bytes: u64,
+    pub origin_bucket: BucketIndexType,
+}
+
+impl ResplitterInitData {
+    /// Share of the file bytes belonging to the sub-buckets with the given sequences,
+    /// weighted by the sub-buckets counters
+    pub fn estimate_bytes(file_size: usize, sequences: u64, total_sequences: u64) -> u64 {
+        if total_sequences == 0 {
+            return file_size as u64;
+        }
+        // Both factors can be large, their product does not fit an u64
+        u64::try_from(file_size as u128 * sequences as u128 / total_sequences as u128)
+            .unwrap_or(u64::MAX)
+    }
 }
 
 impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformResplitter<F> {
@@ -211,7 +261,15 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformResplitte
             while let Ok((address, init_data)) =
                 track!(receiver.obtain_address().await, ADDR_WAITING_COUNTER)
             {
-                let resplit_info = Self::init_processing(global_context, &init_data);
+                let subsplit_buckets_count_log =
+                    Self::subsplit_buckets_count_log(global_context, &init_data);
+                // Held until the sub-buckets files are finalized
+                let open_files_permit = global_context
+                    .open_buckets_limit
+                    .acquire(1 << subsplit_buckets_count_log)
+                    .await;
+                let resplit_info =
+                    Self::init_processing(global_context, &init_data, subsplit_buckets_count_log);
 
                 let mut spawner = address.make_spawner();
 
@@ -227,6 +285,9 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformResplitte
                     1 << resplit_info.subsplit_buckets_count_log,
                     Ordering::Relaxed,
                 );
+                global_context
+                    .bucket_completion
+                    .acquire(init_data.origin_bucket, resplit_info.output_addresses.len());
                 address.declare_addresses(
                     resplit_info.output_addresses.clone(),
                     PACKETS_PRIORITY_DONE_RESPLIT,
@@ -245,14 +306,23 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformResplitte
                     address.packet_send(
                         resplit_info.output_addresses[i].clone(),
                         Packet::new_simple(InputBucketDesc {
+                            file_size: MemoryFs::get_file_size(&bucket).unwrap_or(0),
                             path: bucket,
                             sub_bucket_counters: vec![sub_bucket_count],
                             resplitted: true,
                             rewritten: false,
                             used_hash_bits: 0,
+                            cost_weight: None,
+                            origin_bucket: init_data.origin_bucket,
                         }),
                     );
                 }
+
+                drop(open_files_permit);
+
+                global_context
+                    .bucket_completion
+                    .release_deferred(init_data.origin_bucket);
             }
         }
     }
@@ -291,3 +361,22 @@ impl<F: KmersTransformExecutorFactory> AsyncExecutor for KmersTransformResplitte
 //         ()
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use crate::resplitter::ResplitterInitData;
+
+    #[test]
+    fn resplit_estimated_bytes() {
+        assert_eq!(ResplitterInitData::estimate_bytes(1000, 25, 100), 250);
+        assert_eq!(ResplitterInitData::estimate_bytes(1000, 25, 0), 1000);
+
+        // A big bucket with many sequences, whose product overflows an u64
+        let file_size = 64 << 30;
+        let sequences = 1 << 40;
+        assert_eq!(
+            ResplitterInitData::estimate_bytes(file_size, sequences / 4, sequences),
+            16 << 30
+        );
+    }
+}
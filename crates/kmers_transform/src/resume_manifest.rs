@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Input buckets whose output was committed by the previous attempts of an interrupted run.
+/// Each attempt appends the buckets it completed, one "bucket <path>" line each, followed by a
+/// "commit <generation>" line once its output is durable. The lines after the last commit belong
+/// to an attempt that stopped before its output was durable, they are ignored and then truncated
+pub struct ResumeManifest {
+    path: PathBuf,
+    processed_buckets: HashSet<PathBuf>,
+    generation: usize,
+    /// Length of the manifest up to the end of the last commit
+    committed_len: u64,
+}
+
+impl ResumeManifest {
+    /// Opens the manifest, loading the buckets of the committed attempts if it exists
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let mut processed_buckets = HashSet::new();
+        let mut generation = 0;
+        let mut committed_len = 0;
+
+        let mut contents = String::new();
+        match File::open(&path) {
+            Ok(mut file) => {
+                file.read_to_string(&mut contents)?;
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+
+        let mut pending_buckets = Vec::new();
+        let mut line_start = 0;
+        // The last line is incomplete if the attempt was interrupted while appending it
+        for line in contents
+            .split_inclusive('\n')
+            .filter(|line| line.ends_with('\n'))
+        {
+            line_start += line.len();
+            let line = line.trim_end_matches('\n');
+
+            if let Some(bucket) = line.strip_prefix("bucket ") {
+                pending_buckets.push(PathBuf::from(bucket));
+            } else if let Some(committed_generation) = line.strip_prefix("commit ") {
+                if committed_generation.parse() != Ok(generation) {
+                    return Err(std::io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "Resume manifest {} has commit {} instead of {}",
+                            path.as_ref().display(),
+                            committed_generation,
+                            generation
+                        ),
+                    ));
+                }
+                processed_buckets.extend(pending_buckets.drain(..));
+                generation += 1;
+                committed_len = line_start as u64;
+            } else {
+                return Err(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "Invalid line in resume manifest {}: {}",
+                        path.as_ref().display(),
+                        line
+                    ),
+                ));
+            }
+        }
+
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            processed_buckets,
+            generation,
+            committed_len,
+        })
+    }
+
+    /// Count of the committed attempts, which is also the generation of the current attempt output
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn is_processed(&self, bucket: &Path) -> bool {
+        self.processed_buckets.contains(bucket)
+    }
+
+    /// Records the buckets completed by the current attempt. Must be called only once the output
+    /// of the attempt is durable, as a new run will skip these buckets and use that output instead
+    pub fn commit(&mut self, completed_buckets: &[PathBuf]) -> std::io::Result<()> {
+        let mut lines = String::new();
+        for bucket in completed_buckets {
+            lines.push_str(&format!("bucket {}\n", bucket.display()));
+        }
+        lines.push_str(&format!("commit {}\n", self.generation));
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&self.path)?;
+        // Drop the uncommitted lines, that would otherwise be committed along with these buckets
+        file.set_len(self.committed_len)?;
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(lines.as_bytes())?;
+        file.sync_all()?;
+        self.committed_len += lines.len() as u64;
+
+        self.processed_buckets
+            .extend(completed_buckets.iter().cloned());
+        self.generation += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resume_manifest::ResumeManifest;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn resume_manifest_reload() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("resume-manifest");
+
+        let mut manifest = ResumeManifest::open(&path).unwrap();
+        assert_eq!(manifest.generation(), 0);
+        assert!(!manifest.is_processed(Path::new("bucket.0")));
+        manifest
+            .commit(&[PathBuf::from("bucket.0"), PathBuf::from("bucket.3")])
+            .unwrap();
+        drop(manifest);
+
+        // An attempt interrupted before its output was durable, while appending its buckets
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"bucket bucket.1\nbucket buck")
+            .unwrap();
+
+        let mut manifest = ResumeManifest::open(&path).unwrap();
+        assert_eq!(manifest.generation(), 1);
+        assert!(manifest.is_processed(Path::new("bucket.0")));
+        assert!(manifest.is_processed(Path::new("bucket.3")));
+        assert!(!manifest.is_processed(Path::new("bucket.1")));
+
+        // The uncommitted lines are ignored by the next commits too
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"et.2\n")
+            .unwrap();
+        manifest.commit(&[PathBuf::from("bucket.4")]).unwrap();
+
+        let manifest = ResumeManifest::open(&path).unwrap();
+        assert_eq!(manifest.generation(), 2);
+        assert!(manifest.is_processed(Path::new("bucket.4")));
+        assert!(!manifest.is_processed(Path::new("bucket.1")));
+        assert!(!manifest.is_processed(Path::new("bucket.2")));
+
+        std::fs::write(&path, "bucket bucket.0\ncommit 1\n").unwrap();
+        assert!(ResumeManifest::open(&path).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+/// Aggregates the tiny buckets produced by a reader, so that they share a single buffer
+/// and are submitted as combined packets instead of one mostly empty packet for each bucket
+pub struct SmallBucketsAggregator {
+    max_combined_size: u64,
+    groups_sizes: Vec<u64>,
+}
+
+impl SmallBucketsAggregator {
+    pub fn new(max_combined_size: u64) -> Self {
+        Self {
+            max_combined_size,
+            groups_sizes: Vec::new(),
+        }
+    }
+
+    /// Returns the combined group assigned to the bucket, or None if the bucket is big enough to be submitted alone
+    pub fn add_bucket(&mut self, size: u64) -> Option<usize> {
+        if size >= self.max_combined_size {
+            return None;
+        }
+
+        match self.groups_sizes.last_mut() {
+            Some(last_size) if *last_size + size <= self.max_combined_size => {
+                *last_size += size;
+            }
+            _ => {
+                self.groups_sizes.push(size);
+            }
+        }
+
+        Some(self.groups_sizes.len() - 1)
+    }
+
+    pub fn get_groups_sizes(&self) -> &[u64] {
+        &self.groups_sizes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::small_buckets::SmallBucketsAggregator;
+
+    #[test]
+    fn tiny_buckets_are_combined() {
+        let mut aggregator = SmallBucketsAggregator::new(1000);
+
+        let groups: Vec<_> = (0..256).map(|_| aggregator.add_bucket(10)).collect();
+        assert!(groups.iter().all(|g| g.is_some()));
+        assert_eq!(aggregator.get_groups_sizes(), &[1000, 1000, 560]);
+        assert_eq!(groups[0], Some(0));
+        assert_eq!(groups[255], Some(2));
+
+        assert_eq!(aggregator.add_bucket(5000), None);
+        assert_eq!(aggregator.get_groups_sizes().len(), 3);
+    }
+}
@@ -0,0 +1,178 @@
+use std::cmp::max;
+use std::fmt::{Display, Formatter};
+
+/// Split of the available threads between the bucket readers and the compute executors
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum ThreadSplitPolicy {
+    /// A quarter of the threads for computing and the rest for reading
+    #[default]
+    Auto,
+    /// Fraction of the threads used for reading, in the (0, 1) range.
+    /// Needs at least 2 threads, as both the readers and the executors get one or more
+    ReadFraction(f64),
+    Explicit {
+        read: usize,
+        compute: usize,
+    },
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ThreadSplit {
+    pub read_threads_count: usize,
+    pub compute_threads_count: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ThreadSplitError {
+    InvalidReadFraction(f64),
+    ZeroThreads {
+        read: usize,
+        compute: usize,
+    },
+    TooManyThreads {
+        read: usize,
+        compute: usize,
+        threads_count: usize,
+    },
+}
+
+impl Display for ThreadSplitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ThreadSplitError::InvalidReadFraction(fraction) => {
+                write!(
+                    f,
+                    "Read threads fraction {} is not in the (0, 1) range",
+                    fraction
+                )
+            }
+            ThreadSplitError::ZeroThreads { read, compute } => write!(
+                f,
+                "Read threads ({}) and compute threads ({}) must be both at least 1",
+                read, compute
+            ),
+            ThreadSplitError::TooManyThreads {
+                read,
+                compute,
+                threads_count,
+            } => write!(
+                f,
+                "Read threads ({}) and compute threads ({}) exceed the {} available threads",
+                read, compute, threads_count
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ThreadSplitError {}
+
+impl ThreadSplitPolicy {
+    pub fn split(&self, threads_count: usize) -> Result<ThreadSplit, ThreadSplitError> {
+        match *self {
+            ThreadSplitPolicy::Auto => Ok(ThreadSplit {
+                read_threads_count: max(1, threads_count / 4 * 3),
+                compute_threads_count: max(1, threads_count / 4),
+            }),
+            ThreadSplitPolicy::ReadFraction(fraction) => {
+                if !(fraction > 0.0 && fraction < 1.0) {
+                    return Err(ThreadSplitError::InvalidReadFraction(fraction));
+                }
+                // At least a reader and an executor are needed, without exceeding the threads count
+                if threads_count < 2 {
+                    return Err(ThreadSplitError::TooManyThreads {
+                        read: 1,
+                        compute: 1,
+                        threads_count,
+                    });
+                }
+                let read_threads_count = ((threads_count as f64 * fraction).round() as usize)
+                    .clamp(1, threads_count - 1);
+                Ok(ThreadSplit {
+                    read_threads_count,
+                    compute_threads_count: threads_count - read_threads_count,
+                })
+            }
+            ThreadSplitPolicy::Explicit { read, compute } => {
+                if read == 0 || compute == 0 {
+                    Err(ThreadSplitError::ZeroThreads { read, compute })
+                } else if read + compute > threads_count {
+                    Err(ThreadSplitError::TooManyThreads {
+                        read,
+                        compute,
+                        threads_count,
+                    })
+                } else {
+                    Ok(ThreadSplit {
+                        read_threads_count: read,
+                        compute_threads_count: compute,
+                    })
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::thread_split::{ThreadSplit, ThreadSplitError, ThreadSplitPolicy};
+
+    #[test]
+    fn thread_split_validation() {
+        assert_eq!(
+            ThreadSplitPolicy::Auto.split(16).unwrap(),
+            ThreadSplit {
+                read_threads_count: 12,
+                compute_threads_count: 4
+            }
+        );
+        assert_eq!(
+            ThreadSplitPolicy::ReadFraction(0.25).split(16).unwrap(),
+            ThreadSplit {
+                read_threads_count: 4,
+                compute_threads_count: 12
+            }
+        );
+        assert_eq!(
+            ThreadSplitPolicy::ReadFraction(0.9).split(2).unwrap(),
+            ThreadSplit {
+                read_threads_count: 1,
+                compute_threads_count: 1
+            }
+        );
+        assert_eq!(
+            ThreadSplitPolicy::ReadFraction(0.5).split(1),
+            Err(ThreadSplitError::TooManyThreads {
+                read: 1,
+                compute: 1,
+                threads_count: 1
+            })
+        );
+        assert_eq!(
+            ThreadSplitPolicy::ReadFraction(1.5).split(16),
+            Err(ThreadSplitError::InvalidReadFraction(1.5))
+        );
+        assert_eq!(
+            ThreadSplitPolicy::Explicit {
+                read: 0,
+                compute: 4
+            }
+            .split(16),
+            Err(ThreadSplitError::ZeroThreads {
+                read: 0,
+                compute: 4
+            })
+        );
+        assert_eq!(
+            ThreadSplitPolicy::Explicit {
+                read: 10,
+                compute: 8
+            }
+            .split(16),
+            Err(ThreadSplitError::TooManyThreads {
+                read: 10,
+                compute: 8,
+                threads_count: 16
+            })
+        );
+    }
+}
@@ -0,0 +1,63 @@
+use std::ops::AddAssign;
+
+/// Bases of the input reads, counted once for each read while they are bucketed
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct BasesComposition {
+    /// Counts of the A, C, G and T bases, in this order. The other bases are not counted
+    pub counts: [u64; 4],
+}
+
+impl BasesComposition {
+    #[inline(always)]
+    pub fn count_sequence(&mut self, sequence: &[u8]) {
+        for base in sequence {
+            match *base {
+                b'A' | b'a' => self.counts[0] += 1,
+                b'C' | b'c' => self.counts[1] += 1,
+                b'G' | b'g' => self.counts[2] += 1,
+                b'T' | b't' => self.counts[3] += 1,
+                _ => {}
+            }
+        }
+    }
+
+    pub fn total_bases(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Fraction of G and C in the counted bases, None if no base was counted
+    pub fn gc_fraction(&self) -> Option<f64> {
+        let [_, c, g, _] = self.counts;
+        let total = self.total_bases();
+        (total > 0).then(|| (c + g) as f64 / total as f64)
+    }
+}
+
+impl AddAssign for BasesComposition {
+    fn add_assign(&mut self, other: Self) {
+        for (count, other_count) in self.counts.iter_mut().zip(other.counts) {
+            *count += other_count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bases_composition::BasesComposition;
+
+    #[test]
+    fn bases_composition_counts() {
+        let mut composition = BasesComposition::default();
+        assert_eq!(composition.gc_fraction(), None);
+
+        composition.count_sequence(b"ACGTTGCA");
+        composition.count_sequence(b"ggNNgca");
+        assert_eq!(composition.counts, [3, 3, 5, 2]);
+
+        let mut total = BasesComposition::default();
+        total += composition;
+        total += composition;
+        assert_eq!(total.total_bases(), 26);
+        assert_eq!(total.gc_fraction(), Some(16.0 / 26.0));
+    }
+}
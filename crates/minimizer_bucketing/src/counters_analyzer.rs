@@ -1,10 +1,101 @@
 use config::BucketIndexType;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::path::Path;
+use std::io::{BufReader, BufWriter, ErrorKind, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicU64;
 
+/// Identifies the counters files, that can be produced and consumed by separate runs
+const COUNTERS_FILE_MAGIC: [u8; 8] = *b"GGCATCNT";
+/// Version of the counters file format, increase it on any layout change
+pub const COUNTERS_FILE_VERSION: u32 = 1;
+
+/// Header written at the start of the counters files
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CountersFileHeader {
+    magic: [u8; 8],
+    pub version: u32,
+    pub buckets_count: u64,
+    pub k: u64,
+}
+
+impl CountersFileHeader {
+    fn new(buckets_count: usize, k: usize) -> Self {
+        Self {
+            magic: COUNTERS_FILE_MAGIC,
+            version: COUNTERS_FILE_VERSION,
+            buckets_count: buckets_count as u64,
+            k: k as u64,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CountersError {
+    /// The counters file does not exist, it can be recomputed
+    NotFound(PathBuf),
+    /// The counters file exists but cannot be read or decoded
+    Corrupt(PathBuf),
+    /// The counters file was written with an incompatible format version
+    VersionMismatch { path: PathBuf, found: u32 },
+    /// The counters file was produced with different buckets count or k
+    ParametersMismatch {
+        path: PathBuf,
+        expected: CountersFileHeader,
+        found: CountersFileHeader,
+    },
+    /// The counters file has no counters for the requested bucket
+    MissingBucket { path: PathBuf, bucket: usize },
+    /// The counters file cannot be written
+    WriteFailed(PathBuf, std::io::Error),
+}
+
+impl Display for CountersError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CountersError::NotFound(path) => {
+                write!(f, "Counters file {} not found", path.display())
+            }
+            CountersError::Corrupt(path) => {
+                write!(f, "Counters file {} is corrupt", path.display())
+            }
+            CountersError::VersionMismatch { path, found } => write!(
+                f,
+                "Counters file {} has format version {}, expected version {}",
+                path.display(),
+                found,
+                COUNTERS_FILE_VERSION
+            ),
+            CountersError::ParametersMismatch {
+                path,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Counters file {} was produced with {} buckets and k={}, expected {} buckets and k={}",
+                path.display(),
+                found.buckets_count,
+                found.k,
+                expected.buckets_count,
+                expected.k
+            ),
+            CountersError::MissingBucket { path, bucket } => write!(
+                f,
+                "Counters file {} has no counters for bucket {}",
+                path.display(),
+                bucket
+            ),
+            CountersError::WriteFailed(path, err) => {
+                write!(f, "Cannot write counters file {}: {}", path.display(), err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CountersError {}
+
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
 pub struct BucketCounter {
     pub count: u64,
@@ -75,9 +166,170 @@ impl CountersAnalyzer {
         );
     }
 
-    pub fn load_from_file(path: impl AsRef<Path>, remove: bool) -> Self {
-        let file = BufReader::new(File::open(&path).unwrap());
-        let rval: CountersAnalyzer = bincode::deserialize_from(file).unwrap();
+    /// Reads the header of the counters file, checking that its format version is supported
+    fn read_header(
+        path: &Path,
+        file: &mut BufReader<File>,
+    ) -> Result<CountersFileHeader, CountersError> {
+        let header: CountersFileHeader = bincode::deserialize_from(file)
+            .map_err(|_| CountersError::Corrupt(path.to_path_buf()))?;
+
+        if header.magic != COUNTERS_FILE_MAGIC {
+            return Err(CountersError::Corrupt(path.to_path_buf()));
+        }
+        if header.version != COUNTERS_FILE_VERSION {
+            return Err(CountersError::VersionMismatch {
+                path: path.to_path_buf(),
+                found: header.version,
+            });
+        }
+        Ok(header)
+    }
+
+    fn open_counters_file(path: &Path) -> Result<BufReader<File>, CountersError> {
+        Ok(BufReader::new(File::open(path).map_err(
+            |err| match err.kind() {
+                ErrorKind::NotFound => CountersError::NotFound(path.to_path_buf()),
+                _ => CountersError::Corrupt(path.to_path_buf()),
+            },
+        )?))
+    }
+
+    /// Opens the counters file without loading it, reading the counters of each bucket on demand.
+    /// Only the offsets of the buckets are kept in memory
+    pub fn open_streaming(
+        path: impl AsRef<Path>,
+        remove: bool,
+    ) -> Result<StreamingCountersAnalyzer, CountersError> {
+        Self::try_open_streaming(path.as_ref(), remove, None)
+    }
+
+    /// Like open_streaming, checking that the file was produced with the given buckets count and k
+    pub fn open_streaming_checked(
+        path: impl AsRef<Path>,
+        remove: bool,
+        buckets_count: usize,
+        k: usize,
+    ) -> Result<StreamingCountersAnalyzer, CountersError> {
+        Self::try_open_streaming(
+            path.as_ref(),
+            remove,
+            Some(CountersFileHeader::new(buckets_count, k)),
+        )
+    }
+
+    /// Like open_streaming_checked, but a missing counters file is not an error: a warning is
+    /// printed and None is returned, so that uniform buckets weights are used instead.
+    /// A counters file that exists but cannot be used is still an error
+    pub fn open_streaming_if_exists(
+        path: impl AsRef<Path>,
+        remove: bool,
+        buckets_count: usize,
+        k: usize,
+    ) -> Result<Option<StreamingCountersAnalyzer>, CountersError> {
+        match Self::open_streaming_checked(path, remove, buckets_count, k) {
+            Ok(counters) => Ok(Some(counters)),
+            Err(err @ CountersError::NotFound(_)) => {
+                println!("Warning: {}, using uniform buckets weights", err);
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn try_open_streaming(
+        path: &Path,
+        remove: bool,
+        expected: Option<CountersFileHeader>,
+    ) -> Result<StreamingCountersAnalyzer, CountersError> {
+        let corrupt = || CountersError::Corrupt(path.to_path_buf());
+
+        let mut file = Self::open_counters_file(path)?;
+        let header = Self::read_header(path, &mut file)?;
+        Self::check_header(path, header, expected)?;
+        let file_size = file.get_ref().metadata().map_err(|_| corrupt())?.len();
+
+        // Same layout as the bincode serialization of CountersAnalyzer
+        let buckets_count: u64 = bincode::deserialize_from(&mut file).map_err(|_| corrupt())?;
+        let length_size = bincode::serialized_size(&buckets_count).map_err(|_| corrupt())?;
+        let counter_size =
+            bincode::serialized_size(&BucketCounter { count: 0 }).map_err(|_| corrupt())?;
+        // Each bucket stores at least its counters count, so a larger count cannot fit the file
+        if buckets_count != header.buckets_count || buckets_count > file_size / length_size {
+            return Err(corrupt());
+        }
+
+        let mut buckets_offsets = Vec::with_capacity(buckets_count as usize);
+        for _ in 0..buckets_count {
+            let offset = file.stream_position().map_err(|_| corrupt())?;
+            let counters_count: u64 =
+                bincode::deserialize_from(&mut file).map_err(|_| corrupt())?;
+            let next_offset = counters_count
+                .checked_mul(counter_size)
+                .and_then(|size| size.checked_add(offset + length_size))
+                .filter(|next_offset| *next_offset <= file_size)
+                .ok_or_else(corrupt)?;
+            file.seek(SeekFrom::Start(next_offset))
+                .map_err(|_| corrupt())?;
+            buckets_offsets.push(offset);
+        }
+        let median: u64 = bincode::deserialize_from(&mut file).map_err(|_| corrupt())?;
+
+        Ok(StreamingCountersAnalyzer {
+            file: Mutex::new(file),
+            path: path.to_path_buf(),
+            buckets_offsets,
+            median,
+            remove,
+        })
+    }
+
+    fn check_header(
+        path: &Path,
+        header: CountersFileHeader,
+        expected: Option<CountersFileHeader>,
+    ) -> Result<(), CountersError> {
+        match expected {
+            Some(expected) if header != expected => Err(CountersError::ParametersMismatch {
+                path: path.to_path_buf(),
+                expected,
+                found: header,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Loads a counters file, checking that it was produced with the given buckets count and k,
+    /// as when it is written by a separate bucketing run
+    pub fn try_load_checked(
+        path: impl AsRef<Path>,
+        remove: bool,
+        buckets_count: usize,
+        k: usize,
+    ) -> Result<Self, CountersError> {
+        Self::try_load(
+            path.as_ref(),
+            remove,
+            Some(CountersFileHeader::new(buckets_count, k)),
+        )
+    }
+
+    /// Loads a counters file, optionally removing it once loaded
+    pub fn load_from_file(path: impl AsRef<Path>, remove: bool) -> Result<Self, CountersError> {
+        Self::try_load(path.as_ref(), remove, None)
+    }
+
+    fn try_load(
+        path: &Path,
+        remove: bool,
+        expected: Option<CountersFileHeader>,
+    ) -> Result<Self, CountersError> {
+        let mut file = Self::open_counters_file(path)?;
+        let header = Self::read_header(path, &mut file)?;
+        Self::check_header(path, header, expected)?;
+
+        let rval: CountersAnalyzer = bincode::deserialize_from(file)
+            .map_err(|_| CountersError::Corrupt(path.to_path_buf()))?;
 
         // rval.counters.iter_mut().enumerate().for_each(|(bn, x)| {
         //     x.iter_mut().enumerate().for_each(|(sbn, y)| {
@@ -93,14 +345,267 @@ impl CountersAnalyzer {
         if remove {
             let _ = std::fs::remove_file(path);
         }
-        rval
+        Ok(rval)
     }
 
-    pub fn serialize_to_file(&self, path: impl AsRef<Path>) {
-        let file = BufWriter::new(
-            File::create(path.as_ref())
-                .expect(&format!("Cannot open file {}", path.as_ref().display())),
+    /// Writes the counters preceded by a header with the format version, the buckets count and k,
+    /// so that the file can be loaded by a separate kmers transform run
+    pub fn save_to_file(&self, path: impl AsRef<Path>, k: usize) -> Result<(), CountersError> {
+        let path = path.as_ref();
+        let write_failed = |err| CountersError::WriteFailed(path.to_path_buf(), err);
+
+        let mut file = BufWriter::new(File::create(path).map_err(write_failed)?);
+        let header = CountersFileHeader::new(self.counters.len(), k);
+        bincode::serialize_into(&mut file, &header)
+            .and_then(|_| bincode::serialize_into(&mut file, self))
+            .map_err(|err| write_failed(std::io::Error::new(ErrorKind::Other, err)))?;
+        file.flush().map_err(write_failed)
+    }
+}
+
+/// Counters file opened with CountersAnalyzer::open_streaming
+pub struct StreamingCountersAnalyzer {
+    file: Mutex<BufReader<File>>,
+    path: PathBuf,
+    buckets_offsets: Vec<u64>,
+    median: u64,
+    remove: bool,
+}
+
+impl StreamingCountersAnalyzer {
+    pub fn buckets_count(&self) -> usize {
+        self.buckets_offsets.len()
+    }
+
+    pub fn median(&self) -> u64 {
+        self.median
+    }
+
+    pub fn get_counters_for_bucket(
+        &self,
+        bucket: BucketIndexType,
+    ) -> Result<Vec<BucketCounter>, CountersError> {
+        let corrupt = |_| CountersError::Corrupt(self.path.clone());
+
+        let offset = *self.buckets_offsets.get(bucket as usize).ok_or_else(|| {
+            CountersError::MissingBucket {
+                path: self.path.clone(),
+                bucket: bucket as usize,
+            }
+        })?;
+
+        let mut file = self.file.lock();
+        file.seek(SeekFrom::Start(offset)).map_err(corrupt)?;
+        bincode::deserialize_from(&mut *file).map_err(corrupt)
+    }
+}
+
+impl Drop for StreamingCountersAnalyzer {
+    fn drop(&mut self) {
+        if self.remove {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::counters_analyzer::{CountersAnalyzer, CountersError, COUNTERS_FILE_VERSION};
+    use std::io::Write;
+    use std::sync::atomic::AtomicU64;
+
+    #[test]
+    fn missing_counters_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("missing-counters.dat");
+
+        assert!(matches!(
+            CountersAnalyzer::load_from_file(&path, false),
+            Err(CountersError::NotFound(_))
+        ));
+        assert!(
+            CountersAnalyzer::open_streaming_if_exists(&path, false, 4, 31)
+                .unwrap()
+                .is_none()
         );
-        bincode::serialize_into(file, self).unwrap();
+
+        // Only a missing file falls back to uniform weights
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(&[0xFF; 3])
+            .unwrap();
+        assert!(matches!(
+            CountersAnalyzer::open_streaming_if_exists(&path, false, 4, 31),
+            Err(CountersError::Corrupt(_))
+        ));
+
+        let counters = (0..4)
+            .map(|_| (0..4).map(|_| AtomicU64::new(1)).collect())
+            .collect();
+        CountersAnalyzer::new(counters)
+            .save_to_file(&path, 31)
+            .unwrap();
+        assert!(matches!(
+            CountersAnalyzer::open_streaming_if_exists(&path, false, 8, 31),
+            Err(CountersError::ParametersMismatch { .. })
+        ));
+        assert!(
+            CountersAnalyzer::open_streaming_if_exists(&path, false, 4, 31)
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn corrupt_counters_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("corrupt-counters.dat");
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(&[0xFF; 3])
+            .unwrap();
+
+        let result = CountersAnalyzer::load_from_file(&path, false);
+        assert!(matches!(result, Err(CountersError::Corrupt(_))));
+
+        // Valid header with a truncated body
+        let counters = (0..4)
+            .map(|_| (0..4).map(|_| AtomicU64::new(1)).collect())
+            .collect();
+        CountersAnalyzer::new(counters)
+            .save_to_file(&path, 31)
+            .unwrap();
+        let length = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(length - 4)
+            .unwrap();
+
+        let result = CountersAnalyzer::load_from_file(&path, true);
+        assert!(matches!(result, Err(CountersError::Corrupt(_))));
+    }
+
+    #[test]
+    fn streaming_counters_match_eager() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("streaming-counters.dat");
+
+        let counters = (0..16)
+            .map(|bucket| {
+                (0..(bucket % 5 + 1))
+                    .map(|sub_bucket| AtomicU64::new(bucket * 31 + sub_bucket * 7))
+                    .collect()
+            })
+            .collect();
+        CountersAnalyzer::new(counters)
+            .save_to_file(&path, 31)
+            .unwrap();
+
+        let eager = CountersAnalyzer::load_from_file(&path, false).unwrap();
+        let streaming = CountersAnalyzer::open_streaming(&path, true).unwrap();
+
+        assert_eq!(streaming.buckets_count(), 16);
+        assert_eq!(streaming.median(), eager.median);
+        // Out of order accesses
+        for bucket in (0..16).rev().chain(0..16) {
+            assert!(
+                &streaming.get_counters_for_bucket(bucket).unwrap()
+                    == eager.get_counters_for_bucket(bucket)
+            );
+        }
+
+        assert!(matches!(
+            streaming.get_counters_for_bucket(16),
+            Err(CountersError::MissingBucket { bucket: 16, .. })
+        ));
+
+        drop(streaming);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn streaming_counters_validation() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("streaming-checked-counters.dat");
+
+        let counters = (0..8)
+            .map(|bucket| (0..4).map(|_| AtomicU64::new(bucket)).collect())
+            .collect();
+        CountersAnalyzer::new(counters)
+            .save_to_file(&path, 31)
+            .unwrap();
+
+        let streaming = CountersAnalyzer::open_streaming_checked(&path, false, 8, 31).unwrap();
+        assert_eq!(streaming.get_counters_for_bucket(7).unwrap()[0].count, 7);
+        drop(streaming);
+        assert!(matches!(
+            CountersAnalyzer::open_streaming_checked(&path, false, 16, 31),
+            Err(CountersError::ParametersMismatch { .. })
+        ));
+
+        // Buckets count of the body (after the 28 bytes header) not matching the header
+        let mut data = std::fs::read(&path).unwrap();
+        data[28..36].copy_from_slice(&9u64.to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+        assert!(matches!(
+            CountersAnalyzer::open_streaming(&path, false),
+            Err(CountersError::Corrupt(_))
+        ));
+
+        // Counters count of the first bucket beyond the end of the file
+        data[28..36].copy_from_slice(&8u64.to_le_bytes());
+        data[36..44].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+        assert!(matches!(
+            CountersAnalyzer::open_streaming(&path, false),
+            Err(CountersError::Corrupt(_))
+        ));
+
+        // Huge buckets count in both the header and the body
+        data[12..20].copy_from_slice(&u64::MAX.to_le_bytes());
+        data[28..36].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+        assert!(matches!(
+            CountersAnalyzer::open_streaming(&path, true),
+            Err(CountersError::Corrupt(_))
+        ));
+    }
+
+    #[test]
+    fn counters_file_header_validation() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("checked-counters.dat");
+
+        let counters = (0..8)
+            .map(|bucket| (0..4).map(|_| AtomicU64::new(bucket)).collect())
+            .collect();
+        CountersAnalyzer::new(counters)
+            .save_to_file(&path, 31)
+            .unwrap();
+
+        let loaded = CountersAnalyzer::try_load_checked(&path, false, 8, 31).unwrap();
+        assert_eq!(loaded.get_counters_for_bucket(7)[0].count, 7);
+
+        match CountersAnalyzer::try_load_checked(&path, false, 16, 31) {
+            Err(err @ CountersError::ParametersMismatch { .. }) => {
+                assert!(err.to_string().contains("8 buckets and k=31"))
+            }
+            _ => panic!("The buckets count mismatch was not detected"),
+        }
+        assert!(matches!(
+            CountersAnalyzer::try_load_checked(&path, false, 8, 63),
+            Err(CountersError::ParametersMismatch { .. })
+        ));
+
+        // Bump the version stored after the magic
+        let mut data = std::fs::read(&path).unwrap();
+        data[8..12].copy_from_slice(&(COUNTERS_FILE_VERSION + 1).to_le_bytes());
+        std::fs::write(&path, data).unwrap();
+        assert!(matches!(
+            CountersAnalyzer::load_from_file(&path, true),
+            Err(CountersError::VersionMismatch { .. })
+        ));
     }
 }
@@ -1,9 +1,13 @@
+pub mod bases_composition;
 pub mod counters_analyzer;
+pub mod minimizer_hasher;
 mod queue_data;
 mod reader;
 mod sequences_splitter;
 
+use crate::bases_composition::BasesComposition;
 use crate::counters_analyzer::CountersAnalyzer;
+use crate::minimizer_hasher::MinimizerBucketHasher;
 use crate::queue_data::MinimizerBucketingQueueData;
 use crate::reader::MinimizerBucketingFilesReader;
 use crate::sequences_splitter::SequencesSplitter;
@@ -12,7 +16,7 @@ use config::{
     DEFAULT_PER_CPU_BUFFER_SIZE, MINIMIZER_BUCKETS_CHECKPOINT_SIZE, PACKETS_PRIORITY_DEFAULT,
     READ_INTERMEDIATE_CHUNKS_SIZE, READ_INTERMEDIATE_QUEUE_MULTIPLIER,
 };
-use config::{MAXIMUM_SECOND_BUCKETS_COUNT, USE_SECOND_BUCKET};
+use config::{COUNT_BASES_COMPOSITION, MAXIMUM_SECOND_BUCKETS_COUNT, USE_SECOND_BUCKET};
 use hashes::HashableSequence;
 use io::compressed_read::CompressedRead;
 use io::concurrent::temp_reads::creads_utils::{
@@ -35,7 +39,7 @@ use parallel_processor::execution_manager::memory_tracker::MemoryTracker;
 use parallel_processor::execution_manager::thread_pool::ExecThreadPool;
 use parallel_processor::execution_manager::units_io::{ExecutorInput, ExecutorInputAddressMode};
 use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::cmp::max;
 use std::future::Future;
 use std::marker::PhantomData;
@@ -44,6 +48,7 @@ use std::ops::Range;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use utils::spill_layout::SpillLayout;
 
 pub trait MinimizerInputSequence: HashableSequence + Copy {
     fn get_subslice(&self, range: Range<usize>) -> Self;
@@ -91,10 +96,17 @@ pub trait MinimizerBucketingExecutorFactory: Sized {
 
     type ExecutorType: MinimizerBucketingExecutor<Self>;
 
+    /// Maps the minimizers to their buckets, changing it changes the buckets layout.
+    /// Use DefaultMinimizerBucketHasher to keep the buckets taken from the minimizer hash bits
+    type Hasher: MinimizerBucketHasher;
+
     fn new(global_data: &Arc<MinimizerBucketingCommonData<Self::GlobalData>>)
         -> Self::ExecutorType;
 }
 
+/// Bucket hasher used by a minimizer bucketing factory
+pub type MinimizerBucketingHasher<F> = <F as MinimizerBucketingExecutorFactory>::Hasher;
+
 pub trait MinimizerBucketingExecutor<Factory: MinimizerBucketingExecutorFactory>:
     'static + Sync + Send
 {
@@ -192,6 +204,8 @@ pub struct MinimizerBucketingExecutionContext<GlobalData> {
 
     pub partial_read_copyback: Option<usize>,
     pub copy_ident: bool,
+    /// Bases of the input reads, counted only if COUNT_BASES_COMPOSITION is set
+    pub bases_composition: Option<Mutex<BasesComposition>>,
 }
 
 pub struct GenericMinimizerBucketing;
@@ -210,6 +224,18 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
         &self,
         context: &MinimizerBucketingExecutionContext<E::GlobalData>,
         ops: &ExecutorAddressOperations<'_, Self>,
+    ) {
+        if USE_SECOND_BUCKET.load(Ordering::Relaxed) {
+            self.execute_with_format::<true>(context, ops).await
+        } else {
+            self.execute_with_format::<false>(context, ops).await
+        }
+    }
+
+    async fn execute_with_format<const WITH_SECOND_BUCKET: bool>(
+        &self,
+        context: &MinimizerBucketingExecutionContext<E::GlobalData>,
+        ops: &ExecutorAddressOperations<'_, Self>,
     ) {
         let counters_log = context.common.max_second_buckets_count.ilog2();
         let mut counters: Vec<u8> =
@@ -217,11 +243,7 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
 
         let mut tmp_reads_buffer = BucketsThreadDispatcher::<
             _,
-            CompressedReadsBucketDataSerializer<
-                E::ExtraData,
-                E::FLAGS_COUNT,
-                { USE_SECOND_BUCKET },
-            >,
+            CompressedReadsBucketDataSerializer<E::ExtraData, E::FLAGS_COUNT, WITH_SECOND_BUCKET>,
         >::new(
             &context.buckets,
             BucketsThreadBuffer::new(DEFAULT_PER_CPU_BUFFER_SIZE, context.buckets.count()),
@@ -231,6 +253,7 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
         //     DEFAULT_PER_CPU_BUFFER_SIZE.octets as usize * context.buckets.count()
         // ]);
         let global_counters = &context.common.global_counters;
+        let mut bases_composition = BasesComposition::default();
 
         while let Some(input_packet) = ops.receive_packet().await {
             let mut total_bases = 0;
@@ -244,6 +267,9 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
 
             for (index, (x, seq_info)) in input_packet.iter_sequences().enumerate() {
                 total_bases += x.seq.len() as u64;
+                if context.bases_composition.is_some() {
+                    bases_composition.count_sequence(x.seq);
+                }
                 buckets_processor.preprocess_dna_sequence(
                     &input_packet.stream_info,
                     seq_info,
@@ -331,6 +357,10 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> MinimizerBuck
             }
         }
 
+        if let Some(total_composition) = &context.bases_composition {
+            *total_composition.lock() += bases_composition;
+        }
+
         tmp_reads_buffer.finalize();
     }
 }
@@ -395,6 +425,17 @@ impl<E: MinimizerBucketingExecutorFactory + Sync + Send + 'static> AsyncExecutor
 // }
 
 impl GenericMinimizerBucketing {
+    /// Read chunks that can be queued for the compute threads. The per-call multiplier
+    /// allows concurrent bucketings with different values, else READ_INTERMEDIATE_QUEUE_MULTIPLIER is used
+    fn max_read_buffers_count(
+        compute_threads_count: usize,
+        read_queue_multiplier: Option<usize>,
+    ) -> usize {
+        let read_queue_multiplier = read_queue_multiplier
+            .unwrap_or_else(|| READ_INTERMEDIATE_QUEUE_MULTIPLIER.load(Ordering::Relaxed));
+        compute_threads_count * max(1, read_queue_multiplier)
+    }
+
     pub fn do_bucketing<
         E: MinimizerBucketingExecutorFactory + Sync + Send + 'static,
         S: GenericSequencesStream,
@@ -409,13 +450,16 @@ impl GenericMinimizerBucketing {
         partial_read_copyback: Option<usize>,
         copy_ident: bool,
         ignored_length: usize,
-    ) -> (Vec<PathBuf>, PathBuf) {
+        read_queue_multiplier: Option<usize>,
+    ) -> (Vec<PathBuf>, PathBuf, Option<BasesComposition>) {
         let read_threads_count = max(1, threads_count / 2);
         let compute_threads_count = max(1, threads_count.saturating_sub(read_threads_count / 4));
 
+        let spill_layout = SpillLayout::new(output_path).unwrap_or_else(|err| panic!("{}", err));
+
         let buckets = Arc::new(MultiThreadBuckets::<CompressedBinaryWriter>::new(
             buckets_count,
-            output_path.join("bucket"),
+            spill_layout.buckets_prefix(),
             &(
                 get_memory_mode(SwapPriority::MinimizerBuckets),
                 MINIMIZER_BUCKETS_CHECKPOINT_SIZE,
@@ -448,11 +492,14 @@ impl GenericMinimizerBucketing {
             partial_read_copyback,
             read_threads_count,
             copy_ident,
+            bases_composition: COUNT_BASES_COMPOSITION
+                .load(Ordering::Relaxed)
+                .then(|| Mutex::new(BasesComposition::default())),
         });
 
         {
             let max_read_buffers_count =
-                compute_threads_count * READ_INTERMEDIATE_QUEUE_MULTIPLIER.load(Ordering::Relaxed);
+                Self::max_read_buffers_count(compute_threads_count, read_queue_multiplier);
 
             let execution_context = ExecutionContext::new();
 
@@ -522,10 +569,44 @@ impl GenericMinimizerBucketing {
         let counters_analyzer = CountersAnalyzer::new(common_context.global_counters);
         // counters_analyzer.print_debug();
 
-        let counters_file = output_path.join("buckets-counters.dat");
+        let counters_file = spill_layout.buckets_counters_path();
 
-        counters_analyzer.serialize_to_file(&counters_file);
+        counters_analyzer
+            .save_to_file(&counters_file, common_context.k)
+            .unwrap_or_else(|err| panic!("{}", err));
+
+        (
+            global_context.buckets.finalize(),
+            counters_file,
+            global_context
+                .bases_composition
+                .map(|composition| composition.into_inner()),
+        )
+    }
+}
 
-        (global_context.buckets.finalize(), counters_file)
+#[cfg(test)]
+mod tests {
+    use crate::GenericMinimizerBucketing;
+    use config::READ_INTERMEDIATE_QUEUE_MULTIPLIER;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn read_queue_multiplier_override() {
+        let default_multiplier = READ_INTERMEDIATE_QUEUE_MULTIPLIER.load(Ordering::Relaxed);
+
+        assert_eq!(
+            GenericMinimizerBucketing::max_read_buffers_count(4, None),
+            4 * default_multiplier
+        );
+        assert_eq!(
+            GenericMinimizerBucketing::max_read_buffers_count(4, Some(8)),
+            32
+        );
+        // A zero multiplier would stall the readers
+        assert_eq!(
+            GenericMinimizerBucketing::max_read_buffers_count(4, Some(0)),
+            4
+        );
     }
 }
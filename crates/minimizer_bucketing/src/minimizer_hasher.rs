@@ -0,0 +1,53 @@
+use config::BucketIndexType;
+use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
+
+/// Selects the buckets of a super-kmer from the hash of its minimizer.
+/// Changing it changes the buckets layout, so all the phases reading the buckets
+/// must use the same hasher that was used to write them
+pub trait MinimizerBucketHasher: Sync + Send + 'static {
+    fn get_bucket<H: MinimizerHashFunctionFactory>(
+        used_bits: usize,
+        requested_bits: usize,
+        hash: H::HashTypeUnextendable,
+    ) -> BucketIndexType;
+}
+
+/// Takes the buckets directly from the bits of the minimizer hash
+pub struct DefaultMinimizerBucketHasher;
+
+impl MinimizerBucketHasher for DefaultMinimizerBucketHasher {
+    #[inline(always)]
+    fn get_bucket<H: MinimizerHashFunctionFactory>(
+        used_bits: usize,
+        requested_bits: usize,
+        hash: H::HashTypeUnextendable,
+    ) -> BucketIndexType {
+        H::get_bucket(used_bits, requested_bits, hash)
+    }
+}
+
+/// Mixes the minimizer hash before taking the buckets from its bits,
+/// spreading datasets whose minimizers hashes are not uniformly distributed
+pub struct MixedMinimizerBucketHasher;
+
+impl MixedMinimizerBucketHasher {
+    /// Finalizer of splitmix64
+    #[inline(always)]
+    pub fn hash_minimizer(mut hash: u64) -> u64 {
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+        hash ^ (hash >> 31)
+    }
+}
+
+impl MinimizerBucketHasher for MixedMinimizerBucketHasher {
+    #[inline(always)]
+    fn get_bucket<H: MinimizerHashFunctionFactory>(
+        used_bits: usize,
+        requested_bits: usize,
+        hash: H::HashTypeUnextendable,
+    ) -> BucketIndexType {
+        let mixed = Self::hash_minimizer(<H as HashFunctionFactory>::get_u64(hash));
+        ((mixed >> used_bits) % (1 << requested_bits)) as BucketIndexType
+    }
+}
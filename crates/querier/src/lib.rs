@@ -2,12 +2,15 @@ use crate::pipeline::colored_query_output::colored_query_output;
 use crate::pipeline::colormap_reading::colormap_reading;
 use crate::pipeline::counters_sorting::counters_sorting;
 use crate::pipeline::parallel_kmers_query::parallel_kmers_counting;
-use crate::pipeline::querier_minimizer_bucketing::minimizer_bucketing;
+use crate::pipeline::querier_minimizer_bucketing::{minimizer_bucketing, READ_NAMES_FILE_NAME};
 use ::dynamic_dispatch::dynamic_dispatch;
 use colors::colors_manager::{ColorMapReader, ColorsManager, ColorsMergeManager};
 use colors::DefaultColorsSerializer;
-use config::{INTERMEDIATE_COMPRESSION_LEVEL_FAST, INTERMEDIATE_COMPRESSION_LEVEL_SLOW};
+use config::{
+    INTERMEDIATE_COMPRESSION_LEVEL_FAST, INTERMEDIATE_COMPRESSION_LEVEL_SLOW, PRESERVE_READ_NAMES,
+};
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
+use io::concurrent::temp_reads::read_names::ReadNamesTable;
 use io::sequences_reader::SequencesReader;
 use io::sequences_stream::general::GeneralSequenceBlockData;
 use io::{compute_stats_from_input_blocks, generate_bucket_names};
@@ -16,6 +19,7 @@ use std::fs::File;
 use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
 use std::sync::atomic::Ordering;
+use utils::spill_layout::{create_run_dir, SpillLayout};
 
 mod pipeline;
 mod structs;
@@ -72,8 +76,10 @@ pub fn run_query<
     threads_count: usize,
     default_compression_level: Option<u32>,
     colored_query_output_format: ColoredQueryOutputFormat,
+    read_queue_multiplier: Option<usize>,
 ) -> PathBuf {
-    let temp_dir = temp_dir.unwrap_or(PathBuf::new());
+    // Without a temp dir the run files are kept together in a new directory of the current one
+    let temp_dir = temp_dir.unwrap_or_else(|| create_run_dir("", "query"));
 
     PHASES_TIMES_MONITOR.write().init();
 
@@ -108,12 +114,14 @@ pub fn run_query<
             threads_count,
             k,
             m,
+            read_queue_multiplier,
         )
     } else {
+        let spill_layout = SpillLayout::new(&temp_dir).unwrap_or_else(|err| panic!("{}", err));
         (
             (
-                generate_bucket_names(temp_dir.join("bucket"), buckets_count, None),
-                temp_dir.join("buckets-counters.dat"),
+                generate_bucket_names(spill_layout.buckets_prefix(), buckets_count, None),
+                spill_layout.buckets_counters_path(),
             ),
             {
                 let queries_count = BufReader::new(File::open(&query_input).unwrap())
@@ -155,6 +163,12 @@ pub fn run_query<
         sequences_lengths
     };
 
+    let query_names = if PRESERVE_READ_NAMES.load(Ordering::Relaxed) {
+        Some(ReadNamesTable::load_from_file(temp_dir.join(READ_NAMES_FILE_NAME)).into_names())
+    } else {
+        None
+    };
+
     let colored_buckets = if step <= QuerierStartingStep::CountersSorting {
         counters_sorting::<QuerierColorsManager>(
             k,
@@ -163,6 +177,7 @@ pub fn run_query<
             color_map.colors_subsets_count(),
             output_file_prefix.clone(),
             &query_kmers_count,
+            query_names.as_deref(),
         )
     } else {
         generate_bucket_names(colored_buckets_prefix, buckets_count, None)
@@ -183,6 +198,7 @@ pub fn run_query<
             output_file_prefix.clone(),
             temp_dir,
             &query_kmers_count,
+            query_names.as_deref(),
             colored_query_output_format,
         );
     }
@@ -8,6 +8,7 @@ use config::{
 };
 use flate2::Compression;
 use hashes::{HashFunctionFactory, MinimizerHashFunctionFactory};
+use io::concurrent::temp_reads::read_names::ReadNamesTable;
 use io::get_bucket_index;
 use nightly_quirks::prelude::*;
 use parallel_processor::buckets::readers::compressed_binary_reader::CompressedBinaryReader;
@@ -59,6 +60,7 @@ pub fn colored_query_output<
     output_file: PathBuf,
     temp_dir: PathBuf,
     query_kmers_count: &[u64],
+    query_names: Option<&[String]>,
     colored_query_output_format: ColoredQueryOutputFormat,
 ) {
     PHASES_TIMES_MONITOR
@@ -196,12 +198,16 @@ pub fn colored_query_output<
                     })
                 {
                     jsonline_buffer.clear();
-                    write!(
-                        jsonline_buffer,
-                        "{{\"query_index\":{}, \"matches\":{{",
-                        query
-                    )
-                    .unwrap();
+                    write!(jsonline_buffer, "{{\"query_index\":{}, ", query).unwrap();
+                    if let Some(query_names) = query_names {
+                        write!(
+                            jsonline_buffer,
+                            "\"query_name\":\"{}\", ",
+                            ReadNamesTable::json_escape(&query_names[query])
+                        )
+                        .unwrap();
+                    }
+                    write!(jsonline_buffer, "\"matches\":{{").unwrap();
 
                     temp_colors_list.clear();
                     while query_colors_list_index != usize::MAX {
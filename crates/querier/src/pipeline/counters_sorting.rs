@@ -105,6 +105,7 @@ pub fn counters_sorting<CX: ColorsManager>(
     colors_count: u64,
     output_file: PathBuf,
     query_kmers_count: &[u64],
+    query_names: Option<&[String]>,
 ) -> Vec<PathBuf> {
     PHASES_TIMES_MONITOR
         .write()
@@ -228,31 +229,35 @@ pub fn counters_sorting<CX: ColorsManager>(
         };
 
         let mut writer = csv::Writer::from_path(output_file).unwrap();
-        writer
-            .write_record(&[
-                "query_index",
-                "matched_kmers",
-                "query_kmers",
-                "match_percentage",
-            ])
-            .unwrap();
+        let mut header = vec![
+            "query_index",
+            "matched_kmers",
+            "query_kmers",
+            "match_percentage",
+        ];
+        if query_names.is_some() {
+            header.push("query_name");
+        }
+        writer.write_record(&header).unwrap();
 
         for (query_index, (info, counter)) in query_kmers_count
             .iter()
             .zip(final_counters.iter())
             .enumerate()
         {
-            writer
-                .write_record(&[
-                    query_index.to_string(),
-                    counter.load(Ordering::Relaxed).to_string(),
-                    info.to_string(),
-                    format!(
-                        "{:.2}",
-                        (counter.load(Ordering::Relaxed) as f64 / *info as f64)
-                    ),
-                ])
-                .unwrap();
+            let mut record = vec![
+                query_index.to_string(),
+                counter.load(Ordering::Relaxed).to_string(),
+                info.to_string(),
+                format!(
+                    "{:.2}",
+                    (counter.load(Ordering::Relaxed) as f64 / *info as f64)
+                ),
+            ];
+            if let Some(query_names) = query_names {
+                record.push(query_names[query_index].clone());
+            }
+            writer.write_record(&record).unwrap();
         }
         vec![]
     } else {
@@ -23,9 +23,11 @@ use io::concurrent::temp_reads::extra_data::{
 use io::varint::{decode_varint, encode_varint};
 use kmers_transform::processor::KmersTransformProcessor;
 use kmers_transform::{
-    GroupProcessStats, KmersTransform, KmersTransformExecutorFactory, KmersTransformFinalExecutor,
-    KmersTransformMapProcessor, KmersTransformPreprocessor,
+    FinalStats, GroupProcessStats, KmersTransform, KmersTransformBucketHasher,
+    KmersTransformExecutorFactory, KmersTransformFinalExecutor, KmersTransformMapProcessor,
+    KmersTransformPreprocessor, KmersTransformStatus,
 };
+use minimizer_bucketing::minimizer_hasher::MinimizerBucketHasher;
 use minimizer_bucketing::{MinimizerBucketingCommonData, MinimizerBucketingExecutorFactory};
 use parallel_processor::buckets::concurrent::{BucketsThreadBuffer, BucketsThreadDispatcher};
 use parallel_processor::buckets::writers::lock_free_binary_writer::LockFreeBinaryWriter;
@@ -234,7 +236,7 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
             .min_by_key(|k| H::get_full_minimizer(k.to_unextendable()))
             .unwrap();
 
-        H::get_bucket(
+        KmersTransformBucketHasher::<ParallelKmersQueryFactory<H, MH, CX>>::get_bucket::<H>(
             used_hash_bits,
             bucket_bits_count,
             minimizer.to_unextendable(),
@@ -401,8 +403,9 @@ impl<H: MinimizerHashFunctionFactory, MH: HashFunctionFactory, CX: ColorsManager
         map_struct
     }
 
-    fn finalize(self, _global_data: &GlobalQueryMergeData) {
+    fn finalize(self, _global_data: &GlobalQueryMergeData) -> FinalStats {
         self.counters_tmp.finalize();
+        FinalStats::default()
     }
 }
 
@@ -453,19 +456,23 @@ pub fn parallel_kmers_counting<
         )),
     });
 
-    KmersTransform::<ParallelKmersQueryFactory<H, MH, CX>>::new(
+    let result = KmersTransform::<ParallelKmersQueryFactory<H, MH, CX>>::new(
         file_inputs,
         out_directory.as_ref(),
         buckets_counters_path,
         buckets_count,
-        global_data.clone(),
+        global_data,
         threads_count,
         k,
         MINIMUM_SUBBUCKET_KMERS_COUNT as u64,
     )
-    .parallel_kmers_transform();
+    .parallel_kmers_transform()
+    .unwrap();
 
-    let global_data =
-        Arc::try_unwrap(global_data).unwrap_or_else(|_| panic!("Cannot unwrap global data!"));
+    // The query counters are complete only if all the buckets were processed
+    assert_eq!(result.status, KmersTransformStatus::Completed);
+
+    let global_data = Arc::try_unwrap(result.global_extra_data)
+        .unwrap_or_else(|_| panic!("Cannot unwrap global data!"));
     global_data.counters_buckets.finalize()
 }
@@ -3,7 +3,7 @@ use byteorder::ReadBytesExt;
 use colors::colors_manager::color_types::MinimizerBucketingSeqColorDataType;
 use colors::colors_manager::{ColorsManager, MinimizerBucketingSeqColorData};
 use colors::parsers::{SequenceIdent, SingleSequenceInfo};
-use config::BucketIndexType;
+use config::{BucketIndexType, PRESERVE_READ_NAMES};
 use hashes::rolling::minqueue::RollingMinQueue;
 use hashes::ExtendableHashTraitType;
 use hashes::HashFunction;
@@ -11,13 +11,15 @@ use hashes::MinimizerHashFunctionFactory;
 use io::concurrent::temp_reads::extra_data::{
     HasEmptyExtraBuffer, SequenceExtraData, SequenceExtraDataTempBufferManagement,
 };
+use io::concurrent::temp_reads::read_names::ReadNamesTable;
 use io::sequences_reader::{DnaSequence, DnaSequencesFileType};
 use io::sequences_stream::fasta::FastaFileSequencesStream;
 use io::sequences_stream::SequenceInfo;
 use io::varint::{decode_varint, encode_varint, VARINT_MAX_SIZE};
+use minimizer_bucketing::minimizer_hasher::{DefaultMinimizerBucketHasher, MinimizerBucketHasher};
 use minimizer_bucketing::{
     GenericMinimizerBucketing, MinimizerBucketingCommonData, MinimizerBucketingExecutor,
-    MinimizerBucketingExecutorFactory, MinimizerInputSequence,
+    MinimizerBucketingExecutorFactory, MinimizerBucketingHasher, MinimizerInputSequence,
 };
 use parallel_processor::phase_times_monitor::PHASES_TIMES_MONITOR;
 use std::io::{Read, Write};
@@ -84,8 +86,13 @@ impl SequenceExtraData for KmersQueryData {
     }
 }
 
+/// Name of the read names table written in the temporary directory
+pub const READ_NAMES_FILE_NAME: &str = "read-names.txt";
+
 pub struct QuerierMinimizerBucketingGlobalData {
     pub queries_count: Arc<AtomicUsize>,
+    /// Names of the queries, indexed by the query index carried in the buckets
+    pub read_names: Option<Arc<ReadNamesTable>>,
 }
 
 pub struct QuerierMinimizerBucketingExecutor<H: MinimizerHashFunctionFactory, CX: ColorsManager> {
@@ -99,6 +106,9 @@ pub struct QuerierMinimizerBucketingExecutorFactory<
     CX: ColorsManager,
 >(PhantomData<(H, CX)>);
 
+type QuerierBucketHasher<H, CX> =
+    MinimizerBucketingHasher<QuerierMinimizerBucketingExecutorFactory<H, CX>>;
+
 impl<H: MinimizerHashFunctionFactory, CX: ColorsManager> MinimizerBucketingExecutorFactory
     for QuerierMinimizerBucketingExecutorFactory<H, CX>
 {
@@ -111,6 +121,7 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager> MinimizerBucketingExecu
     type FLAGS_COUNT = typenum::U0;
 
     type ExecutorType = QuerierMinimizerBucketingExecutor<H, CX>;
+    type Hasher = DefaultMinimizerBucketHasher;
 
     fn new(
         global_data: &Arc<MinimizerBucketingCommonData<Self::GlobalData>>,
@@ -179,6 +190,11 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
                     .global_data
                     .queries_count
                     .fetch_add(1, Ordering::Relaxed);
+
+                if let Some(read_names) = &self.global_data.global_data.read_names {
+                    read_names.add_name(read_index as usize, sequence.ident_data);
+                }
+
                 ReadType::Query(NonZeroU64::new(read_index + 1).unwrap())
             }
         }
@@ -230,8 +246,12 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
         for (index, min_hash) in rolling_iter.enumerate() {
             if H::get_full_minimizer(min_hash) != H::get_full_minimizer(last_hash) {
                 push_sequence(
-                    H::get_bucket(used_bits, first_bits, last_hash),
-                    H::get_bucket(used_bits + first_bits, second_bits, last_hash),
+                    QuerierBucketHasher::<H, CX>::get_bucket::<H>(used_bits, first_bits, last_hash),
+                    QuerierBucketHasher::<H, CX>::get_bucket::<H>(
+                        used_bits + first_bits,
+                        second_bits,
+                        last_hash,
+                    ),
                     sequence.get_subslice(last_index..(index + self.global_data.k)),
                     0,
                     match &preprocess_info.read_type {
@@ -250,8 +270,12 @@ impl<H: MinimizerHashFunctionFactory, CX: ColorsManager>
         }
 
         push_sequence(
-            H::get_bucket(used_bits, first_bits, last_hash),
-            H::get_bucket(used_bits + first_bits, second_bits, last_hash),
+            QuerierBucketHasher::<H, CX>::get_bucket::<H>(used_bits, first_bits, last_hash),
+            QuerierBucketHasher::<H, CX>::get_bucket::<H>(
+                used_bits + first_bits,
+                second_bits,
+                last_hash,
+            ),
             sequence.get_subslice(last_index..sequence.seq_len()),
             0,
             match &preprocess_info.read_type {
@@ -274,6 +298,7 @@ pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
     threads_count: usize,
     k: usize,
     m: usize,
+    read_queue_multiplier: Option<usize>,
 ) -> ((Vec<PathBuf>, PathBuf), u64) {
     PHASES_TIMES_MONITOR
         .write()
@@ -285,25 +310,38 @@ pub fn minimizer_bucketing<H: MinimizerHashFunctionFactory, CX: ColorsManager>(
     ];
 
     let queries_count = Arc::new(AtomicUsize::new(0));
+    let read_names = if PRESERVE_READ_NAMES.load(Ordering::Relaxed) {
+        Some(Arc::new(ReadNamesTable::new()))
+    } else {
+        None
+    };
+
+    let (buckets, counters, _) = GenericMinimizerBucketing::do_bucketing::<
+        QuerierMinimizerBucketingExecutorFactory<H, CX>,
+        FastaFileSequencesStream,
+    >(
+        input_files.into_iter(),
+        output_path,
+        buckets_count,
+        threads_count,
+        k,
+        m,
+        QuerierMinimizerBucketingGlobalData {
+            queries_count: queries_count.clone(),
+            read_names: read_names.clone(),
+        },
+        None,
+        CX::COLORS_ENABLED || read_names.is_some(),
+        0,
+        read_queue_multiplier,
+    );
+
+    if let Some(read_names) = read_names {
+        read_names.write_to_file(output_path.join(READ_NAMES_FILE_NAME));
+    }
 
     (
-        GenericMinimizerBucketing::do_bucketing::<
-            QuerierMinimizerBucketingExecutorFactory<H, CX>,
-            FastaFileSequencesStream,
-        >(
-            input_files.into_iter(),
-            output_path,
-            buckets_count,
-            threads_count,
-            k,
-            m,
-            QuerierMinimizerBucketingGlobalData {
-                queries_count: queries_count.clone(),
-            },
-            None,
-            CX::COLORS_ENABLED,
-            0,
-        ),
+        (buckets, counters),
         queries_count.load(Ordering::Relaxed) as u64,
     )
 }
@@ -67,4 +67,41 @@ impl<CHI> MapEntry<CHI> {
         self.get_counter()
             >> ((self.get_flags() == (READ_FLAG_INCL_BEGIN | READ_FLAG_INCL_END)) as u8)
     }
+
+    /// Checks the kmer multiplicity against the inclusive [min_multiplicity, max_multiplicity] range,
+    /// without an upper bound if max_multiplicity is None
+    #[inline(always)]
+    pub fn is_multiplicity_in_range(
+        &self,
+        min_multiplicity: usize,
+        max_multiplicity: Option<usize>,
+    ) -> bool {
+        let multiplicity = self.get_kmer_multiplicity();
+        multiplicity >= min_multiplicity && max_multiplicity.map_or(true, |max| multiplicity <= max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map_entry::MapEntry;
+    use config::{READ_FLAG_INCL_BEGIN, READ_FLAG_INCL_END};
+
+    #[test]
+    fn multiplicity_range() {
+        let mut entry = MapEntry::new(());
+        for _ in 0..5 {
+            entry.incr();
+        }
+
+        assert!(entry.is_multiplicity_in_range(1, None));
+        assert!(entry.is_multiplicity_in_range(5, Some(5)));
+        assert!(entry.is_multiplicity_in_range(2, Some(10)));
+        assert!(!entry.is_multiplicity_in_range(6, None));
+        assert!(!entry.is_multiplicity_in_range(1, Some(4)));
+
+        // Kmers counted twice are checked with their halved multiplicity
+        entry.update_flags(READ_FLAG_INCL_BEGIN | READ_FLAG_INCL_END);
+        assert!(entry.is_multiplicity_in_range(1, Some(2)));
+        assert!(!entry.is_multiplicity_in_range(3, None));
+    }
 }
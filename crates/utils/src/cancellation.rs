@@ -0,0 +1,56 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// Shared flag used to request the early termination of a run
+#[derive(Clone, Default, Debug)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+static GLOBAL_CANCELLATION_TOKEN: OnceLock<CancellationToken> = OnceLock::new();
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Token shared by the whole process, triggered for example by the termination signals
+    pub fn global() -> &'static CancellationToken {
+        GLOBAL_CANCELLATION_TOKEN.get_or_init(CancellationToken::new)
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Installs a handler for SIGTERM and SIGINT that triggers the global cancellation token, so that the
+/// run can be checkpointed within the termination grace period. A second signal exits immediately
+#[cfg(feature = "termination-handler")]
+pub fn install_termination_handler() {
+    extern "C" fn handle_termination(_signal: libc::c_int) {
+        // Only atomic operations and _exit are used here, to keep the handler async-signal-safe
+        if let Some(token) = GLOBAL_CANCELLATION_TOKEN.get() {
+            if token.0.swap(true, Ordering::Relaxed) {
+                unsafe { libc::_exit(130) };
+            }
+        }
+    }
+
+    // Initialize the token before the handler can be called
+    CancellationToken::global();
+
+    unsafe {
+        libc::signal(
+            libc::SIGTERM,
+            handle_termination as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+        libc::signal(
+            libc::SIGINT,
+            handle_termination as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+}
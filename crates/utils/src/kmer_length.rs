@@ -0,0 +1,117 @@
+use crate::compute_best_m;
+use std::fmt::{Display, Formatter};
+
+/// Shortest supported k, as the default minimizer length is derived from k - 4
+pub const MIN_KMER_LENGTH: usize = 4;
+/// Longest k supported by the sequence hashes, that pack the whole k-mer in an u128.
+/// The rolling hashes do not bound k
+pub const MAX_SEQHASH_KMER_LENGTH: usize = 64;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum KmerError {
+    TooShort { k: usize },
+    TooLong { k: usize, max: usize },
+    InvalidMinimizer { k: usize, m: usize },
+}
+
+impl Display for KmerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KmerError::TooShort { k } => write!(
+                f,
+                "K-mers length {} is too short, the minimum supported is {}",
+                k, MIN_KMER_LENGTH
+            ),
+            KmerError::TooLong { k, max } => write!(
+                f,
+                "K-mers length {} is too long, the maximum supported by the selected hash is {}",
+                k, max
+            ),
+            KmerError::InvalidMinimizer { k, m } => write!(
+                f,
+                "Minimizer length {} is not valid with k-mers length {}, it must be between 1 and k",
+                m, k
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KmerError {}
+
+/// Checks that k is supported by the compressed reads and the minimizers scheme.
+/// The buckets format (second bucket byte and flags packed in the length varint) does not depend on k
+pub fn validate_k(k: usize) -> Result<(), KmerError> {
+    if k < MIN_KMER_LENGTH {
+        Err(KmerError::TooShort { k })
+    } else {
+        debug_assert!(compute_best_m(k) < k);
+        Ok(())
+    }
+}
+
+/// Checks k, bounded by max_k when the selected hash does not support any length,
+/// and returns the minimizer length, the default one if not overridden
+pub fn validate_lengths(
+    k: usize,
+    m: Option<usize>,
+    max_k: Option<usize>,
+) -> Result<usize, KmerError> {
+    validate_k(k)?;
+    if let Some(max) = max_k.filter(|max| k > *max) {
+        return Err(KmerError::TooLong { k, max });
+    }
+    match m {
+        Some(m) if m == 0 || m > k => Err(KmerError::InvalidMinimizer { k, m }),
+        Some(m) => Ok(m),
+        None => Ok(compute_best_m(k)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compute_best_m;
+    use crate::kmer_length::{
+        validate_k, validate_lengths, KmerError, MAX_SEQHASH_KMER_LENGTH, MIN_KMER_LENGTH,
+    };
+
+    #[test]
+    fn kmer_length_bounds() {
+        assert_eq!(validate_k(0), Err(KmerError::TooShort { k: 0 }));
+        assert_eq!(
+            validate_k(MIN_KMER_LENGTH - 1),
+            Err(KmerError::TooShort { k: 3 })
+        );
+        assert_eq!(validate_k(MIN_KMER_LENGTH), Ok(()));
+        assert_eq!(validate_k(31), Ok(()));
+        assert_eq!(validate_k(255), Ok(()));
+    }
+
+    #[test]
+    fn kmer_and_minimizer_lengths() {
+        assert_eq!(validate_lengths(31, None, None), Ok(compute_best_m(31)));
+        assert_eq!(validate_lengths(31, Some(15), None), Ok(15));
+        assert_eq!(
+            validate_lengths(0, None, None),
+            Err(KmerError::TooShort { k: 0 })
+        );
+        assert_eq!(
+            validate_lengths(31, Some(0), None),
+            Err(KmerError::InvalidMinimizer { k: 31, m: 0 })
+        );
+        assert_eq!(
+            validate_lengths(31, Some(32), None),
+            Err(KmerError::InvalidMinimizer { k: 31, m: 32 })
+        );
+
+        let max_k = Some(MAX_SEQHASH_KMER_LENGTH);
+        assert!(validate_lengths(MAX_SEQHASH_KMER_LENGTH, None, max_k).is_ok());
+        assert_eq!(
+            validate_lengths(MAX_SEQHASH_KMER_LENGTH + 1, None, max_k),
+            Err(KmerError::TooLong {
+                k: MAX_SEQHASH_KMER_LENGTH + 1,
+                max: MAX_SEQHASH_KMER_LENGTH
+            })
+        );
+        assert!(validate_lengths(1000, None, None).is_ok());
+    }
+}
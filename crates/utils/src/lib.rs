@@ -1,8 +1,12 @@
 #[macro_use]
 pub mod debug_functions;
+pub mod cancellation;
 pub mod fast_rand_bool;
+pub mod kmer_length;
+pub mod open_files;
 pub mod owned_drop;
 pub mod resource_counter;
+pub mod spill_layout;
 pub mod vec_slice;
 
 use std::cmp::max;
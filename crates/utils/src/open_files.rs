@@ -0,0 +1,51 @@
+use std::cmp::max;
+use std::fs::read_to_string;
+
+/// Fraction of the soft open files limit used by default for the buckets being read
+pub const DEFAULT_OPEN_FILES_FRACTION: f64 = 0.5;
+
+/// Open buckets allowed when the open files limit cannot be detected
+pub const FALLBACK_MAX_OPEN_FILES: usize = 512;
+
+fn parse_open_files_limit(limits: &str) -> Option<usize> {
+    let line = limits
+        .lines()
+        .find(|line| line.starts_with("Max open files"))?;
+    // Columns: soft limit, hard limit, units. An unlimited soft limit fails to parse
+    line["Max open files".len()..]
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Returns the soft limit of open files of the process, if it can be detected
+pub fn detect_open_files_limit() -> Option<usize> {
+    parse_open_files_limit(&read_to_string("/proc/self/limits").ok()?)
+}
+
+/// Default maximum of bucket files open at the same time, a fraction of the open files limit
+pub fn default_max_open_files() -> usize {
+    match detect_open_files_limit() {
+        Some(limit) => max(1, (limit as f64 * DEFAULT_OPEN_FILES_FRACTION) as usize),
+        None => FALLBACK_MAX_OPEN_FILES,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::open_files::parse_open_files_limit;
+
+    #[test]
+    fn open_files_limit_parsing() {
+        let limits = "Limit                     Soft Limit           Hard Limit           Units     \n\
+                      Max processes             127431               127431               processes \n\
+                      Max open files            1024                 524288               files     \n";
+        assert_eq!(parse_open_files_limit(limits), Some(1024));
+
+        let unlimited =
+            "Max open files            unlimited            unlimited            files     \n";
+        assert_eq!(parse_open_files_limit(unlimited), None);
+        assert_eq!(parse_open_files_limit(""), None);
+    }
+}
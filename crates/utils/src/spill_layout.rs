@@ -0,0 +1,237 @@
+use std::fmt::{Display, Formatter};
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+
+/// Kind of intermediate files, each stored in its own subdirectory of the run directory
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum SpillKind {
+    /// Minimizer buckets produced by the first bucketing step
+    Buckets,
+    /// Sub-buckets produced by resplitting the oversized buckets
+    Resplit,
+    /// Rewritten sub-buckets of the kmers transform readers
+    Rewrite,
+}
+
+impl SpillKind {
+    pub const fn dir_name(self) -> &'static str {
+        match self {
+            SpillKind::Buckets => "buckets",
+            SpillKind::Resplit => "resplit",
+            SpillKind::Rewrite => "rewrite",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SpillLayoutError {
+    /// The run directory has no name to use as the run id, as `/` or a path ending in `..`
+    EmptyRunId { run_dir: PathBuf },
+}
+
+impl Display for SpillLayoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpillLayoutError::EmptyRunId { run_dir } => write!(
+                f,
+                "Cannot derive a run id from the temporary directory '{}'",
+                run_dir.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SpillLayoutError {}
+
+/// Creates a new run directory inside base_dir, named `<prefix>_<uuid>` so that the runs
+/// sharing the same base directory do not collide. An empty base_dir is the current directory
+pub fn create_run_dir(base_dir: impl AsRef<Path>, prefix: &str) -> PathBuf {
+    let run_dir = base_dir
+        .as_ref()
+        .join(format!("{}_{}", prefix, uuid::Uuid::new_v4()));
+    let _ = create_dir_all(&run_dir);
+    run_dir
+}
+
+/// Layout of the spill files of a run: `<run_id>/<kind>/<run_id>-<name>`,
+/// where the run id is the name of the run temporary directory
+#[derive(Clone, Debug)]
+pub struct SpillLayout {
+    run_dir: PathBuf,
+    run_id: String,
+    /// Additional run directories, inside each stripe directory, where the striped files are spread
+    stripe_run_dirs: Vec<PathBuf>,
+}
+
+impl SpillLayout {
+    /// Returns an error if the run directory has no name, as the files would not carry a run id
+    pub fn new(run_dir: impl AsRef<Path>) -> Result<Self, SpillLayoutError> {
+        let run_dir = run_dir.as_ref().to_path_buf();
+        let run_id = match run_dir.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            _ => return Err(SpillLayoutError::EmptyRunId { run_dir }),
+        };
+        Ok(Self {
+            run_dir,
+            run_id,
+            stripe_run_dirs: Vec::new(),
+        })
+    }
+
+    /// Path prefix of the minimizer buckets, followed by the bucket index
+    pub fn buckets_prefix(&self) -> PathBuf {
+        self.spill_path(SpillKind::Buckets, "bucket")
+    }
+
+    /// Path of the counters of the minimizer buckets, stored next to them
+    pub fn buckets_counters_path(&self) -> PathBuf {
+        self.spill_path(SpillKind::Buckets, "buckets-counters.dat")
+    }
+
+    /// Spreads the striped spill files across the run directory and the given directories
+    pub fn with_stripe_dirs(mut self, stripe_dirs: &[PathBuf]) -> Self {
+        self.stripe_run_dirs = stripe_dirs
+            .iter()
+            .map(|dir| dir.join(&self.run_id))
+            .collect();
+        self
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Returns the subdirectory for the given kind of files, creating it if needed
+    pub fn spill_dir(&self, kind: SpillKind) -> PathBuf {
+        let dir = self.run_dir.join(kind.dir_name());
+        let _ = create_dir_all(&dir);
+        dir
+    }
+
+    /// Returns the path of a spill file, prefixed with the run id
+    pub fn spill_path(&self, kind: SpillKind, name: &str) -> PathBuf {
+        self.spill_dir(kind)
+            .join(format!("{}-{}", self.run_id, name))
+    }
+
+    /// Returns the path of a spill file, assigning it round-robin by index to one of the
+    /// stripe directories. The files keep the same name whatever directory they are in
+    pub fn striped_spill_path(&self, kind: SpillKind, name: &str, index: usize) -> PathBuf {
+        let stripe = index % (self.stripe_run_dirs.len() + 1);
+        if stripe == 0 {
+            return self.spill_path(kind, name);
+        }
+
+        let dir = self.stripe_run_dirs[stripe - 1].join(kind.dir_name());
+        let _ = create_dir_all(&dir);
+        dir.join(format!("{}-{}", self.run_id, name))
+    }
+
+    /// Removes the stripe run directories, if they were left empty
+    pub fn remove_empty_stripe_dirs(&self) {
+        for run_dir in self.stripe_run_dirs.iter() {
+            for kind in [SpillKind::Buckets, SpillKind::Resplit, SpillKind::Rewrite] {
+                let _ = std::fs::remove_dir(run_dir.join(kind.dir_name()));
+            }
+            let _ = std::fs::remove_dir(run_dir);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spill_layout::{create_run_dir, SpillKind, SpillLayout, SpillLayoutError};
+    use std::fs::File;
+    use std::path::Path;
+
+    #[test]
+    fn spill_files_layout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let run_id = "build_graph_spill-test";
+        let run_dir = temp_dir.path().join(run_id);
+        let layout = SpillLayout::new(&run_dir).unwrap();
+        assert_eq!(layout.run_id(), run_id);
+
+        let files = [
+            (
+                SpillKind::Buckets,
+                layout.spill_path(SpillKind::Buckets, "bucket.0"),
+            ),
+            (
+                SpillKind::Resplit,
+                layout.spill_path(SpillKind::Resplit, "resplit-bucket0.3"),
+            ),
+            (
+                SpillKind::Rewrite,
+                layout.spill_path(SpillKind::Rewrite, "bucket-rewrite.1"),
+            ),
+        ];
+
+        for (kind, path) in files.iter() {
+            File::create(path).unwrap();
+
+            let file_name = path.file_name().unwrap().to_str().unwrap();
+            assert!(file_name.starts_with(&format!("{}-", run_id)));
+            assert_eq!(path.parent().unwrap(), run_dir.join(kind.dir_name()));
+            assert!(path.exists());
+        }
+    }
+
+    #[test]
+    fn striped_spill_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let run_id = "build_graph_stripes-test";
+        let run_dir = temp_dir.path().join(run_id);
+        let stripe_dir = temp_dir.path().join("stripe");
+        let layout = SpillLayout::new(&run_dir)
+            .unwrap()
+            .with_stripe_dirs(&[stripe_dir.clone()]);
+
+        let first = layout.striped_spill_path(SpillKind::Resplit, "resplit-bucket0", 0);
+        let second = layout.striped_spill_path(SpillKind::Resplit, "resplit-bucket1", 1);
+        let third = layout.striped_spill_path(SpillKind::Resplit, "resplit-bucket2", 2);
+
+        assert_eq!(first.parent().unwrap(), run_dir.join("resplit"));
+        assert_eq!(
+            second.parent().unwrap(),
+            stripe_dir.join(run_id).join("resplit")
+        );
+        assert_eq!(third.parent().unwrap(), run_dir.join("resplit"));
+        assert_eq!(
+            second.file_name(),
+            layout
+                .spill_path(SpillKind::Resplit, "resplit-bucket1")
+                .file_name()
+        );
+
+        layout.remove_empty_stripe_dirs();
+        assert!(!stripe_dir.join(run_id).exists());
+    }
+
+    #[test]
+    fn empty_run_id() {
+        for run_dir in [Path::new("/"), Path::new("runs/.."), Path::new("")] {
+            assert_eq!(
+                SpillLayout::new(run_dir).unwrap_err(),
+                SpillLayoutError::EmptyRunId {
+                    run_dir: run_dir.to_path_buf()
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn created_run_dirs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let first = create_run_dir(temp_dir.path(), "build_graph");
+        let second = create_run_dir(temp_dir.path(), "build_graph");
+
+        assert_ne!(first, second);
+        for run_dir in [&first, &second] {
+            assert!(run_dir.is_dir());
+            assert_eq!(run_dir.parent().unwrap(), temp_dir.path());
+            let layout = SpillLayout::new(run_dir).unwrap();
+            assert!(layout.run_id().starts_with("build_graph_"));
+        }
+    }
+}